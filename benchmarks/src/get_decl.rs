@@ -13,12 +13,13 @@ pub fn prepare() -> (ast::Program, labels::VarDecl, labels::VarUse) {
     // query the type of its expression
     let (var_decl_label, var_use_label) = {
         let main = program.methods().next().unwrap();
-        let last_statement = &main.body[main.body.len() - 1];
+        let body = main.body.as_ref().unwrap();
+        let last_statement = &body[body.len() - 1];
         let var_use_label = match last_statement {
-            &ast::Statement::VarDecl(ref vd) => {
+            ast::Statement::VarDecl(vd) => {
                 let expr = vd.expr.as_ref().unwrap();
                 match expr {
-                    &ast::Expression::BinaryOp(ref e) => {
+                    ast::Expression::BinaryOp(e) => {
                         e.left.label().as_label().assert_as_var_use()
                     }
                     _ => unreachable!()
@@ -27,9 +28,9 @@ pub fn prepare() -> (ast::Program, labels::VarDecl, labels::VarUse) {
             _ => unreachable!()
         };
 
-        let var_decl_statement = &main.body[main.body.len() - 2];
+        let var_decl_statement = &body[body.len() - 2];
         let var_decl_label = match var_decl_statement {
-            &ast::Statement::VarDecl(ref vd) => vd.label.assert_as_var_decl(),
+            ast::Statement::VarDecl(vd) => vd.label.assert_as_var_decl(),
             _ => unreachable!()
         };
 
@@ -41,7 +42,7 @@ pub fn prepare() -> (ast::Program, labels::VarDecl, labels::VarUse) {
 
 pub fn on_demand(program: &ast::Program, var_decl_label: labels::VarDecl, var_use_label: labels::VarUse) -> u64 {
     let begin = time::precise_time_ns();
-    let mut query_engine = QueryEngine::new(&program);
+    let mut query_engine = QueryEngine::new(program);
     let decl = query_engine.query_var_decl(var_use_label.as_label());
     let end = time::precise_time_ns();
 
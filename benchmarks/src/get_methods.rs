@@ -9,8 +9,8 @@ pub fn prepare() -> ast::Program {
 
 pub fn on_demand(program: &ast::Program) -> u64 {
     let begin = time::precise_time_ns();
-    let mut query_engine = QueryEngine::new(&program);
-    let decl = query_engine.query_class_decl("C955");
+    let mut query_engine = QueryEngine::new(program);
+    let decl = query_engine.query_class_decl("C955").unwrap();
     let class = query_engine.nodes[&decl.as_label()].downcast::<ast::ClassDecl>();
     let end = time::precise_time_ns();
 
@@ -22,8 +22,8 @@ pub fn on_demand(program: &ast::Program) -> u64 {
 
 pub fn traditional(program: &ast::Program) -> u64 {
     let begin = time::precise_time_ns();
-    let data = AstPreprocessor::preprocess(&program);
-    let methods = &data.classes_by_name["C955"].items;
+    let data = AstPreprocessor::preprocess(program);
+    let methods = &data.classes.get("C955").unwrap().items;
     let end = time::precise_time_ns();
 
     // Sanity check
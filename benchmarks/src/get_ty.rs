@@ -15,9 +15,10 @@ pub fn prepare() -> (ast::Program, analysis::labels::Expression) {
 
     let expr_label = {
         let main = program.methods().next().unwrap();
-        let last_statement = &main.body[main.body.len() - 1];
+        let body = main.body.as_ref().unwrap();
+        let last_statement = &body[body.len() - 1];
         match last_statement {
-            &ast::Statement::VarDecl(ref vd) => vd.expr.as_ref().unwrap().label(),
+            ast::Statement::VarDecl(vd) => vd.expr.as_ref().unwrap().label(),
             _ => unreachable!()
         }
     };
@@ -27,7 +28,7 @@ pub fn prepare() -> (ast::Program, analysis::labels::Expression) {
 
 pub fn on_demand(program: &ast::Program, expr_label: analysis::labels::Expression) -> u64 {
     let begin = time::precise_time_ns();
-    let mut query_engine = QueryEngine::new(&program);
+    let mut query_engine = QueryEngine::new(program);
     let ty = query_engine.query_expr_type(expr_label).unwrap();
     let end = time::precise_time_ns();
 
@@ -39,7 +40,7 @@ pub fn on_demand(program: &ast::Program, expr_label: analysis::labels::Expressio
 
 pub fn traditional(program: &ast::Program, expr_label: ast::Label) -> u64 {
     let begin = time::precise_time_ns();
-    let (types, type_map) = type_checker::check(&program);
+    let (types, type_map, _) = type_checker::check(program);
     let ty = types[&expr_label];
     let end = time::precise_time_ns();
 
@@ -0,0 +1,232 @@
+//! Constructors for the diagnostics reported during analysis
+//!
+//! Note: both the `QueryEngine` and the traditional type checker use these, so the same
+//! problem is reported in the same way regardless of the kind of analysis
+
+use ast::{BinaryOperator, UnaryOperator};
+use diagnostics::{Code, Diagnostic};
+use syntax::Span;
+
+pub fn unknown_type(span: Span, name: &str) -> Diagnostic {
+    Diagnostic::error(Code::UnknownType, format!("cannot find type `{}` in this scope", name))
+        .with_primary(span, "not found in this scope".to_string())
+}
+
+/// Reports a name that refers to classes or interfaces imported by several using directives,
+/// given the fully qualified names of two of them
+pub fn ambiguous_name(span: Span, name: &str, first: &str, second: &str) -> Diagnostic {
+    Diagnostic::error(Code::AmbiguousName, format!("`{}` is ambiguous between `{}` and `{}`", name, first, second))
+        .with_primary(span, "ambiguous name".to_string())
+}
+
+pub fn unresolved_name(span: Span, name: &str) -> Diagnostic {
+    Diagnostic::error(Code::UnresolvedName, format!("cannot find value `{}` in this scope", name))
+        .with_primary(span, "not found in this scope".to_string())
+}
+
+/// Reports a class or a namespace (the `kind`) used as a value
+pub fn expected_value(span: Span, kind: &str, name: &str) -> Diagnostic {
+    Diagnostic::error(Code::UnresolvedName, format!("expected value, found {} `{}`", kind, name))
+        .with_primary(span, "not a value".to_string())
+}
+
+pub fn expected_class(span: Span, namespace: &str) -> Diagnostic {
+    Diagnostic::error(Code::UnknownType, format!("expected class, found namespace `{}`", namespace))
+        .with_primary(span, "not a class".to_string())
+}
+
+pub fn mismatched_types(span: Span, expected: &str, found: &str) -> Diagnostic {
+    Diagnostic::error(Code::MismatchedTypes, "mismatched types".to_string())
+        .with_primary(span, format!("expected `{}`, found `{}`", expected, found))
+}
+
+pub fn wrong_argument_count(span: Span, method_name: &str, expected: usize, found: usize) -> Diagnostic {
+    argument_count(span, "method", method_name, expected, found)
+}
+
+pub fn wrong_constructor_argument_count(span: Span, class_name: &str, expected: usize, found: usize) -> Diagnostic {
+    argument_count(span, "constructor", class_name, expected, found)
+}
+
+fn argument_count(span: Span, kind: &str, name: &str, expected: usize, found: usize) -> Diagnostic {
+    let arguments = |n| if n == 1 { "1 argument".to_string() } else { format!("{} arguments", n) };
+    let supplied = if found == 1 { "was" } else { "were" };
+    Diagnostic::error(Code::WrongArgumentCount,
+                      format!("{} `{}` takes {} but {} {} supplied", kind, name, arguments(expected), arguments(found), supplied))
+        .with_primary(span, format!("expected {}", arguments(expected)))
+}
+
+/// Reports a call that none of the constructors of a class accepts, given the names of the
+/// types of its arguments
+pub fn no_matching_constructor(span: Span, class_name: &str, arg_tys: &[String]) -> Diagnostic {
+    Diagnostic::error(Code::NoMatchingOverload,
+                      format!("no constructor of `{}` takes arguments of types `({})`", class_name, arg_tys.join(", ")))
+        .with_primary(span, "no matching constructor".to_string())
+}
+
+/// Reports a call that none of the overloads of a method accepts, given the names of the types of
+/// its arguments
+pub fn no_matching_overload(span: Span, method_name: &str, arg_tys: &[String]) -> Diagnostic {
+    Diagnostic::error(Code::NoMatchingOverload,
+                      format!("no overload of `{}` takes arguments of types `({})`", method_name, arg_tys.join(", ")))
+        .with_primary(span, "no matching overload".to_string())
+}
+
+/// Reports a call that two overloads of a method or constructor (the `name`) accept equally well,
+/// given the names of their parameter types
+pub fn ambiguous_call(span: Span, name: &str, first: &[String], second: &[String]) -> Diagnostic {
+    Diagnostic::error(Code::AmbiguousCall,
+                      format!("the call is ambiguous between `{}({})` and `{}({})`", name, first.join(", "), name, second.join(", ")))
+        .with_primary(span, "ambiguous call".to_string())
+}
+
+pub fn recursive_constructor(span: Span, class_name: &str) -> Diagnostic {
+    Diagnostic::error(Code::RecursiveConstructor, format!("constructor of `{}` calls itself", class_name))
+        .with_primary(span, "the constructors called from here lead back to this one".to_string())
+}
+
+/// Reports a use of `base`, as a value or as a constructor call, in a class without a base class
+pub fn no_base_class(span: Span, class_name: &str) -> Diagnostic {
+    Diagnostic::error(Code::NoBaseClass, format!("the class `{}` has no base class", class_name))
+        .with_primary(span, "`base` used here".to_string())
+}
+
+/// Reports an `override` method for which the base classes have no method with the same signature
+pub fn nothing_to_override(span: Span, method_name: &str) -> Diagnostic {
+    Diagnostic::error(Code::InvalidOverride, format!("no suitable method found to override for `{}`", method_name))
+        .with_primary(span, "marked as `override`".to_string())
+}
+
+/// Reports an `override` of a method that is not `virtual` nor `override`, or that is `sealed`
+pub fn not_overridable(span: Span, overridden: &str, is_sealed: bool) -> Diagnostic {
+    let reason = if is_sealed { "it is sealed" } else { "it is not marked as `virtual` or `override`" };
+    Diagnostic::error(Code::InvalidOverride, format!("cannot override `{}` because {}", overridden, reason))
+        .with_primary(span, "overrides a method that cannot be overridden".to_string())
+}
+
+pub fn override_return_type(span: Span, overridden: &str, expected: &str) -> Diagnostic {
+    Diagnostic::error(Code::InvalidOverride, format!("the return type must be `{}` to match the overridden method `{}`", expected, overridden))
+        .with_primary(span, format!("expected to return `{}`", expected))
+}
+
+/// Reports a missing field of a class or an interface (the `kind`)
+pub fn unknown_field(span: Span, kind: &str, type_name: &str, field_name: &str) -> Diagnostic {
+    Diagnostic::error(Code::UnknownField, format!("no field `{}` on {} `{}`", field_name, kind, type_name))
+        .with_primary(span, "unknown field".to_string())
+}
+
+/// Reports a missing method of a class or an interface (the `kind`)
+pub fn unknown_method(span: Span, kind: &str, type_name: &str, method_name: &str) -> Diagnostic {
+    Diagnostic::error(Code::UnknownMethod, format!("no method named `{}` found for {} `{}`", method_name, kind, type_name))
+        .with_primary(span, "method not found".to_string())
+}
+
+/// Reports a `new` of an interface or an abstract class (the `kind`)
+pub fn abstract_instantiation(span: Span, kind: &str, type_name: &str) -> Diagnostic {
+    Diagnostic::error(Code::AbstractInstantiation, format!("cannot create an instance of the {} `{}`", kind, type_name))
+        .with_primary(span, "cannot be instantiated".to_string())
+}
+
+/// Reports a class that lacks an instance method with the name and parameter types of a method of
+/// one of its interfaces (the `member`, like `IShape.Area`)
+///
+/// Note: the span is the one of the interface in the head of the class
+pub fn missing_interface_member(span: Span, class_name: &str, member: &str) -> Diagnostic {
+    Diagnostic::error(Code::MissingImplementation, format!("`{}` does not implement interface member `{}`", class_name, member))
+        .with_primary(span, format!("missing `{}`", member))
+}
+
+/// Reports a method that would implement a method of an interface (the `member`), if it returned
+/// the same type
+pub fn interface_return_type(span: Span, class_name: &str, member: &str, expected: &str) -> Diagnostic {
+    Diagnostic::error(Code::MissingImplementation, format!("`{}` does not implement interface member `{}`", class_name, member))
+        .with_primary(span, format!("expected to return `{}` to implement `{}`", expected, member))
+}
+
+/// Reports a class that is not abstract and inherits an abstract method (the `member`, like
+/// `Shape.Area`) without overriding it
+///
+/// Note: the span is the one of the base class in the head of the class
+pub fn missing_abstract_member(span: Span, class_name: &str, member: &str) -> Diagnostic {
+    Diagnostic::error(Code::MissingImplementation, format!("`{}` does not implement inherited abstract member `{}`", class_name, member))
+        .with_primary(span, format!("missing `{}`", member))
+}
+
+pub fn abstract_in_concrete_class(span: Span, method_name: &str, class_name: &str) -> Diagnostic {
+    Diagnostic::error(Code::AbstractInConcreteClass, format!("`{}` is abstract, but it is declared in the class `{}`, which is not", method_name, class_name))
+        .with_primary(span, "declared as `abstract`".to_string())
+        .with_note("mark the class as `abstract`, or give the method a body".to_string())
+}
+
+pub fn abstract_base_call(span: Span, member: &str) -> Diagnostic {
+    Diagnostic::error(Code::AbstractBaseCall, format!("cannot call the abstract method `{}` through `base`", member))
+        .with_primary(span, "the method has no body".to_string())
+}
+
+pub fn not_an_object(span: Span, ty_name: &str) -> Diagnostic {
+    Diagnostic::error(Code::NotAnObject, format!("values of type `{}` have no fields or methods", ty_name))
+        .with_primary(span, format!("this is of type `{}`", ty_name))
+}
+
+pub fn static_mismatch(span: Span, method_name: &str, is_static: bool) -> Diagnostic {
+    if is_static {
+        Diagnostic::error(Code::StaticMismatch, format!("static method `{}` cannot be called on an instance", method_name))
+            .with_primary(span, "called on an instance".to_string())
+            .with_note("call it through the name of the class instead".to_string())
+    } else {
+        Diagnostic::error(Code::StaticMismatch, format!("method `{}` is not static", method_name))
+            .with_primary(span, "called without an instance".to_string())
+    }
+}
+
+pub fn static_field_mismatch(span: Span, field_name: &str, is_static: bool) -> Diagnostic {
+    if is_static {
+        Diagnostic::error(Code::StaticMismatch, format!("static field `{}` cannot be accessed through an instance", field_name))
+            .with_primary(span, "accessed through an instance".to_string())
+            .with_note("access it through the name of the class instead".to_string())
+    } else {
+        Diagnostic::error(Code::StaticMismatch, format!("field `{}` is not static", field_name))
+            .with_primary(span, "accessed without an instance".to_string())
+    }
+}
+
+pub fn invalid_operand(span: Span, operator: UnaryOperator, operand: &str) -> Diagnostic {
+    let ty = match operator {
+        UnaryOperator::Not => "bool",
+        _ => "int"
+    };
+    Diagnostic::error(Code::InvalidOperands, format!("cannot apply `{}` to `{}`", operator, operand))
+        .with_primary(span, format!("operator `{}` is only supported for `{}`", operator, ty))
+}
+
+pub fn not_iterable(span: Span, ty_name: &str) -> Diagnostic {
+    Diagnostic::error(Code::NotIterable, format!("`foreach` cannot iterate over values of type `{}`", ty_name))
+        .with_primary(span, "not a collection".to_string())
+}
+
+pub fn read_only(span: Span, name: &str) -> Diagnostic {
+    Diagnostic::error(Code::ReadOnly, format!("cannot assign to `{}`, it is read-only", name))
+        .with_primary(span, "cannot be assigned to".to_string())
+}
+
+pub fn foreach_variable_assignment(span: Span, name: &str) -> Diagnostic {
+    Diagnostic::error(Code::ReadOnly, format!("cannot assign to `{}`, it is the variable of a `foreach` loop", name))
+        .with_primary(span, "cannot be assigned to".to_string())
+}
+
+pub fn not_indexable(span: Span, ty_name: &str) -> Diagnostic {
+    Diagnostic::error(Code::NotIndexable, format!("cannot index into a value of type `{}`", ty_name))
+        .with_primary(span, "not an array".to_string())
+}
+
+pub fn invalid_operands(span: Span, operator: BinaryOperator, left: &str, right: &str) -> Diagnostic {
+    let label = if operator.is_equality() {
+        "only values of the same type can be compared".to_string()
+    } else if operator.is_logical() {
+        format!("operator `{}` is only supported for `bool`", operator)
+    } else {
+        format!("operator `{}` is only supported for `int`", operator)
+    };
+    Diagnostic::error(Code::InvalidOperands, format!("cannot apply `{}` to `{}` and `{}`", operator, left, right))
+        .with_primary(span, label)
+}
@@ -3,6 +3,9 @@ use ast::Label;
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct ClassDecl(pub(crate) Label);
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct InterfaceDecl(pub(crate) Label);
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct TypeUse(pub(crate) Label);
 
@@ -15,6 +18,9 @@ pub struct MethodDecl(pub(crate) Label);
 #[derive(Clone, Copy)]
 pub struct MethodUse(pub(crate) Label);
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct ConstructorDecl(pub(crate) Label);
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub struct VarDecl(pub(crate) Label);
 
@@ -26,6 +32,10 @@ impl Label {
         ClassDecl(self)
     }
 
+    pub fn assert_as_interface_decl(self) -> InterfaceDecl {
+        InterfaceDecl(self)
+    }
+
     pub fn assert_as_type_use(self) -> TypeUse {
         TypeUse(self)
     }
@@ -38,6 +48,10 @@ impl Label {
         MethodUse(self)
     }
 
+    pub fn assert_as_constructor_decl(self) -> ConstructorDecl {
+        ConstructorDecl(self)
+    }
+
     pub fn assert_as_var_decl(self) -> VarDecl {
         VarDecl(self)
     }
@@ -59,4 +73,4 @@ macro_rules! impl_as_label {
     }
 }
 
-impl_as_label!(ClassDecl, Expression, MethodDecl, MethodUse, TypeUse, VarDecl, VarUse);
+impl_as_label!(ClassDecl, ConstructorDecl, Expression, InterfaceDecl, MethodDecl, MethodUse, TypeUse, VarDecl, VarUse);
@@ -1,7 +1,11 @@
+pub mod errors;
 pub mod labels;
 mod on_demand;
 mod preprocess;
+mod resolution;
 
-pub use self::on_demand::type_map::{Type, TypeId, TypeMap};
+pub use self::on_demand::type_map::{Type, TypeId, TypeMap, UnknownType};
 pub use self::on_demand::query_engine::QueryEngine;
 pub use self::preprocess::ast_preprocessor::AstPreprocessor;
+pub use self::preprocess::class_table::{ClassTable, ScopeId};
+pub use self::resolution::Resolver;
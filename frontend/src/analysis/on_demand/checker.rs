@@ -0,0 +1,174 @@
+//! Checks that only make sense for whole statements (e.g. the type of a returned value),
+//! performed on behalf of the `QueryEngine`
+
+use analysis::{errors, Resolver, TypeId};
+use ast::*;
+use ast::visitor::Visitor;
+use syntax::Span;
+use super::query_engine::QueryEngine;
+
+pub fn check_method<'a>(engine: &mut QueryEngine<'a>, md: &'a MethodDecl) {
+    let return_ty = engine.query_return_type(md.label.assert_as_method_decl());
+    let mut checker = Checker { engine, return_ty };
+    checker.visit_method_decl(md);
+
+    // Note: this reports overrides that do not match an overridable method
+    checker.engine.query_overridden_method(md.label.assert_as_method_decl());
+}
+
+pub fn check_constructor<'a>(engine: &mut QueryEngine<'a>, cd: &'a ConstructorDecl) {
+    let void_ty = engine.types().void_ty();
+    let mut checker = Checker { engine, return_ty: void_ty };
+    checker.visit_constructor_decl(cd);
+
+    match cd.initializer {
+        Some(ref call) if checker.engine.calls_itself(cd) => {
+            checker.engine.report(errors::recursive_constructor(call.span, &cd.name));
+        }
+        None if !cd.is_static => {
+            // Implicit call to the parameterless constructor of the base class
+            checker.engine.query_constructor(cd.label);
+        }
+        _ => ()
+    }
+}
+
+pub fn check_field<'a>(engine: &mut QueryEngine<'a>, fd: &'a FieldDecl) {
+    let ty = engine.query_field_type(fd.label.assert_as_var_decl());
+    if let Some(ref expr) = fd.assignment {
+        let mut checker = Checker { engine, return_ty: ty };
+        checker.check_assignment(ty, expr);
+    }
+}
+
+struct Checker<'q, 'a: 'q> {
+    engine: &'q mut QueryEngine<'a>,
+    return_ty: TypeId
+}
+
+impl<'q, 'a> Checker<'q, 'a> {
+    /// Checks that the type of the expression matches the expected one
+    fn check_assignment(&mut self, expected: TypeId, expr: &'a Expression) {
+        let found = self.engine.query_value_type(expr);
+        self.check_unify(expected, found, expr.span());
+    }
+
+    fn check_unify(&mut self, expected: TypeId, found: TypeId, span: Span) {
+        if !self.engine.types().unify(expected, found) {
+            let types = self.engine.types();
+            let diagnostic = errors::mismatched_types(span, &types.name(expected), &types.name(found));
+            self.engine.report(diagnostic);
+        }
+    }
+}
+
+impl<'q, 'a> Visitor<'a> for Checker<'q, 'a> {
+    fn visit_assign(&mut self, assign: &'a Assign) {
+        let expected = self.engine.query_value_type(&assign.target);
+        self.engine.check_assignable(&assign.target);
+        let operator = match assign.operator {
+            Some(operator) => operator,
+            None => return self.check_assignment(expected, &assign.expr)
+        };
+
+        // Compound assignments store the result of the operation in the target
+        let found = self.engine.query_value_type(&assign.expr);
+        let ty = self.engine.types().binary_op_ty(operator, expected, found);
+        match ty {
+            Some(ty) => self.check_unify(expected, ty, assign.span),
+            None => {
+                let types = self.engine.types();
+                let diagnostic = errors::invalid_operands(assign.span, operator, &types.name(expected), &types.name(found));
+                self.engine.report(diagnostic);
+            }
+        }
+    }
+
+    fn visit_constructor_call(&mut self, call: &'a ConstructorCall) {
+        // Note: this also checks the arguments
+        self.engine.query_constructor(call.label);
+    }
+
+    fn visit_expression(&mut self, expr: &'a Expression) {
+        // Note: this also checks the subexpressions
+        self.engine.query_expr_type(expr.label());
+    }
+
+    fn visit_return(&mut self, ret: &'a Return) {
+        let expected = self.return_ty;
+        match ret.expr {
+            Some(ref expr) => self.check_assignment(expected, expr),
+            None => {
+                let void_ty = self.engine.types().void_ty();
+                self.check_unify(expected, void_ty, ret.span);
+            }
+        }
+    }
+
+    fn visit_var_decl(&mut self, var_decl: &'a VarDecl) {
+        let ty = self.engine.query_var_type(var_decl.label.assert_as_var_decl());
+        if let Some(ref expr) = var_decl.expr {
+            self.check_assignment(ty, expr);
+        }
+    }
+
+    fn visit_if_then_else(&mut self, ite: &'a IfThenElse) {
+        let bool_ty = self.engine.types().bool_ty();
+        self.check_assignment(bool_ty, &ite.condition);
+
+        for statement in ite.then.iter().chain(&ite.else_) {
+            self.visit_statement(statement);
+        }
+    }
+
+    fn visit_while(&mut self, while_: &'a While) {
+        let bool_ty = self.engine.types().bool_ty();
+        self.check_assignment(bool_ty, &while_.condition);
+
+        for statement in &while_.body {
+            self.visit_statement(statement);
+        }
+    }
+
+    fn visit_do_while(&mut self, do_while: &'a DoWhile) {
+        for statement in &do_while.body {
+            self.visit_statement(statement);
+        }
+
+        let bool_ty = self.engine.types().bool_ty();
+        self.check_assignment(bool_ty, &do_while.condition);
+    }
+
+    fn visit_for(&mut self, for_: &'a For) {
+        if let Some(ref init) = for_.init {
+            self.visit_statement(init);
+        }
+        if let Some(ref condition) = for_.condition {
+            let bool_ty = self.engine.types().bool_ty();
+            self.check_assignment(bool_ty, condition);
+        }
+        if let Some(ref step) = for_.step {
+            self.visit_statement(step);
+        }
+
+        for statement in &for_.body {
+            self.visit_statement(statement);
+        }
+    }
+
+    fn visit_foreach(&mut self, foreach: &'a Foreach) {
+        let var_ty = self.engine.query_var_type(foreach.var.label.assert_as_var_decl());
+        let collection_ty = self.engine.query_value_type(&foreach.collection);
+        match self.engine.types().element_ty(collection_ty) {
+            Some(element_ty) => self.check_unify(var_ty, element_ty, foreach.var.span),
+            None => {
+                let diagnostic = errors::not_iterable(foreach.collection.span(), &self.engine.types().name(collection_ty));
+                self.engine.report(diagnostic);
+            }
+        }
+
+        for statement in &foreach.body {
+            self.visit_statement(statement);
+        }
+    }
+}
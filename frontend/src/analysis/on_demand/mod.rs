@@ -1,2 +1,3 @@
+mod checker;
 pub mod type_map;
 pub mod query_engine;
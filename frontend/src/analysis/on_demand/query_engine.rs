@@ -1,19 +1,36 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use analysis::{self, labels, AstPreprocessor, TypeId};
+use analysis::{self, errors, labels, AstPreprocessor, ClassTable, Resolver, ScopeId, TypeId};
 use ast::*;
+use diagnostics::{Diagnostic, Diagnostics};
+use syntax::{Location, SourceMap, Span};
+use super::checker;
 use super::type_map::TypeMap;
 
 pub struct QueryEngine<'a> {
     pub nodes: HashMap<Label, Node<'a>>,
     var_map: HashMap<Label, &'a VarDecl>,
     this_map: HashMap<Label, &'a ClassDecl>,
+    field_map: HashMap<Label, &'a FieldDecl>,
+    static_calls: HashSet<Label>,
+    foreach_vars: HashSet<Label>,
     types: TypeMap,
-    classes_by_name: HashMap<&'a str, &'a ClassDecl>,
-    entry_point: &'a MethodDecl
+    classes: ClassTable<'a>,
+    entry_point: Option<&'a MethodDecl>,
+    program: &'a Program,
+    diagnostics: Diagnostics,
+
+    // Caches, so each problem is only reported once
+    expr_types: HashMap<Label, Option<TypeId>>,
+    decl_types: HashMap<Label, TypeId>,
+    fields: HashMap<Label, Option<&'a FieldDecl>>,
+    methods: HashMap<Label, Option<&'a MethodDecl>>,
+    constructors: HashMap<Label, Option<&'a ConstructorDecl>>,
+    overrides: HashMap<Label, Option<&'a MethodDecl>>,
+    values: HashMap<Label, TypeId>,
+    checked: HashSet<Label>
 }
 
-#[allow(unused_variables)]
 impl<'a> QueryEngine<'a> {
     pub fn new(program: &'a Program) -> QueryEngine<'a> {
         let ast_data = AstPreprocessor::preprocess(program);
@@ -23,13 +40,27 @@ impl<'a> QueryEngine<'a> {
             nodes: ast_data.nodes,
             var_map: ast_data.var_map,
             this_map: ast_data.this_map,
-            classes_by_name: ast_data.classes_by_name,
+            field_map: ast_data.field_map,
+            static_calls: ast_data.static_calls,
+            foreach_vars: ast_data.foreach_vars,
+            classes: ast_data.classes,
             entry_point: ast_data.entry_point,
+            program,
+            diagnostics: ast_data.diagnostics,
+            expr_types: HashMap::new(),
+            decl_types: HashMap::new(),
+            fields: HashMap::new(),
+            methods: HashMap::new(),
+            constructors: HashMap::new(),
+            overrides: HashMap::new(),
+            values: HashMap::new(),
+            checked: HashSet::new()
         }
     }
 
-    pub fn entry_point(&self) -> &MethodDecl {
-        &self.entry_point
+    /// Note: programs without an entry point are reported as erroneous
+    pub fn entry_point(&self) -> Option<&'a MethodDecl> {
+        self.entry_point
     }
 
     pub fn types(&self) -> &TypeMap {
@@ -40,63 +71,228 @@ impl<'a> QueryEngine<'a> {
         &mut self.types
     }
 
-    pub fn query_field(&mut self, var_use: labels::VarUse) -> labels::VarDecl {
-        // Get the ClassDecl of the target
-        let target_label = self.nodes[&var_use.as_label()].downcast::<FieldAccess>().target.label();
-        // Note: a field target should always be an expression and have a type
-        let target_ty = self.query_expr_type(target_label).unwrap();
-        let decl_label = self.types.get(target_ty).class_decl();
-        let target_decl: &ClassDecl = self.nodes[&decl_label.as_label()].downcast();
+    pub fn source_map(&self) -> &'a SourceMap {
+        &self.program.source_map
+    }
+
+    /// The problems found so far
+    ///
+    /// Note: since analysis happens on demand, only the problems in the parts of the program that
+    /// have been queried are reported. Use `check_program` to find all of them.
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    pub(super) fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Type checks the whole program, reporting any problems as diagnostics
+    pub fn check_program(&mut self) {
+        let program = self.program;
+        for class in program.classes() {
+            for item in &class.items {
+                match *item {
+                    ClassItem::FieldDecl(ref fd) => self.check_field(fd.label.assert_as_var_decl()),
+                    ClassItem::MethodDecl(ref md) => self.check_method(md.label.assert_as_method_decl()),
+                    ClassItem::ConstructorDecl(ref cd) => self.check_constructor(cd.label.assert_as_constructor_decl())
+                }
+            }
+
+            // The implicit constructor calls the parameterless constructor of the base class
+            if class.constructors().next().is_none() {
+                self.query_constructor(class.label);
+            }
+
+            self.check_class(class.label.assert_as_class_decl());
+        }
+
+        for interface in program.interfaces() {
+            for md in &interface.methods {
+                self.check_method(md.label.assert_as_method_decl());
+            }
+        }
+    }
+
+    /// Checks that a class implements the methods of the interfaces listed in its head and, unless
+    /// it is abstract, the abstract methods it inherits, reporting any problems as diagnostics
+    pub fn check_class(&mut self, class: labels::ClassDecl) {
+        if self.checked.insert(class.as_label()) {
+            let cd: &ClassDecl = self.nodes[&class.as_label()].downcast();
+            self.check_implementations(cd);
+            self.check_abstract_members(cd);
+        }
+    }
+
+    /// Type checks the declaration and the body of a method, reporting any problems as diagnostics
+    pub fn check_method(&mut self, method: labels::MethodDecl) {
+        if self.checked.insert(method.as_label()) {
+            let md: &MethodDecl = self.nodes[&method.as_label()].downcast();
+            checker::check_method(self, md);
+        }
+    }
+
+    /// Type checks the declaration and the body of a constructor, reporting any problems as diagnostics
+    pub fn check_constructor(&mut self, constructor: labels::ConstructorDecl) {
+        if self.checked.insert(constructor.as_label()) {
+            let cd: &ConstructorDecl = self.nodes[&constructor.as_label()].downcast();
+            checker::check_constructor(self, cd);
+        }
+    }
+
+    /// Type checks the declaration of a field and its initializer
+    pub fn check_field(&mut self, field: labels::VarDecl) {
+        if self.checked.insert(field.as_label()) {
+            let fd: &FieldDecl = self.nodes[&field.as_label()].downcast();
+            checker::check_field(self, fd);
+        }
+    }
+
+    /// Returns the span of the source code corresponding to the node with the given label, or
+    /// `None` if there is no such node
+    pub fn query_span(&self, label: Label) -> Option<Span> {
+        self.nodes.get(&label).map(Node::span)
+    }
+
+    /// Returns the line and column where the node with the given label starts
+    ///
+    /// Note: nodes that were not parsed from source code have no location
+    pub fn query_location(&self, label: Label) -> Option<Location> {
+        self.query_span(label).and_then(|span| self.source_map().location(span))
+    }
+
+    /// Returns the field accessed by a field access or by an identifier
+    ///
+    /// Note: identifiers only refer to fields when they name a field of the enclosing class, which
+    /// must be static where `this` is not available
+    pub fn query_field(&mut self, var_use: labels::VarUse) -> Option<labels::VarDecl> {
+        let field = match self.nodes[&var_use.as_label()] {
+            Node::FieldAccess(fa) => self.resolve_field(fa),
+            Node::Identifier(i) => self.field_map.get(&i.label).cloned(),
+            _ => panic!("Called query_field on an AST node that is not a field access or an identifier")
+        };
 
-        // Look up the field
-        target_decl.find_field(&self.nodes[&var_use.as_label()].downcast::<FieldAccess>().field_name)
-                   .assert_as_var_decl()
+        field.map(|fd| fd.label.assert_as_var_decl())
     }
 
-    pub fn query_method_decl(&mut self, method_use: labels::MethodUse) -> labels::MethodDecl {
+    pub fn query_method_decl(&mut self, method_use: labels::MethodUse) -> Option<labels::MethodDecl> {
         // We assume no queries about Console.WriteLine will ever be made
+        let mc: &MethodCall = self.nodes[&method_use.as_label()].downcast();
+        self.resolve_method(mc).map(|md| md.label.assert_as_method_decl())
+    }
 
-        // Get the ClassDecl of the target
-        let target_label = self.nodes[&method_use.as_label()].downcast::<MethodCall>().target.label();
-        let is_static;
-        let decl_label = match self.query_expr_type(target_label) {
-            Some(target_ty) => {
-                // Non-static method
-                is_static = false;
-                self.types.get(target_ty).class_decl()
+    /// Returns the constructor called by a `new` expression or by a `this(...)` or `base(...)`
+    /// constructor call, given the label of either
+    ///
+    /// Constructors without an initializer implicitly call the parameterless constructor of the
+    /// base class, which is returned given the label of the calling constructor (or of the class,
+    /// for its implicit constructor)
+    ///
+    /// Note: `None` stands for the implicit parameterless constructor of classes that declare no
+    /// constructors, as well as for calls that could not be resolved (which are reported as errors)
+    /// and for implicit calls in classes without a base class (see `query_base_class`)
+    pub fn query_constructor(&mut self, call: Label) -> Option<labels::ConstructorDecl> {
+        let constructor = match self.nodes[&call] {
+            Node::New(n) => {
+                // The constructor is resolved along with the type of the expression
+                self.query_expr_type(labels::Expression(n.label));
+                self.constructors.get(&n.label).and_then(|&cd| cd)
             }
-            None => {
-                // Static method
-                // The target should be an Expression::Identifier, naming the type
-                is_static = true;
-                let class_name = self.nodes[&target_label.as_label()].downcast::<Identifier>().name.to_owned();
-                self.query_class_decl(&class_name)
+            Node::ConstructorCall(cc) if cc.is_base => {
+                let class_decl = self.this_map[&cc.label];
+                match self.classes.base_of(class_decl) {
+                    Some(base) => self.resolve_constructor(cc.label, base, &cc.args, cc.span),
+                    None => {
+                        if self.constructors.insert(cc.label, None).is_none() {
+                            for arg in &cc.args {
+                                self.query_value_type(arg);
+                            }
+                            self.report(errors::no_base_class(cc.span, self.classes.full_name(class_decl)));
+                        }
+                        None
+                    }
+                }
             }
+            Node::ConstructorCall(cc) => {
+                let class_decl = self.this_map[&cc.label];
+                self.resolve_constructor(cc.label, class_decl, &cc.args, cc.span)
+            }
+            Node::ConstructorDecl(cd) if cd.initializer.is_none() && !cd.is_static => {
+                let class_decl = self.this_map[&cd.label];
+                let base = self.classes.base_of(class_decl);
+                base.and_then(|base| self.resolve_constructor(cd.label, base, &[], cd.span))
+            }
+            Node::ClassDecl(cd) if cd.constructors().next().is_none() => {
+                let base = self.classes.base_of(cd);
+                base.and_then(|base| self.resolve_constructor(cd.label, base, &[], cd.span))
+            }
+            _ => panic!("Called query_constructor on an AST node that is not a constructor call")
         };
 
-        let target_decl: &ClassDecl = self.nodes[&decl_label.as_label()].downcast();
+        constructor.map(|cd| cd.label.assert_as_constructor_decl())
+    }
 
-        // Look up the field
-        target_decl.find_method(is_static, &self.nodes[&method_use.as_label()].downcast::<MethodCall>().method_name)
-                   .assert_as_method_decl()
+    /// Returns the class the given class inherits from, if any
+    pub fn query_base_class(&mut self, class: labels::ClassDecl) -> Option<labels::ClassDecl> {
+        let class_decl: &ClassDecl = self.nodes[&class.as_label()].downcast();
+        self.classes.base_of(class_decl).map(|base| base.label.assert_as_class_decl())
     }
 
-    pub fn query_param_types(&mut self, method: labels::MethodDecl) -> Vec<TypeId> {
+    /// Returns the method of a base class that the given `override` method overrides
+    ///
+    /// Note: `None` for methods that are not `override`, as well as for those that override no
+    /// method or a method that cannot be overridden (which are reported as errors)
+    pub fn query_overridden_method(&mut self, method: labels::MethodDecl) -> Option<labels::MethodDecl> {
         let md: &MethodDecl = self.nodes[&method.as_label()].downcast();
-        let mut tys = Vec::new();
-        for param in &md.params {
-            let param_ty = self.types.get_from_ast_ty(&param.ty, &self.classes_by_name);
-            tys.push(param_ty);
+        self.resolve_override(md).map(|md| md.label.assert_as_method_decl())
+    }
+
+    /// Returns the interfaces listed in the head of the class, followed by the interfaces they
+    /// extend, without repetitions
+    ///
+    /// Note: the interfaces implemented by the base classes are not included
+    pub fn query_interfaces(&mut self, class: labels::ClassDecl) -> Vec<labels::InterfaceDecl> {
+        let mut interfaces: Vec<&InterfaceDecl> = Vec::new();
+        for &interface in self.classes.interfaces_of(class.as_label()) {
+            for id in self.classes.interface_hierarchy(interface) {
+                if interfaces.iter().all(|other| other.label != id.label) {
+                    interfaces.push(id);
+                }
+            }
         }
-        tys
+
+        interfaces.into_iter().map(|id| id.label.assert_as_interface_decl()).collect()
+    }
+
+    /// Returns the method of the class, possibly inherited, that implements the given method of
+    /// an interface
+    ///
+    /// Note: `None` if the class has no such method, which is reported as an error for the classes
+    /// that list the interface in their head (see `check_class`)
+    pub fn query_implementation(&mut self, class: labels::ClassDecl, method: labels::MethodDecl) -> Option<labels::MethodDecl> {
+        let class_decl: &ClassDecl = self.nodes[&class.as_label()].downcast();
+        let md: &MethodDecl = self.nodes[&method.as_label()].downcast();
+        self.find_implementation(class_decl, md).map(|md| md.label.assert_as_method_decl())
+    }
+
+    pub fn query_param_types(&mut self, method: labels::MethodDecl) -> Vec<TypeId> {
+        let md: &MethodDecl = self.nodes[&method.as_label()].downcast();
+        md.params.iter().map(|param| self.query_var_type(param.label.assert_as_var_decl())).collect()
     }
 
-    pub fn query_class_decl(&mut self, name: &str) -> labels::ClassDecl {
-        self.classes_by_name.get(name).expect("No class decl exist for given class name").label.assert_as_class_decl()
+    /// Returns the class with the given fully qualified name (e.g. `A.B.Foo`)
+    pub fn query_class_decl(&mut self, full_name: &str) -> Option<labels::ClassDecl> {
+        self.classes.get(full_name).map(|cd| cd.label.assert_as_class_decl())
+    }
+
+    /// Returns the class that a (possibly qualified) name refers to, when written at the node with
+    /// the given label. This takes into account the enclosing namespaces and the using directives.
+    pub fn query_class_decl_at(&mut self, name: &str, label: Label) -> Option<labels::ClassDecl> {
+        self.classes.resolve(name, self.classes.scope_of(label)).map(|cd| cd.label.assert_as_class_decl())
     }
 
     pub fn query_var_decl(&mut self, label: Label) -> labels::VarDecl {
-        // The label may correspong to a VarAssign, VarDecl or Identifier
+        // The label may correspond to a VarDecl or an Identifier
         if let Node::VarDecl(_) = self.nodes[&label] {
             return label.assert_as_var_decl();
         }
@@ -107,12 +303,17 @@ impl<'a> QueryEngine<'a> {
 
     pub fn query_var_type(&mut self, identifier: labels::VarDecl) -> TypeId {
         let vd: &VarDecl = self.nodes[&identifier.as_label()].downcast();
-        self.types.get_from_ast_ty(&vd.ty, &self.classes_by_name)
+        self.query_decl_type(vd.label, &vd.ty, vd.span)
+    }
+
+    pub fn query_field_type(&mut self, field: labels::VarDecl) -> TypeId {
+        let fd: &FieldDecl = self.nodes[&field.as_label()].downcast();
+        self.query_decl_type(fd.label, &fd.ty, fd.span)
     }
 
     pub fn query_return_type(&mut self, method: labels::MethodDecl) -> TypeId {
         let md: &MethodDecl = self.nodes[&method.as_label()].downcast();
-        self.types.get_from_ast_ty(&md.return_ty, &self.classes_by_name)
+        self.query_decl_type(md.label, &md.return_ty, md.span)
     }
 
     pub fn query_is_static(&mut self, method: labels::MethodDecl) -> bool {
@@ -121,128 +322,263 @@ impl<'a> QueryEngine<'a> {
 
     /// Returns the type of an expression.
     ///
-    /// Note: references to undefined variables have no type.
+    /// Note: identifiers referring to a class have no type. Expressions that could not be type checked
+    /// have the error type, and the problem is reported as a diagnostic.
     pub fn query_expr_type(&mut self, expr: labels::Expression) -> Option<TypeId> {
+        if let Some(&ty) = self.expr_types.get(&expr.as_label()) {
+            return ty;
+        }
+
+        let ty = self.compute_expr_type(expr);
+        self.expr_types.insert(expr.as_label(), ty);
+        ty
+    }
+
+    /// Returns the type of an expression that is used as a value
+    ///
+    /// Note: if the expression refers to a class, an error is reported and the error type returned
+    pub fn query_value_type(&mut self, expr: &'a Expression) -> TypeId {
+        if let Some(ty) = self.query_expr_type(expr.label()) {
+            return ty;
+        }
+
+        let label = expr.label().as_label();
+        if let Some(&ty) = self.values.get(&label) {
+            return ty;
+        }
+
+        // Only names of classes and namespaces have no type
+        let name = expr.path().expect("Only names of classes and namespaces have no type");
+        let kind = if self.classes.resolve(&name, self.classes.scope_of(label)).is_some() { "class" } else { "namespace" };
+        self.report(errors::expected_value(expr.span(), kind, &name));
+        let error_ty = self.types.error_ty();
+        self.values.insert(label, error_ty);
+        error_ty
+    }
+
+    fn compute_expr_type(&mut self, expr: labels::Expression) -> Option<TypeId> {
         // Here we go with the type checker!
         match self.nodes[&expr.as_label()] {
             Node::FieldAccess(fa) => {
-                // Get the type of the target
-                let target_ty = self.query_expr_type(fa.target.label()).expect("Target of field access has no type");
-                // Go to the class, find the field declaration and return its type
-                match self.types.get(target_ty) {
-                    analysis::Type::Class(cd) => {
-                        let class_decl: &ClassDecl = self.nodes[&cd.as_label()].downcast();
-                        let field = class_decl.find_field(&fa.field_name);
-                        let field_decl: &FieldDecl = self.nodes[&field].downcast();
-                        Some(self.types.get_from_ast_ty(&field_decl.ty, &self.classes_by_name))
+                // The target may be a namespace, in which case this is the name of a class or a nested namespace
+                if let Some(path) = fa.target.path() {
+                    let scope = self.classes.scope_of(fa.label);
+                    if self.query_expr_type(fa.target.label()).is_none() && !self.classes.is_class(&path, scope) {
+                        let path = format!("{}.{}", path, fa.field_name);
+                        if self.classes.is_class(&path, scope) || self.classes.is_namespace(&path, scope) {
+                            return None;
+                        }
+
+                        self.report(errors::unknown_type(fa.span, &path));
+                        return Some(self.types.error_ty());
                     }
-                    x => {
-                        panic!("Attempted to access a field of something that is not a class: {:?}", x)
+                }
+
+                // Arrays have properties instead of fields
+                if let Some(target_ty) = self.query_expr_type(fa.target.label()) {
+                    if let Some(ty) = self.types.property_ty(target_ty, &fa.field_name) {
+                        return Some(ty);
                     }
                 }
+
+                // Find the field declaration (possibly static) and return its type
+                Some(match self.resolve_field(fa) {
+                    Some(fd) => self.query_field_type(fd.label.assert_as_var_decl()),
+                    None => self.types.error_ty()
+                })
             }
             Node::MethodCall(mc) => {
                 // Built in Console.WriteLine
                 if mc.is_console_write_line() {
+                    self.check_console_write_line(mc);
                     return Some(self.types.void_ty());
                 }
 
-                // Get class decl of target
-                let target_ty = self.query_expr_type(mc.target.label());
-                let class_decl = match target_ty {
-                    Some(ty) => {
-                        // Non-static method
-                        let decl_label = self.types.get(ty).class_decl();
-
-                        // Stupid workaround the borrow checker
-                        let class_name: &str = &self.nodes[&decl_label.as_label()].downcast::<ClassDecl>().name;
-                        self.classes_by_name[class_name]
-                    }
+                let method_decl = match self.resolve_method(mc) {
+                    Some(md) => md,
                     None => {
-                        // Static method
-                        let class: &str = &mc.target.identifier().name;
-                        self.classes_by_name[class]
+                        // Still look for problems in the arguments
+                        for arg in &mc.args {
+                            self.query_value_type(arg);
+                        }
+
+                        return Some(self.types.error_ty());
                     }
                 };
 
-                // Find the method
-                let method_decl = class_decl.find_method_any(&mc.method_name);
-
-                // Collect parameter types
-                let mut param_tys = Vec::new();
-                for param in &method_decl.params {
-                    let ty = self.types.get_from_ast_ty(&param.ty, &self.classes_by_name);
-                    param_tys.push(ty);
-                }
-
-                // Collect arg types
-                let mut arg_tys = Vec::new();
-                for arg in &mc.args {
-                    let ty = self.query_expr_type(arg.label()).expect("Unable to get type of method argument");
-                    arg_tys.push(ty);
-                }
+                let param_tys = self.query_param_types(method_decl.label.assert_as_method_decl());
+                let arg_tys: Vec<_> = mc.args.iter().map(|arg| self.query_value_type(arg)).collect();
 
                 // Check length and unification of types
                 if param_tys.len() != arg_tys.len() {
-                    panic!("Mismatched param and arg length in method call");
+                    let diagnostic = errors::wrong_argument_count(mc.span, &method_decl.name, param_tys.len(), arg_tys.len())
+                        .with_secondary(method_decl.span, "method defined here".to_string());
+                    self.report(diagnostic);
                 }
-                for (&param_ty, &arg_ty) in param_tys.iter().zip(arg_tys.iter()) {
+
+                for ((arg, param), (&arg_ty, &param_ty)) in mc.args.iter().zip(&method_decl.params).zip(arg_tys.iter().zip(&param_tys)) {
                     if !self.types.unify(param_ty, arg_ty) {
-                        panic!("Mismatched types in method call arguments");
+                        let diagnostic = errors::mismatched_types(arg.span(), &self.types.name(param_ty), &self.types.name(arg_ty))
+                            .with_secondary(param.span, "parameter declared here".to_string());
+                        self.report(diagnostic);
                     }
                 }
 
                 // The type of the method call is the return type of the method decl
-                Some(self.types.get_from_ast_ty(&method_decl.return_ty, &self.classes_by_name))
+                Some(self.query_return_type(method_decl.label.assert_as_method_decl()))
             }
             Node::Identifier(i) => {
                 // Get the var decl associated to this identifier and return its type
-                // Note: it is possible that the identifier refers to a class name. In that case we return None.
+                // Note: it is possible that the identifier refers to a class or a namespace. In that case we return None.
+                if let Some(field_decl) = self.field_map.get(&i.label) {
+                    return Some(self.query_field_type(field_decl.label.assert_as_var_decl()));
+                }
+
                 match self.var_map.get(&i.label) {
                     Some(var_decl) => {
-                        Some(self.types.get_from_ast_ty(&var_decl.ty, &self.classes_by_name))
+                        Some(self.query_var_type(var_decl.label.assert_as_var_decl()))
                     }
-                    None => {
+                    None if self.classes.is_class(&i.name, self.classes.scope_of(i.label))
+                            || self.classes.is_namespace(&i.name, self.classes.scope_of(i.label)) => {
                         None
                     }
+                    None => {
+                        let scope = self.classes.scope_of(i.label);
+                        let diagnostic = self.classes.ambiguity_error(i.span, &i.name, scope).unwrap_or_else(|| errors::unresolved_name(i.span, &i.name));
+                        self.report(diagnostic);
+                        Some(self.types.error_ty())
+                    }
                 }
             }
             Node::BinaryOp(bo) => {
-                let left_ty = self.query_expr_type(bo.left.label()).expect("No type found for lhs of binary op");
-                let right_ty = self.query_expr_type(bo.right.label()).expect("No type found for rhs of binary op");
-                let well_typed = left_ty == right_ty && left_ty == self.types.int_ty();
-                if !well_typed {
-                    panic!("Mismatched types in binary operation");
+                let left_ty = self.query_value_type(&bo.left);
+                let right_ty = self.query_value_type(&bo.right);
+
+                match self.types.binary_op_ty(bo.operator, left_ty, right_ty) {
+                    Some(ty) => Some(ty),
+                    None => {
+                        let diagnostic = errors::invalid_operands(bo.span, bo.operator, &self.types.name(left_ty), &self.types.name(right_ty));
+                        self.report(diagnostic);
+                        Some(self.types.error_ty())
+                    }
+                }
+            }
+            Node::UnaryOp(uo) => {
+                let operand_ty = self.query_value_type(&uo.operand);
+                if uo.operator.is_increment() {
+                    self.check_assignable(&uo.operand);
+                }
+
+                match self.types.unary_op_ty(uo.operator, operand_ty) {
+                    Some(ty) => Some(ty),
+                    None => {
+                        let diagnostic = errors::invalid_operand(uo.span, uo.operator, &self.types.name(operand_ty));
+                        self.report(diagnostic);
+                        Some(self.types.error_ty())
+                    }
                 }
-                // We only support int operations
-                Some(self.types.int_ty())
             }
             Node::Literal(l) => {
-                match &l.kind {
-                    &LiteralKind::Bool(_) => {
+                match l.kind {
+                    LiteralKind::Bool(_) => {
                         Some(self.types.bool_ty())
                     }
-                    &LiteralKind::Int(_) => {
+                    LiteralKind::Int(_) => {
                         Some(self.types.int_ty())
                     }
-                    &LiteralKind::Null => {
+                    LiteralKind::Null => {
                         Some(self.types.any_ty())
                     }
-                    &LiteralKind::String(_) => {
+                    LiteralKind::String(_) => {
                         Some(self.types.string_ty())
                     }
-                    &LiteralKind::Array(ref ast_ty, _) => {
-                        let inner_ty = self.types.get_from_ast_ty(ast_ty, &self.classes_by_name);
-                        Some(self.types.get_id(analysis::Type::Array(inner_ty)))
+                    LiteralKind::Array(ref ast_ty, ref items) => {
+                        let inner_ty = self.resolve_ast_ty(ast_ty, self.classes.scope_of(l.label), l.span);
+                        for item in items {
+                            let item_ty = self.query_value_type(item);
+                            if !self.types.unify(inner_ty, item_ty) {
+                                let diagnostic = errors::mismatched_types(item.span(), &self.types.name(inner_ty), &self.types.name(item_ty));
+                                self.report(diagnostic);
+                            }
+                        }
+
+                        if inner_ty == self.types.error_ty() {
+                            Some(inner_ty)
+                        } else {
+                            Some(self.types.get_id(analysis::Type::Array(inner_ty)))
+                        }
                     }
                 }
             }
             Node::New(n) => {
-                Some(self.types.get_from_class_name(&n.class_name, &self.classes_by_name))
+                for arg in &n.args {
+                    self.query_value_type(arg);
+                }
+
+                let scope = self.classes.scope_of(n.label);
+                if let Some(interface) = self.classes.resolve_interface(&n.class_name, scope) {
+                    let diagnostic = errors::abstract_instantiation(n.span, "interface", self.classes.interface_full_name(interface));
+                    self.report(diagnostic);
+                    return Some(self.types.get_from_interface(interface, &self.classes));
+                }
+
+                match self.classes.resolve(&n.class_name, scope) {
+                    Some(class_decl) if class_decl.is_abstract => {
+                        let diagnostic = errors::abstract_instantiation(n.span, "abstract class", self.classes.full_name(class_decl));
+                        self.report(diagnostic);
+                        Some(self.class_ty(class_decl))
+                    }
+                    Some(class_decl) => {
+                        self.resolve_constructor(n.label, class_decl, &n.args, n.span);
+                        Some(self.class_ty(class_decl))
+                    }
+                    None => {
+                        let diagnostic = self.classes.ambiguity_error(n.span, &n.class_name, scope)
+                                             .unwrap_or_else(|| errors::unknown_type(n.span, &n.class_name));
+                        self.report(diagnostic);
+                        Some(self.types.error_ty())
+                    }
+                }
+            }
+            Node::NewArray(n) => {
+                self.check_index(&n.length);
+                let elem_ty = self.resolve_ast_ty(&n.elem_ty, self.classes.scope_of(n.label), n.span);
+                if elem_ty == self.types.error_ty() {
+                    Some(elem_ty)
+                } else {
+                    Some(self.types.get_id(analysis::Type::Array(elem_ty)))
+                }
+            }
+            Node::Index(i) => {
+                let target_ty = self.query_value_type(&i.target);
+                self.check_index(&i.index);
+                match self.types.element_ty(target_ty) {
+                    Some(ty) => Some(ty),
+                    None => {
+                        let diagnostic = errors::not_indexable(i.target.span(), &self.types.name(target_ty));
+                        self.report(diagnostic);
+                        Some(self.types.error_ty())
+                    }
+                }
             }
             Node::This(t) => {
                 let class_decl = self.this_map[&t.label];
-                Some(self.types.get_from_class_name(&class_decl.name, &self.classes_by_name))
+                Some(self.class_ty(class_decl))
+            }
+            Node::Base(b) => {
+                // `base` is the current object, seen as an instance of the base class
+                let class_decl = self.this_map[&b.label];
+                match self.classes.base_of(class_decl) {
+                    Some(base) => Some(self.class_ty(base)),
+                    None => {
+                        self.report(errors::no_base_class(b.span, self.classes.full_name(class_decl)));
+                        Some(self.types.error_ty())
+                    }
+                }
+            }
+            Node::ErrorNode(_) => {
+                // The syntax error has already been reported
+                Some(self.types.error_ty())
             }
             // Not an expression
             _ => {
@@ -250,4 +586,585 @@ impl<'a> QueryEngine<'a> {
             }
         }
     }
+
+    /// Checks that an index or an array length is an `int`
+    fn check_index(&mut self, index: &'a Expression) {
+        let int_ty = self.types.int_ty();
+        let ty = self.query_value_type(index);
+        if !self.types.unify(int_ty, ty) {
+            let diagnostic = errors::mismatched_types(index.span(), "int", &self.types.name(ty));
+            self.report(diagnostic);
+        }
+    }
+
+    /// Reports assignments and increments of values that cannot be modified
+    pub(super) fn check_assignable(&mut self, target: &'a Expression) {
+        match *target {
+            Expression::Identifier(ref i) if self.var_map.get(&i.label).is_some_and(|vd| self.foreach_vars.contains(&vd.label)) => {
+                self.report(errors::foreach_variable_assignment(i.span, &i.name));
+            }
+            Expression::FieldAccess(ref fa) => {
+                if let Some(target_ty) = self.query_expr_type(fa.target.label()) {
+                    if self.types.property_ty(target_ty, &fa.field_name).is_some() {
+                        self.report(errors::read_only(fa.span, &fa.field_name));
+                    }
+                }
+            }
+            _ => ()
+        }
+    }
+
+    fn check_console_write_line(&mut self, mc: &'a MethodCall) {
+        if mc.args.len() != 1 {
+            self.report(errors::wrong_argument_count(mc.span, "Console.WriteLine", 1, mc.args.len()));
+        }
+
+        for arg in &mc.args {
+            let ty = self.query_value_type(arg);
+            if ty == self.types.void_ty() {
+                self.report(errors::mismatched_types(arg.span(), "a value", "void"));
+            }
+        }
+    }
+
+    /// Returns the type of the given class
+    fn class_ty(&mut self, class_decl: &ClassDecl) -> TypeId {
+        self.types.get_from_class(class_decl, &self.classes)
+    }
+
+    /// Resolves a type written in the given scope, reporting an error if it is unknown
+    fn resolve_ast_ty(&mut self, ty: &Type, scope: ScopeId, span: Span) -> TypeId {
+        match self.types.get_from_ast_ty(ty, &self.classes, scope) {
+            Ok(ty) => ty,
+            Err(e) => {
+                let diagnostic = self.classes.ambiguity_error(span, &e.name, scope).unwrap_or_else(|| errors::unknown_type(span, &e.name));
+                self.report(diagnostic);
+                self.types.error_ty()
+            }
+        }
+    }
+
+    /// Resolves the declared type of a variable, field or method
+    fn query_decl_type(&mut self, decl: Label, ty: &Type, span: Span) -> TypeId {
+        if let Some(&ty) = self.decl_types.get(&decl) {
+            return ty;
+        }
+
+        let ty = self.resolve_ast_ty(ty, self.classes.scope_of(decl), span);
+        self.decl_types.insert(decl, ty);
+        ty
+    }
+
+    /// Returns the declaration of the interface of the given type, if it is an interface
+    fn interface_of_type(&self, ty: TypeId) -> Option<&'a InterfaceDecl> {
+        if ty == self.types.any_ty() {
+            return None;
+        }
+
+        self.types.get(ty).as_interface_decl().map(|id| self.nodes[&id.as_label()].downcast())
+    }
+
+    /// Returns the declaration of the class of the given type, reporting an error if the type is not a class
+    fn class_of_type(&mut self, ty: TypeId, span: Span) -> Option<&'a ClassDecl> {
+        if ty == self.types.error_ty() {
+            return None;
+        }
+
+        if ty != self.types.any_ty() {
+            if let analysis::Type::Class(cd) = self.types.get(ty) {
+                return Some(self.nodes[&cd.as_label()].downcast());
+            }
+        }
+
+        let diagnostic = errors::not_an_object(span, &self.types.name(ty));
+        self.report(diagnostic);
+        None
+    }
+
+    fn resolve_field(&mut self, fa: &'a FieldAccess) -> Option<&'a FieldDecl> {
+        if let Some(&field) = self.fields.get(&fa.label) {
+            return field;
+        }
+
+        let class_decl = match self.query_expr_type(fa.target.label()) {
+            // Instance field (interfaces have no fields)
+            Some(target_ty) => match self.interface_of_type(target_ty) {
+                Some(interface) => {
+                    let diagnostic = errors::unknown_field(fa.span, "interface", self.classes.interface_full_name(interface), &fa.field_name);
+                    self.report(diagnostic);
+                    None
+                }
+                None => self.class_of_type(target_ty, fa.target.span()).map(|cd| (cd, false))
+            },
+            // Static field. The target names a class, since namespaces are handled by the caller
+            None => {
+                let path = fa.target.path().expect("Only names of classes and namespaces have no type");
+                let class_decl = self.classes.resolve(&path, self.classes.scope_of(fa.label));
+                Some((class_decl.expect("The target of a field access without a type is a class"), true))
+            }
+        };
+
+        let field = class_decl.and_then(|(class_decl, is_static)| {
+            match self.classes.find_field(class_decl, &fa.field_name) {
+                Some(fd) => {
+                    if fd.is_static != is_static {
+                        let diagnostic = errors::static_field_mismatch(fa.span, &fd.name, fd.is_static)
+                            .with_secondary(fd.span, "field defined here".to_string());
+                        self.report(diagnostic);
+                    }
+
+                    Some(fd)
+                }
+                None => {
+                    let diagnostic = errors::unknown_field(fa.span, "class", self.classes.full_name(class_decl), &fa.field_name);
+                    self.report(diagnostic);
+                    None
+                }
+            }
+        });
+
+        self.fields.insert(fa.label, field);
+        field
+    }
+
+    fn resolve_method(&mut self, mc: &'a MethodCall) -> Option<&'a MethodDecl> {
+        if let Some(&method) = self.methods.get(&mc.label) {
+            return method;
+        }
+
+        let target = match mc.target {
+            Some(ref target) => target,
+            // A call without target calls a method of the enclosing class (or of one of its base
+            // classes), which must be static if there is no `this`
+            None => {
+                let is_static = if self.static_calls.contains(&mc.label) { Some(true) } else { None };
+                let method = self.resolve_class_method(mc, self.this_map[&mc.label], is_static);
+                self.methods.insert(mc.label, method);
+                return method;
+            }
+        };
+
+        // Method of an interface, or of one of the interfaces it extends
+        let target_ty = self.query_expr_type(target.label());
+        if let Some(interface) = target_ty.and_then(|ty| self.interface_of_type(ty)) {
+            let overloads = self.classes.find_interface_overloads(interface, &mc.method_name);
+            let method = if overloads.iter().all(Vec::is_empty) {
+                let diagnostic = errors::unknown_method(mc.span, "interface", self.classes.interface_full_name(interface), &mc.method_name);
+                self.report(diagnostic);
+                None
+            } else {
+                self.resolve_overload(mc, overloads)
+            };
+            self.methods.insert(mc.label, method);
+            return method;
+        }
+
+        let class_decl = match target_ty {
+            // Non-static method
+            Some(target_ty) => self.class_of_type(target_ty, target.span()).map(|cd| (cd, false)),
+            // Static method. The target names a class (or a namespace, which is an error)
+            None => {
+                let path = target.path().expect("Only names of classes and namespaces have no type");
+                match self.classes.resolve(&path, self.classes.scope_of(mc.label)) {
+                    Some(cd) => Some((cd, true)),
+                    None => {
+                        self.report(errors::expected_class(target.span(), &path));
+                        None
+                    }
+                }
+            }
+        };
+
+        let method = class_decl.and_then(|(class_decl, is_static)| self.resolve_class_method(mc, class_decl, Some(is_static)));
+        self.methods.insert(mc.label, method);
+        method
+    }
+
+    /// Finds the constructor of the class that takes the given arguments (see `Resolver::find_constructor`)
+    ///
+    /// Note: see `query_constructor` for the meaning of `None`
+    fn resolve_constructor(&mut self, call: Label, class_decl: &'a ClassDecl, args: &'a [Expression], span: Span) -> Option<&'a ConstructorDecl> {
+        if let Some(&constructor) = self.constructors.get(&call) {
+            return constructor;
+        }
+
+        let constructor = self.find_constructor(class_decl, args, span);
+        self.constructors.insert(call, constructor);
+        constructor
+    }
+}
+
+impl<'a> Resolver<'a> for QueryEngine<'a> {
+    fn classes(&self) -> &ClassTable<'a> {
+        &self.classes
+    }
+
+    fn types(&self) -> &TypeMap {
+        &self.types
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn value_ty(&mut self, expr: &'a Expression) -> TypeId {
+        self.query_value_type(expr)
+    }
+
+    fn param_ty(&mut self, param: &'a VarDecl) -> TypeId {
+        self.query_var_type(param.label.assert_as_var_decl())
+    }
+
+    fn return_ty(&mut self, md: &'a MethodDecl) -> TypeId {
+        self.query_return_type(md.label.assert_as_method_decl())
+    }
+
+    fn class_of(&self, member: Label) -> &'a ClassDecl {
+        self.this_map[&member]
+    }
+
+    fn resolve_override(&mut self, md: &'a MethodDecl) -> Option<&'a MethodDecl> {
+        if let Some(&overridden) = self.overrides.get(&md.label) {
+            return overridden;
+        }
+
+        let overridden = self.find_overridden(md);
+        self.overrides.insert(md.label, overridden);
+        overridden
+    }
+
+    fn called_constructor(&mut self, call: &'a ConstructorCall) -> Option<&'a ConstructorDecl> {
+        self.query_constructor(call.label).map(|cd| self.nodes[&cd.as_label()].downcast())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ast::*;
+    use diagnostics::Code;
+    use sample_programs::problems;
+    use syntax::{self, Location};
+    use super::QueryEngine;
+
+    /// Checks a program, returning the codes and locations of its diagnostics
+    fn diagnostics_of(src: &str) -> Vec<(Code, Location)> {
+        let (program, diagnostics) = syntax::parse("test.cs", src);
+        assert!(diagnostics.is_empty());
+        let mut engine = QueryEngine::new(&program);
+        engine.check_program();
+        engine.diagnostics().iter()
+              .map(|d| (d.code, program.source_map.location(d.primary_span().unwrap()).unwrap()))
+              .collect()
+    }
+
+    #[test]
+    fn spans_and_locations() {
+        let src = "class Program {\n    static void Main() {\n        int x = 1 + Program.Two();\n    }\n    static int Two() { return 2; }\n}";
+        let (program, diagnostics) = syntax::parse("test.cs", src);
+        assert!(diagnostics.is_empty());
+        let engine = QueryEngine::new(&program);
+
+        let (decl_label, expr_label) = match program.methods().next().unwrap().body.as_ref().unwrap()[0] {
+            Statement::VarDecl(ref vd) => (vd.label, vd.expr.as_ref().unwrap().label().as_label()),
+            _ => unreachable!()
+        };
+
+        let decl_span = engine.query_span(decl_label).unwrap();
+        let file = program.source_map.file(decl_span.file);
+        assert_eq!(file.text(decl_span), "int x = 1 + Program.Two();");
+        assert_eq!(file.text(engine.query_span(expr_label).unwrap()), "1 + Program.Two()");
+        assert_eq!(engine.query_location(expr_label), Some(Location { line: 3, column: 17 }));
+
+        let two = program.methods().nth(1).unwrap();
+        assert_eq!(engine.query_location(two.label), Some(Location { line: 5, column: 5 }));
+
+        // Labels that do not belong to the program have no span
+        assert_eq!(engine.query_span(fresh_label()), None);
+        assert_eq!(engine.query_location(fresh_label()), None);
+    }
+
+    #[test]
+    fn reports_all_problems() {
+        let (program, diagnostics) = syntax::parse("test.cs", problems::REPORTS_ALL_PROBLEMS);
+        assert!(diagnostics.is_empty());
+        let mut engine = QueryEngine::new(&program);
+
+        // Nothing has been queried yet
+        assert!(engine.diagnostics().is_empty());
+
+        engine.check_program();
+        let codes: Vec<_> = engine.diagnostics().iter().map(|d| d.code).collect();
+        assert_eq!(codes, vec![Code::MismatchedTypes, Code::InvalidOperands, Code::MismatchedTypes]);
+
+        // Problems are only reported once
+        engine.check_program();
+        assert_eq!(engine.diagnostics().len(), 3);
+
+        let locations: Vec<_> = engine.diagnostics().iter()
+                                      .map(|d| program.source_map.location(d.primary_span().unwrap()).unwrap())
+                                      .collect();
+        assert_eq!(locations, vec![Location { line: 3, column: 17 }, Location { line: 5, column: 20 }, Location { line: 7, column: 24 }]);
+    }
+
+    #[test]
+    fn loop_conditions_are_bool() {
+        let src = problems::LOOP_CONDITIONS_ARE_BOOL;
+        assert_eq!(diagnostics_of(src), vec![(Code::MismatchedTypes, Location { line: 3, column: 16 }),
+                                             (Code::MismatchedTypes, Location { line: 4, column: 23 })]);
+    }
+
+    #[test]
+    fn foreach_needs_a_collection() {
+        let src = problems::FOREACH_NEEDS_A_COLLECTION;
+        assert_eq!(diagnostics_of(src), vec![(Code::NotIterable, Location { line: 3, column: 27 }),
+                                             (Code::MismatchedTypes, Location { line: 4, column: 18 }),
+                                             (Code::MismatchedTypes, Location { line: 5, column: 25 }),
+                                             (Code::ReadOnly, Location { line: 6, column: 46 }),
+                                             (Code::ReadOnly, Location { line: 6, column: 55 })]);
+    }
+
+    #[test]
+    fn jumps_outside_of_loops() {
+        let src = problems::JUMPS_OUTSIDE_OF_LOOPS;
+        assert_eq!(diagnostics_of(src), vec![(Code::OutsideLoop, Location { line: 3, column: 9 }),
+                                             (Code::OutsideLoop, Location { line: 5, column: 21 })]);
+    }
+
+    #[test]
+    fn comparison_operands() {
+        let src = problems::COMPARISON_OPERANDS;
+        assert_eq!(diagnostics_of(src), vec![(Code::InvalidOperands, Location { line: 3, column: 19 }),
+                                             (Code::MismatchedTypes, Location { line: 4, column: 21 }),
+                                             (Code::InvalidOperands, Location { line: 5, column: 18 }),
+                                             (Code::InvalidOperands, Location { line: 6, column: 18 }),
+                                             (Code::InvalidOperands, Location { line: 7, column: 18 })]);
+    }
+
+    #[test]
+    fn logical_operands() {
+        let src = problems::LOGICAL_OPERANDS;
+        assert_eq!(diagnostics_of(src), vec![(Code::InvalidOperands, Location { line: 4, column: 18 }),
+                                             (Code::InvalidOperands, Location { line: 5, column: 18 }),
+                                             (Code::MismatchedTypes, Location { line: 6, column: 17 })]);
+    }
+
+    #[test]
+    fn assignment_operands() {
+        let src = problems::ASSIGNMENT_OPERANDS;
+        assert_eq!(diagnostics_of(src), vec![(Code::InvalidOperands, Location { line: 6, column: 9 }),
+                                             (Code::InvalidOperands, Location { line: 7, column: 9 }),
+                                             (Code::UnresolvedName, Location { line: 8, column: 9 }),
+                                             (Code::UnresolvedName, Location { line: 9, column: 9 })]);
+    }
+
+    #[test]
+    fn array_operands() {
+        let src = problems::ARRAY_OPERANDS;
+        assert_eq!(diagnostics_of(src), vec![(Code::MismatchedTypes, Location { line: 3, column: 27 }),
+                                             (Code::MismatchedTypes, Location { line: 4, column: 11 }),
+                                             (Code::MismatchedTypes, Location { line: 5, column: 18 }),
+                                             (Code::ReadOnly, Location { line: 6, column: 9 }),
+                                             (Code::NotIndexable, Location { line: 8, column: 9 }),
+                                             (Code::UnknownType, Location { line: 9, column: 9 }),
+                                             (Code::UnknownType, Location { line: 9, column: 19 })]);
+    }
+
+    #[test]
+    fn constructor_calls() {
+        let src = problems::CONSTRUCTOR_CALLS;
+        assert_eq!(diagnostics_of(src), vec![(Code::NoMatchingOverload, Location { line: 3, column: 24 }),
+                                             (Code::RecursiveConstructor, Location { line: 6, column: 11 }),
+                                             (Code::RecursiveConstructor, Location { line: 7, column: 16 }),
+                                             (Code::NoMatchingOverload, Location { line: 11, column: 15 }),
+                                             (Code::NoMatchingOverload, Location { line: 13, column: 13 }),
+                                             (Code::WrongArgumentCount, Location { line: 14, column: 21 })]);
+    }
+
+    #[test]
+    fn static_fields() {
+        let src = problems::STATIC_FIELDS;
+        assert_eq!(diagnostics_of(src), vec![(Code::DuplicateStaticConstructor, Location { line: 5, column: 5 }),
+                                             (Code::StaticMismatch, Location { line: 7, column: 17 }),
+                                             (Code::StaticMismatch, Location { line: 7, column: 23 }),
+                                             (Code::MismatchedTypes, Location { line: 8, column: 15 }),
+                                             (Code::UnresolvedName, Location { line: 13, column: 17 })]);
+    }
+
+    #[test]
+    fn instance_fields() {
+        // Instance fields can be named without `this` where it is available
+        let src = problems::INSTANCE_FIELDS;
+        assert_eq!(diagnostics_of(src), vec![(Code::UnresolvedName, Location { line: 4, column: 13 }),
+                                             (Code::UnresolvedName, Location { line: 5, column: 29 }),
+                                             (Code::MismatchedTypes, Location { line: 6, column: 30 })]);
+    }
+
+    #[test]
+    fn inheritance() {
+        let src = problems::INHERITANCE;
+        assert_eq!(diagnostics_of(src), vec![(Code::InvalidThis, Location { line: 17, column: 17 }),
+                                             (Code::UnknownType, Location { line: 10, column: 11 }),
+                                             (Code::CyclicInheritance, Location { line: 11, column: 11 }),
+                                             (Code::WrongArgumentCount, Location { line: 9, column: 1 }),
+                                             (Code::MismatchedTypes, Location { line: 16, column: 15 }),
+                                             (Code::NoBaseClass, Location { line: 17, column: 17 })]);
+    }
+
+    #[test]
+    fn calls_without_target() {
+        // Instance methods need `this`, but static methods can be called from anywhere
+        let src = problems::CALLS_WITHOUT_TARGET;
+        assert_eq!(diagnostics_of(src), vec![(Code::StaticMismatch, Location { line: 6, column: 13 }),
+                                             (Code::StaticMismatch, Location { line: 8, column: 33 }),
+                                             (Code::UnknownMethod, Location { line: 10, column: 25 })]);
+    }
+
+    #[test]
+    fn overrides() {
+        let src = problems::OVERRIDES;
+        assert_eq!(diagnostics_of(src), vec![(Code::SealedBaseClass, Location { line: 16, column: 11 }),
+                                             (Code::InvalidOverride, Location { line: 8, column: 5 }),
+                                             (Code::InvalidOverride, Location { line: 9, column: 5 }),
+                                             (Code::InvalidOverride, Location { line: 10, column: 5 }),
+                                             (Code::InvalidOverride, Location { line: 13, column: 5 })]);
+
+        let (program, diagnostics) = syntax::parse("test.cs", src);
+        assert!(diagnostics.is_empty());
+        let mut engine = QueryEngine::new(&program);
+
+        // Valid overrides are bound to the overridden method
+        let methods: Vec<_> = program.methods().map(|md| md.label.assert_as_method_decl()).collect();
+        assert_eq!(engine.query_overridden_method(methods[3]), Some(methods[0]));
+        assert_eq!(engine.query_overridden_method(methods[0]), None);
+    }
+
+    #[test]
+    fn interfaces() {
+        let src = problems::INTERFACES;
+        assert_eq!(diagnostics_of(src), vec![(Code::MissingImplementation, Location { line: 12, column: 5 }),
+                                             (Code::MissingImplementation, Location { line: 14, column: 11 }),
+                                             (Code::MissingImplementation, Location { line: 14, column: 11 }),
+                                             (Code::AbstractInstantiation, Location { line: 20, column: 16 }),
+                                             (Code::MismatchedTypes, Location { line: 22, column: 15 })]);
+
+        let (program, diagnostics) = syntax::parse("test.cs", src);
+        assert!(diagnostics.is_empty());
+        let mut engine = QueryEngine::new(&program);
+
+        // The members of base interfaces are implemented too
+        let classes: Vec<_> = program.classes().map(|cd| cd.label.assert_as_class_decl()).collect();
+        let interfaces: Vec<_> = program.interfaces().map(|id| id.label.assert_as_interface_decl()).collect();
+        assert_eq!(engine.query_interfaces(classes[0]), vec![interfaces[1], interfaces[0]]);
+        let methods: Vec<_> = program.methods().map(|md| md.label.assert_as_method_decl()).collect();
+        let m = program.interfaces().next().unwrap().methods[0].label.assert_as_method_decl();
+        assert_eq!(engine.query_implementation(classes[0], m), Some(methods[0]));
+        assert_eq!(engine.query_implementation(classes[2], m), None);
+    }
+
+    #[test]
+    fn abstract_classes() {
+        let src = problems::ABSTRACT_CLASSES;
+        assert_eq!(diagnostics_of(src), vec![(Code::AbstractBaseCall, Location { line: 9, column: 38 }),
+                                             (Code::MissingImplementation, Location { line: 11, column: 11 }),
+                                             (Code::AbstractInConcreteClass, Location { line: 13, column: 5 }),
+                                             (Code::AbstractInstantiation, Location { line: 17, column: 15 })]);
+    }
+
+    #[test]
+    fn overloads() {
+        let src = problems::OVERLOADS;
+        assert_eq!(diagnostics_of(src), vec![(Code::DuplicateMember, Location { line: 21, column: 5 }),
+                                             (Code::DuplicateMember, Location { line: 23, column: 5 }),
+                                             (Code::NoMatchingOverload, Location { line: 14, column: 9 }),
+                                             (Code::AmbiguousCall, Location { line: 15, column: 9 }),
+                                             (Code::AmbiguousCall, Location { line: 16, column: 9 })]);
+
+        let (program, diagnostics) = syntax::parse("test.cs", src);
+        assert!(diagnostics.is_empty());
+        let mut engine = QueryEngine::new(&program);
+
+        // Calls are bound to the best overload
+        let methods: Vec<_> = program.methods().map(|md| md.label.assert_as_method_decl()).collect();
+        let run = program.methods().find(|md| md.name == "Run").unwrap();
+        let calls: Vec<_> = run.body.as_ref().unwrap()[..3].iter().map(|stmt| match *stmt {
+            Statement::Expression(Expression::MethodCall(ref mc)) => engine.query_method_decl(mc.label.assert_as_method_use()),
+            ref stmt => panic!("Unexpected statement: {:?}", stmt)
+        }).collect();
+        assert_eq!(calls, vec![Some(methods[0]), Some(methods[1]), Some(methods[3])]);
+    }
+
+    #[test]
+    fn multiple_files() {
+        let files = vec![
+            ("a.cs".to_string(), "class Program {\n    static void Main() {\n        Helper h = new Helper();\n        Console.WriteLine(h.Get());\n    }\n}".to_string()),
+            ("b.cs".to_string(), "class Helper {\n    int Get() { return 1; }\n}\nclass Program {\n}".to_string()),
+        ];
+        let (program, diagnostics) = syntax::parse_files(files);
+        assert!(diagnostics.is_empty());
+        let mut engine = QueryEngine::new(&program);
+
+        // Classes from other files can be used, and the first declaration of `Program` is kept
+        engine.check_program();
+        assert_eq!(engine.diagnostics().len(), 1);
+        assert_eq!(engine.entry_point().map(|md| &md.name[..]), Some("Main"));
+
+        let diagnostic = engine.diagnostics().iter().next().unwrap();
+        assert_eq!(diagnostic.code, Code::DuplicateClass);
+        let locations: Vec<_> = diagnostic.labels.iter().map(|l| {
+            let file = program.source_map.file(l.span.file);
+            format!("{}:{}", file.name, file.location(l.span.start))
+        }).collect();
+        assert_eq!(locations, vec!["b.cs:4:1", "a.cs:1:1"]);
+
+        let rendered = diagnostic.render(&program.source_map);
+        assert!(rendered.contains(" --> b.cs:4:1"));
+        assert!(rendered.contains(" ::: a.cs:1:1"));
+    }
+
+    #[test]
+    fn namespaces() {
+        let (program, diagnostics) = syntax::parse("test.cs", problems::NAMESPACES);
+        assert!(diagnostics.is_empty());
+        let mut engine = QueryEngine::new(&program);
+
+        // Classes with the same name in different namespaces
+        let shapes_point = engine.query_class_decl("Shapes.Point").unwrap();
+        let other_point = engine.query_class_decl("Other.Point").unwrap();
+        assert!(shapes_point != other_point);
+        assert_eq!(engine.query_class_decl("Point"), None);
+
+        // Names are resolved in the scope where they are written
+        let methods: Vec<_> = program.methods().collect();
+        assert_eq!(engine.query_class_decl_at("Point", methods[0].label), Some(other_point));
+        assert_eq!(engine.query_class_decl_at("Point", methods[2].label), Some(shapes_point));
+        assert_eq!(engine.query_class_decl_at("Q", methods[2].label), Some(other_point));
+
+        // Names imported by several using directives are ambiguous
+        assert_eq!(engine.query_class_decl_at("Point", methods[4].label), None);
+
+        let return_types: Vec<_> = methods.iter().map(|md| {
+            let ty = engine.query_return_type(md.label.assert_as_method_decl());
+            engine.types().name(ty)
+        }).collect();
+        assert_eq!(return_types, vec!["Other.Point", "Shapes.Point", "void", "Shapes.Point", "{error}"]);
+
+        // The returned values do not match the imported classes
+        engine.check_program();
+        let messages: Vec<_> = engine.diagnostics().iter()
+                                     .filter(|d| d.code == Code::MismatchedTypes)
+                                     .map(|d| d.labels[0].message.clone())
+                                     .collect();
+        assert_eq!(messages, vec![
+            "expected `Shapes.Point`, found `Other.Point`",
+            "expected `Shapes.Point`, found `Other.Point`",
+        ]);
+
+        let ambiguous: Vec<_> = engine.diagnostics().iter()
+                                      .filter(|d| d.code == Code::AmbiguousName)
+                                      .map(|d| (d.message.clone(), program.source_map.location(d.primary_span().unwrap()).unwrap()))
+                                      .collect();
+        let message = "`Point` is ambiguous between `Shapes.Point` and `Other.Point`".to_string();
+        assert_eq!(ambiguous, vec![(message.clone(), Location { line: 19, column: 9 }),
+                                   (message, Location { line: 19, column: 33 })]);
+    }
 }
@@ -1,8 +1,8 @@
 use std::collections::HashMap;
-use std::usize;
+use std::collections::hash_map::Entry;
 
-use analysis::labels;
-use ast;
+use analysis::{labels, ClassTable, ScopeId};
+use ast::{self, BinaryOperator, Label, UnaryOperator};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub struct TypeId(usize);
@@ -16,21 +16,50 @@ pub enum Type {
     Console,
     Array(TypeId),
     Void,
-    Class(labels::ClassDecl)
+    Class(labels::ClassDecl),
+    Interface(labels::InterfaceDecl),
+    /// The type of expressions that could not be type checked
+    ///
+    /// Note: it unifies with any other type, so a single error is not reported over and over
+    Error
 }
 
 impl Type {
     pub fn class_decl(&self) -> labels::ClassDecl {
-        match self {
-            &Type::Class(cd) => cd,
+        match *self {
+            Type::Class(cd) => cd,
             _ => panic!("Type was not a Class type")
         }
     }
+
+    pub fn as_class_decl(&self) -> Option<labels::ClassDecl> {
+        match *self {
+            Type::Class(cd) => Some(cd),
+            _ => None
+        }
+    }
+
+    pub fn as_interface_decl(&self) -> Option<labels::InterfaceDecl> {
+        match *self {
+            Type::Interface(id) => Some(id),
+            _ => None
+        }
+    }
+}
+
+/// Returned when an `ast::Type` refers to a class or interface that does not exist
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnknownType {
+    pub name: String
 }
 
 pub struct TypeMap {
     types: Vec<Type>,
     ids: HashMap<Type, usize>,
+    /// The fully qualified names of the classes and interfaces
+    type_names: HashMap<Label, String>,
+    /// The base class and the interfaces listed in the head of each class or interface
+    supertypes: HashMap<TypeId, Vec<TypeId>>
 }
 
 impl TypeMap {
@@ -80,53 +109,272 @@ impl TypeMap {
         TypeId(4)
     }
 
-    pub fn unify(&self, ty1: TypeId, ty2: TypeId) -> bool {
-        self.any_ty() == ty1 // One of both types are null
-        || self.any_ty() == ty2
-        || ty1 == ty2 // Both types are equal
+    pub fn error_ty(&self) -> TypeId {
+        TypeId(5)
+    }
+
+    /// Returns true if a value of type `found` can be used where a value of type `expected` is
+    /// expected
+    pub fn unify(&self, expected: TypeId, found: TypeId) -> bool {
+        self.error_ty() == expected // One of both types could not be checked
+        || self.error_ty() == found
+        || expected == found // Both types are equal
+        || self.any_ty() == found && self.is_reference(expected) // Only references can be null
+        || self.is_subtype(found, expected)
+    }
+
+    /// Returns true if the first type is a class or interface that derives from (or implements)
+    /// the second one, directly or indirectly
+    fn is_subtype(&self, derived: TypeId, base: TypeId) -> bool {
+        let mut pending = vec![derived];
+        while let Some(ty) = pending.pop() {
+            for &parent in self.supertypes.get(&ty).into_iter().flatten() {
+                if parent == base {
+                    return true;
+                }
+
+                pending.push(parent);
+            }
+        }
+
+        false
+    }
+
+    /// Returns true if arguments of the given types can be passed to parameters of the given types
+    pub fn accepts(&self, params: &[TypeId], args: &[TypeId]) -> bool {
+        params.len() == args.len() && params.iter().zip(args).all(|(&param, &arg)| self.unify(param, arg))
+    }
+
+    /// Picks the overload that best matches arguments of the given types, given the parameter
+    /// types of each overload
+    ///
+    /// Like in C#, an overload that accepts the arguments is better than another one if none of
+    /// its parameters is a worse match than the corresponding parameter of the other one, and at
+    /// least one is a better match (see `is_better_param`). The best overload is better than all
+    /// the other ones that accept the arguments.
+    ///
+    /// Returns `Ok(None)` if no overload accepts the arguments, and `Err` with two overloads that
+    /// are not worse than any other if there is no best one (i.e. the call is ambiguous). If an
+    /// argument could not be typed, the first of those overloads is picked instead.
+    pub fn best_overload(&self, overloads: &[Vec<TypeId>], args: &[TypeId]) -> Result<Option<usize>, (usize, usize)> {
+        let applicable: Vec<_> = (0..overloads.len()).filter(|&i| self.accepts(&overloads[i], args)).collect();
+        let is_better = |first: &[TypeId], second: &[TypeId]| {
+            first.iter().zip(second).all(|(&p, &q)| !self.is_better_param(q, p))
+                && first.iter().zip(second).any(|(&p, &q)| self.is_better_param(p, q))
+        };
+
+        let best: Vec<_> = applicable.iter().cloned().filter(|&i| {
+            applicable.iter().all(|&j| !is_better(&overloads[j], &overloads[i]))
+        }).collect();
+        match best[..] {
+            [] => Ok(None),
+            [i] => Ok(Some(i)),
+            [i, _, ..] if args.contains(&self.error_ty()) => Ok(Some(i)),
+            [i, j, ..] => Err((i, j))
+        }
+    }
+
+    /// Returns true if a parameter of the first type is a better match than one of the second
+    /// type, for an argument that both accept
+    ///
+    /// This is the case when the first type converts to the second one but not the other way
+    /// around, like a class and one of its base classes
+    fn is_better_param(&self, first: TypeId, second: TypeId) -> bool {
+        self.unify(second, first) && !self.unify(first, second)
+    }
+
+    /// Returns the type of a binary operation, or `None` if the operator cannot be applied to
+    /// operands of the given types
+    ///
+    /// Arithmetic and relational operators only take `int`s and logical operators only take
+    /// `bool`s, while `==` and `!=` compare values of the same type, or a reference with `null`
+    pub fn binary_op_ty(&self, operator: BinaryOperator, left: TypeId, right: TypeId) -> Option<TypeId> {
+        let is = |expected, ty| ty == expected || ty == self.error_ty();
+        let well_typed = if operator.is_equality() {
+            self.is_comparable(left, right)
+        } else if operator.is_logical() {
+            is(self.bool_ty(), left) && is(self.bool_ty(), right)
+        } else {
+            is(self.int_ty(), left) && is(self.int_ty(), right)
+        };
+
+        match operator {
+            _ if !well_typed => None,
+            _ if operator.is_arithmetic() => Some(self.int_ty()),
+            _ => Some(self.bool_ty())
+        }
+    }
+
+    /// Returns the type of a unary operation, or `None` if the operator cannot be applied to an
+    /// operand of the given type
+    ///
+    /// `!` takes and produces a `bool`, while the other operators take and produce an `int`
+    pub fn unary_op_ty(&self, operator: UnaryOperator, operand: TypeId) -> Option<TypeId> {
+        let ty = match operator {
+            UnaryOperator::Not => self.bool_ty(),
+            _ => self.int_ty()
+        };
+
+        if operand == ty || operand == self.error_ty() {
+            Some(ty)
+        } else {
+            None
+        }
+    }
+
+    fn is_comparable(&self, left: TypeId, right: TypeId) -> bool {
+        if left == self.error_ty() || right == self.error_ty() {
+            true
+        } else if left == self.any_ty() {
+            self.is_reference(right)
+        } else if right == self.any_ty() {
+            self.is_reference(left)
+        } else {
+            // References to related classes and interfaces can point to the same object
+            let related = left == right || self.is_subtype(left, right) || self.is_subtype(right, left);
+            related && self.get(left) != Type::Void && self.get(left) != Type::Console
+        }
+    }
+
+    /// Returns true if values of the type are references, which can be `null`
+    fn is_reference(&self, ty: TypeId) -> bool {
+        if ty == self.any_ty() || ty == self.error_ty() {
+            return true;
+        }
+
+        matches!(self.get(ty), Type::String | Type::Array(_) | Type::Class(_) | Type::Interface(_))
+    }
+
+    /// Returns the type of the elements of a collection, or `None` if the type cannot be
+    /// iterated with `foreach` or indexed
+    ///
+    /// Note: arrays are the only collections so far, new ones should be added here
+    pub fn element_ty(&self, collection: TypeId) -> Option<TypeId> {
+        if collection == self.error_ty() {
+            return Some(self.error_ty());
+        }
+
+        if collection == self.any_ty() {
+            return None;
+        }
+
+        match self.get(collection) {
+            Type::Array(inner) => Some(inner),
+            _ => None
+        }
     }
 
-    pub fn get_from_class_name(&mut self, name: &str, decls: &HashMap<&str, &ast::ClassDecl>) -> TypeId {
-        match decls.get(name) {
-            Some(ref class) => {
-                let decl = class.label.assert_as_class_decl();
-                self.get_id(Type::Class(decl))
+    /// Returns the type of a read-only property (e.g. the `Length` of an array), or `None` if
+    /// values of the type have no such property
+    pub fn property_ty(&self, ty: TypeId, name: &str) -> Option<TypeId> {
+        if ty == self.any_ty() || ty == self.error_ty() {
+            return None;
+        }
+
+        match self.get(ty) {
+            Type::Array(_) if name == "Length" => Some(self.int_ty()),
+            _ => None
+        }
+    }
+
+    /// Returns the name of the type, as it would be written in C#
+    pub fn name(&self, id: TypeId) -> String {
+        if id == self.any_ty() {
+            return "null".to_string();
+        }
+
+        match self.get(id) {
+            Type::Bool => "bool".to_string(),
+            Type::Int => "int".to_string(),
+            Type::String => "string".to_string(),
+            Type::Console => "Console".to_string(),
+            Type::Array(inner) => format!("{}[]", self.name(inner)),
+            Type::Void => "void".to_string(),
+            Type::Class(cd) => self.type_names[&cd.as_label()].clone(),
+            Type::Interface(id) => self.type_names[&id.as_label()].clone(),
+            Type::Error => "{error}".to_string()
+        }
+    }
+
+    /// Returns the type of the given class
+    pub fn get_from_class(&mut self, class: &ast::ClassDecl, classes: &ClassTable) -> TypeId {
+        let id = self.get_id(Type::Class(class.label.assert_as_class_decl()));
+        if let Entry::Vacant(entry) = self.type_names.entry(class.label) {
+            entry.insert(classes.full_name(class).to_string());
+
+            // Subtyping needs the whole hierarchy of the class
+            let mut supertypes = Vec::new();
+            if let Some(base) = classes.base_of(class) {
+                supertypes.push(self.get_from_class(base, classes));
             }
-            None => {
-                panic!("Class decl not found for `{}`", name);
+            for &interface in classes.interfaces_of(class.label) {
+                supertypes.push(self.get_from_interface(interface, classes));
+            }
+            self.supertypes.insert(id, supertypes);
+        }
+
+        id
+    }
+
+    /// Returns the type of the given interface
+    pub fn get_from_interface(&mut self, interface: &ast::InterfaceDecl, classes: &ClassTable) -> TypeId {
+        let id = self.get_id(Type::Interface(interface.label.assert_as_interface_decl()));
+        if let Entry::Vacant(entry) = self.type_names.entry(interface.label) {
+            entry.insert(classes.interface_full_name(interface).to_string());
+
+            let mut supertypes = Vec::new();
+            for &base in classes.interfaces_of(interface.label) {
+                supertypes.push(self.get_from_interface(base, classes));
             }
+            self.supertypes.insert(id, supertypes);
         }
+
+        id
     }
 
-    pub fn get_from_ast_ty(&mut self, ast_ty: &ast::Type, decls: &HashMap<&str, &ast::ClassDecl>) -> TypeId {
-        match ast_ty {
-            &ast::Type::Array(ref inner_ty) => {
+    /// Returns the type of the class or interface with the given (possibly qualified) name, as
+    /// written in the given scope
+    pub fn get_from_class_name(&mut self, name: &str, classes: &ClassTable, scope: ScopeId) -> Result<TypeId, UnknownType> {
+        if let Some(class) = classes.resolve(name, scope) {
+            return Ok(self.get_from_class(class, classes));
+        }
+
+        match classes.resolve_interface(name, scope) {
+            Some(interface) => Ok(self.get_from_interface(interface, classes)),
+            None => Err(UnknownType { name: name.to_string() })
+        }
+    }
+
+    /// Returns the type corresponding to a type written in the given scope
+    pub fn get_from_ast_ty(&mut self, ast_ty: &ast::Type, classes: &ClassTable, scope: ScopeId) -> Result<TypeId, UnknownType> {
+        match *ast_ty {
+            ast::Type::Array(ref inner_ty) => {
                 // Recursively get the inner type
-                let inner_ty_id = self.get_from_ast_ty(inner_ty, decls);
-                self.get_id(Type::Array(inner_ty_id))
+                let inner_ty_id = self.get_from_ast_ty(inner_ty, classes, scope)?;
+                Ok(self.get_id(Type::Array(inner_ty_id)))
             }
-            &ast::Type::Custom(ref ty_name) => {
+            ast::Type::Custom(ref ty_name) => {
                 // See if there is a class that matches the custom type
                 let ty_name: &str = ty_name;
                 match ty_name {
-                    "bool" => self.bool_ty(),
+                    "bool" => Ok(self.bool_ty()),
                     "int" => {
-                        self.int_ty()
+                        Ok(self.int_ty())
                     }
-                    "string" | "String" => {
-                        self.string_ty()
+                    "string" | "String" | "System.String" => {
+                        Ok(self.string_ty())
                     }
-                    "Console" => {
-                        self.console_ty()
+                    "Console" | "System.Console" => {
+                        Ok(self.console_ty())
                     }
                     class_name => {
-                        // Not a builtin type. We need to find the decl for this class. We assume it exists
-                        self.get_from_class_name(class_name, decls)
+                        // Not a builtin type. We need to find the decl for this class or interface
+                        self.get_from_class_name(class_name, classes, scope)
                     }
                 }
             }
-            &ast::Type::Void => {
-                self.void_ty()
+            ast::Type::Void => {
+                Ok(self.void_ty())
             }
         }
     }
@@ -134,12 +382,14 @@ impl TypeMap {
 
 impl Default for TypeMap {
     fn default() -> TypeMap {
+        // Note: the order must match the ids returned by `int_ty`, `string_ty`, etc.
         let types = vec![
-            Type::Bool,
             Type::Int,
             Type::String,
             Type::Void,
-            Type::Console
+            Type::Bool,
+            Type::Console,
+            Type::Error
         ];
 
         let mut ids = HashMap::new();
@@ -147,8 +397,6 @@ impl Default for TypeMap {
             ids.insert(ty, id);
         }
 
-        ids.insert(Type::Console, 3);
-
-        TypeMap { types, ids }
+        TypeMap { types, ids, type_names: HashMap::new(), supertypes: HashMap::new() }
     }
 }
@@ -1,47 +1,174 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-//use analysis::labels;
+use analysis::errors;
 use ast::*;
 use ast::visitor::Visitor;
+use diagnostics::{Code, Diagnostic, Diagnostics};
+use syntax::Span;
 
-use super::visitor::PreprocessVisitor;
+use super::class_table::ClassTable;
+use super::visitor::{self, PreprocessError, PreprocessVisitor};
 
 pub struct AstData<'a> {
     pub nodes: HashMap<Label, Node<'a>>,
     pub var_map: HashMap<Label, &'a VarDecl>,
     pub this_map: HashMap<Label, &'a ClassDecl>,
-    pub classes_by_name: HashMap<&'a str, &'a ClassDecl>,
-    pub entry_point: &'a MethodDecl,
+    /// Identifiers that refer to a field of the enclosing class (possibly inherited). Instance
+    /// fields are only found where `this` is available
+    pub field_map: HashMap<Label, &'a FieldDecl>,
+    /// Calls without a target where `this` is not available, which can only call static methods
+    pub static_calls: HashSet<Label>,
+    /// The variables declared by `foreach` loops, which cannot be assigned to
+    pub foreach_vars: HashSet<Label>,
+    pub classes: ClassTable<'a>,
+    /// Note: missing if the program has no entry point, which is reported as an error
+    pub entry_point: Option<&'a MethodDecl>,
+    pub diagnostics: Diagnostics
 }
 
 pub struct AstPreprocessor;
 impl AstPreprocessor {
-    pub fn preprocess(p: &Program) -> AstData {
+    pub fn preprocess(p: &Program) -> AstData<'_> {
         let mut visitor = PreprocessVisitor::default();
         visitor.visit_ast(&p.items);
+        visitor.resolve_inheritance(p);
 
-        if visitor.errors.len() > 0 {
-            println!("Errors while preprocessing:");
-            for err in &visitor.errors {
-                println!("{:?}", err);
-            }
-
-            panic!()
+        let mut diagnostics = Diagnostics::default();
+        for err in &visitor.errors {
+            diagnostics.push(to_diagnostic(err, &visitor.nodes, &visitor.classes));
         }
 
-        let ep = match visitor.entry_point {
-            Some(e) => e,
-            None => {
-                panic!("No entry point found")
-            }
-        };
+        if visitor.entry_point.is_none() {
+            diagnostics.push(
+                Diagnostic::error(Code::MissingEntryPoint, "no entry point found".to_string())
+                    .with_note("add a `static void Main()` method to one of the classes".to_string())
+            );
+        }
 
         AstData {
             nodes: visitor.nodes,
             var_map: visitor.var_map,
             this_map: visitor.this_map,
-            classes_by_name: visitor.classes_by_name,
-            entry_point: ep
+            field_map: visitor.field_map,
+            static_calls: visitor.static_calls,
+            foreach_vars: visitor.foreach_vars,
+            classes: visitor.classes,
+            entry_point: visitor.entry_point,
+            diagnostics
+        }
+    }
+}
+
+fn to_diagnostic(err: &PreprocessError, nodes: &HashMap<Label, Node>, classes: &ClassTable) -> Diagnostic {
+    match *err {
+        PreprocessError::MultiTypeDecl { decl, previous } => {
+            let decl = TypeDecl::new(nodes, classes, decl);
+            let previous = TypeDecl::new(nodes, classes, previous);
+            Diagnostic::error(Code::DuplicateClass, format!("the {} `{}` is defined multiple times", previous.kind, previous.full_name))
+                .with_primary(decl.span, format!("`{}` redefined here", decl.name))
+                .with_secondary(previous.span, format!("previous definition of `{}` here", previous.name))
+        }
+        PreprocessError::MultiEntryPoint(decl) => {
+            let decl: &MethodDecl = nodes[&decl.as_label()].downcast();
+            Diagnostic::error(Code::DuplicateEntryPoint, "multiple entry points found".to_string())
+                .with_primary(decl.span, "`Main` redefined here".to_string())
+        }
+        PreprocessError::MultiVarDecl { decl, previous } => {
+            let decl: &VarDecl = nodes[&decl.as_label()].downcast();
+            let previous: &VarDecl = nodes[&previous.as_label()].downcast();
+            Diagnostic::error(Code::DuplicateVariable,
+                              format!("a local variable named `{}` is already defined in this scope", decl.var_name))
+                .with_primary(decl.span, format!("`{}` redeclared here", decl.var_name))
+                .with_secondary(previous.span, format!("previous declaration of `{}` here", previous.var_name))
+        }
+        PreprocessError::MultiStaticConstructor { decl, previous } => {
+            let decl: &ConstructorDecl = nodes[&decl.as_label()].downcast();
+            let previous: &ConstructorDecl = nodes[&previous.as_label()].downcast();
+            Diagnostic::error(Code::DuplicateStaticConstructor,
+                              format!("the class `{}` has more than one static constructor", decl.name))
+                .with_primary(decl.span, "static constructor redefined here".to_string())
+                .with_secondary(previous.span, "previous definition here".to_string())
+        }
+        PreprocessError::MultiMemberDecl { decl, previous } => {
+            let (kind, name, params, span) = match nodes[&decl] {
+                Node::MethodDecl(md) => ("method", &md.name, &md.params, md.span),
+                Node::ConstructorDecl(cd) => ("constructor", &cd.name, &cd.params, cd.span),
+                _ => panic!("Expected a method or a constructor")
+            };
+            Diagnostic::error(Code::DuplicateMember,
+                              format!("the {} `{}` is defined multiple times", kind, visitor::signature(name, params)))
+                .with_primary(span, format!("`{}` redefined here", name))
+                .with_secondary(nodes[&previous].span(), "previous definition here".to_string())
+        }
+        PreprocessError::UnknownBaseType { decl, index } => {
+            let base = &TypeDecl::new(nodes, classes, decl).bases[index];
+            classes.ambiguity_error(base.span, &base.name, classes.scope_of(decl)).unwrap_or_else(|| errors::unknown_type(base.span, &base.name))
+        }
+        PreprocessError::ExpectedInterface { decl, index } => {
+            let decl = TypeDecl::new(nodes, classes, decl);
+            let base = &decl.bases[index];
+            let note = match decl.kind {
+                "class" => "a class can derive from a single class, which must come first in the base list",
+                _ => "interfaces can only extend other interfaces"
+            };
+            Diagnostic::error(Code::ExpectedInterface, format!("expected interface, found class `{}`", base.name))
+                .with_primary(base.span, "not an interface".to_string())
+                .with_note(note.to_string())
+        }
+        PreprocessError::CyclicInheritance { decl, index } => {
+            let decl = TypeDecl::new(nodes, classes, decl);
+            let base = &decl.bases[index];
+            Diagnostic::error(Code::CyclicInheritance, format!("the {} `{}` inherits from itself", decl.kind, decl.full_name))
+                .with_primary(base.span, format!("cyclic base {}", decl.kind))
+        }
+        PreprocessError::SealedBaseClass(decl) => {
+            let decl: &ClassDecl = nodes[&decl.as_label()].downcast();
+            let base = &decl.bases[0];
+            Diagnostic::error(Code::SealedBaseClass, format!("cannot derive from sealed class `{}`", base.name))
+                .with_primary(base.span, "sealed class".to_string())
+        }
+        PreprocessError::InvalidThis(label) => {
+            let keyword = match nodes[&label] {
+                Node::Base(_) => "base",
+                _ => "this"
+            };
+            Diagnostic::error(Code::InvalidThis, format!("`{}` is not available in a static context", keyword))
+                .with_primary(nodes[&label].span(), format!("`{}` used here", keyword))
+                .with_note(format!("`{}` can only be used inside instance methods and constructors", keyword))
+        }
+        PreprocessError::OutsideLoop(label) => {
+            let keyword = match nodes[&label] {
+                Node::Break(_) => "break",
+                _ => "continue"
+            };
+            Diagnostic::error(Code::OutsideLoop, format!("`{}` outside of a loop", keyword))
+                .with_primary(nodes[&label].span(), format!("cannot `{}` outside of a loop", keyword))
+        }
+    }
+}
+
+/// The parts of a class or an interface needed to report problems with its declaration
+struct TypeDecl<'a> {
+    /// Either `class` or `interface`
+    kind: &'static str,
+    name: &'a str,
+    full_name: String,
+    span: Span,
+    bases: &'a [BaseType]
+}
+
+impl<'a> TypeDecl<'a> {
+    fn new(nodes: &HashMap<Label, Node<'a>>, classes: &ClassTable, label: Label) -> TypeDecl<'a> {
+        match nodes[&label] {
+            Node::ClassDecl(cd) => {
+                let full_name = classes.full_name(cd).to_string();
+                TypeDecl { kind: "class", name: &cd.name, full_name, span: cd.span, bases: &cd.bases }
+            }
+            Node::InterfaceDecl(id) => {
+                let full_name = classes.interface_full_name(id).to_string();
+                TypeDecl { kind: "interface", name: &id.name, full_name, span: id.span, bases: &id.bases }
+            }
+            _ => panic!("Expected a class or an interface")
         }
     }
 }
@@ -0,0 +1,303 @@
+//! Resolution of class and interface names, which may be qualified (`A.B.Foo`), declared in
+//! namespaces and imported through using directives
+
+use std::collections::{HashMap, HashSet};
+
+use analysis::{errors, labels};
+use ast::*;
+use diagnostics::Diagnostic;
+use syntax::Span;
+
+/// Identifies the scope in which a name is written (see `ClassTable::resolve`)
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct ScopeId(usize);
+
+/// A namespace declaration (or the top level of a file) together with its using directives
+#[derive(Debug)]
+struct Scope {
+    parent: Option<ScopeId>,
+    /// The fully qualified name of the namespace (empty for the global namespace)
+    namespace: String,
+    /// Namespaces imported through using directives
+    usings: Vec<String>,
+    /// Using alias directives, mapping the alias to a qualified name
+    aliases: HashMap<String, String>
+}
+
+/// The classes and interfaces of a program by fully qualified name, and the scopes in which their
+/// names are resolved
+///
+/// Note: classes and interfaces share their names, so a name refers to at most one of them
+#[derive(Default)]
+pub struct ClassTable<'a> {
+    classes: HashMap<String, &'a ClassDecl>,
+    interfaces: HashMap<String, &'a InterfaceDecl>,
+    /// The fully qualified name of each class and interface
+    full_names: HashMap<Label, String>,
+    /// The base class of each class that has one
+    bases: HashMap<labels::ClassDecl, &'a ClassDecl>,
+    /// The interfaces listed in the head of each class or interface (the ones it implements or
+    /// extends, respectively)
+    declared_interfaces: HashMap<Label, Vec<&'a InterfaceDecl>>,
+    /// The declared namespaces, including the outer namespaces of qualified ones (e.g. `A` for `A.B`)
+    namespaces: HashSet<String>,
+    scopes: Vec<Scope>,
+    /// The scope of each node in the program
+    node_scopes: HashMap<Label, ScopeId>
+}
+
+impl<'a> ClassTable<'a> {
+    // Construction, during preprocessing
+    pub(super) fn add_scope(&mut self, parent: Option<ScopeId>, name: &str) -> ScopeId {
+        let namespace = match parent {
+            Some(parent) => qualify(&self.scopes[parent.0].namespace, name),
+            None => name.to_string()
+        };
+        if !namespace.is_empty() {
+            self.namespaces.insert(namespace.clone());
+        }
+
+        self.scopes.push(Scope { parent, namespace, usings: Vec::new(), aliases: HashMap::new() });
+        ScopeId(self.scopes.len() - 1)
+    }
+
+    pub(super) fn add_using(&mut self, scope: ScopeId, using: &UsingDirective) {
+        let scope = &mut self.scopes[scope.0];
+        match using.alias {
+            Some(ref alias) => { scope.aliases.insert(alias.clone(), using.name.clone()); }
+            None => scope.usings.push(using.name.clone())
+        }
+    }
+
+    /// Adds a class declared in the given scope, returning the label of the previous class or
+    /// interface with the same fully qualified name if there is one (in which case the table is
+    /// not modified)
+    pub(super) fn add_class(&mut self, scope: ScopeId, decl: &'a ClassDecl) -> Option<Label> {
+        let full_name = qualify(&self.scopes[scope.0].namespace, &decl.name);
+        if let Some(previous) = self.label_of(&full_name) {
+            return Some(previous);
+        }
+
+        self.full_names.insert(decl.label, full_name.clone());
+        self.classes.insert(full_name, decl);
+        None
+    }
+
+    /// Like `add_class`, for interfaces
+    pub(super) fn add_interface(&mut self, scope: ScopeId, decl: &'a InterfaceDecl) -> Option<Label> {
+        let full_name = qualify(&self.scopes[scope.0].namespace, &decl.name);
+        if let Some(previous) = self.label_of(&full_name) {
+            return Some(previous);
+        }
+
+        self.full_names.insert(decl.label, full_name.clone());
+        self.interfaces.insert(full_name, decl);
+        None
+    }
+
+    /// Returns the label of the class or interface with the given fully qualified name
+    fn label_of(&self, full_name: &str) -> Option<Label> {
+        self.classes.get(full_name).map(|cd| cd.label)
+            .or_else(|| self.interfaces.get(full_name).map(|id| id.label))
+    }
+
+    pub(super) fn set_scope(&mut self, label: Label, scope: ScopeId) {
+        self.node_scopes.insert(label, scope);
+    }
+
+    /// Sets (or removes, if `None`) the base class of a class
+    pub(super) fn set_base(&mut self, decl: &ClassDecl, base: Option<&'a ClassDecl>) {
+        let decl = decl.label.assert_as_class_decl();
+        match base {
+            Some(base) => self.bases.insert(decl, base),
+            None => self.bases.remove(&decl)
+        };
+    }
+
+    /// Sets the interfaces listed in the head of a class or interface, given its label
+    pub(super) fn set_interfaces(&mut self, decl: Label, interfaces: Vec<&'a InterfaceDecl>) {
+        self.declared_interfaces.insert(decl, interfaces);
+    }
+
+    // Queries
+
+    /// Returns the class with the given fully qualified name
+    pub fn get(&self, full_name: &str) -> Option<&'a ClassDecl> {
+        self.classes.get(full_name).cloned()
+    }
+
+    /// Returns the fully qualified name of a class (e.g. `A.B.Foo`)
+    ///
+    /// Note: classes that share their name with a previously declared class have no entry in
+    /// the table, so their simple name is returned
+    pub fn full_name<'b>(&'b self, decl: &'b ClassDecl) -> &'b str {
+        self.full_names.get(&decl.label).map_or(&decl.name, |name| name)
+    }
+
+    /// Like `full_name`, for interfaces
+    pub fn interface_full_name<'b>(&'b self, decl: &'b InterfaceDecl) -> &'b str {
+        self.full_names.get(&decl.label).map_or(&decl.name, |name| name)
+    }
+
+    /// Returns the base class of a class, if it has one
+    ///
+    /// Note: base classes that could not be resolved, or that would make the class inherit from
+    /// itself, are reported while preprocessing and ignored here
+    pub fn base_of(&self, decl: &ClassDecl) -> Option<&'a ClassDecl> {
+        self.bases.get(&decl.label.assert_as_class_decl()).cloned()
+    }
+
+    /// Returns the class followed by its base classes, from the most to the least derived one
+    pub fn hierarchy(&self, decl: &'a ClassDecl) -> Vec<&'a ClassDecl> {
+        let mut classes = vec![decl];
+        while let Some(base) = self.base_of(classes[classes.len() - 1]) {
+            classes.push(base);
+        }
+
+        classes
+    }
+
+    /// Finds a field declared in the class or inherited from one of its base classes
+    ///
+    /// Note: like in C#, fields of a class hide the fields with the same name of its base classes
+    pub fn find_field(&self, decl: &'a ClassDecl, name: &str) -> Option<&'a FieldDecl> {
+        self.hierarchy(decl).into_iter().find_map(|cd| cd.find_field(name))
+    }
+
+    /// Returns the methods with the given name declared in the class and in each of its base
+    /// classes, starting with the class itself
+    ///
+    /// Note: like in C#, the overloads of a class hide the ones of its base classes, unless none of
+    /// them accepts the arguments of a call (see `TypeMap::best_overload`)
+    pub fn find_overloads(&self, decl: &'a ClassDecl, name: &str) -> Vec<Vec<&'a MethodDecl>> {
+        self.hierarchy(decl).into_iter().map(|cd| cd.find_overloads(name)).collect()
+    }
+
+    /// Returns the interfaces listed in the head of a class or interface, given its label
+    ///
+    /// Note: names that could not be resolved to an interface are reported while preprocessing and
+    /// ignored here, like interfaces that would make an interface extend itself
+    pub fn interfaces_of(&self, decl: Label) -> &[&'a InterfaceDecl] {
+        self.declared_interfaces.get(&decl).map_or(&[], |interfaces| interfaces)
+    }
+
+    /// Returns the interface followed by all the interfaces it extends, directly or indirectly,
+    /// without repetitions
+    pub fn interface_hierarchy(&self, decl: &'a InterfaceDecl) -> Vec<&'a InterfaceDecl> {
+        let mut interfaces = vec![decl];
+        let mut i = 0;
+        while i < interfaces.len() {
+            for &base in self.interfaces_of(interfaces[i].label) {
+                if interfaces.iter().all(|id| id.label != base.label) {
+                    interfaces.push(base);
+                }
+            }
+            i += 1;
+        }
+
+        interfaces
+    }
+
+    /// Returns the methods with the given name declared in the interface and in each of the
+    /// interfaces it extends (see `interface_hierarchy`), like `find_overloads` for classes
+    pub fn find_interface_overloads(&self, decl: &'a InterfaceDecl, name: &str) -> Vec<Vec<&'a MethodDecl>> {
+        self.interface_hierarchy(decl).into_iter().map(|id| id.methods.iter().filter(|md| md.name == name).collect()).collect()
+    }
+
+    /// Returns the scope of the given node
+    pub fn scope_of(&self, label: Label) -> ScopeId {
+        self.node_scopes[&label]
+    }
+
+    /// Resolves a possibly qualified class name, as written in the given scope
+    ///
+    /// Like in C#, the enclosing namespaces are searched from the innermost to the outermost.
+    /// In each of them, the classes declared in the namespace take precedence over aliases, which
+    /// take precedence over classes imported through using directives.
+    ///
+    /// Note: if several using directives of the same namespace import a class or interface with
+    /// the given name, the name is ambiguous and nothing is returned (see `ambiguity_error`)
+    pub fn resolve(&self, name: &str, scope: ScopeId) -> Option<&'a ClassDecl> {
+        self.resolve_full_name(name, scope).ok().and_then(|full_name| self.get(&full_name?))
+    }
+
+    /// Like `resolve`, for interfaces
+    pub fn resolve_interface(&self, name: &str, scope: ScopeId) -> Option<&'a InterfaceDecl> {
+        self.resolve_full_name(name, scope).ok().and_then(|full_name| self.interfaces.get(&full_name?).cloned())
+    }
+
+    /// Returns the error for a name that could not be resolved because it is ambiguous, if that is
+    /// the case
+    pub fn ambiguity_error(&self, span: Span, name: &str, scope: ScopeId) -> Option<Diagnostic> {
+        self.resolve_full_name(name, scope).err().map(|(first, second)| errors::ambiguous_name(span, name, &first, &second))
+    }
+
+    /// Returns the fully qualified name of the class or interface that the given name refers to,
+    /// or two of the ones it could refer to if it is ambiguous
+    fn resolve_full_name(&self, name: &str, scope: ScopeId) -> Result<Option<String>, (String, String)> {
+        for group in self.candidates(name, scope, true) {
+            let mut found = group.into_iter().filter(|full_name| self.label_of(full_name).is_some());
+            if let Some(first) = found.next() {
+                return match found.find(|full_name| *full_name != first) {
+                    Some(second) => Err((first, second)),
+                    None => Ok(Some(first))
+                };
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns true if the name refers to a class, as written in the given scope
+    pub fn is_class(&self, name: &str, scope: ScopeId) -> bool {
+        self.resolve(name, scope).is_some()
+    }
+
+    /// Returns true if the name refers to a namespace, as written in the given scope
+    pub fn is_namespace(&self, name: &str, scope: ScopeId) -> bool {
+        self.candidates(name, scope, false).iter().flatten().any(|full_name| self.namespaces.contains(full_name))
+    }
+
+    /// Returns the fully qualified names the given name could refer to, in groups by order of
+    /// precedence
+    ///
+    /// Note: the names in a group have the same precedence, so the name is ambiguous if it refers
+    /// to more than one of them
+    fn candidates(&self, name: &str, scope: ScopeId, include_usings: bool) -> Vec<Vec<String>> {
+        let (first, rest) = match name.find('.') {
+            Some(i) => (&name[..i], Some(&name[i + 1..])),
+            None => (name, None)
+        };
+
+        let mut candidates = Vec::new();
+        let mut current = Some(scope);
+        while let Some(ScopeId(id)) = current {
+            let scope = &self.scopes[id];
+            candidates.push(vec![qualify(&scope.namespace, name)]);
+
+            if let Some(target) = scope.aliases.get(first) {
+                candidates.push(vec![match rest {
+                    Some(rest) => qualify(target, rest),
+                    None => target.clone()
+                }]);
+            }
+
+            // Note: using directives import classes, not nested namespaces
+            if include_usings && rest.is_none() {
+                candidates.push(scope.usings.iter().map(|using| qualify(using, name)).collect());
+            }
+
+            current = scope.parent;
+        }
+
+        candidates
+    }
+}
+
+fn qualify(namespace: &str, name: &str) -> String {
+    if namespace.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", namespace, name)
+    }
+}
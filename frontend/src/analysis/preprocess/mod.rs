@@ -1,2 +1,3 @@
 pub mod ast_preprocessor;
+pub mod class_table;
 mod visitor;
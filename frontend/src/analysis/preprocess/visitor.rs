@@ -1,14 +1,37 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::mem;
 
 use analysis::labels;
 use ast::*;
 use ast::visitor::Visitor;
+use syntax::FileId;
+use super::class_table::{ClassTable, ScopeId};
 
 #[derive(Debug)]
 pub enum PreprocessError {
-    MultiClassDecl(labels::ClassDecl),
-    MultiEntryPoint(labels::MethodDecl)
+    /// A class or interface with the same fully qualified name as a previous one
+    MultiTypeDecl { decl: Label, previous: Label },
+    MultiEntryPoint(labels::MethodDecl),
+    MultiVarDecl { decl: labels::VarDecl, previous: labels::VarDecl },
+    MultiStaticConstructor { decl: labels::ConstructorDecl, previous: labels::ConstructorDecl },
+    /// A method or constructor with the same name and parameter types as a previous member of
+    /// the same class or interface
+    MultiMemberDecl { decl: Label, previous: Label },
+    /// A name in the base list of a class or interface (at the given index) that does not refer
+    /// to a class or interface
+    UnknownBaseType { decl: Label, index: usize },
+    /// A class in the base list of a class or interface (at the given index) where only
+    /// interfaces are allowed
+    ExpectedInterface { decl: Label, index: usize },
+    /// A class or interface that inherits from itself through the base type at the given index,
+    /// directly or through other classes or interfaces
+    CyclicInheritance { decl: Label, index: usize },
+    /// A class that derives from a sealed class
+    SealedBaseClass(labels::ClassDecl),
+    /// A use of `this` or `base` outside of an instance method
+    InvalidThis(Label),
+    /// A `break` or `continue` outside of a loop
+    OutsideLoop(Label)
 }
 
 // A visitor to collect class names
@@ -17,82 +40,375 @@ pub struct PreprocessVisitor<'a> {
     pub nodes: HashMap<Label, Node<'a>>,
     pub var_map: HashMap<Label, &'a VarDecl>,
     pub this_map: HashMap<Label, &'a ClassDecl>,
-    pub classes_by_name: HashMap<&'a str, &'a ClassDecl>,
+    /// Identifiers that refer to a field of the enclosing class (possibly inherited). Instance
+    /// fields are only found where `this` is available
+    pub field_map: HashMap<Label, &'a FieldDecl>,
+    /// Calls without a target where `this` is not available, which can only call static methods
+    pub static_calls: HashSet<Label>,
+    /// The variables declared by `foreach` loops, which cannot be assigned to
+    pub foreach_vars: HashSet<Label>,
+    pub classes: ClassTable<'a>,
     pub entry_point: Option<&'a MethodDecl>,
     pub errors: Vec<PreprocessError>,
 
     // Used during processing
+    pub file_scopes: HashMap<FileId, ScopeId>,
+    pub current_scope: Option<ScopeId>,
     pub current_class: Option<&'a ClassDecl>,
+    /// Whether `this` is available (i.e. inside an instance method or a constructor)
+    pub has_this: bool,
     pub current_vars: HashMap<&'a str, &'a VarDecl>,
+    /// Identifiers that are not variables, with the class they appear in and whether `this` is
+    /// available there. They may refer to inherited fields, which are only known once the base
+    /// classes are resolved
+    pub unresolved_names: Vec<(&'a Identifier, &'a ClassDecl, bool)>,
+    /// The number of loops around the current statement
+    pub loop_depth: usize,
 }
 
 impl<'a> PreprocessVisitor<'a> {
     fn insert_node(&mut self, label: Label, node: Node<'a>) {
         let repeated = self.nodes.insert(label, node).is_some();
-        assert_eq!(repeated, false, "Node labels should be unique");
+        assert!(!repeated, "Node labels should be unique");
+        self.classes.set_scope(label, self.current_scope.unwrap());
+    }
+
+    /// Returns the scope of the top level of the given file
+    fn file_scope(&mut self, file: FileId) -> ScopeId {
+        let classes = &mut self.classes;
+        *self.file_scopes.entry(file).or_insert_with(|| classes.add_scope(None, ""))
+    }
+
+    /// Visits a nested block (e.g. the body of a loop). Variables declared in it go out of scope at its end
+    /// Visits the body of a loop, in which `break` and `continue` are allowed
+    fn visit_loop_body(&mut self, body: &'a [Statement]) {
+        self.loop_depth += 1;
+        self.visit_block(body);
+        self.loop_depth -= 1;
+    }
+
+    fn visit_block(&mut self, block: &'a [Statement]) {
+        let outer_vars = self.current_vars.clone();
+        for statement in block {
+            self.visit_statement(statement);
+        }
+        self.current_vars = outer_vars;
+    }
+
+    /// Reports the members that repeat the signature (i.e. the name and parameter types) of a
+    /// previous member of the same class or interface. Types are compared as written
+    fn check_signatures<I>(&mut self, members: I) where I: Iterator<Item = (&'a str, &'a [VarDecl], Label)> {
+        let mut signatures = HashMap::new();
+        for (name, params, label) in members {
+            let signature = signature(name, params);
+            if let Some(&previous) = signatures.get(&signature) {
+                self.errors.push(PreprocessError::MultiMemberDecl { decl: label, previous });
+            } else {
+                signatures.insert(signature, label);
+            }
+        }
+    }
+
+    /// Resolves the base classes and interfaces, and the names that depend on them
+    ///
+    /// Note: this must run after visiting the whole program, since base classes and interfaces
+    /// may be declared anywhere
+    pub fn resolve_inheritance(&mut self, program: &'a Program) {
+        let classes: Vec<_> = program.classes().collect();
+        for &decl in &classes {
+            let scope = self.classes.scope_of(decl.label);
+            let mut interfaces = Vec::new();
+            for (index, base) in decl.bases.iter().enumerate() {
+                if let Some(interface) = self.classes.resolve_interface(&base.name, scope) {
+                    interfaces.push(interface);
+                    continue;
+                }
+
+                // Only the first base type can be a class
+                match self.classes.resolve(&base.name, scope) {
+                    Some(_) if index > 0 => self.errors.push(PreprocessError::ExpectedInterface { decl: decl.label, index }),
+                    Some(base_decl) => {
+                        if base_decl.is_sealed {
+                            self.errors.push(PreprocessError::SealedBaseClass(decl.label.assert_as_class_decl()));
+                        }
+                        self.classes.set_base(decl, Some(base_decl));
+                    }
+                    None => self.errors.push(PreprocessError::UnknownBaseType { decl: decl.label, index })
+                }
+            }
+            self.classes.set_interfaces(decl.label, interfaces);
+        }
+
+        // Cycles are broken at the first class of the program that is part of them
+        for &decl in &classes {
+            let mut visited = HashSet::new();
+            let mut current = self.classes.base_of(decl);
+            while let Some(base) = current {
+                if base.label == decl.label {
+                    self.errors.push(PreprocessError::CyclicInheritance { decl: decl.label, index: 0 });
+                    self.classes.set_base(decl, None);
+                    break;
+                }
+                if !visited.insert(base.label) {
+                    // A cycle that does not include this class
+                    break;
+                }
+                current = self.classes.base_of(base);
+            }
+        }
+
+        // Interfaces can only extend interfaces
+        let interfaces: Vec<_> = program.interfaces().collect();
+        let mut extended = HashMap::new();
+        for &decl in &interfaces {
+            let scope = self.classes.scope_of(decl.label);
+            let mut bases = Vec::new();
+            for (index, base) in decl.bases.iter().enumerate() {
+                match self.classes.resolve_interface(&base.name, scope) {
+                    Some(interface) => bases.push((index, interface)),
+                    None if self.classes.resolve(&base.name, scope).is_some() => {
+                        self.errors.push(PreprocessError::ExpectedInterface { decl: decl.label, index });
+                    }
+                    None => self.errors.push(PreprocessError::UnknownBaseType { decl: decl.label, index })
+                }
+            }
+            self.classes.set_interfaces(decl.label, bases.iter().map(|&(_, interface)| interface).collect());
+            extended.insert(decl.label, bases);
+        }
+
+        // Like for classes, cycles are broken at the first interface of the program that is part of them
+        for &decl in &interfaces {
+            let mut bases = extended.remove(&decl.label).unwrap_or_default();
+            let mut cycles = Vec::new();
+            bases.retain(|&(index, base)| {
+                let is_cyclic = self.classes.interface_hierarchy(base).iter().any(|id| id.label == decl.label);
+                if is_cyclic {
+                    cycles.push(PreprocessError::CyclicInheritance { decl: decl.label, index });
+                }
+                !is_cyclic
+            });
+
+            if !cycles.is_empty() {
+                self.errors.extend(cycles);
+                self.classes.set_interfaces(decl.label, bases.into_iter().map(|(_, interface)| interface).collect());
+            }
+        }
+
+        // Unqualified fields may be inherited. Instance fields are accessed through `this`
+        for (identifier, class, has_this) in mem::take(&mut self.unresolved_names) {
+            if let Some(fd) = self.classes.find_field(class, &identifier.name) {
+                if fd.is_static || has_this {
+                    self.field_map.insert(identifier.label, fd);
+                }
+            }
+        }
     }
 }
 
+/// Formats the signature of a method or constructor, e.g. `M(int, string[])`
+pub fn signature(name: &str, params: &[VarDecl]) -> String {
+    let param_tys: Vec<_> = params.iter().map(|p| p.ty.to_string()).collect();
+    format!("{}({})", name, param_tys.join(", "))
+}
+
 impl<'a> Visitor<'a> for PreprocessVisitor<'a> {
+    fn visit_top_item(&mut self, item: &'a TopItem) {
+        // Items outside of namespaces belong to the top level scope of their file
+        let outer_scope = self.current_scope;
+        if outer_scope.is_none() {
+            self.current_scope = Some(self.file_scope(item.span().file));
+        }
+
+        visitor::walk_top_item(self, item);
+        self.current_scope = outer_scope;
+    }
+
+    fn visit_namespace_decl(&mut self, decl: &'a NamespaceDecl) {
+        self.insert_node(decl.label, Node::NamespaceDecl(decl));
+
+        // `namespace A.B` is equivalent to `namespace A { namespace B }`
+        let outer_scope = self.current_scope;
+        for name in decl.name.split('.') {
+            self.current_scope = Some(self.classes.add_scope(self.current_scope, name));
+        }
+
+        visitor::walk_namespace_decl(self, decl);
+        self.current_scope = outer_scope;
+    }
+
+    fn visit_using_directive(&mut self, using: &'a UsingDirective) {
+        self.insert_node(using.label, Node::UsingDirective(using));
+        self.classes.add_using(self.current_scope.unwrap(), using);
+    }
+
     fn visit_class_decl(&mut self, decl: &'a ClassDecl) {
         // Necessary bookkeeping
         self.current_class = Some(decl);
 
-        let label = decl.label.assert_as_class_decl();
+        // Class table (the first declaration wins, possibly from another file)
+        if let Some(previous) = self.classes.add_class(self.current_scope.unwrap(), decl) {
+            self.errors.push(PreprocessError::MultiTypeDecl { decl: decl.label, previous });
+        }
 
-        // Class map
-        let repeated = self.classes_by_name.insert(&decl.name, decl).is_some();
-        if repeated {
-            self.errors.push(PreprocessError::MultiClassDecl(label));
+        // Only one static constructor is allowed (the first one is used)
+        let mut static_constructors = decl.items.iter().filter_map(|i| i.constructor_decl()).filter(|cd| cd.is_static);
+        if let Some(first) = static_constructors.next() {
+            for other in static_constructors {
+                self.errors.push(PreprocessError::MultiStaticConstructor {
+                    decl: other.label.assert_as_constructor_decl(),
+                    previous: first.label.assert_as_constructor_decl()
+                });
+            }
         }
 
+        // Methods and constructors can only be overloaded on their parameter types
+        self.check_signatures(decl.items.iter().filter_map(|item| match *item {
+            ClassItem::MethodDecl(ref md) => Some((&md.name[..], &md.params[..], md.label)),
+            ClassItem::ConstructorDecl(ref cd) if !cd.is_static => Some((&cd.name[..], &cd.params[..], cd.label)),
+            _ => None
+        }));
+
         // Node tracking
-        self.insert_node(decl.label, Node::ClassDecl(&decl));
+        self.insert_node(decl.label, Node::ClassDecl(decl));
 
         visitor::walk_class_decl(self, decl);
     }
 
+    fn visit_interface_decl(&mut self, decl: &'a InterfaceDecl) {
+        // The methods of interfaces have no body, so they never refer to the current class
+        self.current_class = None;
+
+        // Classes and interfaces share the class table
+        if let Some(previous) = self.classes.add_interface(self.current_scope.unwrap(), decl) {
+            self.errors.push(PreprocessError::MultiTypeDecl { decl: decl.label, previous });
+        }
+
+        self.check_signatures(decl.methods.iter().map(|md| (&md.name[..], &md.params[..], md.label)));
+
+        self.insert_node(decl.label, Node::InterfaceDecl(decl));
+        visitor::walk_interface_decl(self, decl);
+    }
+
     fn visit_field_decl(&mut self, decl: &'a FieldDecl) {
-        self.insert_node(decl.label, Node::FieldDecl(&decl));
+        self.has_this = false;
+        self.insert_node(decl.label, Node::FieldDecl(decl));
         visitor::walk_field_decl(self, decl)
     }
 
     fn visit_method_decl(&mut self, decl: &'a MethodDecl) {
         // Necessary bookkeeping for name resolution
         self.current_vars.clear();
+        self.has_this = !decl.is_static;
 
         let label = decl.label.assert_as_method_decl();
 
         // Entry points must be static and be called Main. Parameters are ignored
         if decl.is_static && decl.name == "Main" {
-            let repeated = mem::replace(&mut self.entry_point, Some(decl)).is_some();
+            let repeated = self.entry_point.replace(decl).is_some();
             if repeated {
                 self.errors.push(PreprocessError::MultiEntryPoint(label));
             }
         }
 
-        // Node tracking
-        self.insert_node(decl.label, Node::MethodDecl(&decl));
+        // Node tracking (methods of classes are tracked like `this`, to find the methods they override)
+        if let Some(class) = self.current_class {
+            self.this_map.insert(decl.label, class);
+        }
+        self.insert_node(decl.label, Node::MethodDecl(decl));
         visitor::walk_method_decl(self, decl);
 
         //println!("Method: {}. Declared vars: {:?}", decl.name, self.current_vars);
     }
 
+    fn visit_constructor_decl(&mut self, decl: &'a ConstructorDecl) {
+        // Parameters are in scope in the call to another constructor as well as in the body
+        self.current_vars.clear();
+        self.has_this = !decl.is_static;
+
+        // Constructors without an initializer implicitly call a constructor of the base class
+        self.this_map.insert(decl.label, self.current_class.unwrap());
+        self.insert_node(decl.label, Node::ConstructorDecl(decl));
+        visitor::walk_constructor_decl(self, decl)
+    }
+
+    fn visit_constructor_call(&mut self, call: &'a ConstructorCall) {
+        // The called constructor belongs to the current class, which is tracked like for `this`
+        self.this_map.insert(call.label, self.current_class.unwrap());
+        self.insert_node(call.label, Node::ConstructorCall(call));
+        visitor::walk_constructor_call(self, call)
+    }
+
     fn visit_statement(&mut self, statement: &'a Statement) {
         visitor::walk_statement(self, statement)
     }
 
     fn visit_assign(&mut self, assign: &'a Assign) {
-        // Name resolution
-        let name: &str = &assign.var_name;
-        let decl = self.current_vars[name];
-        self.var_map.insert(assign.label, decl);
-
+        // Node tracking (the target is resolved like any other identifier)
+        self.insert_node(assign.label, Node::Assign(assign));
         visitor::walk_assign(self, assign)
     }
 
     fn visit_if_then_else(&mut self, ite: &'a IfThenElse) {
-        visitor::walk_if_then_else(self, ite)
+        self.insert_node(ite.label, Node::IfThenElse(ite));
+        self.visit_expression(&ite.condition);
+        self.visit_block(&ite.then);
+        self.visit_block(&ite.else_);
+    }
+
+    fn visit_while(&mut self, while_: &'a While) {
+        self.insert_node(while_.label, Node::While(while_));
+        self.visit_expression(&while_.condition);
+        self.visit_loop_body(&while_.body);
+    }
+
+    fn visit_do_while(&mut self, do_while: &'a DoWhile) {
+        self.insert_node(do_while.label, Node::DoWhile(do_while));
+        self.visit_loop_body(&do_while.body);
+        self.visit_expression(&do_while.condition);
+    }
+
+    fn visit_for(&mut self, for_: &'a For) {
+        self.insert_node(for_.label, Node::For(for_));
+
+        // Variables declared by the initializer are in scope in the whole loop
+        let outer_vars = self.current_vars.clone();
+        if let Some(ref init) = for_.init {
+            self.visit_statement(init);
+        }
+        if let Some(ref condition) = for_.condition {
+            self.visit_expression(condition);
+        }
+        if let Some(ref step) = for_.step {
+            self.visit_statement(step);
+        }
+        self.visit_loop_body(&for_.body);
+        self.current_vars = outer_vars;
+    }
+
+    fn visit_foreach(&mut self, foreach: &'a Foreach) {
+        self.insert_node(foreach.label, Node::Foreach(foreach));
+        self.visit_expression(&foreach.collection);
+
+        // The loop variable is only in scope in the body
+        let outer_vars = self.current_vars.clone();
+        self.visit_var_decl(&foreach.var);
+        self.foreach_vars.insert(foreach.var.label);
+        self.visit_loop_body(&foreach.body);
+        self.current_vars = outer_vars;
+    }
+
+    fn visit_break(&mut self, break_: &'a Break) {
+        if self.loop_depth == 0 {
+            self.errors.push(PreprocessError::OutsideLoop(break_.label));
+        }
+        self.insert_node(break_.label, Node::Break(break_));
+    }
+
+    fn visit_continue(&mut self, continue_: &'a Continue) {
+        if self.loop_depth == 0 {
+            self.errors.push(PreprocessError::OutsideLoop(continue_.label));
+        }
+        self.insert_node(continue_.label, Node::Continue(continue_));
     }
 
     fn visit_expression(&mut self, expr: &'a Expression) {
@@ -100,14 +416,18 @@ impl<'a> Visitor<'a> for PreprocessVisitor<'a> {
     }
 
     fn visit_return(&mut self, ret: &'a Return) {
+        self.insert_node(ret.label, Node::Return(ret));
         visitor::walk_return(self, ret)
     }
 
     fn visit_var_decl(&mut self, var_decl: &'a VarDecl) {
         // Var tracking for name resolution
-        if let Some(_) = self.current_vars.insert(&var_decl.var_name, var_decl) {
+        if let Some(previous) = self.current_vars.insert(&var_decl.var_name, var_decl) {
             // A variable with this name already exists in scope
-            panic!("Double declaration of variable: {}", var_decl.var_name);
+            self.errors.push(PreprocessError::MultiVarDecl {
+                decl: var_decl.label.assert_as_var_decl(),
+                previous: previous.label.assert_as_var_decl()
+            });
         }
 
         // Node tracking
@@ -120,8 +440,13 @@ impl<'a> Visitor<'a> for PreprocessVisitor<'a> {
         visitor::walk_binary_op(self, binary_op)
     }
 
+    fn visit_unary_op(&mut self, unary_op: &'a UnaryOp) {
+        self.insert_node(unary_op.label, Node::UnaryOp(unary_op));
+        visitor::walk_unary_op(self, unary_op)
+    }
+
     fn visit_field_access(&mut self, field_access: &'a FieldAccess) {
-        self.insert_node(field_access.label, Node::FieldAccess(&field_access));
+        self.insert_node(field_access.label, Node::FieldAccess(field_access));
         visitor::walk_field_access(self, field_access)
     }
 
@@ -131,8 +456,16 @@ impl<'a> Visitor<'a> for PreprocessVisitor<'a> {
     }
 
     fn visit_method_call(&mut self, method_call: &'a MethodCall) {
+        // Calls without a target call a method of the current class, on `this` if it is available
+        if method_call.target.is_none() {
+            self.this_map.insert(method_call.label, self.current_class.unwrap());
+            if !self.has_this {
+                self.static_calls.insert(method_call.label);
+            }
+        }
+
         // Track nodes
-        self.insert_node(method_call.label, Node::MethodCall(&method_call));
+        self.insert_node(method_call.label, Node::MethodCall(method_call));
 
         visitor::walk_method_call(self, method_call)
     }
@@ -142,19 +475,37 @@ impl<'a> Visitor<'a> for PreprocessVisitor<'a> {
         visitor::walk_new(self, new)
     }
 
+    fn visit_new_array(&mut self, new_array: &'a NewArray) {
+        self.insert_node(new_array.label, Node::NewArray(new_array));
+        visitor::walk_new_array(self, new_array)
+    }
+
+    fn visit_index(&mut self, index: &'a Index) {
+        self.insert_node(index.label, Node::Index(index));
+        visitor::walk_index(self, index)
+    }
+
     fn visit_identifier(&mut self, identifier: &'a Identifier) {
-        // An identifier can refer to a variable or a type. We ignore them in the second case
+        // An identifier can refer to a variable, a field of the current class (see
+        // `resolve_inheritance`) or a type. We ignore them in the last case
         let name: &str = &identifier.name;
         if let Some(vd) = self.current_vars.get(name) {
             self.var_map.insert(identifier.label, vd);
+        } else {
+            self.unresolved_names.push((identifier, self.current_class.unwrap(), self.has_this));
         }
 
         // Node tracking
-        self.insert_node(identifier.label, Node::Identifier(&identifier));
+        self.insert_node(identifier.label, Node::Identifier(identifier));
         visitor::walk_identifier(self, identifier)
     }
 
     fn visit_this(&mut self, this: &'a This) {
+        // `this` is only available inside instance methods and constructors
+        if !self.has_this {
+            self.errors.push(PreprocessError::InvalidThis(this.label));
+        }
+
         // This map
         self.this_map.insert(this.label, self.current_class.unwrap());
 
@@ -162,4 +513,20 @@ impl<'a> Visitor<'a> for PreprocessVisitor<'a> {
         self.insert_node(this.label, Node::This(this));
         visitor::walk_this(self)
     }
+
+    fn visit_base(&mut self, base: &'a Base) {
+        // Like `this`, `base` refers to the current object
+        if !self.has_this {
+            self.errors.push(PreprocessError::InvalidThis(base.label));
+        }
+
+        self.this_map.insert(base.label, self.current_class.unwrap());
+        self.insert_node(base.label, Node::Base(base));
+        visitor::walk_base(self)
+    }
+
+    fn visit_error(&mut self, error: &'a ErrorNode) {
+        self.insert_node(error.label, Node::ErrorNode(error));
+        visitor::walk_error(self)
+    }
 }
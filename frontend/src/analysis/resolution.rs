@@ -0,0 +1,338 @@
+//! Resolution of the declarations used by calls and class members, which is shared by the
+//! `QueryEngine` and the traditional type checker
+//!
+//! Each checker provides the types of expressions and declarations in its own way (e.g. on
+//! demand), so the same problems are found and reported in the same way by both.
+
+use std::collections::HashSet;
+
+use analysis::{errors, ClassTable, TypeId, TypeMap};
+use ast::*;
+use diagnostics::Diagnostic;
+use syntax::Span;
+
+/// The types and the class table of a checker, used to resolve declarations on its behalf
+pub trait Resolver<'a> {
+    fn classes(&self) -> &ClassTable<'a>;
+
+    fn types(&self) -> &TypeMap;
+
+    fn report(&mut self, diagnostic: Diagnostic);
+
+    /// Returns the type of an expression that is used as a value (e.g. an argument)
+    fn value_ty(&mut self, expr: &'a Expression) -> TypeId;
+
+    /// Returns the declared type of a parameter
+    fn param_ty(&mut self, param: &'a VarDecl) -> TypeId;
+
+    /// Returns the declared return type of a method
+    fn return_ty(&mut self, md: &'a MethodDecl) -> TypeId;
+
+    /// Returns the class that declares the given member
+    fn class_of(&self, member: Label) -> &'a ClassDecl;
+
+    /// Like `find_overridden`, but caches the result, so problems are reported only once
+    fn resolve_override(&mut self, md: &'a MethodDecl) -> Option<&'a MethodDecl>;
+
+    /// Returns the constructor called by a `this(...)` or `base(...)` call, if it could be resolved
+    fn called_constructor(&mut self, call: &'a ConstructorCall) -> Option<&'a ConstructorDecl>;
+
+    /// Returns the parameter types of a method
+    fn param_tys(&mut self, md: &'a MethodDecl) -> Vec<TypeId> {
+        md.params.iter().map(|param| self.param_ty(param)).collect()
+    }
+
+    /// Finds the constructor of the class that takes the given arguments, reporting an error if
+    /// there is none
+    ///
+    /// Note: classes without constructors have an implicit parameterless one, for which `None` is
+    /// returned
+    fn find_constructor(&mut self, class_decl: &'a ClassDecl, args: &'a [Expression], span: Span) -> Option<&'a ConstructorDecl> {
+        let arg_tys: Vec<_> = args.iter().map(|arg| self.value_ty(arg)).collect();
+        let candidates: Vec<_> = class_decl.constructors().collect();
+        let param_tys: Vec<Vec<_>> = candidates.iter().map(|cd| {
+            cd.params.iter().map(|param| self.param_ty(param)).collect()
+        }).collect();
+
+        match self.types().best_overload(&param_tys, &arg_tys) {
+            Ok(Some(i)) => Some(candidates[i]),
+            Err((i, j)) => {
+                let names = |tys: &[TypeId]| tys.iter().map(|&ty| self.types().name(ty)).collect::<Vec<_>>();
+                let diagnostic = errors::ambiguous_call(span, &class_decl.name, &names(&param_tys[i]), &names(&param_tys[j]))
+                    .with_secondary(candidates[i].span, "candidate defined here".to_string())
+                    .with_secondary(candidates[j].span, "candidate defined here".to_string());
+                self.report(diagnostic);
+                None
+            }
+            Ok(None) if candidates.is_empty() => {
+                // The implicit constructor takes no arguments
+                if !args.is_empty() {
+                    self.report(errors::wrong_constructor_argument_count(span, &class_decl.name, 0, args.len()));
+                }
+                None
+            }
+            Ok(None) if candidates.len() == 1 => {
+                // Like for methods, point at the arguments that do not match
+                let cd = candidates[0];
+                if cd.params.len() != args.len() {
+                    let diagnostic = errors::wrong_constructor_argument_count(span, &class_decl.name, cd.params.len(), args.len())
+                        .with_secondary(cd.span, "constructor defined here".to_string());
+                    self.report(diagnostic);
+                }
+
+                for ((arg, param), (&arg_ty, &param_ty)) in args.iter().zip(&cd.params).zip(arg_tys.iter().zip(&param_tys[0])) {
+                    if !self.types().unify(param_ty, arg_ty) {
+                        let diagnostic = errors::mismatched_types(arg.span(), &self.types().name(param_ty), &self.types().name(arg_ty))
+                            .with_secondary(param.span, "parameter declared here".to_string());
+                        self.report(diagnostic);
+                    }
+                }
+                Some(cd)
+            }
+            Ok(None) => {
+                let names: Vec<_> = arg_tys.iter().map(|&ty| self.types().name(ty)).collect();
+                let mut diagnostic = errors::no_matching_constructor(span, self.classes().full_name(class_decl), &names);
+                for cd in &candidates {
+                    diagnostic = diagnostic.with_secondary(cd.span, "candidate defined here".to_string());
+                }
+                self.report(diagnostic);
+                None
+            }
+        }
+    }
+
+    /// Returns true if the chain of `this(...)` calls starting at the constructor leads back to it
+    fn calls_itself(&mut self, constructor: &'a ConstructorDecl) -> bool {
+        let mut visited = HashSet::new();
+        let mut current = constructor;
+        while let Some(ref call) = current.initializer {
+            if call.is_base {
+                // The chain leaves the class
+                return false;
+            }
+
+            current = match self.called_constructor(call) {
+                Some(next) => next,
+                None => return false
+            };
+
+            if current.label == constructor.label {
+                return true;
+            }
+            if !visited.insert(current.label) {
+                // A cycle that does not include this constructor
+                return false;
+            }
+        }
+
+        false
+    }
+
+    /// Finds the method overridden by an `override` method, reporting an error if it is missing or
+    /// cannot be overridden
+    ///
+    /// The overridden method is the instance method with the same name and parameter types in the
+    /// nearest base class that declares one
+    fn find_overridden(&mut self, md: &'a MethodDecl) -> Option<&'a MethodDecl> {
+        if !md.virtuality.is_override() {
+            return None;
+        }
+
+        let class_decl = self.class_of(md.label);
+        let param_tys = self.param_tys(md);
+        let bases = self.classes().base_of(class_decl).map_or(Vec::new(), |base| self.classes().hierarchy(base));
+        let found = bases.into_iter().find_map(|base| {
+            let candidate = base.methods().find(|m| m.name == md.name && !m.is_static && self.param_tys(m) == param_tys);
+            candidate.map(|candidate| (base, candidate))
+        });
+
+        let (base, overridden) = match found {
+            Some(found) => found,
+            None => {
+                self.report(errors::nothing_to_override(md.span, &md.name));
+                return None;
+            }
+        };
+
+        let name = format!("{}.{}", self.classes().full_name(base), overridden.name);
+        let return_ty = self.return_ty(md);
+        let expected = self.return_ty(overridden);
+        let error_ty = self.types().error_ty();
+        if !overridden.virtuality.is_overridable() {
+            let is_sealed = overridden.virtuality == Virtuality::SealedOverride;
+            let diagnostic = errors::not_overridable(md.span, &name, is_sealed)
+                .with_secondary(overridden.span, "overridden method defined here".to_string());
+            self.report(diagnostic);
+            None
+        } else if return_ty != expected && return_ty != error_ty && expected != error_ty {
+            let diagnostic = errors::override_return_type(md.span, &name, &self.types().name(expected))
+                .with_secondary(overridden.span, "overridden method defined here".to_string());
+            self.report(diagnostic);
+            None
+        } else {
+            Some(overridden)
+        }
+    }
+
+    /// Finds the method of the class that implements the given method of an interface
+    ///
+    /// The implementation is the instance method with the same name and parameter types in the
+    /// nearest class of the hierarchy that declares one
+    fn find_implementation(&mut self, class_decl: &'a ClassDecl, md: &'a MethodDecl) -> Option<&'a MethodDecl> {
+        let param_tys = self.param_tys(md);
+        self.classes().hierarchy(class_decl).into_iter().find_map(|cd| {
+            cd.methods().find(|m| m.name == md.name && !m.is_static && self.param_tys(m) == param_tys)
+        })
+    }
+
+    /// Reports the methods of the interfaces listed in the head of the class (or of the interfaces
+    /// they extend) that the class does not implement with the same return type
+    fn check_implementations(&mut self, class_decl: &'a ClassDecl) {
+        let scope = self.classes().scope_of(class_decl.label);
+        let mut checked = HashSet::new();
+        for base in &class_decl.bases {
+            let interfaces = match self.classes().resolve_interface(&base.name, scope) {
+                Some(interface) => self.classes().interface_hierarchy(interface),
+                None => continue
+            };
+
+            for interface in interfaces {
+                for md in interface.methods.iter().filter(|md| checked.insert(md.label)) {
+                    let member = format!("{}.{}", self.classes().interface_full_name(interface), md.name);
+                    let implementation = match self.find_implementation(class_decl, md) {
+                        Some(implementation) => implementation,
+                        None => {
+                            let diagnostic = errors::missing_interface_member(base.span, self.classes().full_name(class_decl), &member)
+                                .with_secondary(md.span, "interface method declared here".to_string());
+                            self.report(diagnostic);
+                            continue;
+                        }
+                    };
+
+                    let return_ty = self.return_ty(implementation);
+                    let expected = self.return_ty(md);
+                    let error_ty = self.types().error_ty();
+                    if return_ty != expected && return_ty != error_ty && expected != error_ty {
+                        let diagnostic = errors::interface_return_type(implementation.span, self.classes().full_name(class_decl), &member, &self.types().name(expected))
+                            .with_secondary(md.span, "interface method declared here".to_string());
+                        self.report(diagnostic);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reports the abstract methods declared by a class that is not abstract, and the ones it
+    /// inherits without overriding them
+    fn check_abstract_members(&mut self, class_decl: &'a ClassDecl) {
+        if class_decl.is_abstract {
+            return;
+        }
+
+        for md in class_decl.methods().filter(|md| md.is_abstract) {
+            let diagnostic = errors::abstract_in_concrete_class(md.span, &md.name, self.classes().full_name(class_decl));
+            self.report(diagnostic);
+        }
+
+        // The base classes are visited from the most derived one, so the methods overridden by a
+        // class are known before reaching the class that declares them
+        let mut overridden = HashSet::new();
+        for cd in self.classes().hierarchy(class_decl) {
+            for md in cd.methods() {
+                if md.is_abstract && cd.label != class_decl.label && !overridden.contains(&md.label) {
+                    let member = format!("{}.{}", self.classes().full_name(cd), md.name);
+                    let diagnostic = errors::missing_abstract_member(class_decl.bases[0].span, self.classes().full_name(class_decl), &member)
+                        .with_secondary(md.span, "abstract method declared here".to_string());
+                    self.report(diagnostic);
+                }
+
+                if let Some(method) = self.resolve_override(md) {
+                    overridden.insert(method.label);
+                }
+            }
+        }
+    }
+
+    /// Resolves a call to a method of the given class (or of one of its base classes), reporting an
+    /// error if the method is not static when `is_static` is `Some(true)`, or vice versa
+    fn resolve_class_method(&mut self, mc: &'a MethodCall, class_decl: &'a ClassDecl, is_static: Option<bool>) -> Option<&'a MethodDecl> {
+        let overloads = self.classes().find_overloads(class_decl, &mc.method_name);
+        if overloads.iter().all(Vec::is_empty) {
+            let diagnostic = errors::unknown_method(mc.span, "class", self.classes().full_name(class_decl), &mc.method_name);
+            self.report(diagnostic);
+            return None;
+        }
+
+        let md = self.resolve_overload(mc, overloads)?;
+        if is_static.is_some_and(|is_static| md.is_static != is_static) {
+            let diagnostic = errors::static_mismatch(mc.span, &md.name, md.is_static)
+                .with_secondary(md.span, "method defined here".to_string());
+            self.report(diagnostic);
+        }
+        if md.is_abstract && matches!(mc.target.as_deref(), Some(&Expression::Base(_))) {
+            let member = format!("{}.{}", self.classes().full_name(self.class_of(md.label)), md.name);
+            self.report(errors::abstract_base_call(mc.span, &member));
+        }
+
+        Some(md)
+    }
+
+    /// Picks the overload that best matches the arguments of a call, given the methods with the
+    /// called name declared in each type of a hierarchy, from the most derived one (see
+    /// `ClassTable::find_overloads`), and reports an error if there is none or if the call is
+    /// ambiguous
+    ///
+    /// Like in C#, `override` methods are not candidates: the call is resolved to the method they
+    /// override, and then bound to its most derived override, so calls through `base` run it
+    ///
+    /// Note: if there is a single candidate, it is picked even if it does not accept the arguments,
+    /// so the arguments that do not match are reported when checking the call
+    fn resolve_overload(&mut self, mc: &'a MethodCall, overloads: Vec<Vec<&'a MethodDecl>>) -> Option<&'a MethodDecl> {
+        let arg_tys: Vec<_> = mc.args.iter().map(|arg| self.value_ty(arg)).collect();
+        let overloads: Vec<Vec<_>> = overloads.into_iter().map(|methods| {
+            methods.into_iter().map(|md| (md, self.param_tys(md))).collect()
+        }).collect();
+
+        // Methods hidden by a method with the same parameter types are not candidates either
+        let mut candidates: Vec<(&MethodDecl, &[TypeId])> = Vec::new();
+        for (depth, methods) in overloads.iter().enumerate() {
+            let overrides_base = |params: &[TypeId]| overloads[depth + 1..].iter().flatten().any(|(_, p)| *p == params);
+            let methods: Vec<_> = methods.iter().filter(|&&(md, ref params)| !md.virtuality.is_override() || !overrides_base(params)).collect();
+            let param_tys: Vec<_> = methods.iter().map(|&(_, params)| params.clone()).collect();
+            match self.types().best_overload(&param_tys, &arg_tys) {
+                Ok(Some(i)) => {
+                    let (md, ref params) = *methods[i];
+                    let implementation = overloads[..depth].iter().flatten().find(|&&(m, ref p)| m.virtuality.is_override() && p == params);
+                    return Some(implementation.map_or(md, |&(m, _)| m));
+                }
+                Ok(None) => {}
+                Err((i, j)) => {
+                    let names = |tys: &[TypeId]| tys.iter().map(|&ty| self.types().name(ty)).collect::<Vec<_>>();
+                    let diagnostic = errors::ambiguous_call(mc.span, &mc.method_name, &names(&param_tys[i]), &names(&param_tys[j]))
+                        .with_secondary(methods[i].0.span, "candidate defined here".to_string())
+                        .with_secondary(methods[j].0.span, "candidate defined here".to_string());
+                    self.report(diagnostic);
+                    return None;
+                }
+            }
+
+            for &&(md, ref params) in &methods {
+                if candidates.iter().all(|&(_, other)| other != &params[..]) {
+                    candidates.push((md, params));
+                }
+            }
+        }
+
+        if let [(md, _)] = candidates[..] {
+            return Some(md);
+        }
+
+        let names: Vec<_> = arg_tys.iter().map(|&ty| self.types().name(ty)).collect();
+        let mut diagnostic = errors::no_matching_overload(mc.span, &mc.method_name, &names);
+        for &(md, _) in &candidates {
+            diagnostic = diagnostic.with_secondary(md.span, "candidate defined here".to_string());
+        }
+        self.report(diagnostic);
+        None
+    }
+}
@@ -4,6 +4,8 @@ use std::cell::Cell;
 use std::fmt;
 
 use analysis::labels;
+use syntax::{SourceMap, Span};
+use syntax::cst::SyntaxTree;
 use super::pretty::PrettyPrinter;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
@@ -18,17 +20,17 @@ pub enum Type {
 
 impl fmt::Display for Type {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            &Type::Array(ref ty) => write!(f, "{}[]", ty),
-            &Type::Custom(ref s) => write!(f, "{}", s),
-            &Type::Void => write!(f, "void"),
+        match *self {
+            Type::Array(ref ty) => write!(f, "{}[]", ty),
+            Type::Custom(ref s) => write!(f, "{}", s),
+            Type::Void => write!(f, "void"),
         }
     }
 }
 
 pub fn fresh_label() -> Label {
     thread_local! {
-        static NEXT_LABEL: Cell<u32> = Cell::new(0);
+        static NEXT_LABEL: Cell<u32> = const { Cell::new(0) };
     }
 
     let label = NEXT_LABEL.with(|l| {
@@ -43,7 +45,11 @@ pub fn fresh_label() -> Label {
 // A program
 #[derive(Clone, Debug)]
 pub struct Program {
-    pub items: Vec<TopItem>
+    pub items: Vec<TopItem>,
+    /// The files this program was parsed from (empty for programs built by hand)
+    pub source_map: SourceMap,
+    /// The concrete syntax tree of each file in the source map, in the same order
+    pub syntax: Vec<SyntaxTree>
 }
 
 impl fmt::Display for Program {
@@ -53,22 +59,73 @@ impl fmt::Display for Program {
 }
 
 impl Program {
-    pub fn classes<'a>(&'a self) -> impl Iterator<Item=&'a ClassDecl> {
-        self.items.iter().map(|&TopItem::ClassDecl(ref cd)| cd)
+    /// Returns the classes of the program, including those declared inside namespaces
+    pub fn classes(&self) -> impl Iterator<Item=&ClassDecl> {
+        let mut classes = Vec::new();
+        collect_classes(&self.items, &mut classes);
+        classes.into_iter()
+    }
+
+    /// Returns the interfaces of the program, including those declared inside namespaces
+    pub fn interfaces(&self) -> impl Iterator<Item=&InterfaceDecl> {
+        let mut interfaces = Vec::new();
+        collect_interfaces(&self.items, &mut interfaces);
+        interfaces.into_iter()
     }
 
-    pub fn methods<'a>(&'a self) -> impl Iterator<Item=&'a MethodDecl> {
+    /// Returns the methods of the classes of the program
+    ///
+    /// Note: the methods of interfaces are not included
+    pub fn methods(&self) -> impl Iterator<Item=&MethodDecl> {
         self.classes()
             .flat_map(|cd| cd.items.iter()) // Get a stream of ClassItem
             .filter_map(|ci| match *ci { ClassItem::MethodDecl(ref md) => Some(md), _ => None }) // Get a stream of MethodDecl
     }
 }
 
+fn collect_classes<'a>(items: &'a [TopItem], classes: &mut Vec<&'a ClassDecl>) {
+    for item in items {
+        match *item {
+            TopItem::ClassDecl(ref cd) => classes.push(cd),
+            TopItem::NamespaceDecl(ref nd) => collect_classes(&nd.items, classes),
+            TopItem::InterfaceDecl(_) | TopItem::UsingDirective(_) => {}
+        }
+    }
+}
+
+fn collect_interfaces<'a>(items: &'a [TopItem], interfaces: &mut Vec<&'a InterfaceDecl>) {
+    for item in items {
+        match *item {
+            TopItem::InterfaceDecl(ref id) => interfaces.push(id),
+            TopItem::NamespaceDecl(ref nd) => collect_interfaces(&nd.items, interfaces),
+            TopItem::ClassDecl(_) | TopItem::UsingDirective(_) => {}
+        }
+    }
+}
+
 /// Top-level items
 #[derive(Clone, Debug)]
 pub enum TopItem {
     /// Class declaration
     ClassDecl(ClassDecl),
+    /// Interface declaration
+    InterfaceDecl(InterfaceDecl),
+    /// Namespace declaration
+    NamespaceDecl(NamespaceDecl),
+    /// Using directive
+    UsingDirective(UsingDirective),
+}
+
+impl TopItem {
+    pub fn span(&self) -> Span {
+        match *self {
+            TopItem::ClassDecl(ClassDecl { span, .. })
+            | TopItem::InterfaceDecl(InterfaceDecl { span, .. })
+            | TopItem::NamespaceDecl(NamespaceDecl { span, .. })
+            | TopItem::UsingDirective(UsingDirective { span, .. })
+            => span
+        }
+    }
 }
 
 impl fmt::Display for TopItem {
@@ -77,30 +134,157 @@ impl fmt::Display for TopItem {
     }
 }
 
+/// A namespace declaration, like `namespace A.B { ... }`
+///
+/// Note: file-scoped namespaces (`namespace A.B;`) contain the rest of the items of the file
+#[derive(Clone, Debug)]
+pub struct NamespaceDecl {
+    pub label: Label,
+    pub span: Span,
+    /// The name of the namespace, which may be qualified (e.g. `A.B`)
+    pub name: String,
+    pub items: Vec<TopItem>,
+    pub is_file_scoped: bool
+}
+
+/// A using directive, like `using A.B;`, or a using alias directive, like `using C = A.B.C;`
+#[derive(Clone, Debug)]
+pub struct UsingDirective {
+    pub label: Label,
+    pub span: Span,
+    pub alias: Option<String>,
+    /// The qualified name of the imported namespace (or the aliased namespace or class)
+    pub name: String
+}
+
 #[derive(Clone, Debug)]
 pub struct ClassDecl {
     pub label: Label,
+    pub span: Span,
+    pub access: Option<Access>,
     pub name: String,
-    pub items: Vec<ClassItem>
+    /// The base class and the implemented interfaces, like `A` and `IFoo` in `class B : A, IFoo`
+    ///
+    /// Note: only the first one may be a class, which is only known once names are resolved
+    pub bases: Vec<BaseType>,
+    pub items: Vec<ClassItem>,
+    /// Whether other classes are forbidden to derive from this one
+    pub is_sealed: bool,
+    /// Whether the class cannot be instantiated, which allows it to declare abstract methods
+    pub is_abstract: bool
 }
 
+/// The name of a base class or interface, as written in the head of a class or an interface (it
+/// may be qualified)
+#[derive(Clone, Debug)]
+pub struct BaseType {
+    pub span: Span,
+    pub name: String
+}
+
+/// An interface, like `interface IShape : IDrawable { int Area(); }`
+#[derive(Clone, Debug)]
+pub struct InterfaceDecl {
+    pub label: Label,
+    pub span: Span,
+    pub access: Option<Access>,
+    pub name: String,
+    /// The interfaces this one extends
+    pub bases: Vec<BaseType>,
+    /// The methods that implementing classes must provide, which have no body
+    pub methods: Vec<MethodDecl>
+}
+
+/// Note: the `find_*` methods only search the members declared in the class itself. Inherited
+/// members are found through the `ClassTable` (e.g. `ClassTable::find_overloads`)
 impl ClassDecl {
-    pub fn find_field(&self, name: &str) -> Label {
-        self.items.iter()
-                  .filter_map(|i| i.field_decl())
-                  .find(|fd| &fd.name == name)
-                  .unwrap().label
+    pub fn find_field(&self, name: &str) -> Option<&FieldDecl> {
+        self.items.iter().filter_map(|i| i.field_decl()).find(|fd| fd.name == name)
+    }
+
+    /// Returns the methods declared in the class with the given name, in declaration order
+    pub fn find_overloads(&self, name: &str) -> Vec<&MethodDecl> {
+        self.methods().filter(|md| md.name == name).collect()
+    }
+
+    /// Returns the methods declared in the class
+    pub fn methods(&self) -> impl Iterator<Item=&MethodDecl> {
+        self.items.iter().filter_map(|i| i.method_decl())
+    }
+
+    /// Returns the declared instance constructors of the class
+    ///
+    /// Note: classes without constructors have an implicit parameterless one, which is not
+    /// part of the AST
+    pub fn constructors(&self) -> impl Iterator<Item=&ConstructorDecl> {
+        self.items.iter().filter_map(|i| i.constructor_decl()).filter(|cd| !cd.is_static)
+    }
+
+    /// Returns the static constructor of the class, if any
+    pub fn static_constructor(&self) -> Option<&ConstructorDecl> {
+        self.items.iter().filter_map(|i| i.constructor_decl()).find(|cd| cd.is_static)
+    }
+}
+
+/// An access modifier, as written in the source
+///
+/// Note: access modifiers are only kept for printing, since everything is public in our implementation
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Access {
+    Public,
+    Private,
+    Protected,
+    Internal,
+    ProtectedInternal,
+    PrivateProtected
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Access::Public => "public".fmt(f),
+            Access::Private => "private".fmt(f),
+            Access::Protected => "protected".fmt(f),
+            Access::Internal => "internal".fmt(f),
+            Access::ProtectedInternal => "protected internal".fmt(f),
+            Access::PrivateProtected => "private protected".fmt(f)
+        }
     }
+}
+
+/// How calls to an instance method are bound
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Virtuality {
+    /// Calls are bound to the method itself, given the type of the target at compile time
+    NonVirtual,
+    /// `virtual`: calls are bound to the implementation of the method in the class of the target
+    /// at runtime, which derived classes can provide with `override`
+    Virtual,
+    /// `override`: provides the implementation of a virtual method of a base class
+    Override,
+    /// `sealed override`: like `override`, but derived classes cannot override it again
+    SealedOverride
+}
 
-    pub fn find_method_any<'a>(&'a self, name: &str) -> &'a MethodDecl {
-        self.items.iter().filter_map(|i| i.method_decl()).find(|md| &md.name == name).unwrap()
+impl Virtuality {
+    pub fn is_override(self) -> bool {
+        matches!(self, Virtuality::Override | Virtuality::SealedOverride)
     }
 
-    pub fn find_method(&self, is_static: bool, name: &str) -> Label {
-        self.items.iter()
-                  .filter_map(|i| i.method_decl())
-                  .find(|md| &md.name == name && md.is_static == is_static)
-                  .unwrap().label
+    /// Returns true if the method can be overridden by derived classes
+    pub fn is_overridable(self) -> bool {
+        matches!(self, Virtuality::Virtual | Virtuality::Override)
+    }
+}
+
+impl fmt::Display for Virtuality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Virtuality::NonVirtual => Ok(()),
+            Virtuality::Virtual => "virtual".fmt(f),
+            Virtuality::Override => "override".fmt(f),
+            Virtuality::SealedOverride => "sealed override".fmt(f)
+        }
     }
 }
 
@@ -110,10 +294,21 @@ pub enum ClassItem {
     /// Field declaration
     FieldDecl(FieldDecl),
     /// Method declaration
-    MethodDecl(MethodDecl)
+    MethodDecl(MethodDecl),
+    /// Constructor declaration
+    ConstructorDecl(ConstructorDecl)
 }
 
 impl ClassItem {
+    pub fn span(&self) -> Span {
+        match *self {
+            ClassItem::FieldDecl(FieldDecl { span, .. })
+            | ClassItem::MethodDecl(MethodDecl { span, .. })
+            | ClassItem::ConstructorDecl(ConstructorDecl { span, .. })
+            => span
+        }
+    }
+
     pub fn method_decl(&self) -> Option<&MethodDecl> {
         match *self {
             ClassItem::MethodDecl(ref m) => Some(m),
@@ -127,26 +322,79 @@ impl ClassItem {
             _ => None
         }
     }
+
+    pub fn constructor_decl(&self) -> Option<&ConstructorDecl> {
+        match *self {
+            ClassItem::ConstructorDecl(ref c) => Some(c),
+            _ => None
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct FieldDecl {
     pub label: Label,
+    pub span: Span,
+    pub access: Option<Access>,
     pub name: String,
     pub ty: Type,
-    pub assignment: Option<Expression>
+    pub assignment: Option<Expression>,
+    pub is_static: bool
 }
 
 #[derive(Clone, Debug)]
 pub struct MethodDecl {
     pub label: Label,
+    pub span: Span,
+    pub access: Option<Access>,
     pub name: String,
     pub params: Vec<VarDecl>,
-    pub body: Vec<Statement>,
+    /// The body of the method, or `None` for declarations without one (abstract methods and the
+    /// methods of an interface)
+    pub body: Option<Vec<Statement>>,
     pub is_static: bool,
+    pub virtuality: Virtuality,
+    /// Whether the method is marked with `new`, which states that it hides the methods with the
+    /// same signature of the base classes instead of overriding them
+    pub is_new: bool,
+    /// Whether the method is marked with `abstract`, which means that it has no body and that
+    /// non-abstract derived classes must override it
+    ///
+    /// Note: abstract methods are implicitly virtual, so their `virtuality` is either `Virtual` or
+    /// `Override` (for `abstract override`)
+    pub is_abstract: bool,
     pub return_ty: Type
 }
 
+/// A constructor, like `Point(int x) : this(x, 0) { ... }`
+///
+/// Static constructors (`static Point() { ... }`) have no parameters nor initializer. They run
+/// once, right after the initializers of the static fields, the first time the class is used
+#[derive(Clone, Debug)]
+pub struct ConstructorDecl {
+    pub label: Label,
+    pub span: Span,
+    pub access: Option<Access>,
+    /// The name of the class, which is also the name of its constructors
+    pub name: String,
+    pub params: Vec<VarDecl>,
+    /// The call to another constructor of the class, which runs instead of the field initializers
+    pub initializer: Option<ConstructorCall>,
+    pub body: Vec<Statement>,
+    pub is_static: bool
+}
+
+/// A call to another constructor of the same class or of the base class, like `this(x, 0)` or
+/// `base(x)` in the head of a constructor
+#[derive(Clone, Debug)]
+pub struct ConstructorCall {
+    pub label: Label,
+    pub span: Span,
+    pub args: Vec<Expression>,
+    /// Whether a constructor of the base class is called (`base(...)`) instead of one of the same class
+    pub is_base: bool
+}
+
 /// Statements
 #[derive(Clone, Debug)]
 pub enum Statement {
@@ -160,24 +408,76 @@ pub enum Statement {
     VarDecl(VarDecl),
     /// If then else
     IfThenElse(IfThenElse),
+    /// While loop
+    While(While),
+    /// Do-while loop
+    DoWhile(DoWhile),
+    /// For loop
+    For(For),
+    /// Foreach loop
+    Foreach(Foreach),
+    /// Exit of the innermost enclosing loop
+    Break(Break),
+    /// Jump to the next iteration of the innermost enclosing loop
+    Continue(Continue),
+    /// A statement that could not be parsed
+    Error(ErrorNode),
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match *self {
+            Statement::Assign(Assign { span, .. })
+            | Statement::Return(Return { span, .. })
+            | Statement::VarDecl(VarDecl { span, .. })
+            | Statement::IfThenElse(IfThenElse { span, .. })
+            | Statement::While(While { span, .. })
+            | Statement::DoWhile(DoWhile { span, .. })
+            | Statement::For(For { span, .. })
+            | Statement::Foreach(Foreach { span, .. })
+            | Statement::Break(Break { span, .. })
+            | Statement::Continue(Continue { span, .. })
+            | Statement::Error(ErrorNode { span, .. })
+            => span,
+            Statement::Expression(ref expr) => expr.span()
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct Assign {
     pub label: Label,
-    pub var_name: String,
+    pub span: Span,
+    /// The location being assigned to (see `Expression::is_lvalue`)
+    pub target: Expression,
+    /// The operator of a compound assignment (e.g. `+` in `x += 1`)
+    pub operator: Option<BinaryOperator>,
     pub expr: Expression
 }
 
+#[derive(Clone, Debug)]
+pub struct Break {
+    pub label: Label,
+    pub span: Span
+}
+
+#[derive(Clone, Debug)]
+pub struct Continue {
+    pub label: Label,
+    pub span: Span
+}
+
 #[derive(Clone, Debug)]
 pub struct Return {
     pub label: Label,
+    pub span: Span,
     pub expr: Option<Expression>
 }
 
 #[derive(Clone, Debug)]
 pub struct VarDecl {
     pub label: Label,
+    pub span: Span,
     pub var_name: String,
     pub ty: Type,
     pub expr: Option<Expression>
@@ -186,16 +486,63 @@ pub struct VarDecl {
 #[derive(Clone, Debug)]
 pub struct IfThenElse {
     pub label: Label,
+    pub span: Span,
     pub condition: Expression,
     pub then: Vec<Statement>,
     pub else_: Vec<Statement>
 }
 
+#[derive(Clone, Debug)]
+pub struct While {
+    pub label: Label,
+    pub span: Span,
+    pub condition: Expression,
+    pub body: Vec<Statement>
+}
+
+/// A `do { ... } while (condition);` loop, whose body runs at least once
+#[derive(Clone, Debug)]
+pub struct DoWhile {
+    pub label: Label,
+    pub span: Span,
+    pub body: Vec<Statement>,
+    pub condition: Expression
+}
+
+/// A `for (init; condition; step) { ... }` loop
+///
+/// Note: variables declared by `init` are in scope in the whole loop
+#[derive(Clone, Debug)]
+pub struct For {
+    pub label: Label,
+    pub span: Span,
+    /// A variable declaration, an assignment or an expression statement
+    pub init: Option<Box<Statement>>,
+    /// A missing condition always holds
+    pub condition: Option<Expression>,
+    /// An assignment or an expression statement
+    pub step: Option<Box<Statement>>,
+    pub body: Vec<Statement>
+}
+
+/// A `foreach (T x in collection) { ... }` loop
+#[derive(Clone, Debug)]
+pub struct Foreach {
+    pub label: Label,
+    pub span: Span,
+    /// The loop variable, which is in scope in the body
+    pub var: VarDecl,
+    pub collection: Expression,
+    pub body: Vec<Statement>
+}
+
 /// Expressions
 #[derive(Clone, Debug)]
 pub enum Expression {
     /// Binary operators
     BinaryOp(BinaryOp),
+    /// Unary operators
+    UnaryOp(UnaryOp),
     /// Field access
     FieldAccess(FieldAccess),
     /// Literals
@@ -204,25 +551,43 @@ pub enum Expression {
     MethodCall(MethodCall),
     /// New (construct class and allocate it on the heap)
     New(New),
+    /// Array creation with a length (e.g. `new int[n]`)
+    NewArray(NewArray),
+    /// Element of an array (e.g. `a[i]`)
+    Index(Index),
     /// Identifiers
     ///
     /// Represents a variable usage or a class name when calling a static method
     Identifier(Identifier),
     /// The `this` keyword
     This(This),
+    /// The `base` keyword, which is always the target of a field access or a method call
+    Base(Base),
+    /// An expression that could not be parsed (e.g. a missing operand in `1 + ;`)
+    Error(ErrorNode),
 }
 
 #[derive(Clone, Debug)]
 pub struct BinaryOp {
     pub label: Label,
+    pub span: Span,
     pub operator: BinaryOperator,
     pub left: Box<Expression>,
     pub right: Box<Expression>,
 }
 
+#[derive(Clone, Debug)]
+pub struct UnaryOp {
+    pub label: Label,
+    pub span: Span,
+    pub operator: UnaryOperator,
+    pub operand: Box<Expression>,
+}
+
 #[derive(Clone, Debug)]
 pub struct FieldAccess {
     pub label: Label,
+    pub span: Span,
     pub target: Box<Expression>,
     pub field_name: String,
 }
@@ -230,23 +595,26 @@ pub struct FieldAccess {
 #[derive(Clone, Debug)]
 pub struct Literal {
     pub label: Label,
+    pub span: Span,
     pub kind: LiteralKind
 }
 
 #[derive(Clone, Debug)]
 pub struct MethodCall {
     pub label: Label,
-    pub target: Box<Expression>,
+    pub span: Span,
+    /// The object or class on which the method is called, or `None` for calls without a target
+    /// (e.g. `Foo()`), which call a method of the enclosing class or one of its base classes
+    pub target: Option<Box<Expression>>,
     pub method_name: String,
     pub args: Vec<Expression>
 }
 
 impl MethodCall {
     pub fn is_console_write_line(&self) -> bool {
-        if let &Expression::Identifier(ref class_name) = &*self.target {
-            class_name.name == "Console" && self.method_name == "WriteLine"
-        } else {
-            false
+        match self.target.as_ref().and_then(|target| target.path()) {
+            Some(class_name) => (class_name == "Console" || class_name == "System.Console") && self.method_name == "WriteLine",
+            None => false
         }
     }
 }
@@ -254,12 +622,33 @@ impl MethodCall {
 #[derive(Clone, Debug)]
 pub struct New {
     pub label: Label,
-    pub class_name: String
+    pub span: Span,
+    pub class_name: String,
+    /// The arguments passed to the constructor
+    pub args: Vec<Expression>
+}
+
+#[derive(Clone, Debug)]
+pub struct NewArray {
+    pub label: Label,
+    pub span: Span,
+    /// The type of the elements (e.g. `int[]` in `new int[n][]`)
+    pub elem_ty: Type,
+    pub length: Box<Expression>
+}
+
+#[derive(Clone, Debug)]
+pub struct Index {
+    pub label: Label,
+    pub span: Span,
+    pub target: Box<Expression>,
+    pub index: Box<Expression>
 }
 
 #[derive(Clone, Debug)]
 pub struct Identifier {
     pub label: Label,
+    pub span: Span,
     pub name: String
 }
 
@@ -271,29 +660,86 @@ impl fmt::Display for Identifier {
 
 #[derive(Clone, Debug)]
 pub struct This {
-    pub label: Label
+    pub label: Label,
+    pub span: Span
+}
+
+/// Refers to the current object, with the type of the base class of the current class
+#[derive(Clone, Debug)]
+pub struct Base {
+    pub label: Label,
+    pub span: Span
+}
+
+/// A piece of code where the parser had to recover from a syntax error
+///
+/// Note: the error itself is reported as a diagnostic while parsing
+#[derive(Clone, Debug)]
+pub struct ErrorNode {
+    pub label: Label,
+    pub span: Span
 }
 
 impl Expression {
     pub fn label(&self) -> labels::Expression {
         match *self {
             Expression::BinaryOp(BinaryOp { label, .. })
+            | Expression::UnaryOp(UnaryOp { label, .. })
             | Expression::FieldAccess(FieldAccess { label, .. })
             | Expression::Literal(Literal { label, .. })
             | Expression::MethodCall(MethodCall { label, .. })
             | Expression::New(New { label, .. })
+            | Expression::NewArray(NewArray { label, .. })
+            | Expression::Index(Index { label, .. })
             | Expression::Identifier(Identifier { label, .. })
             | Expression::This(This { label, .. })
+            | Expression::Base(Base { label, .. })
+            | Expression::Error(ErrorNode { label, .. })
             => labels::Expression(label)
         }
     }
 
+    pub fn span(&self) -> Span {
+        match *self {
+            Expression::BinaryOp(BinaryOp { span, .. })
+            | Expression::UnaryOp(UnaryOp { span, .. })
+            | Expression::FieldAccess(FieldAccess { span, .. })
+            | Expression::Literal(Literal { span, .. })
+            | Expression::MethodCall(MethodCall { span, .. })
+            | Expression::New(New { span, .. })
+            | Expression::NewArray(NewArray { span, .. })
+            | Expression::Index(Index { span, .. })
+            | Expression::Identifier(Identifier { span, .. })
+            | Expression::This(This { span, .. })
+            | Expression::Base(Base { span, .. })
+            | Expression::Error(ErrorNode { span, .. })
+            => span
+        }
+    }
+
     pub fn identifier(&self) -> &Identifier {
-        match self {
-            &Expression::Identifier(ref i) => i,
+        match *self {
+            Expression::Identifier(ref i) => i,
             _ => panic!()
         }
     }
+
+    /// Returns true if the expression denotes a location that can be assigned to
+    ///
+    /// Note: right now, the only lvalues are variables, fields and array elements
+    pub fn is_lvalue(&self) -> bool {
+        matches!(*self, Expression::Identifier(_) | Expression::FieldAccess(_) | Expression::Index(_))
+    }
+
+    /// Returns the qualified name (e.g. `A.B.Foo`) if the expression consists only of identifiers
+    /// separated by dots, which may refer to a class or a namespace
+    pub fn path(&self) -> Option<String> {
+        match *self {
+            Expression::Identifier(ref i) => Some(i.name.clone()),
+            Expression::FieldAccess(ref fa) => fa.target.path().map(|target| format!("{}.{}", target, fa.field_name)),
+            _ => None
+        }
+    }
 }
 
 /// Literals
@@ -316,13 +762,32 @@ impl fmt::Display for Literal {
         match self.kind {
             LiteralKind::Bool(x) => x.fmt(f),
             LiteralKind::Int(x) => x.fmt(f),
-            LiteralKind::String(ref s) => write!(f, "\"{}\"", s),
+            LiteralKind::String(ref s) => write!(f, "\"{}\"", escape(s)),
             LiteralKind::Null => "null".fmt(f),
-            LiteralKind::Array(_, _) => unimplemented!()
+            LiteralKind::Array(..) => PrettyPrinter::new().print_literal(f, self)
         }
     }
 }
 
+/// Escapes a string, so it can be written as a C# string literal
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\0' => escaped.push_str("\\0"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04X}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+
+    escaped
+}
+
 /// Operators
 #[derive(Clone, Copy, Debug)]
 pub enum BinaryOperator {
@@ -330,7 +795,45 @@ pub enum BinaryOperator {
     Sub,
     Mul,
     Div,
-    Eq
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or
+}
+
+impl BinaryOperator {
+    /// Operators with a higher precedence bind tighter
+    ///
+    /// Note: all binary operators are left associative
+    pub fn precedence(self) -> u32 {
+        match self {
+            BinaryOperator::Or => 0,
+            BinaryOperator::And => 1,
+            BinaryOperator::Eq | BinaryOperator::Ne => 2,
+            BinaryOperator::Lt | BinaryOperator::Le | BinaryOperator::Gt | BinaryOperator::Ge => 3,
+            BinaryOperator::Add | BinaryOperator::Sub => 4,
+            BinaryOperator::Mul | BinaryOperator::Div => 5
+        }
+    }
+
+    /// Returns true for the operators that take and produce `int`s
+    pub fn is_arithmetic(self) -> bool {
+        matches!(self, BinaryOperator::Add | BinaryOperator::Sub | BinaryOperator::Mul | BinaryOperator::Div)
+    }
+
+    /// Returns true for `==` and `!=`, which compare values of any type
+    pub fn is_equality(self) -> bool {
+        matches!(self, BinaryOperator::Eq | BinaryOperator::Ne)
+    }
+
+    /// Returns true for `&&` and `||`, which only evaluate their right operand if needed
+    pub fn is_logical(self) -> bool {
+        matches!(self, BinaryOperator::And | BinaryOperator::Or)
+    }
 }
 
 impl fmt::Display for BinaryOperator {
@@ -340,7 +843,53 @@ impl fmt::Display for BinaryOperator {
             BinaryOperator::Sub => "-".fmt(f),
             BinaryOperator::Mul => "*".fmt(f),
             BinaryOperator::Div => "/".fmt(f),
-            BinaryOperator::Eq => "==".fmt(f)
+            BinaryOperator::Eq => "==".fmt(f),
+            BinaryOperator::Ne => "!=".fmt(f),
+            BinaryOperator::Lt => "<".fmt(f),
+            BinaryOperator::Le => "<=".fmt(f),
+            BinaryOperator::Gt => ">".fmt(f),
+            BinaryOperator::Ge => ">=".fmt(f),
+            BinaryOperator::And => "&&".fmt(f),
+            BinaryOperator::Or => "||".fmt(f)
+        }
+    }
+}
+
+/// Unary operators
+#[derive(Clone, Copy, Debug)]
+pub enum UnaryOperator {
+    Not,
+    Neg,
+    PreIncrement,
+    PreDecrement,
+    PostIncrement,
+    PostDecrement
+}
+
+impl UnaryOperator {
+    /// Unary operators bind tighter than binary ones (see `BinaryOperator::precedence`)
+    pub fn precedence(self) -> u32 {
+        6
+    }
+
+    /// Returns true for `++` and `--`, which modify their operand
+    pub fn is_increment(self) -> bool {
+        !matches!(self, UnaryOperator::Not | UnaryOperator::Neg)
+    }
+
+    /// Returns true for the operators written after their operand (`x++` and `x--`)
+    pub fn is_postfix(self) -> bool {
+        matches!(self, UnaryOperator::PostIncrement | UnaryOperator::PostDecrement)
+    }
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnaryOperator::Not => "!".fmt(f),
+            UnaryOperator::Neg => "-".fmt(f),
+            UnaryOperator::PreIncrement | UnaryOperator::PostIncrement => "++".fmt(f),
+            UnaryOperator::PreDecrement | UnaryOperator::PostDecrement => "--".fmt(f)
         }
     }
 }
@@ -1,4 +1,5 @@
 use ast::*;
+use syntax::Span;
 
 macro_rules! nodes {
     ( $( $x:ident ),* ) => {
@@ -8,11 +9,25 @@ macro_rules! nodes {
             )*
         }
 
+        impl<'a> Node<'a> {
+            pub fn label(&self) -> Label {
+                match *self {
+                    $( Node::$x(x) => x.label, )*
+                }
+            }
+
+            pub fn span(&self) -> Span {
+                match *self {
+                    $( Node::$x(x) => x.span, )*
+                }
+            }
+        }
+
         $(
             impl DerivedFromNode for $x {
-                fn unwrap<'a>(node: &'a Node) -> &'a $x {
+                fn unwrap<'a>(node: &Node<'a>) -> &'a $x {
                     match *node {
-                        Node::$x(ref x) => x,
+                        Node::$x(x) => x,
                         _ => unreachable!()
                     }
                 }
@@ -26,28 +41,48 @@ macro_rules! nodes {
 nodes! {
     // Top level items
     ClassDecl,
+    InterfaceDecl,
+    NamespaceDecl,
+    UsingDirective,
     // Class items
     FieldDecl,
     MethodDecl,
+    ConstructorDecl,
+    ConstructorCall,
     // Statements
+    Assign,
+    IfThenElse,
+    While,
+    DoWhile,
+    For,
+    Foreach,
+    Break,
+    Continue,
+    Return,
     VarDecl,
     // Expressions
-    FieldAccess, // Right now, this is useless... Since there is no way of assigning a value to a field
+    FieldAccess,
     MethodCall,
     Identifier,
     BinaryOp,
+    UnaryOp,
     Literal,
     New,
-    This
+    NewArray,
+    Index,
+    This,
+    Base,
+    // Statements and expressions that could not be parsed
+    ErrorNode
 }
 
 impl<'a> Node<'a> {
-    pub fn downcast<T>(&'a self) -> &'a T
+    pub fn downcast<T>(&self) -> &'a T
     where T: DerivedFromNode {
         T::unwrap(self)
     }
 }
 
 pub trait DerivedFromNode {
-    fn unwrap<'a>(node: &'a Node) -> &'a Self;
+    fn unwrap<'a>(node: &Node<'a>) -> &'a Self;
 }
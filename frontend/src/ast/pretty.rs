@@ -1,184 +1,885 @@
-use std::fmt::{self, Display};
+//! Printing of ASTs as C# source code
+//!
+//! The printer is also the source formatter (see `format`): given the comments and empty lines
+//! of the original source, it keeps them in the output.
 
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use syntax::Span;
+use syntax::lexer::Keyword;
 use super::ast::*;
 
+/// How nested code is indented
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Indent {
+    Spaces(usize),
+    Tabs
+}
+
+/// Where the opening brace of a block goes
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BraceStyle {
+    /// At the end of the line that opens the block (e.g. `class A {`)
+    SameLine,
+    /// On a line of its own
+    NextLine
+}
+
+/// The layout of printed code
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Style {
+    pub indent: Indent,
+    pub brace_style: BraceStyle,
+    /// The maximum length of a line
+    ///
+    /// Longer lines are broken at argument lists, parameter lists, array literals and binary
+    /// operators. Lines that cannot be broken (e.g. a long string literal) stay too long.
+    pub max_width: usize
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            indent: Indent::Spaces(4),
+            brace_style: BraceStyle::SameLine,
+            max_width: 100
+        }
+    }
+}
+
+impl Style {
+    /// The width of an indentation level, counting tabs as 4 columns
+    fn indent_width(&self) -> usize {
+        match self.indent {
+            Indent::Spaces(n) => n,
+            Indent::Tabs => 4
+        }
+    }
+}
+
+/// A comment in the source being printed
+#[derive(Clone, Debug)]
+pub struct Comment {
+    /// The byte offset of the comment in its file
+    pub start: usize,
+    pub text: String,
+    /// If the comment follows code on the same line, the end of that code
+    pub after: Option<usize>,
+    /// The end of the last token before the comment that is not trivia
+    pub follows: Option<usize>,
+    /// The start of the first token after the comment that is not trivia
+    pub precedes: Option<usize>
+}
+
+impl Comment {
+    fn is_line_comment(&self) -> bool {
+        self.text.starts_with("//")
+    }
+}
+
+/// The parts of a source file that are not in its AST, but are kept by the printer
+#[derive(Clone, Debug, Default)]
+pub struct Trivia {
+    /// The comments of the file, in source order
+    pub comments: Vec<Comment>,
+    /// The offsets of the tokens (comments included) that are preceded by an empty line
+    pub empty_lines: HashSet<usize>
+}
+
+/// Where an expression is rendered. Without a position, expressions are rendered in a single line
+#[derive(Clone, Copy)]
+struct Position {
+    column: usize,
+    /// The length of the text that follows the expression in its last line (e.g. a `;`)
+    suffix: usize,
+    level: usize
+}
+
+/// A comma separated list rendered by `PrettyPrinter::list` (e.g. the arguments of a call)
+struct List {
+    /// The text before the opening delimiter (e.g. the name of the called method)
+    head: String,
+    open: &'static str,
+    close: &'static str,
+    /// Whether the items are separated from the delimiters by a space (e.g. `{ 1, 2 }`)
+    spaced: bool,
+    /// The spans of the items, used to place the comments between them
+    spans: Vec<Span>
+}
+
+impl List {
+    /// A list in parentheses, like the arguments of a call
+    fn parens(head: String, spans: Vec<Span>) -> List {
+        List { head, open: "(", close: ")", spaced: false, spans }
+    }
+
+    /// A list in braces, like the elements of an array literal
+    fn braces(head: String, spans: Vec<Span>) -> List {
+        List { head, open: " {", close: "}", spaced: true, spans }
+    }
+}
+
 pub struct PrettyPrinter {
-    indentation: u32
+    style: Style,
+    level: usize,
+    comments: VecDeque<Comment>,
+    empty_lines: HashSet<usize>,
+    /// The comments that were rendered inside of an expression or a list, by their offset
+    inline_comments: RefCell<HashSet<usize>>,
+    /// The ends of the statements that own the blocks being printed
+    block_ends: Vec<usize>,
+    /// Whether nothing has been printed since the start of the current block
+    block_start: bool
+}
+
+impl Default for PrettyPrinter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PrettyPrinter {
     pub fn new() -> PrettyPrinter {
+        PrettyPrinter::with_style(Style::default())
+    }
+
+    pub fn with_style(style: Style) -> PrettyPrinter {
         PrettyPrinter {
-            indentation: 0
+            style,
+            level: 0,
+            comments: VecDeque::new(),
+            empty_lines: HashSet::new(),
+            inline_comments: RefCell::new(HashSet::new()),
+            block_ends: Vec::new(),
+            block_start: true
         }
     }
 
+    /// Keeps the comments and empty lines of the source of the printed items
+    pub fn with_trivia(mut self, trivia: Trivia) -> PrettyPrinter {
+        self.comments = trivia.comments.into();
+        self.empty_lines = trivia.empty_lines;
+        self
+    }
+
     // Utility methods
-    fn bracket_open(&mut self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.indentation += 4;
-        writeln!(f, "{{")
+    fn indent(&self, f: &mut dyn fmt::Write) -> fmt::Result {
+        write!(f, "{}", self.indentation(self.level))
     }
 
-    fn bracket_close(&mut self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.indentation -= 4;
-        self.indent(f)?;
-        writeln!(f, "}}")
+    fn indentation(&self, level: usize) -> String {
+        match self.style.indent {
+            Indent::Spaces(n) => " ".repeat(n * level),
+            Indent::Tabs => "\t".repeat(level)
+        }
     }
 
-    fn indent(&mut self, f: &mut fmt::Formatter) -> fmt::Result {
-        for _ in 0..self.indentation {
-            write!(f, " ")?;
+    fn column(&self) -> usize {
+        self.level * self.style.indent_width()
+    }
+
+    /// The width of the last line of the given text
+    fn width(&self, text: &str) -> usize {
+        let last_line = text.rsplit('\n').next().unwrap_or("");
+        last_line.chars().map(|c| if c == '\t' { self.style.indent_width() } else { 1 }).sum()
+    }
+
+    /// The length of the text printed after the header of a block, in the same line
+    fn brace_width(&self) -> usize {
+        match self.style.brace_style {
+            BraceStyle::SameLine => 2,
+            BraceStyle::NextLine => 0
         }
+    }
 
+    fn bracket_open(&mut self, f: &mut dyn fmt::Write) -> fmt::Result {
+        match self.style.brace_style {
+            BraceStyle::SameLine => writeln!(f, " {{")?,
+            BraceStyle::NextLine => {
+                writeln!(f)?;
+                self.indent(f)?;
+                writeln!(f, "{{")?;
+            }
+        }
+
+        self.level += 1;
+        self.block_start = true;
         Ok(())
     }
 
-    fn block(&mut self, f: &mut fmt::Formatter, block: &[Statement]) -> fmt::Result {
-        self.bracket_open(f)?;
+    /// Prints the comments left before the end of the block, followed by its closing brace
+    fn bracket_close(&mut self, f: &mut dyn fmt::Write, end: usize) -> fmt::Result {
+        self.comments_before(f, end, false)?;
+        self.level -= 1;
+        self.block_start = false;
+        self.indent(f)?;
+        write!(f, "}}")
+    }
 
-        for statement in block {
-            self.print_statement(f, &statement)?;
+    /// Ends the line of a node ending at the given position
+    ///
+    /// Comments inside the node and comments that followed it in the same line are printed at
+    /// the end of the line. If the node is the last one of a block without braces (e.g. in
+    /// `if (a) return; // Comment`), the comments after it are left to the statement of the block.
+    fn end_line(&mut self, f: &mut dyn fmt::Write, end: usize) -> fmt::Result {
+        // Note: the end of an expression statement does not include its `;`
+        while self.comments.front().is_some_and(|c| c.start < end || c.after.is_some_and(|after| self.is_trailing(after, end))) {
+            let comment = self.comments.pop_front().unwrap();
+            if !self.inline_comments.borrow().contains(&comment.start) {
+                write!(f, " {}", comment.text)?;
+            }
         }
 
-        self.bracket_close(f)
+        writeln!(f)
     }
 
-    fn comma_separated<T, F>(f: &mut fmt::Formatter, items: &[T], format: F) -> fmt::Result
-    where
-        F: Fn(&mut fmt::Formatter, &T) -> fmt::Result
-    {
-        if items.len() > 0 {
-            let last = items.len() - 1;
-            for x in &items[..last] {
-                format(f, x)?;
-                write!(f, ", ")?;
+    /// Returns true if a comment following code that ends at `after` trails a node ending at `end`
+    fn is_trailing(&self, after: usize, end: usize) -> bool {
+        (after == end || after == end + 1) && !self.block_ends.contains(&after)
+    }
+
+    /// Prints an empty line if there was one before the given position, or if `force` is set,
+    /// unless at the start of a block
+    fn empty_line(&mut self, f: &mut dyn fmt::Write, pos: usize, force: bool) -> fmt::Result {
+        if !self.block_start && (force || self.empty_lines.contains(&pos)) {
+            writeln!(f)?;
+        }
+
+        self.block_start = false;
+        Ok(())
+    }
+
+    /// Prints the comments before the given position in their own lines, returning whether
+    /// `separate` still needs to be honored (see `start_item`)
+    fn comments_before(&mut self, f: &mut dyn fmt::Write, pos: usize, mut separate: bool) -> Result<bool, fmt::Error> {
+        while self.comments.front().is_some_and(|c| c.start < pos) {
+            let comment = self.comments.pop_front().unwrap();
+            if self.inline_comments.borrow().contains(&comment.start) {
+                continue;
             }
 
-            let x = &items[last];
-            format(f, x)
-        } else {
-            Ok(())
+            self.empty_line(f, comment.start, separate)?;
+            separate = false;
+            self.indent(f)?;
+            writeln!(f, "{}", comment.text)?;
         }
+
+        Ok(separate)
+    }
+
+    /// Starts an item (a top level item, a class member or a statement) at the given position,
+    /// printing the comments before it. If `separate` is set, the item is preceded by an empty line.
+    fn start_item(&mut self, f: &mut dyn fmt::Write, pos: usize, separate: bool) -> fmt::Result {
+        let separate = self.comments_before(f, pos, separate)?;
+        self.empty_line(f, pos, separate)
     }
 
     // AST-related
-    pub fn print_program(&mut self, f: &mut fmt::Formatter, p: &Program) -> fmt::Result {
-        for item in &p.items {
+    pub fn print_program(&mut self, f: &mut dyn fmt::Write, p: &Program) -> fmt::Result {
+        self.print_items(f, &p.items)
+    }
+
+    /// Prints the top level items of a file, followed by the comments after them
+    pub fn print_items(&mut self, f: &mut dyn fmt::Write, items: &[TopItem]) -> fmt::Result {
+        self.top_items(f, items, false)?;
+        self.comments_before(f, usize::MAX, false)?;
+        Ok(())
+    }
+
+    fn top_items(&mut self, f: &mut dyn fmt::Write, items: &[TopItem], separate_first: bool) -> fmt::Result {
+        let mut previous: Option<&TopItem> = None;
+        for item in items {
+            // Using directives are grouped, other items are separated by an empty line
+            let separate = match previous {
+                Some(&TopItem::UsingDirective(_)) => !matches!(*item, TopItem::UsingDirective(_)),
+                Some(_) => true,
+                None => separate_first
+            };
+            self.start_item(f, item.span().start, separate)?;
             self.print_top_item(f, item)?;
-            writeln!(f)?;
+            previous = Some(item);
         }
 
         Ok(())
     }
 
-    pub fn print_top_item(&mut self, f: &mut fmt::Formatter, i: &TopItem) -> fmt::Result {
-        let &TopItem::ClassDecl(ref cd) = i;
-        write!(f, "class {} ", cd.name)?;
+    pub fn print_top_item(&mut self, f: &mut dyn fmt::Write, i: &TopItem) -> fmt::Result {
+        match *i {
+            TopItem::ClassDecl(ref cd) => self.print_class_decl(f, cd),
+            TopItem::InterfaceDecl(ref id) => self.print_interface_decl(f, id),
+            TopItem::NamespaceDecl(ref nd) => {
+                self.indent(f)?;
+                if nd.is_file_scoped {
+                    writeln!(f, "namespace {};", nd.name)?;
+                    self.block_start = false;
+                    return self.top_items(f, &nd.items, true);
+                }
 
+                write!(f, "namespace {}", nd.name)?;
+                self.bracket_open(f)?;
+                self.top_items(f, &nd.items, false)?;
+                self.bracket_close(f, nd.span.end)?;
+                self.end_line(f, nd.span.end)
+            }
+            TopItem::UsingDirective(ref ud) => {
+                self.indent(f)?;
+                match ud.alias {
+                    Some(ref alias) => write!(f, "using {} = {};", alias, ud.name)?,
+                    None => write!(f, "using {};", ud.name)?
+                }
+                self.end_line(f, ud.span.end)
+            }
+        }
+    }
+
+    pub fn print_class_decl(&mut self, f: &mut dyn fmt::Write, cd: &ClassDecl) -> fmt::Result {
+        self.indent(f)?;
+        let abstract_ = if cd.is_abstract { "abstract " } else { "" };
+        let sealed = if cd.is_sealed { "sealed " } else { "" };
+        write!(f, "{}{}{}class {}{}", modifiers(cd.access), abstract_, sealed, cd.name, base_list(&cd.bases))?;
         self.bracket_open(f)?;
 
+        let mut previous: Option<&ClassItem> = None;
         for item in &cd.items {
-            self.indent(f)?;
-            // Note: all fields and methods are public in our implementation
-            write!(f, "public ")?;
+            // Methods and constructors are separated from other members by an empty line
+            let is_method = |item: &ClassItem| item.field_decl().is_none();
+            let separate = previous.is_some_and(|previous| is_method(previous) || is_method(item));
+            self.start_item(f, item.span().start, separate)?;
             match *item {
-                ClassItem::FieldDecl(ref fd) => {
-                    write!(f, "{} {}", fd.ty, fd.name)?;
-                    if let Some(ref assignment) = fd.assignment {
-                        self.print_expression(f, assignment)?;
-                    }
-                    writeln!(f, ";")?;
-                }
-                ClassItem::MethodDecl(ref md) => {
-                    if md.is_static {
-                        write!(f, "static ")?;
-                    }
+                ClassItem::FieldDecl(ref fd) => self.print_field_decl(f, fd)?,
+                ClassItem::MethodDecl(ref md) => self.print_method_decl(f, md)?,
+                ClassItem::ConstructorDecl(ref cd) => self.print_constructor_decl(f, cd)?
+            }
+            previous = Some(item);
+        }
 
-                    write!(f, "{} {}(", md.return_ty, md.name)?;
-                    PrettyPrinter::comma_separated(f, &md.params, |f, param| {
-                        write!(f, "{} {}", param.ty, param.var_name)
-                    })?;
-                    write!(f, ") ")?;
-                    self.block(f, &md.body)?;
-                }
+        self.bracket_close(f, cd.span.end)?;
+        self.end_line(f, cd.span.end)
+    }
+
+    pub fn print_interface_decl(&mut self, f: &mut dyn fmt::Write, id: &InterfaceDecl) -> fmt::Result {
+        self.indent(f)?;
+        write!(f, "{}interface {}{}", modifiers(id.access), id.name, base_list(&id.bases))?;
+        self.bracket_open(f)?;
+
+        for md in &id.methods {
+            self.start_item(f, md.span.start, false)?;
+            self.print_method_decl(f, md)?;
+        }
+
+        self.bracket_close(f, id.span.end)?;
+        self.end_line(f, id.span.end)
+    }
+
+    fn print_field_decl(&mut self, f: &mut dyn fmt::Write, fd: &FieldDecl) -> fmt::Result {
+        self.indent(f)?;
+        let static_ = if fd.is_static { "static " } else { "" };
+        let head = format!("{}{}{} {}", modifiers(fd.access), static_, fd.ty, ident(&fd.name));
+        match fd.assignment {
+            Some(ref assignment) => self.line(f, head + " = ", assignment, ";", fd.span.end),
+            None => {
+                write!(f, "{};", head)?;
+                self.end_line(f, fd.span.end)
             }
         }
+    }
 
-        self.bracket_close(f)
+    fn print_method_decl(&mut self, f: &mut dyn fmt::Write, md: &MethodDecl) -> fmt::Result {
+        self.indent(f)?;
+        let static_ = if md.is_static { "static " } else { "" };
+        let new = if md.is_new { "new " } else { "" };
+        // Abstract methods are implicitly virtual
+        let virtuality = match (md.is_abstract, md.virtuality) {
+            (false, Virtuality::NonVirtual) => String::new(),
+            (true, Virtuality::Virtual) => "abstract ".to_string(),
+            (true, virtuality) => format!("abstract {} ", virtuality),
+            (false, virtuality) => format!("{} ", virtuality)
+        };
+        let head = format!("{}{}{}{}{} {}", modifiers(md.access), static_, new, virtuality, md.return_ty, ident(&md.name));
+        let suffix = if md.body.is_some() { self.brace_width() } else { 1 };
+        let position = Position { column: self.column(), suffix, level: self.level };
+        let params = List::parens(head, md.params.iter().map(|param| param.span).collect());
+        let header = self.list(params, &md.params, Some(position), |param, _| {
+            format!("{} {}", param.ty, ident(&param.var_name))
+        });
+        write!(f, "{}", header)?;
+        match md.body {
+            Some(ref body) => self.block(f, body, md.span.end)?,
+            None => write!(f, ";")?
+        }
+        self.end_line(f, md.span.end)
     }
 
-    pub fn print_statement(&mut self, f: &mut fmt::Formatter, s: &Statement) -> fmt::Result {
+    fn print_constructor_decl(&mut self, f: &mut dyn fmt::Write, cd: &ConstructorDecl) -> fmt::Result {
         self.indent(f)?;
+        let static_ = if cd.is_static { "static " } else { "" };
+        let head = format!("{}{}{}", modifiers(cd.access), static_, ident(&cd.name));
+        let initializer = match cd.initializer {
+            Some(ref call) => {
+                let args: Vec<_> = call.args.iter().map(|arg| self.expression(arg, None)).collect();
+                let keyword = if call.is_base { "base" } else { "this" };
+                format!(" : {}({})", keyword, args.join(", "))
+            }
+            None => String::new()
+        };
+        let position = Position { column: self.column(), suffix: self.width(&initializer) + self.brace_width(), level: self.level };
+        let params = List::parens(head, cd.params.iter().map(|param| param.span).collect());
+        let header = self.list(params, &cd.params, Some(position), |param, _| {
+            format!("{} {}", param.ty, ident(&param.var_name))
+        });
+        write!(f, "{}{}", header, initializer)?;
+        self.block(f, &cd.body, cd.span.end)?;
+        self.end_line(f, cd.span.end)
+    }
+
+    fn block(&mut self, f: &mut dyn fmt::Write, block: &[Statement], end: usize) -> fmt::Result {
+        self.bracket_open(f)?;
+
+        self.block_ends.push(end);
+        for statement in block {
+            self.start_item(f, statement.span().start, false)?;
+            self.print_statement(f, statement)?;
+        }
+        self.block_ends.pop();
+
+        self.bracket_close(f, end)
+    }
+
+    /// Prints `head`, followed by an expression and `tail`, as a line that is broken if too long
+    fn line(&mut self, f: &mut dyn fmt::Write, head: String, e: &Expression, tail: &str, end: usize) -> fmt::Result {
+        let position = Position { column: self.column() + self.width(&head), suffix: tail.len(), level: self.level };
+        let expression = self.expression(e, Some(position));
+        write!(f, "{}{}{}", head, expression, tail)?;
+        self.end_line(f, end)
+    }
+
+    pub fn print_statement(&mut self, f: &mut dyn fmt::Write, s: &Statement) -> fmt::Result {
+        self.indent(f)?;
+        self.statement(f, s)
+    }
+
+    /// Prints a statement, which starts at the current position of the line
+    fn statement(&mut self, f: &mut dyn fmt::Write, s: &Statement) -> fmt::Result {
         match *s {
             Statement::Assign(ref assign) => {
-                write!(f, "{} = ", assign.var_name)?;
-                self.print_expression(f, &assign.expr)?;
-                writeln!(f, ";")
+                self.line(f, format!("{} ", self.assignment_head(assign)), &assign.expr, ";", assign.span.end)
             }
             Statement::Expression(ref expr) => {
-                self.print_expression(f, expr)?;
-                writeln!(f, ";")
+                self.line(f, String::new(), expr, ";", expr.span().end)
             }
             Statement::Return(ref ret) => {
-                write!(f, "return")?;
-                if let Some(ref expr) = ret.expr {
-                    write!(f, " ")?;
-                    self.print_expression(f, expr)?;
+                match ret.expr {
+                    Some(ref expr) => self.line(f, "return ".to_string(), expr, ";", ret.span.end),
+                    None => {
+                        write!(f, "return;")?;
+                        self.end_line(f, ret.span.end)
+                    }
                 }
-                writeln!(f, ";")
             }
             Statement::VarDecl(ref decl) => {
-                write!(f, "{} {}", decl.ty, decl.var_name)?;
-                if let Some(ref expr) = decl.expr {
-                    write!(f, " = ")?;
-                    self.print_expression(f, expr)?;
+                let head = format!("{} {}", decl.ty, ident(&decl.var_name));
+                match decl.expr {
+                    Some(ref expr) => self.line(f, head + " = ", expr, ";", decl.span.end),
+                    None => {
+                        write!(f, "{};", head)?;
+                        self.end_line(f, decl.span.end)
+                    }
                 }
-                writeln!(f, ";")
             }
             Statement::IfThenElse(ref ite) => {
-                write!(f, "if (")?;
-                self.print_expression(f, &ite.condition)?;
-                write!(f, ") ")?;
-                self.block(f, &ite.then)?;
-                self.indent(f)?;
-                write!(f, "else ")?;
-                self.block(f, &ite.else_)
+                let position = Position { column: self.column() + 4, suffix: 1 + self.brace_width(), level: self.level };
+                let condition = self.expression(&ite.condition, Some(position));
+                write!(f, "if ({})", condition)?;
+                let then_end = ite.else_.first().map_or(ite.span.end, |s| s.span().start);
+                self.block(f, &ite.then, then_end)?;
+                if ite.else_.is_empty() {
+                    return self.end_line(f, ite.span.end);
+                }
+
+                match self.style.brace_style {
+                    BraceStyle::SameLine => write!(f, " else")?,
+                    BraceStyle::NextLine => {
+                        writeln!(f)?;
+                        self.indent(f)?;
+                        write!(f, "else")?;
+                    }
+                }
+
+                match ite.else_[..] {
+                    // Keep `else if` chains flat
+                    [ref nested @ Statement::IfThenElse(_)] => {
+                        write!(f, " ")?;
+                        self.statement(f, nested)
+                    }
+                    _ => {
+                        self.block(f, &ite.else_, ite.span.end)?;
+                        self.end_line(f, ite.span.end)
+                    }
+                }
+            }
+            Statement::While(ref while_) => {
+                let position = Position { column: self.column() + 7, suffix: 1 + self.brace_width(), level: self.level };
+                let condition = self.expression(&while_.condition, Some(position));
+                write!(f, "while ({})", condition)?;
+                self.block(f, &while_.body, while_.span.end)?;
+                self.end_line(f, while_.span.end)
+            }
+            Statement::DoWhile(ref do_while) => {
+                write!(f, "do")?;
+                self.block(f, &do_while.body, do_while.condition.span().start)?;
+                // The condition follows `} while (` or `while (`
+                let (head, column) = match self.style.brace_style {
+                    BraceStyle::SameLine => (" while (".to_string(), self.column() + 9),
+                    BraceStyle::NextLine => (format!("\n{}while (", self.indentation(self.level)), self.column() + 7)
+                };
+                let position = Position { column, suffix: 2, level: self.level };
+                let condition = self.expression(&do_while.condition, Some(position));
+                write!(f, "{}{});", head, condition)?;
+                self.end_line(f, do_while.span.end)
+            }
+            Statement::For(ref for_) => {
+                let init = for_.init.as_ref().map_or(String::new(), |init| self.clause(init));
+                let condition = for_.condition.as_ref().map_or(String::new(), |c| format!(" {}", self.expression(c, None)));
+                let step = for_.step.as_ref().map_or(String::new(), |step| format!(" {}", self.clause(step)));
+                write!(f, "for ({};{};{})", init, condition, step)?;
+                self.block(f, &for_.body, for_.span.end)?;
+                self.end_line(f, for_.span.end)
+            }
+            Statement::Foreach(ref foreach) => {
+                let head = format!("foreach ({} {} in ", foreach.var.ty, ident(&foreach.var.var_name));
+                let position = Position { column: self.column() + self.width(&head), suffix: 1 + self.brace_width(), level: self.level };
+                let collection = self.expression(&foreach.collection, Some(position));
+                write!(f, "{}{})", head, collection)?;
+                self.block(f, &foreach.body, foreach.span.end)?;
+                self.end_line(f, foreach.span.end)
+            }
+            Statement::Break(ref break_) => {
+                write!(f, "break;")?;
+                self.end_line(f, break_.span.end)
+            }
+            Statement::Continue(ref continue_) => {
+                write!(f, "continue;")?;
+                self.end_line(f, continue_.span.end)
+            }
+            Statement::Error(ref error) => {
+                write!(f, "/* error */;")?;
+                self.end_line(f, error.span.end)
+            }
+        }
+    }
+
+    /// Renders the target and the operator of an assignment (e.g. `x +=`)
+    fn assignment_head(&self, assign: &Assign) -> String {
+        let operator = assign.operator.map(|op| op.to_string()).unwrap_or_default();
+        format!("{} {}=", self.expression(&assign.target, None), operator)
+    }
+
+    /// Renders the init or the step of a `for` loop in a single line, without a trailing `;`
+    fn clause(&self, s: &Statement) -> String {
+        match *s {
+            Statement::Assign(ref assign) => format!("{} {}", self.assignment_head(assign), self.expression(&assign.expr, None)),
+            Statement::VarDecl(ref decl) => match decl.expr {
+                Some(ref expr) => format!("{} {} = {}", decl.ty, ident(&decl.var_name), self.expression(expr, None)),
+                None => format!("{} {}", decl.ty, ident(&decl.var_name))
+            },
+            Statement::Expression(ref expr) => self.expression(expr, None),
+            _ => unreachable!("Only simple statements can be used in the header of a `for` loop")
+        }
+    }
+
+    pub fn print_expression(&self, f: &mut dyn fmt::Write, e: &Expression) -> fmt::Result {
+        write!(f, "{}", self.expression(e, None))
+    }
+
+    pub fn print_literal(&self, f: &mut dyn fmt::Write, l: &Literal) -> fmt::Result {
+        write!(f, "{}", self.literal(l, None))
+    }
+
+    /// Renders an expression, which is broken over several lines if it does not fit in the
+    /// given position
+    ///
+    /// Expressions containing line comments are broken too, so that the comments can end a line
+    fn expression(&self, e: &Expression, position: Option<Position>) -> String {
+        let flat = self.render(e, None);
+        match position {
+            Some(position) if position.column + self.width(&flat) + position.suffix > self.style.max_width
+                || self.has_line_comment(e.span()) => {
+                self.render(e, Some(position))
+            }
+            _ => flat
+        }
+    }
+
+    /// Returns true if there is a line comment in the given span that has not been printed yet
+    fn has_line_comment(&self, span: Span) -> bool {
+        self.comments.iter()
+            .skip_while(|c| c.start < span.start)
+            .take_while(|c| c.start < span.end)
+            .any(Comment::is_line_comment)
+    }
+
+    /// The comments in the gap between two parts of an expression or a list (see `gap_comments`)
+    fn gap<'a>(&'a self, start: usize, end: Option<usize>) -> impl Iterator<Item = &'a Comment> + 'a {
+        self.comments.iter()
+            .skip_while(move |c| c.start < start)
+            .take_while(move |c| end.map_or(c.follows == Some(start), |end| c.start < end))
+    }
+
+    /// The comments right before the first item of a list, which starts at `start`
+    fn leading<'a>(&'a self, start: usize) -> impl Iterator<Item = &'a Comment> + 'a {
+        self.comments.iter().take_while(move |c| c.start < start).filter(move |c| c.precedes == Some(start))
+    }
+
+    /// Renders the comments right before the first item of a list, each followed by a space or,
+    /// for line comments, by a line break to the given level
+    fn leading_comments(&self, start: usize, level: Option<usize>) -> String {
+        let mut leading = String::new();
+        for comment in self.leading(start) {
+            match level {
+                Some(level) if comment.is_line_comment() => {
+                    leading += &format!("{}\n{}", comment.text, self.indentation(level));
+                }
+                _ if comment.is_line_comment() => continue,
+                _ => leading += &format!("{} ", comment.text)
+            }
+            self.inline_comments.borrow_mut().insert(comment.start);
+        }
+
+        leading
+    }
+
+    /// Renders the comments in the gap between two parts of an expression or a list, where
+    /// `start` is the end of the first part and `end` the start of the second one. Without an
+    /// end, the gap only holds the comments right after the first part.
+    ///
+    /// Returns the comments that go before the separator of the parts (e.g. an operator or a
+    /// comma), which are the block comments right after the first part, and the ones that go
+    /// after it. Line comments are only rendered given the level of the line that follows them.
+    fn gap_comments(&self, start: usize, end: Option<usize>, level: Option<usize>) -> (String, String) {
+        let mut before = String::new();
+        let mut after = String::new();
+        let mut ends_line = false;
+        for comment in self.gap(start, end) {
+            if comment.is_line_comment() && level.is_none() {
+                continue;
+            }
+
+            if !comment.is_line_comment() && after.is_empty() && comment.follows == Some(start) {
+                before += &format!(" {}", comment.text);
+            } else if ends_line {
+                after += &format!("\n{}{}", self.indentation(level.unwrap()), comment.text);
+            } else {
+                after += &format!(" {}", comment.text);
             }
+            ends_line = comment.is_line_comment();
+            self.inline_comments.borrow_mut().insert(comment.start);
         }
 
+        (before, after)
     }
 
-    pub fn print_expression(&self, f: &mut fmt::Formatter, e: &Expression) -> fmt::Result {
+    /// Renders an expression in a single line or, given a position, over several lines
+    fn render(&self, e: &Expression, position: Option<Position>) -> String {
         match *e {
             Expression::BinaryOp(ref op) => {
-                self.print_expression(f, &op.left)?;
-                write!(f, " {} ", op.operator)?;
-                self.print_expression(f, &op.right)?;
+                let precedence = op.operator.precedence();
+                let gap = (op.left.span().end, Some(op.right.span().start));
+                match position {
+                    None => {
+                        let (before, after) = self.gap_comments(gap.0, gap.1, None);
+                        format!("{}{} {}{} {}", self.operand(&op.left, precedence, None), before, op.operator, after,
+                                self.operand(&op.right, precedence + 1, None))
+                    }
+                    Some(position) => {
+                        // The line is broken after the operator, and the right operand is indented
+                        let level = position.level + 1;
+                        let (before, after) = self.gap_comments(gap.0, gap.1, Some(level));
+                        let operator = format!("{} {}{}", before, op.operator, after);
+                        let left = self.operand(&op.left, precedence, Some(Position { suffix: self.width(&operator), ..position }));
+                        let right_position = Position { column: level * self.style.indent_width(), level, ..position };
+                        let right = self.operand(&op.right, precedence + 1, Some(right_position));
+                        format!("{}{}\n{}{}", left, operator, self.indentation(level), right)
+                    }
+                }
             }
-            Expression::FieldAccess(ref access) => {
-                self.print_expression(f, &access.target)?;
-                write!(f, ".{}", access.field_name)?;
+            Expression::UnaryOp(ref op) if op.operator.is_postfix() => {
+                let operator = op.operator.to_string();
+                let position = position.map(|p| Position { suffix: p.suffix + operator.len(), ..p });
+                format!("{}{}", self.operand(&op.operand, op.operator.precedence(), position), operator)
             }
-            Expression::Literal(ref l) => {
-                l.fmt(f)?;
+            Expression::UnaryOp(ref op) => {
+                let operator = op.operator.to_string();
+                let position = position.map(|p| Position { column: p.column + operator.len(), ..p });
+                if operator.ends_with('-') && starts_with_minus(&op.operand) {
+                    // Avoid printing `- -x` as `--x`
+                    let position = position.map(|p| Position { column: p.column + 1, suffix: p.suffix + 1, ..p });
+                    format!("{}({})", operator, self.expression(&op.operand, position))
+                } else {
+                    format!("{}{}", operator, self.operand(&op.operand, op.operator.precedence(), position))
+                }
+            }
+            Expression::FieldAccess(ref access) => {
+                let position = position.map(|p| Position { suffix: p.suffix + access.field_name.len() + 1, ..p });
+                format!("{}.{}", self.target(&access.target, position), ident(&access.field_name))
             }
+            Expression::Literal(ref l) => self.literal(l, position),
             Expression::MethodCall(ref call) => {
-                self.print_expression(f, &call.target)?;
-                write!(f, ".{}(", call.method_name)?;
-                PrettyPrinter::comma_separated(f, &call.args, |f, expr| self.print_expression(f, expr) )?;
-                write!(f, ")")?;
+                let head = match call.target {
+                    Some(ref target) => format!("{}.{}", self.target(target, position.map(|p| Position { suffix: 0, ..p })), ident(&call.method_name)),
+                    None => ident(&call.method_name)
+                };
+                let args = List::parens(head, call.args.iter().map(Expression::span).collect());
+                self.list(args, &call.args, position, |arg, position| self.expression(arg, position))
             }
             Expression::New(ref new) => {
-                write!(f, "new {}()", new.class_name)?;
+                let args = List::parens(format!("new {}", new.class_name), new.args.iter().map(Expression::span).collect());
+                self.list(args, &new.args, position, |arg, position| self.expression(arg, position))
             }
-            Expression::Identifier(ref s) => {
-                s.name.fmt(f)?;
+            Expression::NewArray(ref new_array) => {
+                // The length goes before the brackets of the element type (e.g. `new int[n][]`)
+                let mut base = &new_array.elem_ty;
+                let mut brackets = String::new();
+                while let Type::Array(ref inner) = *base {
+                    base = inner;
+                    brackets.push_str("[]");
+                }
+                format!("new {}[{}]{}", base, self.expression(&new_array.length, None), brackets)
             }
-            Expression::This(_) => {
-                "this".fmt(f)?;
+            Expression::Index(ref index) => {
+                let rendered_index = self.expression(&index.index, None);
+                let position = position.map(|p| Position { suffix: p.suffix + rendered_index.len() + 2, ..p });
+                let target = match *index.target {
+                    // Otherwise the index would be read as the brackets of the element type
+                    Expression::NewArray(_) => format!("({})", self.expression(&index.target, None)),
+                    _ => self.target(&index.target, position)
+                };
+                format!("{}[{}]", target, rendered_index)
             }
+            Expression::Identifier(ref i) => ident(&i.name),
+            Expression::This(_) => "this".to_string(),
+            Expression::Base(_) => "base".to_string(),
+            Expression::Error(_) => "/* error */".to_string()
         }
+    }
 
-        Ok(())
+    fn literal(&self, l: &Literal, position: Option<Position>) -> String {
+        match l.kind {
+            LiteralKind::Array(ref ty, ref elems) => {
+                let elements = List::braces(format!("new {}[]", ty), elems.iter().map(Expression::span).collect());
+                self.list(elements, elems, position, |elem, position| self.expression(elem, position))
+            }
+            _ => l.to_string()
+        }
+    }
+
+    /// Renders an operand of an operator, which needs parentheses if its own operator has a
+    /// lower precedence than `min_precedence`
+    fn operand(&self, e: &Expression, min_precedence: u32, position: Option<Position>) -> String {
+        let precedence = match *e {
+            Expression::BinaryOp(ref op) => op.operator.precedence(),
+            Expression::UnaryOp(ref op) => op.operator.precedence(),
+            _ => u32::MAX
+        };
+
+        if precedence < min_precedence {
+            let position = position.map(|p| Position { column: p.column + 1, suffix: p.suffix + 1, ..p });
+            format!("({})", self.expression(e, position))
+        } else {
+            self.expression(e, position)
+        }
     }
-}
\ No newline at end of file
+
+    /// Renders the target of a field access, a method call or an index
+    fn target(&self, e: &Expression, position: Option<Position>) -> String {
+        match *e {
+            Expression::Literal(Literal { kind: LiteralKind::Int(i), .. }) if i < 0 => format!("({})", i),
+            _ => self.operand(e, u32::MAX, position)
+        }
+    }
+
+    /// Renders a comma separated list, which is broken into one item per line if it does not
+    /// fit in the given position or if it contains line comments
+    fn list<T, F>(&self, list: List, items: &[T], position: Option<Position>, render: F) -> String
+    where
+        F: Fn(&T, Option<Position>) -> String
+    {
+        // The gap after an item ends at the start of the next one
+        let gap = |i: usize| (list.spans[i].end, list.spans.get(i + 1).map(|span| span.start));
+        let leading = |level| list.spans.first().map_or(String::new(), |first| self.leading_comments(first.start, level));
+        let flat_items: Vec<_> = items.iter().enumerate().map(|(i, item)| {
+            let (before, after) = self.gap_comments(gap(i).0, gap(i).1, None);
+            let comma = if i + 1 < items.len() { "," } else { "" };
+            format!("{}{}{}{}", render(item, None), before, comma, after)
+        }).collect();
+        let flat = format!("{}{}", leading(None), flat_items.join(" "));
+        let flat = match (list.spaced, flat_items.is_empty()) {
+            (true, true) => " ".to_string(),
+            (true, false) => format!(" {} ", flat),
+            (false, _) => flat
+        };
+
+        let position = match position {
+            Some(position) if !items.is_empty() => position,
+            _ => return format!("{}{}{}{}", list.head, list.open, flat, list.close)
+        };
+
+        let column = if list.head.contains('\n') { self.width(&list.head) } else { position.column + self.width(&list.head) };
+        let first = list.spans[0];
+        let last = list.spans[list.spans.len() - 1];
+        let has_line_comment = self.has_line_comment(Span { start: first.start, ..last })
+            || self.gap(last.end, None).any(Comment::is_line_comment)
+            || self.leading(first.start).any(Comment::is_line_comment);
+        if !has_line_comment && column + list.open.len() + self.width(&flat) + list.close.len() + position.suffix <= self.style.max_width {
+            return format!("{}{}{}{}", list.head, list.open, flat, list.close);
+        }
+
+        let level = position.level + 1;
+        let mut broken = format!("{}{}\n{}", list.head, list.open, self.indentation(level));
+        broken += &leading(Some(level));
+        for (i, item) in items.iter().enumerate() {
+            let (before, after) = self.gap_comments(gap(i).0, gap(i).1, Some(level));
+            let comma = if i + 1 < items.len() { "," } else { "" };
+            let item_position = Position { column: level * self.style.indent_width(), suffix: before.len() + comma.len(), level };
+            let indentation = if i > 0 { self.indentation(level) } else { String::new() };
+            broken += &format!("{}{}{}{}{}\n", indentation, render(item, Some(item_position)), before, comma, after);
+        }
+        broken += &self.indentation(position.level);
+        broken += list.close;
+        broken
+    }
+}
+
+/// Renders the modifiers of a declaration, followed by a space
+fn modifiers(access: Option<Access>) -> String {
+    access.map_or(String::new(), |access| format!("{} ", access))
+}
+
+/// Renders the base class and interfaces in the head of a class or an interface (e.g. ` : A, IFoo`)
+fn base_list(bases: &[BaseType]) -> String {
+    if bases.is_empty() {
+        return String::new();
+    }
+
+    let names: Vec<_> = bases.iter().map(|base| &base.name[..]).collect();
+    format!(" : {}", names.join(", "))
+}
+
+/// Renders an identifier, which is written as a verbatim identifier if it is a keyword (e.g. `@class`)
+fn ident(name: &str) -> String {
+    match name {
+        "true" | "false" | "null" => format!("@{}", name),
+        _ if Keyword::from_ident(name).is_some() => format!("@{}", name),
+        _ => name.to_string()
+    }
+}
+
+/// Returns true if the rendered expression starts with a `-` (e.g. `-1` or `--x`)
+fn starts_with_minus(e: &Expression) -> bool {
+    match *e {
+        Expression::Literal(Literal { kind: LiteralKind::Int(value), .. }) => value < 0,
+        Expression::UnaryOp(ref op) => matches!(op.operator, UnaryOperator::Neg | UnaryOperator::PreDecrement),
+        _ => false
+    }
+}
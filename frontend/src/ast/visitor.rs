@@ -6,10 +6,26 @@ pub trait Visitor<'a>: Sized {
         walk_program(self, program)
     }
 
+    fn visit_top_item(&mut self, top_item: &'a TopItem) {
+        walk_top_item(self, top_item)
+    }
+
+    fn visit_namespace_decl(&mut self, namespace_decl: &'a NamespaceDecl) {
+        walk_namespace_decl(self, namespace_decl)
+    }
+
+    fn visit_using_directive(&mut self, using: &'a UsingDirective) {
+        walk_using_directive(self, using)
+    }
+
     fn visit_class_decl(&mut self, class_decl: &'a ClassDecl) {
         walk_class_decl(self, class_decl)
     }
 
+    fn visit_interface_decl(&mut self, interface_decl: &'a InterfaceDecl) {
+        walk_interface_decl(self, interface_decl)
+    }
+
     fn visit_class_item(&mut self, class_item: &'a ClassItem) {
         walk_class_item(self, class_item)
     }
@@ -22,6 +38,14 @@ pub trait Visitor<'a>: Sized {
         walk_method_decl(self, method_decl)
     }
 
+    fn visit_constructor_decl(&mut self, constructor_decl: &'a ConstructorDecl) {
+        walk_constructor_decl(self, constructor_decl)
+    }
+
+    fn visit_constructor_call(&mut self, constructor_call: &'a ConstructorCall) {
+        walk_constructor_call(self, constructor_call)
+    }
+
     fn visit_statement(&mut self, statement: &'a Statement) {
         walk_statement(self, statement)
     }
@@ -46,6 +70,26 @@ pub trait Visitor<'a>: Sized {
         walk_if_then_else(self, ite)
     }
 
+    fn visit_while(&mut self, while_: &'a While) {
+        walk_while(self, while_)
+    }
+
+    fn visit_do_while(&mut self, do_while: &'a DoWhile) {
+        walk_do_while(self, do_while)
+    }
+
+    fn visit_for(&mut self, for_: &'a For) {
+        walk_for(self, for_)
+    }
+
+    fn visit_foreach(&mut self, foreach: &'a Foreach) {
+        walk_foreach(self, foreach)
+    }
+
+    fn visit_unary_op(&mut self, unary_op: &'a UnaryOp) {
+        walk_unary_op(self, unary_op)
+    }
+
     fn visit_binary_op(&mut self, binary_op: &'a BinaryOp) {
         walk_binary_op(self, binary_op)
     }
@@ -66,32 +110,79 @@ pub trait Visitor<'a>: Sized {
         walk_new(self, new)
     }
 
+    fn visit_new_array(&mut self, new_array: &'a NewArray) {
+        walk_new_array(self, new_array)
+    }
+
+    fn visit_index(&mut self, index: &'a Index) {
+        walk_index(self, index)
+    }
+
     fn visit_identifier(&mut self, identifier: &'a Identifier) {
         walk_identifier(self, identifier)
     }
 
+    fn visit_break(&mut self, break_: &'a Break) {
+        walk_break(self)
+    }
+
+    fn visit_continue(&mut self, continue_: &'a Continue) {
+        walk_continue(self)
+    }
+
     fn visit_this(&mut self, this: &'a This) {
         walk_this(self)
     }
+
+    fn visit_base(&mut self, base: &'a Base) {
+        walk_base(self)
+    }
+
+    fn visit_error(&mut self, error: &'a ErrorNode) {
+        walk_error(self)
+    }
 }
 
 pub fn walk_program<'a, V: Visitor<'a>>(visitor: &mut V, program: &'a [TopItem]) {
     for item in program {
-        let &TopItem::ClassDecl(ref cd) = item;
-        visitor.visit_class_decl(cd);
+        visitor.visit_top_item(item);
     }
 }
 
+pub fn walk_top_item<'a, V: Visitor<'a>>(visitor: &mut V, top_item: &'a TopItem) {
+    match *top_item {
+        TopItem::ClassDecl(ref cd) => visitor.visit_class_decl(cd),
+        TopItem::InterfaceDecl(ref id) => visitor.visit_interface_decl(id),
+        TopItem::NamespaceDecl(ref nd) => visitor.visit_namespace_decl(nd),
+        TopItem::UsingDirective(ref ud) => visitor.visit_using_directive(ud)
+    }
+}
+
+pub fn walk_namespace_decl<'a, V: Visitor<'a>>(visitor: &mut V, namespace_decl: &'a NamespaceDecl) {
+    for item in &namespace_decl.items {
+        visitor.visit_top_item(item);
+    }
+}
+
+pub fn walk_using_directive<'a, V: Visitor<'a>>(_visitor: &mut V, _using: &'a UsingDirective) { }
+
 pub fn walk_class_decl<'a, V: Visitor<'a>>(visitor: &mut V, class_decl: &'a ClassDecl) {
     for item in &class_decl.items {
         visitor.visit_class_item(item);
     }
 }
 
+pub fn walk_interface_decl<'a, V: Visitor<'a>>(visitor: &mut V, interface_decl: &'a InterfaceDecl) {
+    for method in &interface_decl.methods {
+        visitor.visit_method_decl(method);
+    }
+}
+
 pub fn walk_class_item<'a, V: Visitor<'a>>(visitor: &mut V, class_item: &'a ClassItem) {
     match *class_item {
         ClassItem::FieldDecl(ref fd) => visitor.visit_field_decl(fd),
-        ClassItem::MethodDecl(ref md) => visitor.visit_method_decl(md)
+        ClassItem::MethodDecl(ref md) => visitor.visit_method_decl(md),
+        ClassItem::ConstructorDecl(ref cd) => visitor.visit_constructor_decl(cd)
     }
 }
 
@@ -106,22 +197,50 @@ pub fn walk_method_decl<'a, V: Visitor<'a>>(visitor: &mut V, method_decl: &'a Me
         visitor.visit_var_decl(param);
     }
 
-    for statement in &method_decl.body {
+    for statement in method_decl.body.iter().flatten() {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_constructor_decl<'a, V: Visitor<'a>>(visitor: &mut V, constructor_decl: &'a ConstructorDecl) {
+    for param in &constructor_decl.params {
+        visitor.visit_var_decl(param);
+    }
+
+    if let Some(ref initializer) = constructor_decl.initializer {
+        visitor.visit_constructor_call(initializer);
+    }
+
+    for statement in &constructor_decl.body {
         visitor.visit_statement(statement);
     }
 }
 
+pub fn walk_constructor_call<'a, V: Visitor<'a>>(visitor: &mut V, constructor_call: &'a ConstructorCall) {
+    for arg in &constructor_call.args {
+        visitor.visit_expression(arg);
+    }
+}
+
 pub fn walk_statement<'a, V: Visitor<'a>>(visitor: &mut V, statement: &'a Statement) {
     match *statement {
         Statement::Assign(ref a) => visitor.visit_assign(a),
         Statement::Expression(ref e) => visitor.visit_expression(e),
         Statement::Return(ref r) => visitor.visit_return(r),
         Statement::VarDecl(ref vd) => visitor.visit_var_decl(vd),
-        Statement::IfThenElse(ref ite) => visitor.visit_if_then_else(ite)
+        Statement::IfThenElse(ref ite) => visitor.visit_if_then_else(ite),
+        Statement::While(ref w) => visitor.visit_while(w),
+        Statement::DoWhile(ref dw) => visitor.visit_do_while(dw),
+        Statement::For(ref f) => visitor.visit_for(f),
+        Statement::Foreach(ref f) => visitor.visit_foreach(f),
+        Statement::Break(ref b) => visitor.visit_break(b),
+        Statement::Continue(ref c) => visitor.visit_continue(c),
+        Statement::Error(ref e) => visitor.visit_error(e)
     }
 }
 
 pub fn walk_assign<'a, V: Visitor<'a>>(visitor: &mut V, assign: &'a Assign) {
+    visitor.visit_expression(&assign.target);
     visitor.visit_expression(&assign.expr)
 }
 
@@ -147,15 +266,57 @@ pub fn walk_if_then_else<'a, V: Visitor<'a>>(visitor: &mut V, ite: &'a IfThenEls
     }
 }
 
+pub fn walk_while<'a, V: Visitor<'a>>(visitor: &mut V, while_: &'a While) {
+    visitor.visit_expression(&while_.condition);
+    for stmt in &while_.body {
+        visitor.visit_statement(stmt);
+    }
+}
+
+pub fn walk_do_while<'a, V: Visitor<'a>>(visitor: &mut V, do_while: &'a DoWhile) {
+    for stmt in &do_while.body {
+        visitor.visit_statement(stmt);
+    }
+    visitor.visit_expression(&do_while.condition);
+}
+
+pub fn walk_for<'a, V: Visitor<'a>>(visitor: &mut V, for_: &'a For) {
+    if let Some(ref init) = for_.init {
+        visitor.visit_statement(init);
+    }
+    if let Some(ref condition) = for_.condition {
+        visitor.visit_expression(condition);
+    }
+    if let Some(ref step) = for_.step {
+        visitor.visit_statement(step);
+    }
+    for stmt in &for_.body {
+        visitor.visit_statement(stmt);
+    }
+}
+
+pub fn walk_foreach<'a, V: Visitor<'a>>(visitor: &mut V, foreach: &'a Foreach) {
+    visitor.visit_expression(&foreach.collection);
+    visitor.visit_var_decl(&foreach.var);
+    for stmt in &foreach.body {
+        visitor.visit_statement(stmt);
+    }
+}
+
 pub fn walk_expression<'a, V: Visitor<'a>>(visitor: &mut V, expr: &'a Expression) {
     match *expr {
         Expression::BinaryOp(ref op) => visitor.visit_binary_op(op),
+        Expression::UnaryOp(ref op) => visitor.visit_unary_op(op),
         Expression::FieldAccess(ref fa) => visitor.visit_field_access(fa),
         Expression::Literal(ref l) => visitor.visit_literal(l),
         Expression::MethodCall(ref mc) => visitor.visit_method_call(mc),
         Expression::New(ref n) => visitor.visit_new(n),
+        Expression::NewArray(ref n) => visitor.visit_new_array(n),
+        Expression::Index(ref i) => visitor.visit_index(i),
         Expression::Identifier(ref i) => visitor.visit_identifier(i),
-        Expression::This(ref t) => visitor.visit_this(t)
+        Expression::This(ref t) => visitor.visit_this(t),
+        Expression::Base(ref b) => visitor.visit_base(b),
+        Expression::Error(ref e) => visitor.visit_error(e)
     }
 }
 
@@ -164,21 +325,54 @@ pub fn walk_binary_op<'a, V: Visitor<'a>>(visitor: &mut V, binary_op: &'a Binary
     visitor.visit_expression(&binary_op.right);
 }
 
+pub fn walk_unary_op<'a, V: Visitor<'a>>(visitor: &mut V, unary_op: &'a UnaryOp) {
+    visitor.visit_expression(&unary_op.operand);
+}
+
 pub fn walk_field_access<'a, V: Visitor<'a>>(visitor: &mut V, field_access: &'a FieldAccess) {
     visitor.visit_expression(&field_access.target);
 }
 
-pub fn walk_literal<'a, V: Visitor<'a>>(_visitor: &mut V, _literal: &'a Literal) { }
+pub fn walk_literal<'a, V: Visitor<'a>>(visitor: &mut V, literal: &'a Literal) {
+    if let LiteralKind::Array(_, ref items) = literal.kind {
+        for item in items {
+            visitor.visit_expression(item);
+        }
+    }
+}
 
 pub fn walk_method_call<'a, V: Visitor<'a>>(visitor: &mut V, method_call: &'a MethodCall) {
-    visitor.visit_expression(&method_call.target);
+    if let Some(ref target) = method_call.target {
+        visitor.visit_expression(target);
+    }
     for arg in &method_call.args {
         visitor.visit_expression(arg);
     }
 }
 
-pub fn walk_new<'a, V: Visitor<'a>>(_visitor: &mut V, _new: &'a New) { }
+pub fn walk_new<'a, V: Visitor<'a>>(visitor: &mut V, new: &'a New) {
+    for arg in &new.args {
+        visitor.visit_expression(arg);
+    }
+}
+
+pub fn walk_new_array<'a, V: Visitor<'a>>(visitor: &mut V, new_array: &'a NewArray) {
+    visitor.visit_expression(&new_array.length);
+}
+
+pub fn walk_index<'a, V: Visitor<'a>>(visitor: &mut V, index: &'a Index) {
+    visitor.visit_expression(&index.target);
+    visitor.visit_expression(&index.index);
+}
 
 pub fn walk_identifier<'a, V: Visitor<'a>>(_visitor: &mut V, _identifier: &'a Identifier) { }
 
 pub fn walk_this<'a, V: Visitor<'a>>(_visitor: &mut V) { }
+
+pub fn walk_base<'a, V: Visitor<'a>>(_visitor: &mut V) { }
+
+pub fn walk_break<'a, V: Visitor<'a>>(_visitor: &mut V) { }
+
+pub fn walk_continue<'a, V: Visitor<'a>>(_visitor: &mut V) { }
+
+pub fn walk_error<'a, V: Visitor<'a>>(_visitor: &mut V) { }
@@ -0,0 +1,69 @@
+use std::fmt;
+
+macro_rules! codes {
+    ($($name:ident => $code:expr, $description:expr;)*) => {
+        /// Identifies the kind of problem a diagnostic reports
+        #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+        pub enum Code {
+            $($name),*
+        }
+
+        impl Code {
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(Code::$name => $code),*
+                }
+            }
+
+            /// A short explanation of the problem
+            pub fn description(self) -> &'static str {
+                match self {
+                    $(Code::$name => $description),*
+                }
+            }
+        }
+    }
+}
+
+codes! {
+    Syntax => "E0001", "the source code could not be parsed";
+    DuplicateClass => "E0100", "a class or interface with the same name has already been declared";
+    DuplicateEntryPoint => "E0101", "more than one `static void Main` method was found";
+    MissingEntryPoint => "E0102", "no `static void Main` method was found";
+    DuplicateVariable => "E0103", "a local variable with the same name is already in scope";
+    UnresolvedName => "E0104", "a name could not be resolved to a variable or class";
+    InvalidThis => "E0105", "`this` or `base` was used outside of an instance method or a constructor";
+    OutsideLoop => "E0106", "`break` or `continue` was used outside of a loop";
+    DuplicateStaticConstructor => "E0107", "a class declares more than one static constructor";
+    CyclicInheritance => "E0108", "a class or interface inherits from itself, directly or through other types";
+    SealedBaseClass => "E0109", "a class derives from a sealed class";
+    ExpectedInterface => "E0110", "a class was listed where only interfaces are allowed";
+    DuplicateMember => "E0111", "a method or constructor has the same name and parameter types as another one in the same type";
+    UnknownType => "E0200", "a type could not be resolved";
+    MismatchedTypes => "E0201", "an expression has a different type than expected";
+    WrongArgumentCount => "E0202", "a method was called with the wrong number of arguments";
+    UnknownField => "E0203", "a field does not exist";
+    UnknownMethod => "E0204", "a method does not exist";
+    NotAnObject => "E0205", "members were accessed on a value that is not an object";
+    StaticMismatch => "E0206", "a static member was used as an instance one, or vice versa";
+    InvalidOperands => "E0207", "an operator was applied to values of unsupported types";
+    NotIterable => "E0208", "`foreach` was used on a value that is not a collection";
+    ReadOnly => "E0209", "a value that cannot be modified was assigned to";
+    NotIndexable => "E0210", "a value that is not an array was indexed";
+    NoMatchingOverload => "E0211", "no overload accepts the number and types of the given arguments";
+    RecursiveConstructor => "E0212", "a constructor calls itself through `this(...)`";
+    NoBaseClass => "E0213", "`base` was used in a class that does not inherit from another one";
+    InvalidOverride => "E0214", "an `override` method does not match an overridable method of a base class";
+    MissingImplementation => "E0215", "a class does not implement a method of one of its interfaces, or an abstract method it inherits";
+    AbstractInstantiation => "E0216", "`new` was used on an interface or an abstract class";
+    AbstractInConcreteClass => "E0217", "an abstract method was declared in a class that is not abstract";
+    AbstractBaseCall => "E0218", "an abstract method was called through `base`";
+    AmbiguousCall => "E0219", "a call matches several overloads, none of which is better than the others";
+    AmbiguousName => "E0220", "a name refers to classes imported by several using directives";
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
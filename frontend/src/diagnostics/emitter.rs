@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use syntax::{FileId, SourceFile, SourceMap};
+use super::{Diagnostic, SpanLabel};
+
+/// Renders a diagnostic in the style of rustc:
+///
+/// ```text
+/// error[E0201]: mismatched types
+///  --> test.cs:3:17
+///   |
+/// 3 |         int x = "hello";
+///   |                 ^^^^^^^ expected `int`, found `string`
+/// ```
+///
+/// Note: labels with dummy spans are not displayed
+pub fn render(diagnostic: &Diagnostic, source_map: &SourceMap) -> String {
+    let mut out = String::new();
+    writeln!(out, "{}", diagnostic).unwrap();
+
+    let labels: Vec<_> = diagnostic.labels.iter().filter(|l| !l.span.is_dummy()).collect();
+
+    // The file of the primary label goes first, the rest in order of appearance
+    let mut files: Vec<FileId> = Vec::new();
+    for label in labels.iter().filter(|l| l.is_primary).chain(labels.iter().filter(|l| !l.is_primary)) {
+        if !files.contains(&label.span.file) {
+            files.push(label.span.file);
+        }
+    }
+
+    let max_line = labels.iter()
+                         .map(|l| source_map.file(l.span.file).location(l.span.start).line)
+                         .max()
+                         .unwrap_or(0);
+    let gutter = if labels.is_empty() { 0 } else { max_line.to_string().len() };
+    let pad = " ".repeat(gutter);
+
+    for (i, &file_id) in files.iter().enumerate() {
+        let file = source_map.file(file_id);
+        let file_labels: Vec<_> = labels.iter().cloned().filter(|l| l.span.file == file_id).collect();
+        let main_label = file_labels.iter().find(|l| l.is_primary).unwrap_or(&file_labels[0]);
+
+        let arrow = if i == 0 { "-->" } else { ":::" };
+        writeln!(out, "{}{} {}:{}", pad, arrow, file.name, file.location(main_label.span.start)).unwrap();
+        writeln!(out, "{} |", pad).unwrap();
+        render_snippet(&mut out, file, &file_labels, gutter);
+    }
+
+    if !diagnostic.notes.is_empty() && !labels.is_empty() {
+        writeln!(out, "{} |", pad).unwrap();
+    }
+    for note in &diagnostic.notes {
+        writeln!(out, "{} = note: {}", pad, note).unwrap();
+    }
+
+    out
+}
+
+fn render_snippet(out: &mut String, file: &SourceFile, labels: &[&SpanLabel], gutter: usize) {
+    let mut lines: BTreeMap<usize, Vec<&SpanLabel>> = BTreeMap::new();
+    for &label in labels {
+        let line = file.location(label.span.start).line;
+        lines.entry(line).or_default().push(label);
+    }
+
+    let pad = " ".repeat(gutter);
+    let mut previous_line = None;
+    for (line, mut line_labels) in lines {
+        if previous_line.is_some_and(|previous| line > previous + 1) {
+            writeln!(out, "...").unwrap();
+        }
+        previous_line = Some(line);
+
+        let text = file.line(line);
+        writeln!(out, "{:<width$} | {}", line, expand_tabs(text), width = gutter).unwrap();
+
+        line_labels.sort_by_key(|l| l.span.start);
+        for label in line_labels {
+            let column = file.location(label.span.start).column - 1;
+            let prefix: String = text.chars().take(column).collect();
+
+            // Spans covering several lines are only underlined on their first line
+            let rest: String = text.chars().skip(column).collect();
+            let underlined = &rest[..(label.span.len()).min(rest.len())];
+
+            let marker = if label.is_primary { "^" } else { "-" };
+            let marks = marker.repeat(display_width(underlined).max(1));
+            let annotation = format!("{}{} {}", " ".repeat(display_width(&prefix)), marks, label.message);
+            writeln!(out, "{} | {}", pad, annotation.trim_end()).unwrap();
+        }
+    }
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', "    ")
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
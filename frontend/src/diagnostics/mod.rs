@@ -0,0 +1,216 @@
+//! Structured reporting of problems found in a program
+//!
+//! Instead of aborting at the first problem, the different phases of the compiler push
+//! `Diagnostic`s into a `Diagnostics` sink and keep going. The diagnostics can then be
+//! rendered in a rustc-like format, including snippets of the offending source code.
+
+mod codes;
+mod emitter;
+
+use std::fmt;
+use std::slice;
+
+use syntax::{SourceMap, Span};
+
+pub use self::codes::Code;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Note => "note".fmt(f),
+            Severity::Warning => "warning".fmt(f),
+            Severity::Error => "error".fmt(f)
+        }
+    }
+}
+
+/// A message attached to a piece of source code
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpanLabel {
+    pub span: Span,
+    pub message: String,
+    /// Primary labels point to the cause of the problem, secondary ones give context
+    pub is_primary: bool
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub code: Code,
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<SpanLabel>,
+    pub notes: Vec<String>
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: Code, message: String) -> Diagnostic {
+        Diagnostic {
+            code,
+            severity,
+            message,
+            labels: Vec::new(),
+            notes: Vec::new()
+        }
+    }
+
+    pub fn error(code: Code, message: String) -> Diagnostic {
+        Diagnostic::new(Severity::Error, code, message)
+    }
+
+    pub fn warning(code: Code, message: String) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, code, message)
+    }
+
+    pub fn with_primary(mut self, span: Span, message: String) -> Diagnostic {
+        self.labels.push(SpanLabel { span, message, is_primary: true });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: String) -> Diagnostic {
+        self.labels.push(SpanLabel { span, message, is_primary: false });
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Diagnostic {
+        self.notes.push(note);
+        self
+    }
+
+    /// The span of the first primary label, if any
+    pub fn primary_span(&self) -> Option<Span> {
+        self.labels.iter().find(|l| l.is_primary).map(|l| l.span)
+    }
+
+    /// Renders the diagnostic, including snippets of the source code it refers to
+    pub fn render(&self, source_map: &SourceMap) -> String {
+        emitter::render(self, source_map)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
+
+/// A sink where diagnostics are collected
+#[derive(Clone, Debug, Default)]
+pub struct Diagnostics {
+    diagnostics: Vec<Diagnostic>
+}
+
+impl Diagnostics {
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn extend<I: IntoIterator<Item=Diagnostic>>(&mut self, diagnostics: I) {
+        self.diagnostics.extend(diagnostics);
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Diagnostic> {
+        self.diagnostics.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.diagnostics.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.diagnostics.iter().filter(|d| d.severity == Severity::Error).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    pub fn into_vec(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+
+    /// Renders all diagnostics, followed by a summary line if there are errors
+    pub fn render(&self, source_map: &SourceMap) -> String {
+        let mut out = String::new();
+        for diagnostic in &self.diagnostics {
+            out.push_str(&diagnostic.render(source_map));
+            out.push('\n');
+        }
+
+        match self.error_count() {
+            0 => {}
+            1 => out.push_str("error: aborting due to previous error\n"),
+            n => out.push_str(&format!("error: aborting due to {} previous errors\n", n))
+        }
+
+        out
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = Diagnostic;
+    type IntoIter = ::std::vec::IntoIter<Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.diagnostics.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Diagnostics {
+    type Item = &'a Diagnostic;
+    type IntoIter = slice::Iter<'a, Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.diagnostics.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use syntax::{SourceMap, Span};
+    use super::*;
+
+    #[test]
+    fn render_with_snippets() {
+        let mut source_map = SourceMap::default();
+        let src = "class Program {\n    static void Main() {\n        int x = \"hello\";\n    }\n}";
+        let file = source_map.add_file("test.cs".to_string(), src.to_string());
+        let start = src.find('"').unwrap();
+
+        let diagnostic = Diagnostic::error(Code::MismatchedTypes, "mismatched types".to_string())
+            .with_primary(Span::new(file, start, start + 7), "expected `int`, found `string`".to_string())
+            .with_secondary(Span::new(file, 0, 5), "in this class".to_string())
+            .with_note("strings cannot be converted to ints".to_string());
+
+        let expected = "\
+error[E0201]: mismatched types
+ --> test.cs:3:17
+  |
+1 | class Program {
+  | ----- in this class
+...
+3 |         int x = \"hello\";
+  |                 ^^^^^^^ expected `int`, found `string`
+  |
+  = note: strings cannot be converted to ints
+";
+        assert_eq!(diagnostic.render(&source_map), expected);
+    }
+
+    #[test]
+    fn render_without_source() {
+        let diagnostic = Diagnostic::error(Code::MissingEntryPoint, "no entry point found".to_string())
+            .with_primary(Span::dummy(), "ignored".to_string());
+        assert_eq!(diagnostic.render(&SourceMap::default()), "error[E0102]: no entry point found\n");
+    }
+}
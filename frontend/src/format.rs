@@ -0,0 +1,184 @@
+//! Formatting of C# source files in a canonical style
+//!
+//! The formatter prints the AST of a file with `ast::pretty::PrettyPrinter`, keeping the comments
+//! and the empty lines found in its concrete syntax tree. Files with syntax errors cannot be
+//! formatted, since part of their code is missing from the AST.
+
+use ast::Program;
+use ast::pretty::{Comment, PrettyPrinter, Trivia};
+use syntax::FileId;
+use syntax::lexer::TokenKind;
+
+pub use ast::pretty::{BraceStyle, Indent, Style};
+
+/// Returns the formatted source of a file of the program
+///
+/// Panics if the file has syntax errors
+pub fn format_file(program: &Program, file: FileId, style: Style) -> String {
+    let tree = &program.syntax[file.0 as usize];
+    assert!(!tree.errors.has_errors(), "Files with syntax errors cannot be formatted");
+
+    let items: Vec<_> = program.items.iter().filter(|item| item.span().file == file).cloned().collect();
+    let mut formatted = String::new();
+    PrettyPrinter::with_style(style)
+        .with_trivia(trivia(program, file))
+        .print_items(&mut formatted, &items)
+        .expect("Writing to a string cannot fail");
+    formatted
+}
+
+/// Collects the comments and the empty lines of a file
+fn trivia(program: &Program, file: FileId) -> Trivia {
+    let mut trivia = Trivia::default();
+    // The end of the last token in the current line that is not trivia
+    let mut code_end = None;
+    // The end of the last token that is not trivia
+    let mut token_end = None;
+    let mut newlines = 0;
+    for token in program.syntax[file.0 as usize].root.tokens() {
+        let span = token.span();
+        match *token.kind() {
+            TokenKind::Whitespace => {
+                let count = token.text().matches('\n').count();
+                if count > 0 {
+                    code_end = None;
+                    newlines += count;
+                }
+                continue;
+            }
+            TokenKind::LineComment | TokenKind::BlockComment => {
+                let text = token.text().to_string();
+                trivia.comments.push(Comment { start: span.start, text, after: code_end, follows: token_end, precedes: None });
+            }
+            TokenKind::Eof => continue,
+            _ => {
+                code_end = Some(span.end);
+                token_end = Some(span.end);
+                for comment in trivia.comments.iter_mut().rev().take_while(|c| c.precedes.is_none()) {
+                    comment.precedes = Some(span.start);
+                }
+            }
+        }
+
+        if newlines > 1 {
+            trivia.empty_lines.insert(span.start);
+        }
+        newlines = 0;
+    }
+
+    trivia
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use syntax;
+
+    /// Formats the source, checking that the output parses to the same AST
+    fn format(src: &str, style: Style) -> String {
+        let (program, diagnostics) = syntax::parse("test.cs", src);
+        assert!(diagnostics.is_empty(), "{}", diagnostics.render(&program.source_map));
+        let formatted = format_file(&program, FileId(0), style);
+
+        let (reparsed, diagnostics) = syntax::parse("test.cs", &formatted);
+        assert!(diagnostics.is_empty(), "{}\n{}", formatted, diagnostics.render(&reparsed.source_map));
+        assert_eq!(reparsed.to_string(), program.to_string());
+
+        // Formatting is idempotent
+        assert_eq!(format_file(&reparsed, FileId(0), style), formatted);
+        formatted
+    }
+
+    #[test]
+    fn canonical_style() {
+        let src = r#"
+using System;
+namespace App {
+    // The entry point
+    public class Program {
+        private int count   = 1 - (2 - 3); // Trailing comment
+        int[] values = new int[]{1,2,};
+
+
+        public static void Main() { Console.WriteLine("Say \"hi\"\n"); if (1 == 1) { return; } else { Console.WriteLine(@"C:\dir"); } }
+        static int @class(int @int) { return (@int + 1) * 2; }
+        /* Before the end of the class */
+    }
+}
+"#;
+        let expected = r#"using System;
+
+namespace App {
+    // The entry point
+    public class Program {
+        private int count = 1 - (2 - 3); // Trailing comment
+        int[] values = new int[] { 1, 2 };
+
+        public static void Main() {
+            Console.WriteLine("Say \"hi\"\n");
+            if (1 == 1) {
+                return;
+            } else {
+                Console.WriteLine("C:\\dir");
+            }
+        }
+
+        static int @class(int @int) {
+            return (@int + 1) * 2;
+        }
+        /* Before the end of the class */
+    }
+}
+"#;
+        assert_eq!(format(src, Style::default()), expected);
+    }
+
+    #[test]
+    fn configurable_style() {
+        let src = "class A { int Sum(int first, int second, int third) { return Helper.Add(first, second) + Helper.Add(third, 1000000); } }";
+        let style = Style { indent: Indent::Tabs, brace_style: BraceStyle::NextLine, max_width: 42 };
+        let expected = "class A\n{\n\tint Sum(\n\t\tint first,\n\t\tint second,\n\t\tint third\n\t)\n\t{\n\t\treturn Helper.Add(first, second) +\n\t\t\tHelper.Add(third, 1000000);\n\t}\n}\n";
+        assert_eq!(format(src, style), expected);
+    }
+
+    #[test]
+    fn comments_inside_code() {
+        let src = r#"
+class A {
+    int F(int a /* first */, int b) {
+        if (a == 1) { return; } // after if
+        if (a == 2) return; // after braceless if
+        int c = G(/* before */ a, b // last
+        );
+        return a + // plus
+            b;
+    }
+}
+"#;
+        let expected = r#"class A {
+    int F(int a /* first */, int b) {
+        if (a == 1) {
+            return;
+        } // after if
+        if (a == 2) {
+            return;
+        } // after braceless if
+        int c = G(
+            /* before */ a,
+            b // last
+        );
+        return a + // plus
+            b;
+    }
+}
+"#;
+        assert_eq!(format(src, Style::default()), expected);
+    }
+
+    #[test]
+    fn calls_without_target() {
+        let src = "class A { static int F(int a, int b) { return F(1, 2); } int Get() { return Get() + this.Get(); } }";
+        let expected = "class A {\n    static int F(int a, int b) {\n        return F(1, 2);\n    }\n\n    int Get() {\n        return Get() + this.Get();\n    }\n}\n";
+        assert_eq!(format(src, Style::default()), expected);
+    }
+}
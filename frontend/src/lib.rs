@@ -1,5 +1,6 @@
-#![feature(conservative_impl_trait)]
-
 pub mod analysis;
 pub mod ast;
+pub mod diagnostics;
+pub mod format;
 pub mod sample_programs;
+pub mod syntax;
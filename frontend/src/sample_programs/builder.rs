@@ -1,4 +1,5 @@
 use ast::*;
+use syntax::Span;
 
 pub struct Builder {
 
@@ -9,36 +10,37 @@ impl Builder {
         let label = fresh_label();
         let var_name = name.to_string();
         let ty = Type::Custom("String".to_string());
-        let expr = Expression::Literal(Literal { label: fresh_label(), kind: LiteralKind::String(assign.to_string()) });
-        Statement::VarDecl(VarDecl { label, var_name, ty, expr: Some(expr) })
+        let expr = Expression::Literal(Literal { label: fresh_label(), span: Span::dummy(), kind: LiteralKind::String(assign.to_string()) });
+        Statement::VarDecl(VarDecl { label, span: Span::dummy(), var_name, ty, expr: Some(expr) })
     }
 
     pub fn decl_int(name: &str, assign: i64) -> Statement {
         let label = fresh_label();
         let var_name = name.to_string();
         let ty = Type::Custom("int".to_string());
-        let expr = Expression::Literal(Literal { label: fresh_label(), kind: LiteralKind::Int(assign) });
-        Statement::VarDecl(VarDecl { label, var_name, ty, expr: Some(expr) })
+        let expr = Expression::Literal(Literal { label: fresh_label(), span: Span::dummy(), kind: LiteralKind::Int(assign) });
+        Statement::VarDecl(VarDecl { label, span: Span::dummy(), var_name, ty, expr: Some(expr) })
     }
 
     pub fn decl_string_from_var(name: &str, var: &str) -> Statement {
         let label = fresh_label();
         let var_name = name.to_string();
         let ty = Type::Custom("String".to_string());
-        let expr = Expression::Identifier(Identifier { label: fresh_label(), name: var.to_string() });
-        Statement::VarDecl(VarDecl { label, var_name, ty, expr: Some(expr) })
+        let expr = Expression::Identifier(Identifier { label: fresh_label(), span: Span::dummy(), name: var.to_string() });
+        Statement::VarDecl(VarDecl { label, span: Span::dummy(), var_name, ty, expr: Some(expr) })
     }
 
     pub fn decl_int_from_expr(name: &str, expr: Expression) -> Statement {
         let label = fresh_label();
         let var_name = name.to_string();
         let ty = Type::Custom("int".to_string());
-        Statement::VarDecl(VarDecl { label, var_name, ty, expr: Some(expr) })
+        Statement::VarDecl(VarDecl { label, span: Span::dummy(), var_name, ty, expr: Some(expr) })
     }
 
     pub fn return_(expr: Expression) -> Statement {
         Statement::Return(Return {
             label: fresh_label(),
+            span: Span::dummy(),
             expr: Some(expr)
         })
     }
@@ -47,23 +49,25 @@ impl Builder {
         let label = fresh_label();
         Statement::Return(Return {
             label,
+            span: Span::dummy(),
             expr: Some(Builder::var_use(name))
         })
     }
 
     pub fn literal(lit: LiteralKind) -> Expression {
-        Expression::Literal(Literal { label: fresh_label(), kind: lit })
+        Expression::Literal(Literal { label: fresh_label(), span: Span::dummy(), kind: lit })
     }
 
     pub fn var_use(name: &str) -> Expression {
         let label = fresh_label();
         let name = name.to_string();
-        Expression::Identifier(Identifier { label, name })
+        Expression::Identifier(Identifier { label, span: Span::dummy(), name })
     }
 
     pub fn binary_op(operator: BinaryOperator, left: Expression, right: Expression) -> Expression {
         Expression::BinaryOp(BinaryOp {
             label: fresh_label(),
+            span: Span::dummy(),
             operator,
             left: Box::new(left),
             right: Box::new(right)
@@ -75,7 +79,7 @@ impl Builder {
         let operator = BinaryOperator::Add;
         let left = Box::new(Builder::var_use(x));
         let right = Box::new(Builder::var_use(y));
-        Expression::BinaryOp(BinaryOp { label, operator, left, right })
+        Expression::BinaryOp(BinaryOp { label, span: Span::dummy(), operator, left, right })
     }
 
     pub fn write_line(var: &str) -> Statement {
@@ -93,6 +97,7 @@ impl Builder {
     pub fn if_then_else(condition: Expression, then: Vec<Statement>, else_: Vec<Statement>) -> Statement {
         Statement::IfThenElse(IfThenElse {
             label: fresh_label(),
+            span: Span::dummy(),
             condition,
             then,
             else_
@@ -110,7 +115,8 @@ impl Builder {
     pub fn method_call_expr(class: &str, method: &str, args: Vec<Expression>) -> Expression {
         Expression::MethodCall(MethodCall {
             label: fresh_label(),
-            target: Box::new(Expression::Identifier(Identifier { name: class.to_string(), label: fresh_label() })),
+            span: Span::dummy(),
+            target: Some(Box::new(Expression::Identifier(Identifier { name: class.to_string(), label: fresh_label(), span: Span::dummy() }))),
             method_name: method.to_string(),
             args
         })
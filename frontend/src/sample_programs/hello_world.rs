@@ -1,26 +1,38 @@
 use ast::*;
+use syntax::{SourceMap, Span};
 
 fn program_class() -> TopItem {
     let main_method = ClassItem::MethodDecl(MethodDecl {
         label: fresh_label(),
+        span: Span::dummy(),
+        access: Some(Access::Public),
         name: "Main".to_string(),
         params: Vec::new(),
-        body: vec![
+        body: Some(vec![
             Statement::Expression(Expression::MethodCall(MethodCall {
                 label: fresh_label(),
-                target: Box::new(Expression::Identifier(Identifier { name: "Console".to_string(), label: fresh_label() })),
+                span: Span::dummy(),
+                target: Some(Box::new(Expression::Identifier(Identifier { name: "Console".to_string(), label: fresh_label(), span: Span::dummy() }))),
                 method_name: "WriteLine".to_string(),
-                args: vec![Expression::Literal(Literal { label: fresh_label(), kind: LiteralKind::String("Hello world!".to_string()) })]
+                args: vec![Expression::Literal(Literal { label: fresh_label(), span: Span::dummy(), kind: LiteralKind::String("Hello world!".to_string()) })]
             }))
-        ],
+        ]),
         is_static: true,
+        virtuality: Virtuality::NonVirtual,
+        is_new: false,
+        is_abstract: false,
         return_ty: Type::Void
     });
 
     TopItem::ClassDecl(ClassDecl {
         label: fresh_label(),
+        span: Span::dummy(),
+        access: None,
         name: "Program".to_string(),
-        items: vec![main_method]
+        bases: Vec::new(),
+        items: vec![main_method],
+        is_sealed: false,
+        is_abstract: false
     })
 }
 
@@ -28,6 +40,8 @@ pub fn hello_world() -> Program {
     Program {
         items: vec![
             program_class()
-        ]
+        ],
+        source_map: SourceMap::default(),
+        syntax: Vec::new()
     }
 }
@@ -1,4 +1,5 @@
 use ast::*;
+use syntax::{SourceMap, Span};
 use super::Builder;
 
 fn statements() -> Vec<Statement> {
@@ -20,17 +21,27 @@ fn statements() -> Vec<Statement> {
 fn program_class() -> TopItem {
     let main_method = ClassItem::MethodDecl(MethodDecl {
         label: fresh_label(),
+        span: Span::dummy(),
+        access: Some(Access::Public),
         name: "Main".to_string(),
         params: Vec::new(),
-        body: statements(),
+        body: Some(statements()),
         is_static: true,
+        virtuality: Virtuality::NonVirtual,
+        is_new: false,
+        is_abstract: false,
         return_ty: Type::Void
     });
 
     TopItem::ClassDecl(ClassDecl {
         label: fresh_label(),
+        span: Span::dummy(),
+        access: None,
         name: "Program".to_string(),
-        items: vec![main_method]
+        bases: Vec::new(),
+        items: vec![main_method],
+        is_sealed: false,
+        is_abstract: false
     })
 }
 
@@ -38,6 +49,8 @@ pub fn large_fn() -> Program {
     Program {
         items: vec![
             program_class()
-        ]
+        ],
+        source_map: SourceMap::default(),
+        syntax: Vec::new()
     }
 }
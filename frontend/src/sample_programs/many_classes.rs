@@ -1,12 +1,18 @@
 use ast::*;
+use syntax::{SourceMap, Span};
 
 fn empty_method(name: &str) -> ClassItem {
     ClassItem::MethodDecl(MethodDecl {
         label: fresh_label(),
+        span: Span::dummy(),
+        access: Some(Access::Public),
         name: name.to_string(),
         params: Vec::new(),
-        body: Vec::new(),
+        body: Some(Vec::new()),
         is_static: true,
+        virtuality: Virtuality::NonVirtual,
+        is_new: false,
+        is_abstract: false,
         return_ty: Type::Void
     })
 }
@@ -28,8 +34,13 @@ fn classes() -> Vec<TopItem> {
         let class_name = format!("C{}", class_count);
         let class = TopItem::ClassDecl(ClassDecl {
             label: fresh_label(),
+            span: Span::dummy(),
+            access: None,
             name: class_name,
-            items: empty_methods()
+            bases: Vec::new(),
+            items: empty_methods(),
+            is_sealed: false,
+            is_abstract: false
         });
         classes.push(class);
     }
@@ -39,8 +50,13 @@ fn classes() -> Vec<TopItem> {
 fn main_class() -> TopItem {
     TopItem::ClassDecl(ClassDecl {
         label: fresh_label(),
+        span: Span::dummy(),
+        access: None,
         name: "Program".to_string(),
-        items: vec![empty_method("Main")]
+        bases: Vec::new(),
+        items: vec![empty_method("Main")],
+        is_sealed: false,
+        is_abstract: false
     })
 }
 
@@ -48,6 +64,8 @@ pub fn many_classes() -> Program {
     let mut classes = classes();
     classes.push(main_class());
     Program {
-        items: classes
+        items: classes,
+        source_map: SourceMap::default(),
+        syntax: Vec::new()
     }
 }
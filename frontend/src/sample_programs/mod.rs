@@ -3,6 +3,7 @@ mod hello_world;
 mod large_fn;
 mod many_classes;
 mod variables;
+pub mod problems;
 
 pub use self::builder::Builder;
 pub use self::hello_world::hello_world;
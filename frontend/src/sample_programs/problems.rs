@@ -0,0 +1,247 @@
+//! Programs with problems, which both checkers must report in the same way
+//!
+//! Note: the expected diagnostics are listed by the tests of the `QueryEngine`
+
+pub const REPORTS_ALL_PROBLEMS: &str = "class Program {\n    static void Main() {\n        int x = true;\n        Foo();\n        string s = x + \"a\";\n    }\n    static int Foo() { return; }\n}";
+
+pub const LOOP_CONDITIONS_ARE_BOOL: &str = "class Program {\n    static void Main() {\n        while (1) { }\n        do { } while (\"no\");\n        while (true) { }\n    }\n}";
+
+pub const FOREACH_NEEDS_A_COLLECTION: &str = "class Program {\n    static void Main() {\n        foreach (int x in 1) { }\n        foreach (string s in new int[] { }) { }\n        for (int i = 0; i; i = i + 1) { }\n        foreach (int x in new int[] { 1 }) { x = 100; x++; }\n    }\n}";
+
+pub const JUMPS_OUTSIDE_OF_LOOPS: &str = "class Program {\n    static void Main() {\n        break;\n        while (true) { if (true) { continue; } }\n        if (true) { continue; }\n    }\n}";
+
+pub const COMPARISON_OPERANDS: &str = "class Program {\n    static void Main() {\n        bool ok = 1 < 2 == \"a\" != null;\n        int wrong = 1 == 2;\n        bool b = true < false;\n        bool c = 1 != null;\n        bool d = new Program() == \"p\";\n    }\n}";
+
+pub const LOGICAL_OPERANDS: &str = "class Program {\n    static void Main() {\n        bool ok = !(1 < 2) && true || !false;\n        bool a = 1 && true;\n        bool b = !1;\n        int c = !true;\n    }\n}";
+
+pub const ASSIGNMENT_OPERANDS: &str = "class Program {\n    static void Main() {\n        int x = -1;\n        x += x++ * -x;\n        bool b = true;\n        b += 1;\n        b++;\n        y -= 1;\n        Program = 1;\n    }\n}";
+
+pub const ARRAY_OPERANDS: &str = "class Program {\n    static void Main() {\n        int[] a = new int[true];\n        a[false] = 1;\n        bool b = a[0];\n        a.Length = 2;\n        int x = 1;\n        x[0]++;\n        Foo[] f = new Foo[1];\n    }\n}";
+
+pub const CONSTRUCTOR_CALLS: &str = "\
+class A {
+    A(int x) { }
+    A(int x, bool b) : this(b) { }
+}
+class B {
+    B() : this(1) { }
+    B(int x) : this() { }
+}
+class Program {
+    static void Main() {
+        A a = new A();
+        a = new A(1, true);
+        a = new A(\"a\", 2);
+        Program p = new Program(1);
+    }
+}";
+
+pub const STATIC_FIELDS: &str = "\
+class A {
+    static int s = 1;
+    int i;
+    static A() { s = 2; }
+    static A() { }
+    void M() {
+        int x = A.i + this.s + s;
+        A.s = true;
+    }
+}
+class Program {
+    static void Main() {
+        int y = s + A.s;
+    }
+}";
+
+pub const INSTANCE_FIELDS: &str = "\
+class A {
+    int i;
+    static int s;
+    int j = i;
+    static int S() { return i + s; }
+    bool M() { i = s; return i; }
+}
+class Program {
+    static void Main() { }
+}";
+
+pub const INHERITANCE: &str = "\
+class A {
+    int f;
+    A(int x) { }
+    void M() { }
+}
+class B : A {
+    B() : base(1) { base.M(); this.f = 2; }
+}
+class C : A { }
+class D : Missing { }
+class E : F { }
+class F : E { }
+class Program {
+    static void Main() {
+        A a = new B();
+        B b = a;
+        int x = base.f;
+    }
+}";
+
+pub const CALLS_WITHOUT_TARGET: &str = "\
+class A {
+    static int Zero() { return 0; }
+    int One() { return 1; }
+}
+class B : A {
+    int f = One();
+    static int Two() { return Zero() + 2; }
+    static int Three() { return One() + 2; }
+    int Four() { return One() + Two() + Zero(); }
+    int Five() { return Missing(); }
+}
+class Program {
+    static void Main() { }
+}";
+
+pub const OVERRIDES: &str = "\
+class A {
+    virtual int M(int x) { return x; }
+    void N() { }
+    virtual void O() { }
+}
+class B : A {
+    sealed override int M(int x) { return 1; }
+    override void N() { }
+    override bool O() { return true; }
+    override void P() { }
+}
+class C : B {
+    override int M(int x) { return 2; }
+}
+sealed class D { }
+class E : D { }
+class Program {
+    static void Main() { }
+}";
+
+pub const INTERFACES: &str = "\
+interface IA {
+    int M(int x);
+}
+interface IB : IA {
+    void N();
+}
+class A : IB {
+    public int M(int x) { return x; }
+    public void N() { }
+}
+class B : IA {
+    public bool M(int x) { return true; }
+}
+class C : IB {
+    public int M(bool x) { return 1; }
+}
+class Program {
+    static void Main() {
+        IA a = new A();
+        IB b = new IB();
+        b.N();
+        A c = a;
+    }
+}";
+
+pub const ABSTRACT_CLASSES: &str = "\
+abstract class A {
+    public abstract int M();
+    public abstract void N();
+}
+abstract class B : A {
+    public override void N() { }
+}
+class C : B {
+    public override int M() { return base.M(); }
+}
+class D : B { }
+class E {
+    public abstract void O();
+}
+class Program {
+    static void Main() {
+        A a = new B();
+        A c = new C();
+    }
+}";
+
+pub const OVERLOADS: &str = "\
+class A { }
+class B : A { }
+class C {
+    void M(int x) { }
+    void M(string s) { }
+    void M(A a) { }
+    void M(B b) { }
+    void N(A a, B b) { }
+    void N(B b, A a) { }
+    void Run() {
+        this.M(1);
+        this.M(\"s\");
+        this.M(new B());
+        this.M(true);
+        this.M(null);
+        this.N(new B(), new B());
+    }
+}
+class D {
+    D(int a) { }
+    D(int b) { }
+    static void M(A a) { }
+    void M(A b) { }
+    void M(A[] a) { }
+}
+class Program {
+    static void Main() { }
+}";
+
+pub const NAMESPACES: &str = "\
+using Shapes;
+using Q = Other.Point;
+namespace Shapes { class Point { } }
+namespace Other {
+    class Point { }
+    class User {
+        Point Mine() { return new Point(); }
+        Shapes.Point Theirs() { return new Q(); }
+    }
+}
+class Program {
+    static void Main() { }
+    Point Imported() { return new Other.Point(); }
+}
+namespace Both {
+    using Shapes;
+    using Other;
+    class Mixed {
+        Point Either() { return new Point(); }
+    }
+}";
+
+/// All the programs above, by name
+pub const ALL: &[(&str, &str)] = &[
+    ("reports_all_problems", REPORTS_ALL_PROBLEMS),
+    ("loop_conditions_are_bool", LOOP_CONDITIONS_ARE_BOOL),
+    ("foreach_needs_a_collection", FOREACH_NEEDS_A_COLLECTION),
+    ("jumps_outside_of_loops", JUMPS_OUTSIDE_OF_LOOPS),
+    ("comparison_operands", COMPARISON_OPERANDS),
+    ("logical_operands", LOGICAL_OPERANDS),
+    ("assignment_operands", ASSIGNMENT_OPERANDS),
+    ("array_operands", ARRAY_OPERANDS),
+    ("constructor_calls", CONSTRUCTOR_CALLS),
+    ("static_fields", STATIC_FIELDS),
+    ("instance_fields", INSTANCE_FIELDS),
+    ("inheritance", INHERITANCE),
+    ("calls_without_target", CALLS_WITHOUT_TARGET),
+    ("overrides", OVERRIDES),
+    ("interfaces", INTERFACES),
+    ("abstract_classes", ABSTRACT_CLASSES),
+    ("overloads", OVERLOADS),
+    ("namespaces", NAMESPACES),
+];
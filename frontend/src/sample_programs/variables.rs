@@ -1,12 +1,15 @@
 use ast::*;
+use syntax::{SourceMap, Span};
 use super::Builder;
 
 fn program_class() -> TopItem {
     let main_method = ClassItem::MethodDecl(MethodDecl {
         label: fresh_label(),
+        span: Span::dummy(),
+        access: Some(Access::Public),
         name: "Main".to_string(),
         params: Vec::new(),
-        body: vec![
+        body: Some(vec![
             Builder::write_line_str("Part one"),
             Builder::write_line_str("========"),
             Builder::decl_string("msg", "Hello there!"),
@@ -24,29 +27,39 @@ fn program_class() -> TopItem {
             Builder::write_line_expr(Builder::method_call_literal("Program", "Factorial", vec![LiteralKind::Int(0)])),
             Builder::write_line_str("Factorial of 5"),
             Builder::write_line_expr(Builder::method_call_literal("Program", "Factorial", vec![LiteralKind::Int(5)])),
-        ],
+        ]),
         is_static: true,
+        virtuality: Virtuality::NonVirtual,
+        is_new: false,
+        is_abstract: false,
         return_ty: Type::Void
     });
 
     let aux_method = ClassItem::MethodDecl(MethodDecl {
         label: fresh_label(),
+        span: Span::dummy(),
+        access: None,
         name: "Aux".to_string(),
-        params: vec![VarDecl { label: fresh_label(), var_name: "x".to_string(), ty: Type::Custom("int".to_string()), expr: None }],
-        body: vec![
+        params: vec![VarDecl { label: fresh_label(), span: Span::dummy(), var_name: "x".to_string(), ty: Type::Custom("int".to_string()), expr: None }],
+        body: Some(vec![
             Builder::decl_int("two", 2),
             Builder::decl_int_from_expr("sum", Builder::sum_vars("x", "two")),
             Builder::return_var("sum")
-        ],
+        ]),
         is_static: true,
+        virtuality: Virtuality::NonVirtual,
+        is_new: false,
+        is_abstract: false,
         return_ty: Type::Custom("int".to_string())
     });
 
     let factorial_method = ClassItem::MethodDecl(MethodDecl {
         label: fresh_label(),
+        span: Span::dummy(),
+        access: Some(Access::Public),
         name: "Factorial".to_string(),
-        params: vec![VarDecl { label: fresh_label(), var_name: "x".to_string(), ty: Type::Custom("int".to_string()), expr: None }],
-        body: vec![
+        params: vec![VarDecl { label: fresh_label(), span: Span::dummy(), var_name: "x".to_string(), ty: Type::Custom("int".to_string()), expr: None }],
+        body: Some(vec![
             Builder::if_then_else(
                 Builder::binary_op(BinaryOperator::Eq, Builder::literal(LiteralKind::Int(0)), Builder::var_use("x")),
                 vec![Builder::return_(Builder::literal(LiteralKind::Int(1)))],
@@ -62,15 +75,23 @@ fn program_class() -> TopItem {
                         ])
                     ))]
             )
-        ],
+        ]),
         is_static: true,
+        virtuality: Virtuality::NonVirtual,
+        is_new: false,
+        is_abstract: false,
         return_ty: Type::Custom("int".to_string())
     });
 
     TopItem::ClassDecl(ClassDecl {
         label: fresh_label(),
+        span: Span::dummy(),
+        access: None,
         name: "Program".to_string(),
-        items: vec![main_method, aux_method, factorial_method]
+        bases: Vec::new(),
+        items: vec![main_method, aux_method, factorial_method],
+        is_sealed: false,
+        is_abstract: false
     })
 }
 
@@ -78,6 +99,8 @@ pub fn variables() -> Program {
     Program {
         items: vec![
             program_class()
-        ]
+        ],
+        source_map: SourceMap::default(),
+        syntax: Vec::new()
     }
 }
@@ -0,0 +1,401 @@
+//! Lossless concrete syntax tree
+//!
+//! Unlike the AST, the concrete syntax tree keeps every byte of the source: whitespace,
+//! comments, unrecognized characters and the exact text of each token. Printing a tree gives
+//! back the text it was parsed from, which is what tools that rewrite code need.
+//!
+//! The tree has two layers:
+//!
+//! * Green nodes are immutable and only know their kind, their children and the length of their
+//!   text. They have no position, so they can be shared between trees (e.g. after an edit, the
+//!   untouched parts of a file are reused as they are).
+//! * Red nodes (`SyntaxNode` and `SyntaxToken`) are created on demand on top of green nodes.
+//!   They know their parent and their position in the file.
+//!
+//! The parser builds the tree and the AST side by side in the same pass (see `syntax::parser`),
+//! from the same tokens. Nothing turns a tree back into an AST, though: a tool that changes the
+//! tree of a file has to print it and parse the resulting text again to get the new AST (or use
+//! `Program::apply_edit`, which reparses as little as possible).
+
+use std::fmt;
+use std::rc::Rc;
+
+use diagnostics::Diagnostics;
+use super::lexer::TokenKind;
+use super::span::{FileId, Span};
+
+/// The kind of a node in the concrete syntax tree
+///
+/// Note: most kinds correspond to the AST node with the same name
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum SyntaxKind {
+    /// The root of the tree of a file
+    SourceFile,
+    UsingDirective,
+    NamespaceDecl,
+    ClassDecl,
+    InterfaceDecl,
+    /// The `: A, IFoo` naming the base class and the interfaces, in the head of a class or an interface
+    BaseList,
+    FieldDecl,
+    MethodDecl,
+    ConstructorDecl,
+    /// The `: this(...)` or `: base(...)` call to another constructor, in the head of a constructor
+    ConstructorInitializer,
+    /// The parenthesized parameters of a method or a constructor
+    ParamList,
+    Param,
+    Type,
+    Block,
+    VarDecl,
+    Assign,
+    Return,
+    IfThenElse,
+    While,
+    DoWhile,
+    For,
+    Foreach,
+    Break,
+    Continue,
+    /// An expression followed by a `;`
+    ExpressionStatement,
+    BinaryOp,
+    UnaryOp,
+    FieldAccess,
+    MethodCall,
+    /// The parenthesized arguments of a method call, a constructor call or `new`
+    ArgList,
+    New,
+    NewArray,
+    Index,
+    ArrayLiteral,
+    Literal,
+    Identifier,
+    This,
+    Base,
+    ParenExpr,
+    /// Tokens skipped during error recovery, possibly preceded by an incomplete node
+    Error
+}
+
+/// The concrete syntax tree of a file, together with the syntax errors found while parsing it
+#[derive(Clone, Debug)]
+pub struct SyntaxTree {
+    pub root: SyntaxNode,
+    pub errors: Diagnostics
+}
+
+// Green tree
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    text_len: usize,
+    children: Vec<GreenElement>
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> GreenNode {
+        let text_len = children.iter().map(GreenElement::text_len).sum();
+        GreenNode { kind, text_len, children }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text_len(&self) -> usize {
+        self.text_len
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    /// Returns a copy of this node, with the child at the given index replaced
+    pub fn replace_child(&self, index: usize, child: GreenElement) -> GreenNode {
+        let mut children = self.children.clone();
+        children[index] = child;
+        GreenNode::new(self.kind, children)
+    }
+}
+
+impl fmt::Display for GreenNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for child in &self.children {
+            match *child {
+                GreenElement::Node(ref node) => write!(f, "{}", node)?,
+                GreenElement::Token(ref token) => write!(f, "{}", token.text)?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GreenToken {
+    kind: TokenKind,
+    text: String
+}
+
+impl GreenToken {
+    pub fn new(kind: TokenKind, text: String) -> GreenToken {
+        GreenToken { kind, text }
+    }
+
+    pub fn kind(&self) -> &TokenKind {
+        &self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>)
+}
+
+impl GreenElement {
+    pub fn text_len(&self) -> usize {
+        match *self {
+            GreenElement::Node(ref node) => node.text_len,
+            GreenElement::Token(ref token) => token.text.len()
+        }
+    }
+}
+
+/// A position in the children of the node being built, used to wrap already built children
+/// in a new node (e.g. the left operand of a binary operator)
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Checkpoint(usize);
+
+/// Builds a green tree from the top down
+#[derive(Default)]
+pub(super) struct GreenBuilder {
+    /// The nodes being built, with the index of their first child in `children`
+    parents: Vec<(SyntaxKind, usize)>,
+    children: Vec<GreenElement>
+}
+
+impl GreenBuilder {
+    pub(super) fn start_node(&mut self, kind: SyntaxKind) {
+        self.parents.push((kind, self.children.len()));
+    }
+
+    /// Starts a node whose first children are the ones added since the checkpoint
+    pub(super) fn start_node_at(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        debug_assert!(self.parents.last().is_none_or(|&(_, first)| first <= checkpoint.0),
+                      "Checkpoint should be inside the current node");
+        self.parents.push((kind, checkpoint.0));
+    }
+
+    pub(super) fn finish_node(&mut self) {
+        let (kind, first) = self.parents.pop().expect("No node to finish");
+        let children = self.children.drain(first..).collect();
+        self.children.push(GreenElement::Node(Rc::new(GreenNode::new(kind, children))));
+    }
+
+    pub(super) fn token(&mut self, kind: TokenKind, text: &str) {
+        self.children.push(GreenElement::Token(Rc::new(GreenToken::new(kind, text.to_string()))));
+    }
+
+    pub(super) fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.children.len())
+    }
+
+    /// The number of nodes being built
+    pub(super) fn depth(&self) -> usize {
+        self.parents.len()
+    }
+
+    /// Finishes nodes until only `depth` nodes are being built
+    ///
+    /// Note: this is used after a syntax error, which may interrupt the parsing of several nodes
+    pub(super) fn finish_to(&mut self, depth: usize) {
+        while self.parents.len() > depth {
+            self.finish_node();
+        }
+    }
+
+    /// Returns the root of the tree, which must have been finished
+    pub(super) fn finish(mut self) -> Rc<GreenNode> {
+        assert!(self.parents.is_empty(), "All nodes should be finished");
+        match self.children.pop() {
+            Some(GreenElement::Node(ref root)) if self.children.is_empty() => root.clone(),
+            _ => panic!("The tree should have a single root node")
+        }
+    }
+}
+
+// Red tree
+
+/// A node of the concrete syntax tree, which knows its position and its parent
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData {
+    green: Rc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    /// The index of the node in the children of its parent
+    index: usize,
+    file: FileId,
+    /// The byte offset of the node in its file
+    offset: usize
+}
+
+impl SyntaxNode {
+    /// Returns the root of the tree of the given file
+    pub fn new_root(green: Rc<GreenNode>, file: FileId) -> SyntaxNode {
+        SyntaxNode(Rc::new(NodeData { green, parent: None, index: 0, file, offset: 0 }))
+    }
+
+    pub fn green(&self) -> &Rc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind
+    }
+
+    /// The span of the node, including the trivia inside it
+    ///
+    /// Note: a node starts at its first token that is not trivia, since the trivia before it
+    /// belongs to its parent
+    pub fn span(&self) -> Span {
+        Span::new(self.0.file, self.0.offset, self.0.offset + self.0.green.text_len)
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    /// The exact source text of the node
+    pub fn text(&self) -> String {
+        self.0.green.to_string()
+    }
+
+    pub fn children_with_tokens(&self) -> impl Iterator<Item=SyntaxElement> {
+        let mut offset = self.0.offset;
+        let mut children = Vec::new();
+        for (index, child) in self.0.green.children.iter().enumerate() {
+            children.push(match *child {
+                GreenElement::Node(ref green) => SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                    green: green.clone(),
+                    parent: Some(self.clone()),
+                    index,
+                    file: self.0.file,
+                    offset
+                }))),
+                GreenElement::Token(ref green) => SyntaxElement::Token(SyntaxToken {
+                    green: green.clone(),
+                    parent: self.clone(),
+                    offset
+                })
+            });
+            offset += child.text_len();
+        }
+
+        children.into_iter()
+    }
+
+    pub fn children(&self) -> impl Iterator<Item=SyntaxNode> {
+        self.children_with_tokens().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None
+        })
+    }
+
+    /// Returns this node and all nodes below it, in preorder
+    pub fn descendants(&self) -> impl Iterator<Item=SyntaxNode> {
+        let mut nodes = Vec::new();
+        let mut stack = vec![self.clone()];
+        while let Some(node) = stack.pop() {
+            let mut children: Vec<_> = node.children().collect();
+            children.reverse();
+            stack.extend(children);
+            nodes.push(node);
+        }
+
+        nodes.into_iter()
+    }
+
+    /// Returns all tokens below this node, trivia included, in source order
+    pub fn tokens(&self) -> impl Iterator<Item=SyntaxToken> {
+        let mut tokens = Vec::new();
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token)
+            }
+        }
+
+        tokens.into_iter()
+    }
+
+    /// Returns the green root of a copy of the tree in which this node is replaced
+    ///
+    /// Note: only the ancestors of the node are copied, the rest of the tree is shared
+    pub fn replace_with(&self, replacement: Rc<GreenNode>) -> Rc<GreenNode> {
+        match self.0.parent {
+            Some(ref parent) => {
+                let green = parent.0.green.replace_child(self.0.index, GreenElement::Node(replacement));
+                parent.replace_with(Rc::new(green))
+            }
+            None => replacement
+        }
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.green)
+    }
+}
+
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}@{}", self.kind(), self.span())
+    }
+}
+
+/// A token of the concrete syntax tree, which knows its position and its parent
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    parent: SyntaxNode,
+    offset: usize
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> &TokenKind {
+        &self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.parent.0.file, self.offset, self.offset + self.green.text.len())
+    }
+
+    pub fn parent(&self) -> SyntaxNode {
+        self.parent.clone()
+    }
+}
+
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}@{} {:?}", self.kind(), self.span(), self.text())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken)
+}
@@ -0,0 +1,505 @@
+//! Incremental reparsing of edited programs
+//!
+//! Parsing gives fresh labels to all nodes, which invalidates everything that was computed about
+//! them. Most edits (e.g. typing inside a method) only affect a single method, so in that case
+//! only the method is reparsed. The rest of the program keeps its labels, so whatever was computed
+//! about it stays valid.
+
+use std::slice;
+
+use ast::*;
+use diagnostics::Diagnostics;
+use super::lexer::TokenKind;
+use super::cst::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxTree};
+use super::parser;
+use super::span::{FileId, Span};
+
+impl Program {
+    /// Replaces the source code at `range` by `text` and updates the program accordingly
+    ///
+    /// If the edit is contained in a method, only the method is reparsed and gets new labels.
+    /// Otherwise, the whole file is reparsed. Returns the syntax errors of the edited file.
+    ///
+    /// Note: the program must have been parsed from source (see `syntax::parse`)
+    pub fn apply_edit(&mut self, range: Span, text: &str) -> Diagnostics {
+        let mut src = self.source_map.file(range.file).src.clone();
+        src.replace_range(range.start..range.end, text);
+        self.source_map.set_src(range.file, src);
+
+        if !self.reparse_method(range, text.len()) {
+            self.reparse_file(range.file);
+        }
+
+        self.syntax[range.file.0 as usize].errors.clone()
+    }
+
+    /// Reparses the method containing the edited range, if any
+    ///
+    /// Returns false if the edit may have changed anything outside of the method, in which case
+    /// nothing is modified.
+    fn reparse_method(&mut self, range: Span, inserted: usize) -> bool {
+        let file = range.file;
+        let old_root = self.syntax[file.0 as usize].root.clone();
+        let node = match covering_method(&old_root, range) {
+            Some(node) => node,
+            None => return false
+        };
+        let class_node = match node.parent() {
+            Some(ref parent) if parent.kind() == SyntaxKind::ClassDecl => parent.clone(),
+            _ => return false
+        };
+
+        // An unclosed method reports an error at the member after it, which we would not update
+        if !is_closed(&node) {
+            return false;
+        }
+
+        // A member that ends in an error is parsed up to the next token that can start a member,
+        // which may be moved by the edit
+        let previous = class_node.children().take_while(|child| child.span().start < node.span().start).last();
+        if previous.is_some_and(|child| child.kind() == SyntaxKind::Error) {
+            return false;
+        }
+
+        let new_file = self.source_map.file(file);
+        let old_span = node.span();
+        let (class_label, class_name, old_label) = {
+            let class = match self.classes().find(|cd| cd.span == class_node.span()) {
+                Some(class) => class,
+                None => return false
+            };
+            match class.items.iter().filter_map(ClassItem::method_decl).find(|md| md.span == old_span) {
+                Some(method) => (class.label, class.name.clone(), method.label),
+                None => return false
+            }
+        };
+
+        let (method, green, method_errors) = match parser::reparse_method(new_file, &class_name, old_span.start) {
+            Some(result) => result,
+            None => return false
+        };
+        // Same for the reparsed method (e.g. at the end of an unclosed class)
+        if !is_closed(&SyntaxNode::new_root(green.clone(), file)) {
+            return false;
+        }
+
+        // The method must end right before the code that follows the edit
+        let shift = Shift { file, at: old_span.end, delta: inserted as isize - range.len() as isize };
+        if method.span.end != shift.offset(old_span.end) {
+            return false;
+        }
+
+        // Everything after the method moves, but keeps its labels
+        shift.top_items(&mut self.items);
+        let class = class_mut(&mut self.items, class_label).expect("The class should exist");
+        for item in &mut class.items {
+            if let ClassItem::MethodDecl(ref mut md) = *item {
+                if md.label == old_label {
+                    *md = method;
+                    break;
+                }
+            }
+        }
+
+        let mut errors = Vec::new();
+        for error in self.syntax[file.0 as usize].errors.iter() {
+            let in_method = error.primary_span().is_some_and(|span| old_span.start < span.start && span.start < old_span.end);
+            if !in_method {
+                let mut error = error.clone();
+                for label in &mut error.labels {
+                    shift.span(&mut label.span);
+                }
+                errors.push(error);
+            }
+        }
+        errors.extend(method_errors.iter().cloned());
+        let errors = parser::sorted(errors);
+
+        let root = SyntaxNode::new_root(node.replace_with(green), file);
+        self.syntax[file.0 as usize] = SyntaxTree { root, errors };
+        true
+    }
+
+    /// Reparses a whole file, replacing its items
+    fn reparse_file(&mut self, file: FileId) {
+        let (items, tree) = parser::parse_file(self.source_map.file(file));
+
+        // The items are sorted by file
+        let start = self.items.iter().position(|item| item.span().file >= file).unwrap_or(self.items.len());
+        let end = self.items.iter().position(|item| item.span().file > file).unwrap_or(self.items.len());
+        self.items.splice(start..end, items);
+        self.syntax[file.0 as usize] = tree;
+    }
+}
+
+/// Returns the method declaration that contains the range, if any
+fn covering_method(root: &SyntaxNode, range: Span) -> Option<SyntaxNode> {
+    let mut node = root.clone();
+    while node.kind() != SyntaxKind::MethodDecl {
+        node = node.children().find(|child| child.span().start <= range.start && range.end <= child.span().end)?;
+    }
+
+    Some(node)
+}
+
+/// Returns true if the body of the method ends with a `}`
+fn is_closed(method: &SyntaxNode) -> bool {
+    match method.children().find(|child| child.kind() == SyntaxKind::Block) {
+        Some(block) => match block.children_with_tokens().last() {
+            Some(SyntaxElement::Token(ref token)) => *token.kind() == TokenKind::CloseBrace,
+            _ => false
+        },
+        None => false
+    }
+}
+
+fn class_mut(items: &mut [TopItem], label: Label) -> Option<&mut ClassDecl> {
+    for item in items {
+        match *item {
+            TopItem::ClassDecl(ref mut cd) if cd.label == label => return Some(cd),
+            TopItem::NamespaceDecl(ref mut nd) => {
+                if let Some(cd) = class_mut(&mut nd.items, label) {
+                    return Some(cd);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Moves the positions in a file that come at or after `at` by `delta` bytes
+struct Shift {
+    file: FileId,
+    at: usize,
+    delta: isize
+}
+
+impl Shift {
+    fn offset(&self, offset: usize) -> usize {
+        if offset >= self.at {
+            (offset as isize + self.delta) as usize
+        } else {
+            offset
+        }
+    }
+
+    fn span(&self, span: &mut Span) {
+        if span.file == self.file {
+            span.start = self.offset(span.start);
+            span.end = self.offset(span.end);
+        }
+    }
+
+    /// Returns true if nothing in the span moves, so the node can be skipped
+    fn skip(&self, span: Span) -> bool {
+        span.file != self.file || span.end < self.at
+    }
+
+    fn top_items(&self, items: &mut [TopItem]) {
+        for item in items {
+            if self.skip(item.span()) {
+                continue;
+            }
+
+            match *item {
+                TopItem::ClassDecl(ref mut cd) => self.class_decl(cd),
+                TopItem::InterfaceDecl(ref mut id) => self.interface_decl(id),
+                TopItem::NamespaceDecl(ref mut nd) => {
+                    self.span(&mut nd.span);
+                    self.top_items(&mut nd.items);
+                }
+                TopItem::UsingDirective(ref mut ud) => self.span(&mut ud.span)
+            }
+        }
+    }
+
+    fn class_decl(&self, decl: &mut ClassDecl) {
+        self.span(&mut decl.span);
+        for base in &mut decl.bases {
+            self.span(&mut base.span);
+        }
+        for item in &mut decl.items {
+            match *item {
+                ClassItem::FieldDecl(ref mut fd) if !self.skip(fd.span) => {
+                    self.span(&mut fd.span);
+                    if let Some(ref mut expr) = fd.assignment {
+                        self.expression(expr);
+                    }
+                }
+                ClassItem::MethodDecl(ref mut md) if !self.skip(md.span) => self.method_decl(md),
+                ClassItem::ConstructorDecl(ref mut cd) if !self.skip(cd.span) => {
+                    self.span(&mut cd.span);
+                    for param in &mut cd.params {
+                        self.span(&mut param.span);
+                    }
+                    if let Some(ref mut call) = cd.initializer {
+                        self.span(&mut call.span);
+                        for arg in &mut call.args {
+                            self.expression(arg);
+                        }
+                    }
+                    self.statements(&mut cd.body);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn interface_decl(&self, decl: &mut InterfaceDecl) {
+        self.span(&mut decl.span);
+        for base in &mut decl.bases {
+            self.span(&mut base.span);
+        }
+        for md in &mut decl.methods {
+            if !self.skip(md.span) {
+                self.method_decl(md);
+            }
+        }
+    }
+
+    fn method_decl(&self, decl: &mut MethodDecl) {
+        self.span(&mut decl.span);
+        for param in &mut decl.params {
+            self.span(&mut param.span);
+        }
+        if let Some(ref mut body) = decl.body {
+            self.statements(body);
+        }
+    }
+
+    fn statements(&self, statements: &mut [Statement]) {
+        for statement in statements {
+            match *statement {
+                Statement::Assign(ref mut assign) => {
+                    self.span(&mut assign.span);
+                    self.expression(&mut assign.target);
+                    self.expression(&mut assign.expr);
+                }
+                Statement::Expression(ref mut expr) => self.expression(expr),
+                Statement::Break(Break { ref mut span, .. }) | Statement::Continue(Continue { ref mut span, .. }) => {
+                    self.span(span);
+                }
+                Statement::Return(ref mut ret) => {
+                    self.span(&mut ret.span);
+                    if let Some(ref mut expr) = ret.expr {
+                        self.expression(expr);
+                    }
+                }
+                Statement::VarDecl(ref mut decl) => {
+                    self.span(&mut decl.span);
+                    if let Some(ref mut expr) = decl.expr {
+                        self.expression(expr);
+                    }
+                }
+                Statement::IfThenElse(ref mut ite) => {
+                    self.span(&mut ite.span);
+                    self.expression(&mut ite.condition);
+                    self.statements(&mut ite.then);
+                    self.statements(&mut ite.else_);
+                }
+                Statement::While(ref mut w) => {
+                    self.span(&mut w.span);
+                    self.expression(&mut w.condition);
+                    self.statements(&mut w.body);
+                }
+                Statement::DoWhile(ref mut dw) => {
+                    self.span(&mut dw.span);
+                    self.statements(&mut dw.body);
+                    self.expression(&mut dw.condition);
+                }
+                Statement::For(ref mut f) => {
+                    self.span(&mut f.span);
+                    if let Some(ref mut init) = f.init {
+                        self.statements(slice::from_mut(&mut **init));
+                    }
+                    if let Some(ref mut condition) = f.condition {
+                        self.expression(condition);
+                    }
+                    if let Some(ref mut step) = f.step {
+                        self.statements(slice::from_mut(&mut **step));
+                    }
+                    self.statements(&mut f.body);
+                }
+                Statement::Foreach(ref mut f) => {
+                    self.span(&mut f.span);
+                    self.span(&mut f.var.span);
+                    self.expression(&mut f.collection);
+                    self.statements(&mut f.body);
+                }
+                Statement::Error(ref mut error) => self.span(&mut error.span)
+            }
+        }
+    }
+
+    fn expression(&self, expr: &mut Expression) {
+        match *expr {
+            Expression::BinaryOp(ref mut op) => {
+                self.span(&mut op.span);
+                self.expression(&mut op.left);
+                self.expression(&mut op.right);
+            }
+            Expression::UnaryOp(ref mut op) => {
+                self.span(&mut op.span);
+                self.expression(&mut op.operand);
+            }
+            Expression::FieldAccess(ref mut fa) => {
+                self.span(&mut fa.span);
+                self.expression(&mut fa.target);
+            }
+            Expression::Literal(ref mut literal) => {
+                self.span(&mut literal.span);
+                if let LiteralKind::Array(_, ref mut elems) = literal.kind {
+                    for elem in elems {
+                        self.expression(elem);
+                    }
+                }
+            }
+            Expression::MethodCall(ref mut mc) => {
+                self.span(&mut mc.span);
+                if let Some(ref mut target) = mc.target {
+                    self.expression(target);
+                }
+                for arg in &mut mc.args {
+                    self.expression(arg);
+                }
+            }
+            Expression::New(ref mut new) => {
+                self.span(&mut new.span);
+                for arg in &mut new.args {
+                    self.expression(arg);
+                }
+            }
+            Expression::NewArray(ref mut new_array) => {
+                self.span(&mut new_array.span);
+                self.expression(&mut new_array.length);
+            }
+            Expression::Index(ref mut index) => {
+                self.span(&mut index.span);
+                self.expression(&mut index.target);
+                self.expression(&mut index.index);
+            }
+            Expression::Identifier(ref mut identifier) => self.span(&mut identifier.span),
+            Expression::This(ref mut this) => self.span(&mut this.span),
+            Expression::Base(ref mut base) => self.span(&mut base.span),
+            Expression::Error(ref mut error) => self.span(&mut error.span)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use syntax::parse;
+
+    const SRC: &str = "\
+class A {
+    int f = 1;
+    static int Two() {
+        return 2;
+    }
+    int Get() {
+        // The edits happen here
+        return this.f;
+    }
+}
+namespace N {
+    class B {
+        int Three() { return A.Two() + 1; }
+    }
+}";
+
+    /// Applies the edit to the program, returning the labels of the methods and the errors
+    fn edit(program: &mut Program, needle: &str, text: &str) -> (Vec<Label>, Vec<String>) {
+        let start = program.source_map.files()[0].src.find(needle).unwrap();
+        let span = Span::new(program.source_map.files()[0].id, start, start + needle.len());
+        let errors = program.apply_edit(span, text).iter().map(|d| d.message.clone()).collect();
+        (program.methods().map(|m| m.label).collect(), errors)
+    }
+
+    /// Returns the debug representation of the program, without labels
+    fn without_labels(program: &Program) -> String {
+        let debug = format!("{:?}", program.items);
+        let mut result = String::new();
+        for (i, part) in debug.split("Label(").enumerate() {
+            if i > 0 {
+                result.push_str("Label(");
+                result.push_str(part.trim_start_matches(|c: char| c.is_ascii_digit()));
+            } else {
+                result.push_str(part);
+            }
+        }
+
+        result
+    }
+
+    /// Checks that the program is the same as if it had been parsed from scratch
+    fn assert_reparsed(program: &Program, errors: &[String]) {
+        let (expected, expected_errors) = parse("test.cs", &program.source_map.files()[0].src);
+        assert_eq!(without_labels(program), without_labels(&expected));
+        assert_eq!(program.syntax[0].root.green(), expected.syntax[0].root.green());
+        assert_eq!(program.syntax[0].root.text(), program.source_map.files()[0].src);
+        let expected_errors: Vec<_> = expected_errors.iter().map(|d| d.message.clone()).collect();
+        assert_eq!(errors, &expected_errors[..]);
+        let spans: Vec<_> = program.syntax[0].errors.iter().map(|d| d.primary_span()).collect();
+        let expected_spans: Vec<_> = expected.syntax[0].errors.iter().map(|d| d.primary_span()).collect();
+        assert_eq!(spans, expected_spans);
+    }
+
+    #[test]
+    fn edits_inside_a_method_keep_labels() {
+        let (mut program, _) = parse("test.cs", SRC);
+        let labels: Vec<_> = program.methods().map(|m| m.label).collect();
+        let classes: Vec<_> = program.classes().map(|c| c.label).collect();
+
+        // Only `Get` gets a new label
+        let (new_labels, errors) = edit(&mut program, "return this.f;", "int x = this.f * 100;\n        return x;");
+        assert!(errors.is_empty());
+        assert_eq!((new_labels[0], new_labels[2]), (labels[0], labels[2]));
+        assert!(new_labels[1] != labels[1]);
+        assert_eq!(program.classes().map(|c| c.label).collect::<Vec<_>>(), classes);
+        assert_reparsed(&program, &errors);
+
+        // Syntax errors inside the method are reported, without affecting the rest
+        let (labels, errors) = edit(&mut program, "return x;", "return x +;");
+        assert_eq!(errors, vec!["expected expression, found `;`"]);
+        assert_eq!((labels[0], labels[2]), (new_labels[0], new_labels[2]));
+        assert_reparsed(&program, &errors);
+
+        // Fixing the error removes it
+        let (labels, errors) = edit(&mut program, "x +;", "x + 1;");
+        assert!(errors.is_empty());
+        assert_reparsed(&program, &errors);
+
+        // Renaming a method only reparses it, even if it is static
+        let (new_labels, errors) = edit(&mut program, "static int Two()", "static int Deux()");
+        assert!(new_labels[0] != labels[0]);
+        assert_eq!((new_labels[1], new_labels[2]), (labels[1], labels[2]));
+        assert_reparsed(&program, &errors);
+    }
+
+    #[test]
+    fn other_edits_reparse_the_file() {
+        let cases = [
+            // Removes the closing brace of the method
+            ("        return this.f;\n    }", "        return this.f;"),
+            // Outside of any method
+            ("int f = 1;", "int f = 2;"),
+            ("namespace N", "namespace M"),
+        ];
+
+        for &(needle, text) in &cases {
+            let (mut program, _) = parse("test.cs", SRC);
+            let labels: Vec<_> = program.methods().map(|m| m.label).collect();
+            let (new_labels, errors) = edit(&mut program, needle, text);
+            assert!(new_labels.iter().all(|label| !labels.contains(label)), "{}", text);
+            assert_reparsed(&program, &errors);
+        }
+
+        // Removes the closing brace of a method at the end of an unclosed class
+        let (mut program, _) = parse("test.cs", "class A {\n    void M() {\n    }");
+        let (_, errors) = edit(&mut program, "    }", "    x");
+        assert_reparsed(&program, &errors);
+    }
+}
@@ -0,0 +1,664 @@
+//! Tokenizer for the subset of C# that we support
+//!
+//! The lexer never fails: malformed input is reported through `LexError`s and still
+//! produces tokens. Every byte of the input ends up in exactly one token (whitespace and
+//! comments included), so slicing the source with the token spans gives back the original text.
+
+use std::fmt;
+
+use super::span::{FileId, Span};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    // Trivia
+    Whitespace,
+    LineComment,
+    BlockComment,
+
+    // Names
+    /// Identifier (without the `@` prefix of verbatim identifiers)
+    Ident(String),
+    Keyword(Keyword),
+
+    // Literals (string and char literals contain the unescaped value)
+    IntLiteral(i64),
+    StringLiteral(String),
+    CharLiteral(char),
+    BoolLiteral(bool),
+    NullLiteral,
+
+    // Punctuation
+    OpenParen,
+    CloseParen,
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    Semicolon,
+    Comma,
+    Dot,
+    Colon,
+    Question,
+
+    // Operators
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    PlusPlus,
+    MinusMinus,
+    Eq,
+    PlusEq,
+    MinusEq,
+    StarEq,
+    SlashEq,
+    PercentEq,
+    EqEq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    AndAnd,
+    OrOr,
+    Not,
+    And,
+    Or,
+    Caret,
+    Tilde,
+
+    /// A character that cannot start any token
+    Unknown,
+    /// End of input
+    Eof
+}
+
+impl TokenKind {
+    /// Whitespace and comments
+    pub fn is_trivia(&self) -> bool {
+        matches!(*self, TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment)
+    }
+
+    /// The exact source text of tokens that don't carry a value
+    pub fn as_str(&self) -> Option<&'static str> {
+        use self::TokenKind::*;
+        let s = match *self {
+            Keyword(kw) => kw.as_str(),
+            BoolLiteral(true) => "true",
+            BoolLiteral(false) => "false",
+            NullLiteral => "null",
+            OpenParen => "(",
+            CloseParen => ")",
+            OpenBrace => "{",
+            CloseBrace => "}",
+            OpenBracket => "[",
+            CloseBracket => "]",
+            Semicolon => ";",
+            Comma => ",",
+            Dot => ".",
+            Colon => ":",
+            Question => "?",
+            Plus => "+",
+            Minus => "-",
+            Star => "*",
+            Slash => "/",
+            Percent => "%",
+            PlusPlus => "++",
+            MinusMinus => "--",
+            Eq => "=",
+            PlusEq => "+=",
+            MinusEq => "-=",
+            StarEq => "*=",
+            SlashEq => "/=",
+            PercentEq => "%=",
+            EqEq => "==",
+            Ne => "!=",
+            Lt => "<",
+            Le => "<=",
+            Gt => ">",
+            Ge => ">=",
+            AndAnd => "&&",
+            OrOr => "||",
+            Not => "!",
+            And => "&",
+            Or => "|",
+            Caret => "^",
+            Tilde => "~",
+            _ => return None
+        };
+
+        Some(s)
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TokenKind::Whitespace => "whitespace".fmt(f),
+            TokenKind::LineComment | TokenKind::BlockComment => "comment".fmt(f),
+            TokenKind::Ident(ref name) => write!(f, "identifier `{}`", name),
+            TokenKind::Keyword(kw) => write!(f, "keyword `{}`", kw.as_str()),
+            TokenKind::IntLiteral(_) => "integer literal".fmt(f),
+            TokenKind::StringLiteral(_) => "string literal".fmt(f),
+            TokenKind::CharLiteral(_) => "char literal".fmt(f),
+            TokenKind::Unknown => "unknown token".fmt(f),
+            TokenKind::Eof => "end of file".fmt(f),
+            ref kind => write!(f, "`{}`", kind.as_str().unwrap())
+        }
+    }
+}
+
+macro_rules! keywords {
+    ( $( $x:ident => $s:expr ),* ) => {
+        /// Reserved words
+        #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+        pub enum Keyword {
+            $( $x, )*
+        }
+
+        impl Keyword {
+            pub fn from_ident(s: &str) -> Option<Keyword> {
+                match s {
+                    $( $s => Some(Keyword::$x), )*
+                    _ => None
+                }
+            }
+
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $( Keyword::$x => $s, )*
+                }
+            }
+        }
+    }
+}
+
+keywords! {
+    Abstract => "abstract",
+    Base => "base",
+    Bool => "bool",
+    Break => "break",
+    Class => "class",
+    Continue => "continue",
+    Do => "do",
+    Else => "else",
+    For => "for",
+    Foreach => "foreach",
+    If => "if",
+    In => "in",
+    Int => "int",
+    Interface => "interface",
+    Internal => "internal",
+    Namespace => "namespace",
+    New => "new",
+    Override => "override",
+    Private => "private",
+    Protected => "protected",
+    Public => "public",
+    Return => "return",
+    Sealed => "sealed",
+    Static => "static",
+    String => "string",
+    This => "this",
+    Using => "using",
+    Virtual => "virtual",
+    Void => "void",
+    While => "while"
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LexErrorKind {
+    UnexpectedChar(char),
+    UnterminatedString,
+    UnterminatedChar,
+    UnterminatedComment,
+    EmptyChar,
+    CharTooLong,
+    InvalidEscape,
+    InvalidIntLiteral,
+    IntegerOverflow
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            LexErrorKind::UnexpectedChar(c) => write!(f, "unexpected character `{}`", c.escape_default()),
+            LexErrorKind::UnterminatedString => "unterminated string literal".fmt(f),
+            LexErrorKind::UnterminatedChar => "unterminated char literal".fmt(f),
+            LexErrorKind::UnterminatedComment => "unterminated block comment".fmt(f),
+            LexErrorKind::EmptyChar => "empty char literal".fmt(f),
+            LexErrorKind::CharTooLong => "too many characters in char literal".fmt(f),
+            LexErrorKind::InvalidEscape => "unrecognized escape sequence".fmt(f),
+            LexErrorKind::InvalidIntLiteral => "invalid integer literal".fmt(f),
+            LexErrorKind::IntegerOverflow => "integer literal is too large".fmt(f)
+        }
+    }
+}
+
+/// Tokenizes the whole input, returning every token (trivia included) followed by `Eof`
+pub fn tokenize(src: &str, file: FileId) -> (Vec<Token>, Vec<LexError>) {
+    let mut lexer = Lexer::new(src, file);
+    let mut tokens = Vec::new();
+    loop {
+        let token = lexer.next_token();
+        let is_eof = token.kind == TokenKind::Eof;
+        tokens.push(token);
+        if is_eof {
+            break;
+        }
+    }
+
+    (tokens, lexer.errors)
+}
+
+pub struct Lexer<'src> {
+    src: &'src str,
+    file: FileId,
+    pos: usize,
+    errors: Vec<LexError>
+}
+
+impl<'src> Lexer<'src> {
+    pub fn new(src: &'src str, file: FileId) -> Lexer<'src> {
+        Lexer {
+            src,
+            file,
+            pos: 0,
+            errors: Vec::new()
+        }
+    }
+
+    pub fn errors(&self) -> &[LexError] {
+        &self.errors
+    }
+
+    /// Returns the next token. Once the input is exhausted, keeps returning `Eof`
+    pub fn next_token(&mut self) -> Token {
+        let start = self.pos;
+        let kind = match self.bump() {
+            Some(c) => self.token_kind(c, start),
+            None => TokenKind::Eof
+        };
+
+        Token { kind, span: Span::new(self.file, start, self.pos) }
+    }
+
+    // Utility methods
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_while<F: Fn(char) -> bool>(&mut self, pred: F) {
+        while self.peek().is_some_and(&pred) {
+            self.bump();
+        }
+    }
+
+    fn error(&mut self, kind: LexErrorKind, start: usize) {
+        self.errors.push(LexError { kind, span: Span::new(self.file, start, self.pos) });
+    }
+
+    // Token classes
+    fn token_kind(&mut self, c: char, start: usize) -> TokenKind {
+        use self::TokenKind::*;
+        match c {
+            c if c.is_whitespace() => {
+                self.eat_while(char::is_whitespace);
+                Whitespace
+            }
+            '/' if self.eat('/') => {
+                self.eat_while(|c| c != '\n');
+                LineComment
+            }
+            '/' if self.eat('*') => self.block_comment(start),
+            c if is_ident_start(c) => self.ident_or_keyword(start),
+            '@' if self.peek() == Some('"') => {
+                self.bump();
+                self.verbatim_string(start)
+            }
+            '@' if self.peek().is_some_and(is_ident_start) => {
+                // Verbatim identifiers may have the same name as a keyword
+                self.eat_while(is_ident_continue);
+                Ident(self.src[start + 1..self.pos].to_string())
+            }
+            '0'..='9' => self.int_literal(c, start),
+            '"' => self.string(start),
+            '\'' => self.char_literal(start),
+            '(' => OpenParen,
+            ')' => CloseParen,
+            '{' => OpenBrace,
+            '}' => CloseBrace,
+            '[' => OpenBracket,
+            ']' => CloseBracket,
+            ';' => Semicolon,
+            ',' => Comma,
+            '.' => Dot,
+            ':' => Colon,
+            '?' => Question,
+            '~' => Tilde,
+            '^' => Caret,
+            '+' => if self.eat('+') { PlusPlus } else if self.eat('=') { PlusEq } else { Plus },
+            '-' => if self.eat('-') { MinusMinus } else if self.eat('=') { MinusEq } else { Minus },
+            '*' => if self.eat('=') { StarEq } else { Star },
+            '/' => if self.eat('=') { SlashEq } else { Slash },
+            '%' => if self.eat('=') { PercentEq } else { Percent },
+            '=' => if self.eat('=') { EqEq } else { Eq },
+            '!' => if self.eat('=') { Ne } else { Not },
+            '<' => if self.eat('=') { Le } else { Lt },
+            '>' => if self.eat('=') { Ge } else { Gt },
+            '&' => if self.eat('&') { AndAnd } else { And },
+            '|' => if self.eat('|') { OrOr } else { Or },
+            c => {
+                self.error(LexErrorKind::UnexpectedChar(c), start);
+                Unknown
+            }
+        }
+    }
+
+    fn block_comment(&mut self, start: usize) -> TokenKind {
+        loop {
+            match self.bump() {
+                Some('*') if self.eat('/') => break,
+                Some(_) => {}
+                None => {
+                    self.error(LexErrorKind::UnterminatedComment, start);
+                    break;
+                }
+            }
+        }
+
+        TokenKind::BlockComment
+    }
+
+    fn ident_or_keyword(&mut self, start: usize) -> TokenKind {
+        self.eat_while(is_ident_continue);
+        let text = &self.src[start..self.pos];
+        match text {
+            "true" => TokenKind::BoolLiteral(true),
+            "false" => TokenKind::BoolLiteral(false),
+            "null" => TokenKind::NullLiteral,
+            _ => match Keyword::from_ident(text) {
+                Some(kw) => TokenKind::Keyword(kw),
+                None => TokenKind::Ident(text.to_string())
+            }
+        }
+    }
+
+    fn int_literal(&mut self, first: char, start: usize) -> TokenKind {
+        let radix = if first == '0' && (self.eat('x') || self.eat('X')) {
+            16
+        } else if first == '0' && (self.eat('b') || self.eat('B')) {
+            2
+        } else {
+            10
+        };
+
+        let digits_start = if radix == 10 { start } else { self.pos };
+        self.eat_while(|c| c.is_digit(radix) || c == '_');
+        let digits: String = self.src[digits_start..self.pos].chars().filter(|&c| c != '_').collect();
+
+        // Letters glued to the number (e.g. `12abc`) are considered part of the literal
+        let digits_end = self.pos;
+        self.eat_while(is_ident_continue);
+        if digits_end != self.pos || digits.is_empty() {
+            self.error(LexErrorKind::InvalidIntLiteral, start);
+            return TokenKind::IntLiteral(0);
+        }
+
+        match i64::from_str_radix(&digits, radix) {
+            Ok(value) => TokenKind::IntLiteral(value),
+            Err(_) => {
+                self.error(LexErrorKind::IntegerOverflow, start);
+                TokenKind::IntLiteral(0)
+            }
+        }
+    }
+
+    fn string(&mut self, start: usize) -> TokenKind {
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.bump();
+                    break;
+                }
+                Some('\\') => {
+                    if let Some(c) = self.escape() {
+                        value.push(c);
+                    }
+                }
+                // Regular strings cannot span several lines
+                Some('\n') | None => {
+                    self.error(LexErrorKind::UnterminatedString, start);
+                    break;
+                }
+                Some(c) => {
+                    self.bump();
+                    value.push(c);
+                }
+            }
+        }
+
+        TokenKind::StringLiteral(value)
+    }
+
+    fn verbatim_string(&mut self, start: usize) -> TokenKind {
+        // Note: escape sequences are not interpreted, except for `""` which stands for `"`
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => {
+                    if self.eat('"') {
+                        value.push('"');
+                    } else {
+                        break;
+                    }
+                }
+                Some(c) => value.push(c),
+                None => {
+                    self.error(LexErrorKind::UnterminatedString, start);
+                    break;
+                }
+            }
+        }
+
+        TokenKind::StringLiteral(value)
+    }
+
+    fn char_literal(&mut self, start: usize) -> TokenKind {
+        let value = match self.peek() {
+            Some('\'') => {
+                self.bump();
+                self.error(LexErrorKind::EmptyChar, start);
+                return TokenKind::CharLiteral('\0');
+            }
+            Some('\\') => self.escape(),
+            Some('\n') | None => None,
+            Some(c) => {
+                self.bump();
+                Some(c)
+            }
+        };
+
+        if self.eat('\'') {
+            return TokenKind::CharLiteral(value.unwrap_or('\0'));
+        }
+
+        // Recover by skipping to the closing quote, as long as it is on the same line
+        let rest = &self.src[self.pos..];
+        match rest.find(['\'', '\n']) {
+            Some(i) if rest[i..].starts_with('\'') => {
+                self.pos += i + 1;
+                self.error(LexErrorKind::CharTooLong, start);
+            }
+            _ => self.error(LexErrorKind::UnterminatedChar, start)
+        }
+
+        TokenKind::CharLiteral(value.unwrap_or('\0'))
+    }
+
+    /// Lexes an escape sequence, starting at the backslash
+    ///
+    /// Returns `None` if the sequence is invalid (an error is reported in that case)
+    fn escape(&mut self) -> Option<char> {
+        let start = self.pos;
+        self.bump();
+        let c = match self.bump() {
+            Some('\'') => '\'',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('0') => '\0',
+            Some('a') => '\u{7}',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('v') => '\u{b}',
+            Some('x') => return self.hex_escape(start, 1, 4),
+            Some('u') => return self.hex_escape(start, 4, 4),
+            Some('U') => return self.hex_escape(start, 8, 8),
+            _ => {
+                self.error(LexErrorKind::InvalidEscape, start);
+                return None;
+            }
+        };
+
+        Some(c)
+    }
+
+    fn hex_escape(&mut self, start: usize, min_digits: usize, max_digits: usize) -> Option<char> {
+        let digits_start = self.pos;
+        while self.pos - digits_start < max_digits && self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+            self.bump();
+        }
+
+        let digits = &self.src[digits_start..self.pos];
+        let c = if digits.len() < min_digits {
+            None
+        } else {
+            u32::from_str_radix(digits, 16).ok().and_then(::std::char::from_u32)
+        };
+
+        if c.is_none() {
+            self.error(LexErrorKind::InvalidEscape, start);
+        }
+
+        c
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::TokenKind::*;
+
+    fn kinds(src: &str) -> Vec<TokenKind> {
+        let (tokens, errors) = tokenize(src, FileId(0));
+        assert_eq!(errors, vec![]);
+        tokens.into_iter().map(|t| t.kind).filter(|k| !k.is_trivia()).collect()
+    }
+
+    fn first_error(src: &str) -> LexError {
+        tokenize(src, FileId(0)).1.into_iter().next().expect("expected a lexer error")
+    }
+
+    #[test]
+    fn class_header() {
+        assert_eq!(kinds("public static class Foo { }"), vec![
+            Keyword(super::Keyword::Public),
+            Keyword(super::Keyword::Static),
+            Keyword(super::Keyword::Class),
+            Ident("Foo".to_string()),
+            OpenBrace,
+            CloseBrace,
+            Eof
+        ]);
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(kinds("a+=b++==c!=d<=e&&!f"), vec![
+            Ident("a".to_string()), PlusEq, Ident("b".to_string()), PlusPlus, EqEq, Ident("c".to_string()),
+            Ne, Ident("d".to_string()), Le, Ident("e".to_string()), AndAnd, Not, Ident("f".to_string()), Eof
+        ]);
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(kinds("42 0x2A 1_000 true null 'a' '\\n'"), vec![
+            IntLiteral(42), IntLiteral(42), IntLiteral(1000), BoolLiteral(true), NullLiteral,
+            CharLiteral('a'), CharLiteral('\n'), Eof
+        ]);
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(kinds(r#""tab\t quote\" \u0041\x42""#), vec![StringLiteral("tab\t quote\" AB".to_string()), Eof]);
+    }
+
+    #[test]
+    fn verbatim_strings_and_identifiers() {
+        assert_eq!(kinds("@\"C:\\dir \"\"x\"\"\n\" @class"), vec![
+            StringLiteral("C:\\dir \"x\"\n".to_string()),
+            Ident("class".to_string()),
+            Eof
+        ]);
+    }
+
+    #[test]
+    fn tokens_cover_the_source() {
+        let src = "class A { // comment\n  /* block */ int x = \"s\"; }";
+        let (tokens, _) = tokenize(src, FileId(0));
+        let text: String = tokens.iter().map(|t| &src[t.span.start..t.span.end]).collect();
+        assert_eq!(text, src);
+
+        let comment = tokens.iter().find(|t| t.kind == BlockComment).unwrap();
+        assert_eq!(&src[comment.span.start..comment.span.end], "/* block */");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(first_error("x = \"abc\n;").kind, LexErrorKind::UnterminatedString);
+        assert_eq!(first_error("99999999999999999999").kind, LexErrorKind::IntegerOverflow);
+        assert_eq!(first_error("\"\\q\"").span, Span::new(FileId(0), 1, 3));
+        assert_eq!(first_error("a # b").kind, LexErrorKind::UnexpectedChar('#'));
+        assert_eq!(first_error("/* never closed").kind, LexErrorKind::UnterminatedComment);
+    }
+}
@@ -0,0 +1,11 @@
+//! Everything related to reading C# source text
+pub mod cst;
+mod incremental;
+pub mod lexer;
+pub mod parser;
+mod source_map;
+mod span;
+
+pub use self::parser::{parse, parse_files};
+pub use self::source_map::{Location, SourceFile, SourceMap};
+pub use self::span::{FileId, Span};
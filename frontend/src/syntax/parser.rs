@@ -0,0 +1,1974 @@
+//! Recursive descent parser for the subset of C# that we support
+
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use ast::*;
+use diagnostics::{Code, Diagnostic, Diagnostics};
+use super::cst::{Checkpoint, GreenBuilder, GreenNode, SyntaxKind, SyntaxNode, SyntaxTree};
+use super::lexer::{self, Keyword, Token, TokenKind};
+use super::source_map::{SourceFile, SourceMap};
+use super::span::{FileId, Span};
+
+#[derive(Clone, Debug, PartialEq)]
+struct ParseError {
+    message: String,
+    span: Span
+}
+
+impl ParseError {
+    fn into_diagnostic(self) -> Diagnostic {
+        Diagnostic::error(Code::Syntax, self.message).with_primary(self.span, String::new())
+    }
+}
+
+type PResult<T> = Result<T, ParseError>;
+
+/// Parses a C# source file into a `Program`
+///
+/// Syntax errors are reported as diagnostics. The parser recovers from them, so the returned
+/// program contains everything that could be parsed, with `ErrorNode`s where recovery happened.
+///
+/// Besides the AST, the parser builds the lossless concrete syntax tree of each file (see
+/// `syntax::cst`), which is stored in `Program::syntax`. Both are built from the same tokens, but
+/// the AST is not derived from the tree.
+pub fn parse(name: &str, src: &str) -> (Program, Diagnostics) {
+    parse_files(vec![(name.to_string(), src.to_string())])
+}
+
+/// Parses several C# source files, given as `(name, source)` pairs, into a single `Program`
+///
+/// The items of all files end up in the same program, in the order in which the files were
+/// given. Each file gets its own `FileId` in the program's `SourceMap`.
+pub fn parse_files(files: Vec<(String, String)>) -> (Program, Diagnostics) {
+    let mut source_map = SourceMap::default();
+    let mut items = Vec::new();
+    let mut syntax = Vec::new();
+    let mut diagnostics = Diagnostics::default();
+    for (name, src) in files {
+        let file = source_map.add_file(name, src);
+        let (file_items, file_syntax) = parse_file(source_map.file(file));
+        items.extend(file_items);
+        diagnostics.extend(file_syntax.errors.iter().cloned());
+        syntax.push(file_syntax);
+    }
+
+    (Program { items, source_map, syntax }, diagnostics)
+}
+
+pub(super) fn parse_file(file: &SourceFile) -> (Vec<TopItem>, SyntaxTree) {
+    let mut parser = Parser::new(&file.src, file.id);
+    parser.builder.start_node(SyntaxKind::SourceFile);
+    let items = parser.parse_items();
+    parser.flush_remaining();
+    parser.builder.finish_node();
+
+    let root = SyntaxNode::new_root(parser.builder.finish(), file.id);
+    (items, SyntaxTree { root, errors: sorted(parser.diagnostics.iter().cloned().collect()) })
+}
+
+/// Sorts syntax errors by position, instead of reporting all errors of the lexer first
+///
+/// Note: the sort is stable, so errors at the same position keep the order in which they were reported
+pub(super) fn sorted(mut errors: Vec<Diagnostic>) -> Diagnostics {
+    errors.sort_by_key(|d| d.primary_span().map(|span| span.start));
+    let mut diagnostics = Diagnostics::default();
+    diagnostics.extend(errors);
+    diagnostics
+}
+
+/// Parses the method declaration that starts at the given offset, as a member of the given class
+///
+/// Returns the method, its green node and its syntax errors. Returns `None` if the tokens at the
+/// offset don't form a method, or if parsing the method reported errors after its end.
+pub(super) fn reparse_method(file: &SourceFile, class_name: &str, start: usize)
+    -> Option<(MethodDecl, Rc<GreenNode>, Diagnostics)> {
+    let mut parser = Parser::new(&file.src, file.id);
+    parser.pos = parser.token_at(start)?;
+    parser.raw_pos = parser.raw_indices[parser.pos];
+    parser.class_name = class_name.to_string();
+    let lex_errors: Vec<_> = parser.diagnostics.iter().cloned().collect();
+    parser.diagnostics = Diagnostics::default();
+
+    // Like in `parse_class_decl`, these end the class instead of starting a member
+    if *parser.peek() == TokenKind::Eof || *parser.peek() == TokenKind::CloseBrace || parser.at_class_start() {
+        return None;
+    }
+
+    let method = match parser.parse_class_item() {
+        Ok(ClassItem::MethodDecl(method)) => method,
+        _ => return None
+    };
+
+    // Errors after the method (e.g. a missing `}` before the next member) belong to the rest of the class
+    let inside = |d: &Diagnostic| d.primary_span().is_some_and(|span| start <= span.start && span.start < method.span.end);
+    if !parser.diagnostics.iter().all(inside) {
+        return None;
+    }
+
+    let errors = lex_errors.into_iter().filter(inside).chain(parser.diagnostics.iter().cloned()).collect();
+    Some((method, parser.builder.finish(), sorted(errors)))
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    /// All tokens of the source, including trivia and unknown tokens
+    raw_tokens: Vec<Token>,
+    /// The index in `raw_tokens` of the next token to be added to the syntax tree
+    raw_pos: usize,
+    /// The tokens of the source, without trivia. The last one is always `Eof`
+    tokens: Vec<Token>,
+    /// The index in `raw_tokens` of each token in `tokens`
+    raw_indices: Vec<usize>,
+    pos: usize,
+    builder: GreenBuilder,
+
+    /// The name of the current class, used to recognize constructors
+    class_name: String,
+
+    /// The number of namespaces enclosing the current position
+    namespace_depth: usize,
+
+    diagnostics: Diagnostics,
+    /// The position of the token at which the last error was reported
+    last_error: Option<usize>
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a str, file: FileId) -> Parser<'a> {
+        let (raw_tokens, lex_errors) = lexer::tokenize(src, file);
+
+        // Note: unknown tokens have already been reported by the lexer
+        let raw_indices: Vec<_> = raw_tokens.iter()
+            .enumerate()
+            .filter(|&(_, t)| !t.kind.is_trivia() && t.kind != TokenKind::Unknown)
+            .map(|(i, _)| i)
+            .collect();
+        let mut parser = Parser {
+            src,
+            tokens: raw_indices.iter().map(|&i| raw_tokens[i].clone()).collect(),
+            raw_tokens,
+            raw_pos: 0,
+            raw_indices,
+            pos: 0,
+            builder: GreenBuilder::default(),
+            class_name: String::new(),
+            namespace_depth: 0,
+            diagnostics: Diagnostics::default(),
+            last_error: None
+        };
+
+        for err in lex_errors {
+            let err = parser.error_at(err.span, err.to_string());
+            parser.diagnostics.push(err.into_diagnostic());
+        }
+
+        parser
+    }
+
+    // Utility methods
+    fn peek(&self) -> &TokenKind {
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&self, n: usize) -> &TokenKind {
+        let i = (self.pos + n).min(self.tokens.len() - 1);
+        &self.tokens[i].kind
+    }
+
+    fn span(&self) -> Span {
+        self.tokens[self.pos].span
+    }
+
+    /// Returns the position of the token that starts at the given offset, if there is one
+    fn token_at(&self, offset: usize) -> Option<usize> {
+        self.tokens.binary_search_by_key(&offset, |t| t.span.start).ok()
+    }
+
+    fn prev_span(&self) -> Span {
+        self.tokens[self.pos.saturating_sub(1)].span
+    }
+
+    /// Returns a span from the start of `start` to the end of the last consumed token
+    ///
+    /// Note: if no tokens have been consumed since `start`, the span is empty
+    fn span_from(&self, start: Span) -> Span {
+        let end = self.prev_span();
+        if end.end < start.start {
+            Span::new(start.file, start.start, start.start)
+        } else {
+            start.to(end)
+        }
+    }
+
+    fn bump(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.kind != TokenKind::Eof {
+            self.flush_trivia();
+            self.add_raw_token();
+            self.pos += 1;
+        }
+
+        token
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if self.peek() == kind {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, kw: Keyword) -> bool {
+        self.eat(&TokenKind::Keyword(kw))
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> PResult<Span> {
+        if *self.peek() == kind {
+            Ok(self.bump().span)
+        } else {
+            Err(self.unexpected(&kind.to_string()))
+        }
+    }
+
+    fn expect_keyword(&mut self, kw: Keyword) -> PResult<Span> {
+        self.expect(TokenKind::Keyword(kw))
+    }
+
+    fn expect_ident(&mut self) -> PResult<String> {
+        match *self.peek() {
+            TokenKind::Ident(_) => {}
+            _ => return Err(self.unexpected("identifier"))
+        }
+
+        match self.bump().kind {
+            TokenKind::Ident(name) => Ok(name),
+            _ => unreachable!()
+        }
+    }
+
+    fn error_at(&self, span: Span, message: String) -> ParseError {
+        ParseError { message, span }
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        self.error_at(self.span(), message)
+    }
+
+    fn unexpected(&self, expected: &str) -> ParseError {
+        self.error(format!("expected {}, found {}", expected, self.peek()))
+    }
+
+    /// Reports an error as a diagnostic, so parsing can continue
+    fn report(&mut self, err: ParseError) {
+        // Avoid a cascade of errors at the same token
+        if self.last_error == Some(self.pos) {
+            return;
+        }
+
+        self.last_error = Some(self.pos);
+        self.diagnostics.push(err.into_diagnostic());
+    }
+
+    // Syntax tree construction
+
+    fn add_raw_token(&mut self) {
+        let Token { ref kind, span } = self.raw_tokens[self.raw_pos];
+        self.builder.token(kind.clone(), &self.src[span.start..span.end]);
+        self.raw_pos += 1;
+    }
+
+    /// Adds the trivia (and unknown tokens) before the current token to the node being built
+    fn flush_trivia(&mut self) {
+        while self.raw_pos < self.raw_indices[self.pos] {
+            self.add_raw_token();
+        }
+    }
+
+    /// Adds all remaining tokens to the node being built, except for `Eof`
+    fn flush_remaining(&mut self) {
+        while self.raw_tokens[self.raw_pos].kind != TokenKind::Eof {
+            self.add_raw_token();
+        }
+    }
+
+    /// Starts a node at the current token
+    ///
+    /// Note: the trivia before the token belongs to the parent node
+    fn start(&mut self, kind: SyntaxKind) {
+        self.flush_trivia();
+        self.builder.start_node(kind);
+    }
+
+    fn finish(&mut self) {
+        self.builder.finish_node();
+    }
+
+    /// Returns a checkpoint at the current token, to start a node there once its kind is known
+    fn checkpoint(&mut self) -> Checkpoint {
+        self.flush_trivia();
+        self.builder.checkpoint()
+    }
+
+    /// Finishes the nodes that were interrupted by a syntax error, leaving `depth` nodes open
+    ///
+    /// Note: this must happen before recovery, so the skipped tokens end up outside those nodes
+    fn abandon_nodes(&mut self, depth: usize) {
+        self.builder.finish_to(depth);
+    }
+
+    /// Wraps everything since the checkpoint (e.g. an incomplete node and the tokens skipped
+    /// during recovery) in an `Error` node
+    fn finish_error(&mut self, checkpoint: Checkpoint) {
+        self.builder.start_node_at(checkpoint, SyntaxKind::Error);
+        self.builder.finish_node();
+    }
+
+    // Error recovery
+
+    /// Returns true if the current token starts a class member or a class
+    ///
+    /// Note: this is used to detect missing closing braces
+    fn at_member_start(&self) -> bool {
+        matches!(*self.peek(), TokenKind::Keyword(Keyword::Public)
+                               | TokenKind::Keyword(Keyword::Private)
+                               | TokenKind::Keyword(Keyword::Protected)
+                               | TokenKind::Keyword(Keyword::Internal)
+                               | TokenKind::Keyword(Keyword::Static)
+                               | TokenKind::Keyword(Keyword::Virtual)
+                               | TokenKind::Keyword(Keyword::Override)
+                               | TokenKind::Keyword(Keyword::Sealed)
+                               | TokenKind::Keyword(Keyword::Abstract)
+                               | TokenKind::Keyword(Keyword::Class)
+                               | TokenKind::Keyword(Keyword::Interface))
+    }
+
+    /// Returns true if the tokens at the current position start a class or an interface declaration
+    fn at_class_start(&self) -> bool {
+        let mut i = 0;
+        loop {
+            match *self.peek_nth(i) {
+                TokenKind::Keyword(Keyword::Class) | TokenKind::Keyword(Keyword::Interface) => return true,
+                TokenKind::Keyword(Keyword::Public)
+                | TokenKind::Keyword(Keyword::Private)
+                | TokenKind::Keyword(Keyword::Protected)
+                | TokenKind::Keyword(Keyword::Internal)
+                | TokenKind::Keyword(Keyword::Static)
+                | TokenKind::Keyword(Keyword::Sealed)
+                | TokenKind::Keyword(Keyword::Abstract) => i += 1,
+                _ => return false
+            }
+        }
+    }
+
+    /// Skips tokens until the start of the next class, namespace or using directive
+    ///
+    /// When inside a namespace block, it also stops before the `}` closing the namespace
+    fn recover_top_item(&mut self, in_block: bool) {
+        let mut depth = 0;
+        loop {
+            match *self.peek() {
+                TokenKind::Eof => return,
+                TokenKind::OpenBrace => depth += 1,
+                TokenKind::CloseBrace if depth > 0 => depth -= 1,
+                TokenKind::CloseBrace if in_block => return,
+                TokenKind::Keyword(Keyword::Namespace) | TokenKind::Keyword(Keyword::Using) if depth == 0 => return,
+                _ if depth == 0 && self.at_class_start() => return,
+                _ => {}
+            }
+
+            self.bump();
+        }
+    }
+
+    /// Skips tokens until the end of the current class member
+    ///
+    /// Stops after a `;` or a block (e.g. a method body), or before a `}` closing the class
+    /// or something that looks like the start of the next member
+    fn recover_member(&mut self) {
+        let mut depth = 0;
+        loop {
+            match *self.peek() {
+                TokenKind::Eof => return,
+                TokenKind::OpenBrace => depth += 1,
+                TokenKind::CloseBrace if depth == 0 => return,
+                TokenKind::CloseBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.bump();
+                        return;
+                    }
+                }
+                TokenKind::Semicolon if depth == 0 => {
+                    self.bump();
+                    return;
+                }
+                _ if depth == 0 && self.at_member_start() => return,
+                _ => {}
+            }
+
+            self.bump();
+        }
+    }
+
+    /// Skips tokens until the end of the current statement
+    ///
+    /// Stops after a `;` or a block (including its `else` block, if any), or before a `}` closing
+    /// the enclosing block or something that looks like the start of a class member
+    fn recover_statement(&mut self) {
+        let mut depth = 0;
+        loop {
+            match *self.peek() {
+                TokenKind::Eof => return,
+                TokenKind::OpenBrace => depth += 1,
+                TokenKind::CloseBrace if depth == 0 => return,
+                TokenKind::CloseBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.bump();
+                        if !self.eat_keyword(Keyword::Else) {
+                            return;
+                        }
+                        continue;
+                    }
+                }
+                TokenKind::Semicolon if depth == 0 => {
+                    self.bump();
+                    return;
+                }
+                _ if depth == 0 && self.at_member_start() => return,
+                _ => {}
+            }
+
+            self.bump();
+        }
+    }
+
+    // Top level items
+    fn parse_items(&mut self) -> Vec<TopItem> {
+        self.parse_namespace_items(false)
+    }
+
+    /// Parses the items of a file or a namespace, up to the end of the file or the `}` closing the namespace block
+    fn parse_namespace_items(&mut self, in_block: bool) -> Vec<TopItem> {
+        let mut items = Vec::new();
+        loop {
+            match *self.peek() {
+                TokenKind::Eof => {
+                    if in_block {
+                        let err = self.unexpected("`}`");
+                        self.report(err);
+                    }
+
+                    return items;
+                }
+                TokenKind::CloseBrace if in_block => {
+                    self.bump();
+                    return items;
+                }
+                _ => {}
+            }
+
+            let before = self.pos;
+            let depth = self.builder.depth();
+            let checkpoint = self.checkpoint();
+            match self.parse_top_item(&items) {
+                Ok(item) => items.push(item),
+                Err(err) => {
+                    self.report(err);
+                    self.abandon_nodes(depth);
+                    if self.pos == before {
+                        self.bump();
+                    }
+                    self.recover_top_item(in_block);
+                    self.finish_error(checkpoint);
+                }
+            }
+        }
+    }
+
+    /// Parses a class, namespace or using directive, given the items that precede it in the same namespace
+    fn parse_top_item(&mut self, previous: &[TopItem]) -> PResult<TopItem> {
+        let only_usings_before = previous.iter().all(|item| matches!(*item, TopItem::UsingDirective(_)));
+        match *self.peek() {
+            TokenKind::Keyword(Keyword::Using) => {
+                if !only_usings_before {
+                    return Err(self.error("using directives must come before the classes and namespaces".to_string()));
+                }
+
+                self.parse_using_directive().map(TopItem::UsingDirective)
+            }
+            TokenKind::Keyword(Keyword::Namespace) => {
+                self.parse_namespace_decl(only_usings_before).map(TopItem::NamespaceDecl)
+            }
+            _ => self.parse_type_decl()
+        }
+    }
+
+    /// Parses a class or an interface, which start with the same modifiers
+    fn parse_type_decl(&mut self) -> PResult<TopItem> {
+        let start = self.span();
+        let checkpoint = self.checkpoint();
+        let modifiers = self.parse_modifiers()?;
+        if *self.peek() == TokenKind::Keyword(Keyword::Interface) {
+            self.builder.start_node_at(checkpoint, SyntaxKind::InterfaceDecl);
+            self.parse_interface_decl(start, modifiers).map(TopItem::InterfaceDecl)
+        } else {
+            self.builder.start_node_at(checkpoint, SyntaxKind::ClassDecl);
+            self.parse_class_decl(start, modifiers).map(TopItem::ClassDecl)
+        }
+    }
+
+    fn parse_namespace_decl(&mut self, only_usings_before: bool) -> PResult<NamespaceDecl> {
+        let start = self.span();
+        self.start(SyntaxKind::NamespaceDecl);
+        self.expect_keyword(Keyword::Namespace)?;
+        let name = self.parse_qualified_name()?;
+
+        // A file-scoped namespace contains the rest of the file
+        let is_file_scoped = self.eat(&TokenKind::Semicolon);
+        if is_file_scoped && (self.namespace_depth > 0 || !only_usings_before) {
+            let message = "file-scoped namespaces must come before the classes and namespaces of the file".to_string();
+            return Err(self.error_at(self.span_from(start), message));
+        }
+        if !is_file_scoped {
+            self.expect(TokenKind::OpenBrace)?;
+        }
+
+        self.namespace_depth += 1;
+        let items = self.parse_namespace_items(!is_file_scoped);
+        self.namespace_depth -= 1;
+        self.finish();
+
+        Ok(NamespaceDecl {
+            label: fresh_label(),
+            span: self.span_from(start),
+            name,
+            items,
+            is_file_scoped
+        })
+    }
+
+    fn parse_using_directive(&mut self) -> PResult<UsingDirective> {
+        let start = self.span();
+        self.start(SyntaxKind::UsingDirective);
+        self.expect_keyword(Keyword::Using)?;
+        let mut name = self.parse_qualified_name()?;
+
+        // Using alias directive
+        let mut alias = None;
+        if self.eat(&TokenKind::Eq) {
+            if name.contains('.') {
+                return Err(self.error_at(self.span_from(start), "the alias of a using directive must be a simple name".to_string()));
+            }
+
+            alias = Some(name);
+            name = self.parse_qualified_name()?;
+        }
+        self.expect(TokenKind::Semicolon)?;
+        self.finish();
+
+        Ok(UsingDirective {
+            label: fresh_label(),
+            span: self.span_from(start),
+            alias,
+            name
+        })
+    }
+
+    /// Parses a possibly qualified name, like `A.B.C`
+    fn parse_qualified_name(&mut self) -> PResult<String> {
+        let mut name = self.expect_ident()?;
+        while self.eat(&TokenKind::Dot) {
+            name.push('.');
+            name.push_str(&self.expect_ident()?);
+        }
+
+        Ok(name)
+    }
+
+    fn parse_modifiers(&mut self) -> PResult<Modifiers> {
+        let mut modifiers = Modifiers::default();
+        loop {
+            let kw = match *self.peek() {
+                TokenKind::Keyword(kw @ Keyword::Public)
+                | TokenKind::Keyword(kw @ Keyword::Private)
+                | TokenKind::Keyword(kw @ Keyword::Protected)
+                | TokenKind::Keyword(kw @ Keyword::Internal)
+                | TokenKind::Keyword(kw @ Keyword::Static)
+                | TokenKind::Keyword(kw @ Keyword::Virtual)
+                | TokenKind::Keyword(kw @ Keyword::Override)
+                | TokenKind::Keyword(kw @ Keyword::Sealed)
+                | TokenKind::Keyword(kw @ Keyword::Abstract)
+                | TokenKind::Keyword(kw @ Keyword::New) => kw,
+                _ => return Ok(modifiers)
+            };
+
+            if !modifiers.seen.insert(kw) {
+                return Err(self.error(format!("duplicate `{}` modifier", kw.as_str())));
+            }
+
+            // Note: access modifiers are only kept for printing, since everything is public in our implementation
+            let access = match kw {
+                Keyword::Public => Some(Access::Public),
+                Keyword::Private => Some(Access::Private),
+                Keyword::Protected => Some(Access::Protected),
+                Keyword::Internal => Some(Access::Internal),
+                _ => None
+            };
+            if let Some(access) = access {
+                modifiers.access = match (modifiers.access, access) {
+                    (None, access) => Some(access),
+                    (Some(Access::Protected), Access::Internal) | (Some(Access::Internal), Access::Protected) => Some(Access::ProtectedInternal),
+                    (Some(Access::Private), Access::Protected) | (Some(Access::Protected), Access::Private) => Some(Access::PrivateProtected),
+                    _ => return Err(self.error("more than one access modifier".to_string()))
+                };
+            }
+
+            self.bump();
+        }
+    }
+
+    /// Reports the first of the given modifiers that was used on a declaration of the given kind
+    fn reject_modifiers(&self, modifiers: &Modifiers, keywords: &[Keyword], kind: &str, start: Span) -> PResult<()> {
+        match keywords.iter().find(|kw| modifiers.seen.contains(kw)) {
+            Some(kw) => Err(self.error_at(start, format!("the `{}` modifier is not valid on {}", kw.as_str(), kind))),
+            None => Ok(())
+        }
+    }
+
+    /// Returns how the method with the given modifiers is bound, rejecting invalid combinations
+    ///
+    /// Note: abstract methods are implicitly virtual
+    fn method_virtuality(&self, modifiers: &Modifiers, start: Span) -> PResult<Virtuality> {
+        let has = |kw| modifiers.seen.contains(&kw);
+        if has(Keyword::Abstract) {
+            if let Some(kw) = [Keyword::Static, Keyword::Virtual, Keyword::Sealed].iter().find(|&&kw| has(kw)) {
+                return Err(self.error_at(start, format!("abstract methods cannot be `{}`", kw.as_str())));
+            }
+        }
+
+        let virtuality = match (has(Keyword::Virtual), has(Keyword::Override), has(Keyword::Sealed)) {
+            (false, false, false) => Virtuality::NonVirtual,
+            (true, false, false) => Virtuality::Virtual,
+            (false, true, false) => Virtuality::Override,
+            (false, true, true) => Virtuality::SealedOverride,
+            (true, true, _) => return Err(self.error_at(start, "a method cannot be both `virtual` and `override`".to_string())),
+            (_, false, true) => return Err(self.error_at(start, "only `override` methods can be `sealed`".to_string()))
+        };
+
+        if virtuality != Virtuality::NonVirtual && has(Keyword::Static) {
+            return Err(self.error_at(start, format!("static methods cannot be `{}`", virtuality)));
+        }
+        if virtuality.is_override() && has(Keyword::New) {
+            return Err(self.error_at(start, "a method cannot be both `new` and `override`".to_string()));
+        }
+
+        if has(Keyword::Abstract) && virtuality == Virtuality::NonVirtual {
+            return Ok(Virtuality::Virtual);
+        }
+        Ok(virtuality)
+    }
+
+    /// Parses the rest of a class, given its modifiers
+    fn parse_class_decl(&mut self, start: Span, modifiers: Modifiers) -> PResult<ClassDecl> {
+        self.reject_modifiers(&modifiers, &[Keyword::Virtual, Keyword::Override, Keyword::New], "classes", start)?;
+        let is_abstract = modifiers.seen.contains(&Keyword::Abstract);
+        let is_sealed = modifiers.seen.contains(&Keyword::Sealed);
+        if is_abstract && is_sealed {
+            return Err(self.error_at(start, "a class cannot be both `abstract` and `sealed`".to_string()));
+        }
+        self.expect_keyword(Keyword::Class)?;
+        let name = self.expect_ident()?;
+        let bases = self.parse_base_list()?;
+        self.expect(TokenKind::OpenBrace)?;
+
+        self.class_name = name.clone();
+
+        let mut items = Vec::new();
+        while !self.eat(&TokenKind::CloseBrace) {
+            if *self.peek() == TokenKind::Eof || self.at_class_start() {
+                // Keep the members that were parsed
+                let err = self.unexpected("`}`");
+                self.report(err);
+                break;
+            }
+
+            let before = self.pos;
+            let depth = self.builder.depth();
+            let checkpoint = self.checkpoint();
+            match self.parse_class_item() {
+                Ok(item) => items.push(item),
+                Err(err) => {
+                    self.report(err);
+                    self.abandon_nodes(depth);
+                    self.recover_member();
+                    if self.pos == before && *self.peek() != TokenKind::CloseBrace {
+                        self.bump();
+                    }
+                    self.finish_error(checkpoint);
+                }
+            }
+        }
+        self.finish();
+
+        Ok(ClassDecl {
+            label: fresh_label(),
+            span: self.span_from(start),
+            access: modifiers.access,
+            name,
+            bases,
+            items,
+            is_sealed,
+            is_abstract
+        })
+    }
+
+    /// Parses the base class and interfaces in the head of a class or an interface (e.g. `: A, IFoo`),
+    /// if there are any
+    fn parse_base_list(&mut self) -> PResult<Vec<BaseType>> {
+        let mut bases = Vec::new();
+        if *self.peek() != TokenKind::Colon {
+            return Ok(bases);
+        }
+
+        self.start(SyntaxKind::BaseList);
+        self.bump();
+        loop {
+            let start = self.span();
+            self.start(SyntaxKind::Type);
+            let name = self.parse_qualified_name()?;
+            self.finish();
+            bases.push(BaseType { span: self.span_from(start), name });
+
+            if !self.eat(&TokenKind::Comma) {
+                self.finish();
+                return Ok(bases);
+            }
+        }
+    }
+
+    /// Parses the rest of an interface, given its modifiers
+    fn parse_interface_decl(&mut self, start: Span, modifiers: Modifiers) -> PResult<InterfaceDecl> {
+        self.reject_modifiers(&modifiers, &[Keyword::Static, Keyword::Virtual, Keyword::Override, Keyword::Sealed, Keyword::Abstract, Keyword::New],
+                              "interfaces", start)?;
+        self.expect_keyword(Keyword::Interface)?;
+        let name = self.expect_ident()?;
+        let bases = self.parse_base_list()?;
+        self.expect(TokenKind::OpenBrace)?;
+
+        let mut methods = Vec::new();
+        while !self.eat(&TokenKind::CloseBrace) {
+            if *self.peek() == TokenKind::Eof || self.at_class_start() {
+                let err = self.unexpected("`}`");
+                self.report(err);
+                break;
+            }
+
+            let before = self.pos;
+            let depth = self.builder.depth();
+            let checkpoint = self.checkpoint();
+            match self.parse_interface_method(&name) {
+                Ok(method) => methods.push(method),
+                Err(err) => {
+                    self.report(err);
+                    self.abandon_nodes(depth);
+                    self.recover_member();
+                    if self.pos == before && *self.peek() != TokenKind::CloseBrace {
+                        self.bump();
+                    }
+                    self.finish_error(checkpoint);
+                }
+            }
+        }
+        self.finish();
+
+        Ok(InterfaceDecl {
+            label: fresh_label(),
+            span: self.span_from(start),
+            access: modifiers.access,
+            name,
+            bases,
+            methods
+        })
+    }
+
+    /// Parses a member of the interface with the given name, which must be a method without a body
+    fn parse_interface_method(&mut self, interface_name: &str) -> PResult<MethodDecl> {
+        let start = self.span();
+        let checkpoint = self.checkpoint();
+        let modifiers = self.parse_modifiers()?;
+        self.reject_modifiers(&modifiers, &[Keyword::Static, Keyword::Virtual, Keyword::Override, Keyword::Sealed, Keyword::Abstract, Keyword::New],
+                              "interface members", start)?;
+
+        if let TokenKind::Ident(ref name) = *self.peek() {
+            if name == interface_name && *self.peek_nth(1) == TokenKind::OpenParen {
+                return Err(self.error("interfaces cannot contain constructors".to_string()));
+            }
+        }
+
+        let return_ty = self.parse_return_type()?;
+        let name = self.expect_ident()?;
+        if *self.peek() != TokenKind::OpenParen {
+            return Err(self.error_at(self.span_from(start), "interfaces cannot contain fields".to_string()));
+        }
+
+        self.builder.start_node_at(checkpoint, SyntaxKind::MethodDecl);
+        let params = self.parse_params()?;
+        if *self.peek() == TokenKind::OpenBrace {
+            return Err(self.error("interface methods cannot have a body".to_string()));
+        }
+        self.expect(TokenKind::Semicolon)?;
+        self.finish();
+
+        Ok(MethodDecl {
+            label: fresh_label(),
+            span: self.span_from(start),
+            access: modifiers.access,
+            name,
+            params,
+            body: None,
+            is_static: false,
+            virtuality: Virtuality::NonVirtual,
+            is_new: false,
+            is_abstract: false,
+            return_ty
+        })
+    }
+
+    fn parse_class_item(&mut self) -> PResult<ClassItem> {
+        let start = self.span();
+        let checkpoint = self.checkpoint();
+        let modifiers = self.parse_modifiers()?;
+        let is_static = modifiers.seen.contains(&Keyword::Static);
+
+        if let TokenKind::Ident(ref name) = *self.peek() {
+            if *name == self.class_name && *self.peek_nth(1) == TokenKind::OpenParen {
+                self.reject_modifiers(&modifiers, &[Keyword::Virtual, Keyword::Override, Keyword::Sealed, Keyword::Abstract, Keyword::New],
+                                      "constructors", start)?;
+                if is_static && modifiers.access.is_some() {
+                    return Err(self.error_at(start, "access modifiers are not allowed on static constructors".to_string()));
+                }
+
+                self.builder.start_node_at(checkpoint, SyntaxKind::ConstructorDecl);
+                let name = self.expect_ident()?;
+                let params = self.parse_params()?;
+                if is_static && !params.is_empty() {
+                    return Err(self.error_at(params[0].span, "static constructors cannot have parameters".to_string()));
+                }
+
+                let initializer = if *self.peek() == TokenKind::Colon {
+                    if is_static {
+                        return Err(self.error("static constructors cannot call other constructors".to_string()));
+                    }
+                    Some(self.parse_constructor_initializer()?)
+                } else {
+                    None
+                };
+                let body = self.parse_block()?;
+                self.finish();
+                return Ok(ClassItem::ConstructorDecl(ConstructorDecl {
+                    label: fresh_label(),
+                    span: self.span_from(start),
+                    access: modifiers.access,
+                    name,
+                    params,
+                    initializer,
+                    body,
+                    is_static
+                }));
+            }
+        }
+
+        let ty = self.parse_return_type()?;
+        let name = self.expect_ident()?;
+
+        if *self.peek() == TokenKind::OpenParen {
+            let virtuality = self.method_virtuality(&modifiers, start)?;
+            self.builder.start_node_at(checkpoint, SyntaxKind::MethodDecl);
+            let params = self.parse_params()?;
+            let is_abstract = modifiers.seen.contains(&Keyword::Abstract);
+            let body = if is_abstract {
+                if *self.peek() == TokenKind::OpenBrace {
+                    return Err(self.error("abstract methods cannot have a body".to_string()));
+                }
+                self.expect(TokenKind::Semicolon)?;
+                None
+            } else {
+                Some(self.parse_block()?)
+            };
+            self.finish();
+            return Ok(ClassItem::MethodDecl(MethodDecl {
+                label: fresh_label(),
+                span: self.span_from(start),
+                access: modifiers.access,
+                name,
+                params,
+                body,
+                is_static,
+                virtuality,
+                is_new: modifiers.seen.contains(&Keyword::New),
+                is_abstract,
+                return_ty: ty
+            }));
+        }
+
+        if let Type::Void = ty {
+            return Err(self.unexpected("`(`"));
+        }
+
+        self.reject_modifiers(&modifiers, &[Keyword::Virtual, Keyword::Override, Keyword::Sealed, Keyword::Abstract, Keyword::New], "fields", start)?;
+        self.builder.start_node_at(checkpoint, SyntaxKind::FieldDecl);
+        let assignment = if self.eat(&TokenKind::Eq) {
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+        self.expect(TokenKind::Semicolon)?;
+        self.finish();
+
+        Ok(ClassItem::FieldDecl(FieldDecl {
+            label: fresh_label(),
+            span: self.span_from(start),
+            access: modifiers.access,
+            name,
+            ty,
+            assignment,
+            is_static
+        }))
+    }
+
+    /// Parses the call to another constructor in the head of a constructor (e.g. `: this(x, 0)`
+    /// or `: base(x)`)
+    fn parse_constructor_initializer(&mut self) -> PResult<ConstructorCall> {
+        self.start(SyntaxKind::ConstructorInitializer);
+        self.expect(TokenKind::Colon)?;
+        let start = self.span();
+        let is_base = match *self.peek() {
+            TokenKind::Keyword(Keyword::This) => false,
+            TokenKind::Keyword(Keyword::Base) => true,
+            _ => return Err(self.unexpected("`this` or `base`"))
+        };
+        self.bump();
+        let args = self.parse_args()?;
+        self.finish();
+
+        Ok(ConstructorCall { label: fresh_label(), span: self.span_from(start), args, is_base })
+    }
+
+    fn parse_params(&mut self) -> PResult<Vec<VarDecl>> {
+        self.start(SyntaxKind::ParamList);
+        self.expect(TokenKind::OpenParen)?;
+        let mut params = Vec::new();
+        if self.eat(&TokenKind::CloseParen) {
+            self.finish();
+            return Ok(params);
+        }
+
+        loop {
+            let start = self.span();
+            self.start(SyntaxKind::Param);
+            let ty = self.parse_type()?;
+            let var_name = self.expect_ident()?;
+            self.finish();
+            params.push(VarDecl { label: fresh_label(), span: self.span_from(start), var_name, ty, expr: None });
+
+            if !self.eat(&TokenKind::Comma) {
+                self.expect(TokenKind::CloseParen)?;
+                self.finish();
+                return Ok(params);
+            }
+        }
+    }
+
+    // Types
+    fn parse_return_type(&mut self) -> PResult<Type> {
+        if *self.peek() == TokenKind::Keyword(Keyword::Void) {
+            self.start(SyntaxKind::Type);
+            self.bump();
+            self.finish();
+            Ok(Type::Void)
+        } else {
+            self.parse_type()
+        }
+    }
+
+    fn parse_type(&mut self) -> PResult<Type> {
+        self.start(SyntaxKind::Type);
+        let mut ty = self.parse_type_name()?;
+        while *self.peek() == TokenKind::OpenBracket {
+            self.bump();
+            self.expect(TokenKind::CloseBracket)?;
+            ty = Type::Array(Box::new(ty));
+        }
+        self.finish();
+
+        Ok(ty)
+    }
+
+    /// Returns true if the tokens at the current position start a variable declaration
+    fn at_var_decl(&self) -> bool {
+        match *self.peek() {
+            TokenKind::Keyword(Keyword::Int)
+            | TokenKind::Keyword(Keyword::Bool)
+            | TokenKind::Keyword(Keyword::String) => true,
+            // `Foo x`, `A.B.Foo x` or `Foo[] x`
+            TokenKind::Ident(_) => {
+                // Skip the rest of a qualified name
+                let mut i = 1;
+                while *self.peek_nth(i) == TokenKind::Dot && matches!(*self.peek_nth(i + 1), TokenKind::Ident(_)) {
+                    i += 2;
+                }
+
+                match *self.peek_nth(i) {
+                    TokenKind::Ident(_) => true,
+                    TokenKind::OpenBracket => *self.peek_nth(i + 1) == TokenKind::CloseBracket,
+                    _ => false
+                }
+            }
+            _ => false
+        }
+    }
+
+    // Statements
+    fn parse_block(&mut self) -> PResult<Vec<Statement>> {
+        self.start(SyntaxKind::Block);
+        self.expect(TokenKind::OpenBrace)?;
+        let mut statements = Vec::new();
+        while !self.eat(&TokenKind::CloseBrace) {
+            if *self.peek() == TokenKind::Eof || self.at_member_start() {
+                // Keep the statements that were parsed
+                let err = self.unexpected("`}`");
+                self.report(err);
+                break;
+            }
+
+            statements.push(self.parse_statement_or_recover());
+        }
+        self.finish();
+
+        Ok(statements)
+    }
+
+    /// Parses a statement. In case of a syntax error, the error is reported and the statement
+    /// replaced by an `ErrorNode`
+    fn parse_statement_or_recover(&mut self) -> Statement {
+        let start = self.span();
+        let before = self.pos;
+        let depth = self.builder.depth();
+        let checkpoint = self.checkpoint();
+        match self.parse_statement() {
+            Ok(statement) => statement,
+            Err(err) => {
+                self.report(err);
+                self.abandon_nodes(depth);
+                self.recover_statement();
+                if self.pos == before && *self.peek() != TokenKind::CloseBrace {
+                    self.bump();
+                }
+                self.finish_error(checkpoint);
+
+                Statement::Error(ErrorNode { label: fresh_label(), span: self.span_from(start) })
+            }
+        }
+    }
+
+    /// Parses the body of an `if`, an `else` or a loop, which may be a single statement without braces
+    fn parse_embedded_statement(&mut self) -> PResult<Vec<Statement>> {
+        if *self.peek() == TokenKind::OpenBrace {
+            self.parse_block()
+        } else if self.at_var_decl() {
+            Err(self.error("a variable declaration cannot be used as an embedded statement".to_string()))
+        } else {
+            Ok(vec![self.parse_statement_or_recover()])
+        }
+    }
+
+    fn parse_statement(&mut self) -> PResult<Statement> {
+        let start = self.span();
+        match *self.peek() {
+            TokenKind::OpenBrace => {
+                Err(self.error("nested blocks are not supported".to_string()))
+            }
+            TokenKind::Keyword(Keyword::If) => {
+                self.start(SyntaxKind::IfThenElse);
+                self.bump();
+                self.expect(TokenKind::OpenParen)?;
+                let condition = self.parse_expression()?;
+                self.expect(TokenKind::CloseParen)?;
+                let then = self.parse_embedded_statement()?;
+                let else_ = if self.eat_keyword(Keyword::Else) {
+                    self.parse_embedded_statement()?
+                } else {
+                    Vec::new()
+                };
+                self.finish();
+
+                Ok(Statement::IfThenElse(IfThenElse { label: fresh_label(), span: self.span_from(start), condition, then, else_ }))
+            }
+            TokenKind::Keyword(Keyword::While) => {
+                self.start(SyntaxKind::While);
+                self.bump();
+                self.expect(TokenKind::OpenParen)?;
+                let condition = self.parse_expression()?;
+                self.expect(TokenKind::CloseParen)?;
+                let body = self.parse_embedded_statement()?;
+                self.finish();
+
+                Ok(Statement::While(While { label: fresh_label(), span: self.span_from(start), condition, body }))
+            }
+            TokenKind::Keyword(Keyword::Do) => {
+                self.start(SyntaxKind::DoWhile);
+                self.bump();
+                let body = self.parse_embedded_statement()?;
+                self.expect_keyword(Keyword::While)?;
+                self.expect(TokenKind::OpenParen)?;
+                let condition = self.parse_expression()?;
+                self.expect(TokenKind::CloseParen)?;
+                self.expect(TokenKind::Semicolon)?;
+                self.finish();
+
+                Ok(Statement::DoWhile(DoWhile { label: fresh_label(), span: self.span_from(start), body, condition }))
+            }
+            TokenKind::Keyword(Keyword::For) => {
+                self.start(SyntaxKind::For);
+                self.bump();
+                self.expect(TokenKind::OpenParen)?;
+                let init = if self.eat(&TokenKind::Semicolon) {
+                    None
+                } else {
+                    Some(Box::new(self.parse_simple_statement(true)?))
+                };
+                let condition = if *self.peek() == TokenKind::Semicolon {
+                    None
+                } else {
+                    Some(self.parse_expression()?)
+                };
+                self.expect(TokenKind::Semicolon)?;
+                let step = if *self.peek() == TokenKind::CloseParen {
+                    None
+                } else if self.at_var_decl() {
+                    return Err(self.error("the step of a `for` loop cannot declare variables".to_string()));
+                } else {
+                    Some(Box::new(self.parse_simple_statement(false)?))
+                };
+                self.expect(TokenKind::CloseParen)?;
+                let body = self.parse_embedded_statement()?;
+                self.finish();
+
+                Ok(Statement::For(For { label: fresh_label(), span: self.span_from(start), init, condition, step, body }))
+            }
+            TokenKind::Keyword(Keyword::Foreach) => {
+                self.start(SyntaxKind::Foreach);
+                self.bump();
+                self.expect(TokenKind::OpenParen)?;
+                let var_start = self.span();
+                self.start(SyntaxKind::VarDecl);
+                let ty = self.parse_type()?;
+                let var_name = self.expect_ident()?;
+                self.finish();
+                let var = VarDecl { label: fresh_label(), span: self.span_from(var_start), var_name, ty, expr: None };
+                self.expect_keyword(Keyword::In)?;
+                let collection = self.parse_expression()?;
+                self.expect(TokenKind::CloseParen)?;
+                let body = self.parse_embedded_statement()?;
+                self.finish();
+
+                Ok(Statement::Foreach(Foreach { label: fresh_label(), span: self.span_from(start), var, collection, body }))
+            }
+            TokenKind::Keyword(Keyword::Break) => {
+                self.start(SyntaxKind::Break);
+                self.bump();
+                self.expect(TokenKind::Semicolon)?;
+                self.finish();
+
+                Ok(Statement::Break(Break { label: fresh_label(), span: self.span_from(start) }))
+            }
+            TokenKind::Keyword(Keyword::Continue) => {
+                self.start(SyntaxKind::Continue);
+                self.bump();
+                self.expect(TokenKind::Semicolon)?;
+                self.finish();
+
+                Ok(Statement::Continue(Continue { label: fresh_label(), span: self.span_from(start) }))
+            }
+            TokenKind::Keyword(Keyword::Return) => {
+                self.start(SyntaxKind::Return);
+                self.bump();
+                let expr = if *self.peek() == TokenKind::Semicolon {
+                    None
+                } else {
+                    Some(self.parse_expression()?)
+                };
+                self.expect(TokenKind::Semicolon)?;
+                self.finish();
+
+                Ok(Statement::Return(Return { label: fresh_label(), span: self.span_from(start), expr }))
+            }
+            _ => self.parse_simple_statement(true)
+        }
+    }
+
+    /// Parses a variable declaration, an assignment or an expression used as a statement
+    ///
+    /// Only terminated statements end with a `;` (the step of a `for` loop is not terminated)
+    fn parse_simple_statement(&mut self, terminated: bool) -> PResult<Statement> {
+        let start = self.span();
+        match *self.peek() {
+            _ if self.at_var_decl() => {
+                self.start(SyntaxKind::VarDecl);
+                let ty = self.parse_type()?;
+                let var_name = self.expect_ident()?;
+                let expr = if self.eat(&TokenKind::Eq) {
+                    Some(self.parse_expression()?)
+                } else {
+                    None
+                };
+                if terminated {
+                    self.expect(TokenKind::Semicolon)?;
+                }
+                self.finish();
+
+                Ok(Statement::VarDecl(VarDecl { label: fresh_label(), span: self.span_from(start), var_name, ty, expr }))
+            }
+            _ => {
+                let checkpoint = self.checkpoint();
+                let expr = self.parse_expression()?;
+                if let Some(operator) = assignment_operator(self.peek()) {
+                    self.builder.start_node_at(checkpoint, SyntaxKind::Assign);
+                    if !expr.is_lvalue() {
+                        return Err(self.error_at(expr.span(), "only variables, fields and array elements can be assigned to".to_string()));
+                    }
+                    self.bump();
+                    let value = self.parse_expression()?;
+                    if terminated {
+                        self.expect(TokenKind::Semicolon)?;
+                    }
+                    self.finish();
+
+                    return Ok(Statement::Assign(Assign { label: fresh_label(), span: self.span_from(start), target: expr, operator, expr: value }));
+                }
+
+                self.builder.start_node_at(checkpoint, SyntaxKind::ExpressionStatement);
+                match expr {
+                    Expression::MethodCall(_) | Expression::New(_) | Expression::Error(_) => {}
+                    Expression::UnaryOp(ref op) if op.operator.is_increment() => {}
+                    _ => {
+                        return Err(self.error_at(expr.span(), "only method calls, `new`, `++` and `--` can be used as a statement".to_string()));
+                    }
+                }
+                if terminated {
+                    self.expect(TokenKind::Semicolon)?;
+                }
+                self.finish();
+
+                Ok(Statement::Expression(expr))
+            }
+        }
+    }
+
+    // Expressions
+    fn parse_expression(&mut self) -> PResult<Expression> {
+        let expr = self.parse_binary(0)?;
+
+        // Give a helpful error for C# operators that we don't support
+        match *self.peek() {
+            TokenKind::Ne | TokenKind::Lt | TokenKind::Le | TokenKind::Gt | TokenKind::Ge
+            | TokenKind::AndAnd | TokenKind::OrOr | TokenKind::Percent | TokenKind::And
+            | TokenKind::Or | TokenKind::Caret | TokenKind::Question | TokenKind::PercentEq => {
+                Err(self.error(format!("operator {} is not supported", self.peek())))
+            }
+            _ => Ok(expr)
+        }
+    }
+
+    /// Parses binary operators by precedence climbing. Operators with a precedence
+    /// lower than `min_precedence` are left for the caller.
+    fn parse_binary(&mut self, min_precedence: u32) -> PResult<Expression> {
+        let checkpoint = self.checkpoint();
+        let mut left = self.parse_unary()?;
+        loop {
+            let operator = match binary_operator(self.peek()) {
+                Some(op) if op.precedence() >= min_precedence => op,
+                _ => return Ok(left)
+            };
+            self.builder.start_node_at(checkpoint, SyntaxKind::BinaryOp);
+            self.bump();
+
+            // All binary operators are left associative
+            let right = self.parse_binary(operator.precedence() + 1)?;
+            self.finish();
+            left = Expression::BinaryOp(BinaryOp {
+                label: fresh_label(),
+                span: left.span().to(right.span()),
+                operator,
+                left: Box::new(left),
+                right: Box::new(right)
+            });
+        }
+    }
+
+    fn parse_unary(&mut self) -> PResult<Expression> {
+        match *self.peek() {
+            // Negative integer literals
+            TokenKind::Minus if matches!(*self.peek_nth(1), TokenKind::IntLiteral(_)) => {
+                self.start(SyntaxKind::Literal);
+                let start = self.bump().span;
+                let (end, value) = match self.bump() {
+                    Token { span, kind: TokenKind::IntLiteral(value) } => (span, value),
+                    _ => unreachable!()
+                };
+                self.finish();
+                Ok(Expression::Literal(Literal { label: fresh_label(), span: start.to(end), kind: LiteralKind::Int(-value) }))
+            }
+            TokenKind::Not | TokenKind::Minus | TokenKind::PlusPlus | TokenKind::MinusMinus => {
+                let operator = match *self.peek() {
+                    TokenKind::Not => UnaryOperator::Not,
+                    TokenKind::Minus => UnaryOperator::Neg,
+                    TokenKind::PlusPlus => UnaryOperator::PreIncrement,
+                    _ => UnaryOperator::PreDecrement
+                };
+                self.start(SyntaxKind::UnaryOp);
+                let start = self.bump().span;
+                let operand = self.parse_unary()?;
+                if operator.is_increment() && !operand.is_lvalue() {
+                    return Err(self.error_at(operand.span(), format!("the operand of `{}` must be a variable, a field or an array element", operator)));
+                }
+                self.finish();
+                Ok(Expression::UnaryOp(UnaryOp {
+                    label: fresh_label(),
+                    span: start.to(operand.span()),
+                    operator,
+                    operand: Box::new(operand)
+                }))
+            }
+            TokenKind::Tilde | TokenKind::Plus => {
+                Err(self.error(format!("unary operator {} is not supported", self.peek())))
+            }
+            _ => self.parse_postfix()
+        }
+    }
+
+    fn parse_postfix(&mut self) -> PResult<Expression> {
+        let checkpoint = self.checkpoint();
+        let mut expr = self.parse_primary()?;
+        loop {
+            match *self.peek() {
+                TokenKind::Dot => {
+                    self.bump();
+                    let name = self.expect_ident()?;
+                    if *self.peek() == TokenKind::OpenParen {
+                        self.builder.start_node_at(checkpoint, SyntaxKind::MethodCall);
+                        let args = self.parse_args()?;
+                        self.finish();
+                        expr = Expression::MethodCall(MethodCall {
+                            label: fresh_label(),
+                            span: self.span_from(expr.span()),
+                            target: Some(Box::new(expr)),
+                            method_name: name,
+                            args
+                        });
+                    } else {
+                        self.builder.start_node_at(checkpoint, SyntaxKind::FieldAccess);
+                        self.finish();
+                        expr = Expression::FieldAccess(FieldAccess {
+                            label: fresh_label(),
+                            span: self.span_from(expr.span()),
+                            target: Box::new(expr),
+                            field_name: name
+                        });
+                    }
+                }
+                TokenKind::OpenBracket => {
+                    self.builder.start_node_at(checkpoint, SyntaxKind::Index);
+                    self.bump();
+                    let index = self.parse_expression()?;
+                    self.expect(TokenKind::CloseBracket)?;
+                    self.finish();
+                    expr = Expression::Index(Index {
+                        label: fresh_label(),
+                        span: self.span_from(expr.span()),
+                        target: Box::new(expr),
+                        index: Box::new(index)
+                    });
+                }
+                TokenKind::PlusPlus | TokenKind::MinusMinus => {
+                    let operator = if *self.peek() == TokenKind::PlusPlus {
+                        UnaryOperator::PostIncrement
+                    } else {
+                        UnaryOperator::PostDecrement
+                    };
+                    if !expr.is_lvalue() {
+                        return Err(self.error(format!("the operand of `{}` must be a variable, a field or an array element", operator)));
+                    }
+                    self.builder.start_node_at(checkpoint, SyntaxKind::UnaryOp);
+                    let end = self.bump().span;
+                    self.finish();
+                    expr = Expression::UnaryOp(UnaryOp {
+                        label: fresh_label(),
+                        span: expr.span().to(end),
+                        operator,
+                        operand: Box::new(expr)
+                    });
+                }
+                _ => return Ok(expr)
+            }
+        }
+    }
+
+    fn parse_args(&mut self) -> PResult<Vec<Expression>> {
+        self.start(SyntaxKind::ArgList);
+        self.expect(TokenKind::OpenParen)?;
+        let mut args = Vec::new();
+        if self.eat(&TokenKind::CloseParen) {
+            self.finish();
+            return Ok(args);
+        }
+
+        loop {
+            args.push(self.parse_expression()?);
+            if !self.eat(&TokenKind::Comma) {
+                self.expect(TokenKind::CloseParen)?;
+                self.finish();
+                return Ok(args);
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> PResult<Expression> {
+        let label = fresh_label();
+        let span = self.span();
+        let literal = match *self.peek() {
+            TokenKind::IntLiteral(i) => Some(LiteralKind::Int(i)),
+            TokenKind::StringLiteral(ref s) => Some(LiteralKind::String(s.clone())),
+            TokenKind::BoolLiteral(b) => Some(LiteralKind::Bool(b)),
+            TokenKind::NullLiteral => Some(LiteralKind::Null),
+            _ => None
+        };
+        if let Some(kind) = literal {
+            self.start(SyntaxKind::Literal);
+            self.bump();
+            self.finish();
+            return Ok(Expression::Literal(Literal { label, span, kind }));
+        }
+
+        match *self.peek() {
+            TokenKind::Keyword(Keyword::This) => {
+                self.start(SyntaxKind::This);
+                self.bump();
+                self.finish();
+                Ok(Expression::This(This { label, span }))
+            }
+            TokenKind::Keyword(Keyword::Base) => {
+                // `base` is only allowed before a member access
+                self.start(SyntaxKind::Base);
+                self.bump();
+                self.finish();
+                if *self.peek() != TokenKind::Dot {
+                    return Err(self.unexpected("`.`"));
+                }
+                Ok(Expression::Base(Base { label, span }))
+            }
+            TokenKind::Keyword(Keyword::New) => self.parse_new(),
+            TokenKind::Ident(ref name) if *self.peek_nth(1) != TokenKind::OpenParen => {
+                let name = name.clone();
+                self.start(SyntaxKind::Identifier);
+                self.bump();
+                self.finish();
+                Ok(Expression::Identifier(Identifier { label, span, name }))
+            }
+            TokenKind::Ident(_) => {
+                // A method call without target, which calls a method of the current class
+                self.start(SyntaxKind::MethodCall);
+                let name = self.expect_ident()?;
+                let args = self.parse_args()?;
+                self.finish();
+
+                Ok(Expression::MethodCall(MethodCall {
+                    label,
+                    span: self.span_from(span),
+                    target: None,
+                    method_name: name,
+                    args
+                }))
+            }
+            TokenKind::OpenParen => {
+                self.start(SyntaxKind::ParenExpr);
+                self.bump();
+                let expr = self.parse_expression()?;
+                self.expect(TokenKind::CloseParen)?;
+                self.finish();
+                Ok(expr)
+            }
+            TokenKind::CharLiteral(_) => {
+                Err(self.error("char literals are not supported".to_string()))
+            }
+            // The expression is missing (e.g. `int x = ;`). Keep parsing the surrounding code
+            TokenKind::Semicolon | TokenKind::CloseParen | TokenKind::CloseBrace
+            | TokenKind::CloseBracket | TokenKind::Comma | TokenKind::Eof => {
+                let err = self.unexpected("expression");
+                self.report(err);
+                self.start(SyntaxKind::Error);
+                self.finish();
+                Ok(Expression::Error(ErrorNode { label, span: Span::new(span.file, span.start, span.start) }))
+            }
+            _ => Err(self.unexpected("expression"))
+        }
+    }
+
+    /// Parses object creation (`new Foo()`), array creation (`new int[n]`) and array literals
+    /// (`new int[] { 1, 2 }`)
+    fn parse_new(&mut self) -> PResult<Expression> {
+        let checkpoint = self.checkpoint();
+        let start = self.expect_keyword(Keyword::New)?;
+        self.start(SyntaxKind::Type);
+        let ty = self.parse_type_name()?;
+        self.finish();
+
+        if *self.peek() == TokenKind::OpenBracket && *self.peek_nth(1) != TokenKind::CloseBracket {
+            self.builder.start_node_at(checkpoint, SyntaxKind::NewArray);
+            self.bump();
+            let length = self.parse_expression()?;
+            self.expect(TokenKind::CloseBracket)?;
+            let elem_ty = self.parse_array_suffix(ty)?;
+            if *self.peek() == TokenKind::OpenBrace {
+                return Err(self.error("array creation with both a length and elements is not supported".to_string()));
+            }
+            self.finish();
+
+            let span = self.span_from(start);
+            return Ok(Expression::NewArray(NewArray { label: fresh_label(), span, elem_ty, length: Box::new(length) }));
+        }
+
+        if *self.peek() == TokenKind::OpenBracket {
+            self.builder.start_node_at(checkpoint, SyntaxKind::ArrayLiteral);
+            self.bump();
+            self.bump();
+
+            let elem_ty = self.parse_array_suffix(ty)?;
+
+            self.expect(TokenKind::OpenBrace)?;
+            let mut elems = Vec::new();
+            while !self.eat(&TokenKind::CloseBrace) {
+                elems.push(self.parse_expression()?);
+                if !self.eat(&TokenKind::Comma) {
+                    self.expect(TokenKind::CloseBrace)?;
+                    break;
+                }
+            }
+            self.finish();
+
+            let span = self.span_from(start);
+            return Ok(Expression::Literal(Literal { label: fresh_label(), span, kind: LiteralKind::Array(elem_ty, elems) }));
+        }
+
+        let class_name = match ty {
+            Type::Custom(name) => name,
+            _ => unreachable!()
+        };
+        self.builder.start_node_at(checkpoint, SyntaxKind::New);
+        let args = self.parse_args()?;
+        self.finish();
+
+        Ok(Expression::New(New { label: fresh_label(), span: self.span_from(start), class_name, args }))
+    }
+
+    /// Parses the `[]` pairs after the brackets of an array creation, which make the elements arrays
+    fn parse_array_suffix(&mut self, ty: Type) -> PResult<Type> {
+        let mut elem_ty = ty;
+        while self.eat(&TokenKind::OpenBracket) {
+            self.expect(TokenKind::CloseBracket)?;
+            elem_ty = Type::Array(Box::new(elem_ty));
+        }
+
+        Ok(elem_ty)
+    }
+
+    /// Parses a type without array brackets. Class names may be qualified (e.g. `A.B.Foo`)
+    fn parse_type_name(&mut self) -> PResult<Type> {
+        match *self.peek() {
+            TokenKind::Keyword(kw @ Keyword::Int)
+            | TokenKind::Keyword(kw @ Keyword::Bool)
+            | TokenKind::Keyword(kw @ Keyword::String) => {
+                self.bump();
+                Ok(Type::Custom(kw.as_str().to_string()))
+            }
+            TokenKind::Ident(_) => Ok(Type::Custom(self.parse_qualified_name()?)),
+            _ => Err(self.unexpected("type"))
+        }
+    }
+}
+
+#[derive(Default)]
+struct Modifiers {
+    seen: HashSet<Keyword>,
+    access: Option<Access>
+}
+
+fn binary_operator(kind: &TokenKind) -> Option<BinaryOperator> {
+    let op = match *kind {
+        TokenKind::Plus => BinaryOperator::Add,
+        TokenKind::Minus => BinaryOperator::Sub,
+        TokenKind::Star => BinaryOperator::Mul,
+        TokenKind::Slash => BinaryOperator::Div,
+        TokenKind::EqEq => BinaryOperator::Eq,
+        TokenKind::Ne => BinaryOperator::Ne,
+        TokenKind::Lt => BinaryOperator::Lt,
+        TokenKind::Le => BinaryOperator::Le,
+        TokenKind::Gt => BinaryOperator::Gt,
+        TokenKind::Ge => BinaryOperator::Ge,
+        TokenKind::AndAnd => BinaryOperator::And,
+        TokenKind::OrOr => BinaryOperator::Or,
+        _ => return None
+    };
+
+    Some(op)
+}
+
+/// Returns `Some(None)` for `=` and the operator of compound assignments (e.g. `Some(Some(Add))` for `+=`)
+fn assignment_operator(kind: &TokenKind) -> Option<Option<BinaryOperator>> {
+    let op = match *kind {
+        TokenKind::Eq => None,
+        TokenKind::PlusEq => Some(BinaryOperator::Add),
+        TokenKind::MinusEq => Some(BinaryOperator::Sub),
+        TokenKind::StarEq => Some(BinaryOperator::Mul),
+        TokenKind::SlashEq => Some(BinaryOperator::Div),
+        _ => return None
+    };
+
+    Some(op)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use analysis::QueryEngine;
+    use sample_programs;
+
+    /// Parses the source, returning the program and the errors as `line:column: message`
+    fn parse_with_errors(src: &str) -> (Program, Vec<String>) {
+        let (program, diagnostics) = parse("test.cs", src);
+        let errors = diagnostics.iter().map(|d| {
+            let location = program.source_map.location(d.primary_span().unwrap()).unwrap();
+            format!("{}: {}", location, d.message)
+        }).collect();
+
+        (program, errors)
+    }
+
+    #[test]
+    fn variables_program() {
+        let src = r#"
+            class Program {
+                public static void Main() {
+                    Console.WriteLine("Part one");
+                    Console.WriteLine("========");
+                    String msg = "Hello there!";
+                    String msg_copy = msg;
+                    Console.WriteLine(msg_copy);
+                    int x = 42;
+                    int y = Program.Aux(x);
+                    int z = Program.Aux(y);
+                    Console.WriteLine(x);
+                    Console.WriteLine(y);
+                    Console.WriteLine(z);
+                    Console.WriteLine("Part two");
+                    Console.WriteLine("========");
+                    Console.WriteLine("Factorial of 0");
+                    Console.WriteLine(Program.Factorial(0));
+                    Console.WriteLine("Factorial of 5");
+                    Console.WriteLine(Program.Factorial(5));
+                }
+
+                static int Aux(int x) {
+                    int two = 2;
+                    int sum = x + two;
+                    return sum;
+                }
+
+                public static int Factorial(int x) {
+                    if (0 == x) {
+                        return 1;
+                    } else
+                        return x * Program.Factorial(x - 1);
+                }
+            }
+        "#;
+
+        let (parsed, errors) = parse_with_errors(src);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(parsed.to_string(), sample_programs::variables().to_string());
+    }
+
+    #[test]
+    fn precedence_and_targets() {
+        let src = "class A { int Get() { return 1 - 2 * 3 + (4 - 5); } int Twice() { return Get() + this.Get(); } }";
+        let (program, diagnostics) = parse("test.cs", src);
+        assert!(diagnostics.is_empty());
+        let printed = program.to_string();
+        assert!(printed.contains("return 1 - 2 * 3 + (4 - 5);"), "{}", printed);
+        assert!(printed.contains("return Get() + this.Get();"), "{}", printed);
+
+        // `(1 - (2 * 3)) + (4 - 5)`
+        let op = match program.methods().next().unwrap().body.as_ref().unwrap()[0] {
+            Statement::Return(Return { expr: Some(Expression::BinaryOp(ref op)), .. }) => op,
+            _ => unreachable!()
+        };
+        assert_eq!(op.operator.to_string(), "+");
+        match (&*op.left, &*op.right) {
+            (Expression::BinaryOp(left), Expression::BinaryOp(right)) => {
+                assert_eq!(left.operator.to_string(), "-");
+                assert_eq!(right.operator.to_string(), "-");
+            }
+            _ => panic!("wrong associativity")
+        }
+    }
+
+    #[test]
+    fn errors_have_positions() {
+        let (_, errors) = parse_with_errors("class A {\n    void M() {\n        int x = 1\n    }\n}");
+        assert_eq!(errors, vec!["4:5: expected `;`, found `}`"]);
+
+        let (_, errors) = parse_with_errors("class A { void M() { x + 1; } }");
+        assert_eq!(errors, vec!["1:22: only method calls, `new`, `++` and `--` can be used as a statement"]);
+
+        let (_, errors) = parse_with_errors("class A { string s = \"oops; }");
+        assert_eq!(errors[0], "1:22: unterminated string literal");
+    }
+
+    #[test]
+    fn recovers_from_errors() {
+        let src = "\
+class A {
+    int f = ;
+    void M() {
+        int x = 1 +;
+        Foo(1 2);
+        if (x == 1 { return; }
+        Console.WriteLine(x);
+    }
+    int Broken( { return 1; }
+    int N() {
+        return 2;
+    }
+    void Unclosed() {
+        int y = 3;
+class B {
+    int Get() { return 4; }
+}";
+        let (program, errors) = parse_with_errors(src);
+        assert_eq!(errors, vec![
+            "2:13: expected expression, found `;`",
+            "4:20: expected expression, found `;`",
+            "5:15: expected `)`, found integer literal",
+            "6:20: expected `)`, found `{`",
+            "9:17: expected type, found `{`",
+            // Note: the missing braces of both `Unclosed` and `A` are reported once
+            "15:1: expected `}`, found keyword `class`",
+        ]);
+
+        // Everything that could be parsed is kept
+        let classes: Vec<_> = program.classes().map(|c| &c.name[..]).collect();
+        assert_eq!(classes, vec!["A", "B"]);
+        let methods: Vec<_> = program.methods().map(|m| &m.name[..]).collect();
+        assert_eq!(methods, vec!["M", "N", "Unclosed", "Get"]);
+
+        let m = program.methods().next().unwrap();
+        let body = m.body.as_ref().unwrap();
+        assert_eq!(body.len(), 4);
+        match (&body[0], &body[1], &body[2], &body[3]) {
+            (Statement::VarDecl(_), Statement::Error(_), Statement::Error(_), Statement::Expression(_)) => {}
+            _ => panic!("unexpected statements: {:?}", m.body)
+        }
+
+        // The rest of the program can still be queried
+        let mut engine = QueryEngine::new(&program);
+        let n = program.methods().nth(1).unwrap().label.assert_as_method_decl();
+        let int_ty = engine.types().int_ty();
+        assert_eq!(engine.query_return_type(n), int_ty);
+        engine.check_method(n);
+        assert!(!engine.diagnostics().iter().any(|d| d.code != ::diagnostics::Code::MissingEntryPoint));
+    }
+
+    #[test]
+    fn truncated_input() {
+        let (program, errors) = parse_with_errors("class A { int M(int n) { if (n ==");
+        // Note: the missing `)` and braces are at the same position, so only the first error is reported
+        assert_eq!(errors, vec!["1:34: expected expression, found end of file"]);
+        assert_eq!(program.methods().count(), 1);
+    }
+
+    #[test]
+    fn method_modifiers() {
+        let src = "\
+sealed class A {
+    public new virtual int M() { return 0; }
+    sealed override void N() { }
+    static virtual void O() { }
+    sealed void P() { }
+    virtual int f;
+}";
+        let (program, errors) = parse_with_errors(src);
+        assert_eq!(errors, vec![
+            "4:5: static methods cannot be `virtual`",
+            "5:5: only `override` methods can be `sealed`",
+            "6:5: the `virtual` modifier is not valid on fields",
+        ]);
+
+        let class = program.classes().next().unwrap();
+        assert!(class.is_sealed);
+        let methods: Vec<_> = program.methods().map(|md| (&md.name[..], md.virtuality, md.is_new)).collect();
+        assert_eq!(methods, vec![("M", Virtuality::Virtual, true), ("N", Virtuality::SealedOverride, false)]);
+    }
+
+    #[test]
+    fn interfaces() {
+        let src = "\
+public interface IA : IB, IC {
+    int M(int x);
+    virtual void N();
+    void O() { }
+    int f;
+}
+class A : B, IA { }";
+        let (program, errors) = parse_with_errors(src);
+        assert_eq!(errors, vec![
+            "3:5: the `virtual` modifier is not valid on interface members",
+            "4:14: interface methods cannot have a body",
+            "5:5: interfaces cannot contain fields",
+        ]);
+
+        let interface = program.interfaces().next().unwrap();
+        let bases: Vec<_> = interface.bases.iter().map(|b| &b.name[..]).collect();
+        assert_eq!(bases, vec!["IB", "IC"]);
+        assert_eq!(interface.methods[0].name, "M");
+        assert!(interface.methods[0].body.is_none());
+
+        let class = program.classes().next().unwrap();
+        let bases: Vec<_> = class.bases.iter().map(|b| &b.name[..]).collect();
+        assert_eq!(bases, vec!["B", "IA"]);
+    }
+
+    #[test]
+    fn abstract_members() {
+        let src = "\
+abstract class A {
+    public abstract int M();
+    abstract override void N();
+    abstract void O() { }
+    static abstract void P();
+    abstract int f;
+}
+abstract sealed class B { }";
+        let (program, errors) = parse_with_errors(src);
+        assert_eq!(errors, vec![
+            "4:23: abstract methods cannot have a body",
+            "5:5: abstract methods cannot be `static`",
+            "6:5: the `abstract` modifier is not valid on fields",
+            "8:1: a class cannot be both `abstract` and `sealed`",
+        ]);
+
+        let class = program.classes().next().unwrap();
+        assert!(class.is_abstract);
+        let methods: Vec<_> = program.methods().map(|md| (&md.name[..], md.virtuality, md.is_abstract, md.body.is_some())).collect();
+        assert_eq!(methods, vec![("M", Virtuality::Virtual, true, false), ("N", Virtuality::Override, true, false)]);
+    }
+
+    #[test]
+    fn namespaces_and_usings() {
+        let src = "\
+using System;
+using P = A.B.Point;
+namespace A.B {
+    class Point { A.B.Point[] Copy() { return null; } }
+    namespace C { class D { } }
+}
+namespace A {
+    using B;
+    class Point { }
+}
+class Main { }
+using Late;
+namespace F;
+";
+        let (program, errors) = parse_with_errors(src);
+        assert_eq!(errors, vec![
+            "12:1: using directives must come before the classes and namespaces",
+            "13:1: file-scoped namespaces must come before the classes and namespaces of the file",
+        ]);
+
+        let names: Vec<_> = program.classes().map(|c| &c.name[..]).collect();
+        assert_eq!(names, vec!["Point", "D", "Point", "Main"]);
+        match (&program.items[1], &program.items[2]) {
+            (TopItem::UsingDirective(ref ud), TopItem::NamespaceDecl(ref nd)) => {
+                assert_eq!((ud.alias.as_ref().map(|a| &a[..]), &ud.name[..]), (Some("P"), "A.B.Point"));
+                assert_eq!((&nd.name[..], nd.items.len(), nd.is_file_scoped), ("A.B", 2, false));
+            }
+            _ => panic!("unexpected items: {:?}", program.items)
+        }
+
+        let copy = program.methods().next().unwrap();
+        assert_eq!(copy.return_ty.to_string(), "A.B.Point[]");
+
+        let (program, errors) = parse_with_errors("using A;\nnamespace N;\nclass C { }\nclass D { }");
+        assert!(errors.is_empty());
+        match program.items[1] {
+            TopItem::NamespaceDecl(ref nd) => assert_eq!((&nd.name[..], nd.items.len(), nd.is_file_scoped), ("N", 2, true)),
+            _ => panic!("unexpected items: {:?}", program.items)
+        }
+    }
+
+    #[test]
+    fn lossless_syntax_tree() {
+        let src = "\
+// Comments and whitespace are kept
+using System;
+
+class A {
+    /* a field */ int f;
+    int M(int n) {
+        return 1 +   (n * -2); // trailing comment
+    }
+    void Broken() { int x = 1 @ 2; if (x == { } }
+}
+class B { int G() { return \"unterminated; } }";
+        let (program, errors) = parse_with_errors(src);
+        assert_eq!(program.syntax.len(), 1);
+        assert!(!errors.is_empty());
+
+        // Printing the tree gives back the source, even with syntax and lexical errors
+        let root = &program.syntax[0].root;
+        assert_eq!(root.text(), src);
+        let tokens: String = root.tokens().map(|t| t.text().to_string()).collect();
+        assert_eq!(tokens, src);
+
+        // Nodes start at their first token, since the trivia before them belongs to their parent
+        let kinds: Vec<_> = root.children().map(|n| n.kind()).collect();
+        assert_eq!(kinds, vec![SyntaxKind::UsingDirective, SyntaxKind::ClassDecl, SyntaxKind::ClassDecl]);
+        let class = root.children().nth(1).unwrap();
+        assert!(class.text().starts_with("class A {"));
+        assert_eq!(class.span(), program.classes().next().unwrap().span);
+
+        let field = class.children().next().unwrap();
+        assert_eq!((field.kind(), &field.text()[..]), (SyntaxKind::FieldDecl, "int f;"));
+
+        let ret = class.descendants().find(|n| n.kind() == SyntaxKind::Return).unwrap();
+        assert_eq!(ret.text(), "return 1 +   (n * -2);");
+        let op = ret.children().next().unwrap();
+        assert_eq!(op.kind(), SyntaxKind::BinaryOp);
+        assert_eq!(op.parent().unwrap().kind(), SyntaxKind::Return);
+
+        // The statements that could not be parsed end up in error nodes
+        let errors: Vec<_> = root.descendants().filter(|n| n.kind() == SyntaxKind::Error).map(|n| n.text()).collect();
+        assert_eq!(errors, vec!["int x = 1 @ 2;", "if (x == { }", "return \"unterminated; } }"]);
+
+        // Multiple files
+        let files = vec![("a.cs".to_string(), "class A { }\n".to_string()), ("b.cs".to_string(), "  class B { }".to_string())];
+        let (program, _) = parse_files(files);
+        let texts: Vec<_> = program.syntax.iter().map(|tree| tree.root.text()).collect();
+        assert_eq!(texts, vec!["class A { }\n", "  class B { }"]);
+        assert_eq!(program.syntax[1].root.span().file, program.source_map.files()[1].id);
+    }
+}
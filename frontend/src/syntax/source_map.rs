@@ -0,0 +1,96 @@
+use std::fmt;
+
+use super::span::{FileId, Span};
+
+/// A human readable position in a source file
+///
+/// Note: both the line and the column are 1-based. The column is counted in characters.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SourceFile {
+    pub id: FileId,
+    pub name: String,
+    pub src: String,
+    /// Byte offsets at which each line starts
+    line_starts: Vec<usize>
+}
+
+impl SourceFile {
+    pub fn new(id: FileId, name: String, src: String) -> SourceFile {
+        let line_starts = ::std::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        SourceFile { id, name, src, line_starts }
+    }
+
+    pub fn location(&self, offset: usize) -> Location {
+        // The line is the last one starting at or before the offset
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1
+        };
+        let column = self.src[self.line_starts[line]..offset].chars().count();
+
+        Location { line: line + 1, column: column + 1 }
+    }
+
+    /// Returns the text of the given line (1-based), without the line terminator
+    pub fn line(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = self.line_starts.get(line).map_or(self.src.len(), |&next| next - 1);
+        self.src[start..end].trim_end_matches('\r')
+    }
+
+    pub fn text(&self, span: Span) -> &str {
+        &self.src[span.start..span.end]
+    }
+}
+
+/// The source files a program was parsed from
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>
+}
+
+impl SourceMap {
+    pub fn add_file(&mut self, name: String, src: String) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        self.files.push(SourceFile::new(id, name, src));
+        id
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0 as usize]
+    }
+
+    /// Replaces the source of a file, e.g. after an edit
+    pub fn set_src(&mut self, id: FileId, src: String) {
+        let file = &mut self.files[id.0 as usize];
+        *file = SourceFile::new(id, file.name.clone(), src);
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    /// Returns the location of the start of the span, unless the span is dummy
+    pub fn location(&self, span: Span) -> Option<Location> {
+        if span.is_dummy() {
+            None
+        } else {
+            Some(self.file(span.file).location(span.start))
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt;
+
+/// Identifies a file in a `SourceMap`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct FileId(pub(crate) u32);
+
+impl FileId {
+    /// The file of spans that don't point to real source code
+    pub fn dummy() -> FileId {
+        FileId(u32::MAX)
+    }
+}
+
+/// A range of bytes in a source file
+///
+/// Note: `end` is exclusive
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize
+}
+
+impl Span {
+    pub fn new(file: FileId, start: usize, end: usize) -> Span {
+        debug_assert!(start <= end, "Span start should not come after its end");
+        Span { file, start, end }
+    }
+
+    /// A span for nodes that were not parsed from source code (e.g. the sample programs)
+    pub fn dummy() -> Span {
+        Span::new(FileId::dummy(), 0, 0)
+    }
+
+    pub fn is_dummy(&self) -> bool {
+        self.file == FileId::dummy()
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Returns the smallest span that covers both `self` and `other`
+    pub fn to(self, other: Span) -> Span {
+        debug_assert_eq!(self.file, other.file, "Cannot join spans of different files");
+        Span::new(self.file, self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
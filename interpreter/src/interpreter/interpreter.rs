@@ -1,10 +1,22 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::io;
+use std::rc::Rc;
 
 use frontend::analysis::labels;
+use frontend::ast::BinaryOperator;
 use lowering::ClassInfo;
 use ir;
 use super::runtime as rt;
 
+/// A place whose target has already been evaluated, so it can be read and written
+enum Location {
+    Stack(usize),
+    Field(Rc<rt::Object>, usize),
+    Static(labels::ClassDecl, usize),
+    Element(Rc<RefCell<Vec<rt::Value>>>, usize)
+}
+
 enum NextAction {
     Continue,
     Jump(usize),
@@ -13,9 +25,12 @@ enum NextAction {
 
 pub struct Interpreter<'a> {
     pub classes: HashMap<labels::ClassDecl, ClassInfo>,
+    /// The static fields of the classes whose initialization has started
+    pub statics: HashMap<labels::ClassDecl, Vec<rt::Value>>,
     pub stack: Vec<rt::Value>,
     pub stack_ptr: usize,
-    pub program: &'a ir::Program
+    pub program: &'a ir::Program,
+    pub output: &'a mut dyn io::Write
 }
 
 impl<'a> Interpreter<'a> {
@@ -26,6 +41,25 @@ impl<'a> Interpreter<'a> {
         self.run_method(&method, vec![]);
     }
 
+    /// Initializes the static fields of the class and runs its static constructor, the first time
+    /// it is called for the class
+    ///
+    /// Note: the class counts as initialized while its initializer runs, so using the class from
+    /// there (e.g. calling one of its static methods) sees the fields that are not initialized yet,
+    /// like in C#
+    fn init_class(&mut self, class: labels::ClassDecl) {
+        if self.statics.contains_key(&class) {
+            return;
+        }
+
+        let defaults = self.classes[&class].static_field_defaults.clone();
+        let fields = defaults.iter().map(|l| self.run_literal(l)).collect();
+        self.statics.insert(class, fields);
+
+        let method = self.program.methods[self.classes[&class].initializer.0].clone();
+        self.run_method(&method, vec![]);
+    }
+
     // Note: var_id is 0-based
     fn stack_addr(&self, var_id: usize) -> usize {
         self.stack_ptr + var_id
@@ -66,8 +100,16 @@ impl<'a> Interpreter<'a> {
         use self::ir::Statement::*;
         match *s {
             Assign(ref assign) => {
-                let addr = self.stack_addr(assign.var_id.0);
-                self.stack[addr] = self.run_expression(&assign.value);
+                let location = self.locate(&assign.place);
+                let value = match assign.operator {
+                    // The current value is read before evaluating the right operand
+                    Some(op) => {
+                        let current = self.load(&location);
+                        int_op(op, current, self.run_expression(&assign.value))
+                    }
+                    None => self.run_expression(&assign.value)
+                };
+                self.store(&location, value);
                 NextAction::Continue
             }
             Expression(ref expr) => {
@@ -77,8 +119,14 @@ impl<'a> Interpreter<'a> {
             Return(ref val) => {
                 NextAction::Return(val.as_ref().map(|v| self.run_expression(v)))
             }
-            VarDecl => {
-                self.stack.push(rt::Value::Int(::std::i64::MAX));
+            VarDecl(var_id) => {
+                debug_assert_eq!(self.stack.len(), self.stack_addr(var_id.0), "Stack slots are out of sync with variable ids");
+                self.stack.push(rt::Value::Int(i64::MAX));
+                NextAction::Continue
+            }
+            FreeVars(count) => {
+                let len = self.stack.len() - count;
+                self.stack.truncate(len);
                 NextAction::Continue
             }
             Branch(ref expr, i) => {
@@ -91,7 +139,8 @@ impl<'a> Interpreter<'a> {
             Jump(i) => {
                 NextAction::Jump(i)
             }
-            Nop => {
+            InitClass(class) => {
+                self.init_class(class);
                 NextAction::Continue
             }
         }
@@ -101,13 +150,12 @@ impl<'a> Interpreter<'a> {
         use self::ir::Expression::*;
         match *e {
             FieldAccess(ref fa) => {
-                let target = self.run_expression(&fa.target);
-                // Because of type checking, we know this is an object
-                match target {
-                    // FIXME: we do nothing to deal with by ref vs by val
-                    rt::Value::Object(ref obj) => obj.fields[fa.field_id.0].clone(),
-                    _ => unreachable!()
-                }
+                let location = self.locate_field(fa);
+                self.load(&location)
+            }
+            StaticField(field) => {
+                let location = self.locate_static(field);
+                self.load(&location)
             }
             Literal(ref l) => {
                 self.run_literal(l)
@@ -123,16 +171,114 @@ impl<'a> Interpreter<'a> {
                 // Note: for void methods we just return null. The return type will be ignored anyway.
                 self.run_method(method, args).unwrap_or(rt::Value::Null)
             }
+            VirtualCall(ref vc) => {
+                let args: Vec<_> = vc.arguments.iter().map(|expr| self.run_expression(expr)).collect();
+                let method_id = match args[0] {
+                    rt::Value::Object(ref obj) => self.classes[&obj.class].vtable[vc.slot],
+                    rt::Value::Null => panic!("Attempt to call a method on null"),
+                    ref v => unreachable!("Attempt to call a method on a value that is not an object: {:?}", v)
+                };
+
+                let method = &self.program.methods[method_id.0].clone();
+                self.run_method(method, args).unwrap_or(rt::Value::Null)
+            }
+            InterfaceCall(ref ic) => {
+                let args: Vec<_> = ic.arguments.iter().map(|expr| self.run_expression(expr)).collect();
+                let method_id = match args[0] {
+                    rt::Value::Object(ref obj) => self.classes[&obj.class].interface_table[&ic.slot],
+                    rt::Value::Null => panic!("Attempt to call a method on null"),
+                    ref v => unreachable!("Attempt to call a method on a value that is not an object: {:?}", v)
+                };
+
+                let method = &self.program.methods[method_id.0].clone();
+                self.run_method(method, args).unwrap_or(rt::Value::Null)
+            }
             VarRead(var_id) => {
                 let addr = self.stack_addr(var_id.0);
-                // FIXME: we do nothing to deal with by ref vs by val
                 self.stack[addr].clone()
             }
             NewObject(class) => {
-                // Note: constructors don't exist in our implementation
-                let fields = vec![rt::Value::Null; self.classes[&class].field_names.len()];
-                rt::Value::Object(rt::Object { class, fields })
+                // Note: the constructor is called afterwards, with the object as `this`
+                let defaults = self.classes[&class].field_defaults.clone();
+                let fields = defaults.iter().map(|l| self.run_literal(l)).collect();
+                rt::Value::Object(Rc::new(rt::Object { class, fields: RefCell::new(fields) }))
+            }
+            Conditional(ref c) => {
+                match self.run_expression(&c.condition) {
+                    rt::Value::Bool(true) => self.run_expression(&c.then),
+                    rt::Value::Bool(false) => self.run_expression(&c.else_),
+                    v => panic!("[Unreachable code] Condition is not a boolean: {:?}", v)
+                }
+            }
+            Increment(ref inc) => {
+                let location = self.locate(&inc.place);
+                let old = match self.load(&location) {
+                    rt::Value::Int(i) => i,
+                    v => panic!("[Unreachable code] Attempt to increment a value that is not an integer: {:?}", v)
+                };
+                let new = old + inc.delta;
+                self.store(&location, rt::Value::Int(new));
+                rt::Value::Int(if inc.postfix { old } else { new })
+            }
+        }
+    }
+
+    fn locate(&mut self, place: &ir::Place) -> Location {
+        match *place {
+            ir::Place::Var(var_id) => Location::Stack(self.stack_addr(var_id.0)),
+            ir::Place::Field(ref fa) => self.locate_field(fa),
+            ir::Place::Static(field) => self.locate_static(field),
+            ir::Place::Element(ref element) => self.locate_element(&element.0, &element.1)
+        }
+    }
+
+    fn locate_field(&mut self, fa: &ir::FieldAccess) -> Location {
+        // Because of type checking, we know this is an object or null
+        match self.run_expression(&fa.target) {
+            rt::Value::Object(obj) => Location::Field(obj, fa.field_id.0),
+            rt::Value::Null => panic!("Attempt to access a field of null"),
+            v => unreachable!("Attempt to access a field of a value that is not an object: {:?}", v)
+        }
+    }
+
+    /// Initializes the class of the field, if needed
+    fn locate_static(&mut self, field: ir::StaticField) -> Location {
+        self.init_class(field.class);
+        Location::Static(field.class, field.field_id.0)
+    }
+
+    /// Evaluates the array and the index, checking that the index is within bounds
+    fn locate_element(&mut self, array: &ir::Expression, index: &ir::Expression) -> Location {
+        let array = self.run_expression(array);
+        let index = self.run_expression(index);
+        match (array, index) {
+            (rt::Value::Array(elements), rt::Value::Int(i)) => {
+                let length = elements.borrow().len();
+                if i < 0 || i as usize >= length {
+                    panic!("Index {} is out of range for an array of length {}", i, length);
+                }
+                Location::Element(elements, i as usize)
             }
+            (rt::Value::Null, _) => panic!("Attempt to index null"),
+            (array, index) => unreachable!("Attempt to index {:?} with {:?}", array, index)
+        }
+    }
+
+    fn load(&self, location: &Location) -> rt::Value {
+        match *location {
+            Location::Stack(addr) => self.stack[addr].clone(),
+            Location::Field(ref obj, i) => obj.fields.borrow()[i].clone(),
+            Location::Static(class, i) => self.statics[&class][i].clone(),
+            Location::Element(ref elements, i) => elements.borrow()[i].clone()
+        }
+    }
+
+    fn store(&mut self, location: &Location, value: rt::Value) {
+        match *location {
+            Location::Stack(addr) => self.stack[addr] = value,
+            Location::Field(ref obj, i) => obj.fields.borrow_mut()[i] = value,
+            Location::Static(class, i) => self.statics.get_mut(&class).unwrap()[i] = value,
+            Location::Element(ref elements, i) => elements.borrow_mut()[i] = value
         }
     }
 
@@ -142,7 +288,10 @@ impl<'a> Interpreter<'a> {
             Bool(b) => rt::Value::Bool(b),
             Int(i) => rt::Value::Int(i),
             String(ref s) => rt::Value::String(s.clone()),
-            Array(ref exprs) => rt::Value::Array(exprs.iter().map(|e| self.run_expression(e)).collect()),
+            Array(ref exprs) => {
+                let elements = exprs.iter().map(|e| self.run_expression(e)).collect();
+                rt::Value::Array(Rc::new(RefCell::new(elements)))
+            }
             Null => rt::Value::Null
         }
     }
@@ -152,64 +301,112 @@ impl<'a> Interpreter<'a> {
         use self::rt::Value::*;
         use frontend::ast::BinaryOperator::*;
         match *i {
-            IntOp(ref op, ref e1, ref e2) => {
+            IntOp(op, ref e1, ref e2) => {
                 let e1 = self.run_expression(e1);
                 let e2 = self.run_expression(e2);
-                let (e1, e2) = match (e1, e2) {
-                    (rt::Value::Int(e1), rt::Value::Int(e2)) => (e1, e2),
-                    (e1, e2) => panic!("[This code should be unreachable] Attempt to add values of incompatible types: {:?} and {:?}", e1, e2)
-                };
+                int_op(op, e1, e2)
+            }
+            Not(ref e) => {
+                match self.run_expression(e) {
+                    Bool(b) => Bool(!b),
+                    v => panic!("[Unreachable code] Attempt to negate a value that is not a boolean: {:?}", v)
+                }
+            }
+            Equality(ref op, ref e1, ref e2) => {
+                let equal = self.run_expression(e1).equals(&self.run_expression(e2));
                 match *op {
-                    // Integer -> Integer
-                    Add => Int(e1 + e2),
-                    Sub => Int(e1 - e2),
-                    Mul => Int(e1 * e2),
-                    Div => Int(e1 / e2),
-                    // Integer -> Bool
-                    Eq => Bool(e1 == e2)
+                    Eq => Bool(equal),
+                    Ne => Bool(!equal),
+                    _ => unreachable!("Only `==` and `!=` are equality operators")
                 }
             }
             PrintLine(ref expr) => {
                 let val = self.run_expression(expr);
-                self.print_value(&val);
-                println!();
+                let mut line = ::std::string::String::new();
+                self.print_value(&mut line, &val);
+                writeln!(self.output, "{}", line).expect("Unable to write the output of the program");
 
                 // Return null. The type system ensures this return value will be ignored anyway
                 rt::Value::Null
             }
+            ArrayLength(ref array) => {
+                match self.run_expression(array) {
+                    Array(ref elements) => Int(elements.borrow().len() as i64),
+                    Null => panic!("Attempt to get the length of null"),
+                    value => panic!("Attempt to get the length of a value that is not an array: {:?}", value)
+                }
+            }
+            ArrayElement(ref array, ref index) => {
+                let location = self.locate_element(array, index);
+                self.load(&location)
+            }
+            NewArray(ref length, ref value) => {
+                let length = match self.run_expression(length) {
+                    Int(length) if length >= 0 => length as usize,
+                    Int(length) => panic!("Attempt to create an array with a negative length: {}", length),
+                    value => panic!("[Unreachable code] Array length is not an integer: {:?}", value)
+                };
+                let value = self.run_expression(value);
+                Array(Rc::new(RefCell::new(vec![value; length])))
+            }
         }
     }
 
-    fn print_value(&self, v: &rt::Value) {
+    fn print_value(&self, out: &mut String, v: &rt::Value) {
         match *v {
-            rt::Value::String(ref s) => print!("{}", s),
+            rt::Value::String(ref s) => out.push_str(s),
             rt::Value::Array(ref v) => {
-                print!("[");
-                if v.len() > 0 {
+                let v = v.borrow();
+                out.push('[');
+                if !v.is_empty() {
                     let last = v.len() - 1;
                     for x in &v[..last] {
-                        self.print_value(x);
-                        print!(", ");
+                        self.print_value(out, x);
+                        out.push_str(", ");
                     }
 
                     let x = &v[last];
-                    self.print_value(x);
+                    self.print_value(out, x);
                 }
-                print!("]");
+                out.push(']');
             }
-            rt::Value::Bool(b) => print!("{}", b),
-            rt::Value::Int(i) => print!("{}", i),
+            rt::Value::Bool(b) => out.push_str(&b.to_string()),
+            rt::Value::Int(i) => out.push_str(&i.to_string()),
             rt::Value::Object(ref obj) => {
                 let class = &self.classes[&obj.class];
-                println!("{} {{", class.name);
-                for (name, value) in class.field_names.iter().zip(obj.fields.iter()) {
-                    print!("    {}: ", name);
-                    self.print_value(value);
-                    println!(",");
+                out.push_str(&format!("{} {{\n", class.name));
+                for (name, value) in class.field_names.iter().zip(obj.fields.borrow().iter()) {
+                    out.push_str(&format!("    {}: ", name));
+                    self.print_value(out, value);
+                    out.push_str(",\n");
                 }
-                print!("}}");
+                out.push('}');
             }
-            rt::Value::Null => print!("null")
+            rt::Value::Null => out.push_str("null")
         }
     }
 }
+
+fn int_op(op: BinaryOperator, e1: rt::Value, e2: rt::Value) -> rt::Value {
+    use self::rt::Value::*;
+    use frontend::ast::BinaryOperator::*;
+    let (e1, e2) = match (e1, e2) {
+        (Int(e1), Int(e2)) => (e1, e2),
+        (e1, e2) => panic!("[This code should be unreachable] Attempt to add values of incompatible types: {:?} and {:?}", e1, e2)
+    };
+    match op {
+        // Integer -> Integer
+        Add => Int(e1 + e2),
+        Sub => Int(e1 - e2),
+        Mul => Int(e1 * e2),
+        Div => Int(e1 / e2),
+        // Integer -> Bool
+        Eq => Bool(e1 == e2),
+        Ne => Bool(e1 != e2),
+        Lt => Bool(e1 < e2),
+        Le => Bool(e1 <= e2),
+        Gt => Bool(e1 > e2),
+        Ge => Bool(e1 >= e2),
+        And | Or => unreachable!("Logical operators are desugared into conditionals")
+    }
+}
@@ -3,19 +3,28 @@
 //! It is intended as a proof of concept to show that the on-demand nature
 //! of our analysis also works well in a compiler setting.
 
+#[allow(clippy::module_inception)]
 mod interpreter;
 mod runtime;
 
 use std::collections::HashMap;
+use std::io;
 
 use frontend::analysis::labels;
 use lowering::ClassInfo;
 
 pub fn run(program: &::ir::Program, classes: HashMap<labels::ClassDecl, ClassInfo>) {
+    run_with_output(program, classes, &mut io::stdout());
+}
+
+/// Runs the program, writing what it prints to the given output instead of stdout
+pub fn run_with_output(program: &::ir::Program, classes: HashMap<labels::ClassDecl, ClassInfo>, output: &mut dyn io::Write) {
     self::interpreter::Interpreter {
         classes,
+        statics: HashMap::new(),
         program,
         stack: Vec::new(),
-        stack_ptr: 0
+        stack_ptr: 0,
+        output
     }.run();
 }
@@ -1,21 +1,42 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use frontend::analysis::labels;
 
 /// Internal representation of a value
 ///
-/// Note: this resembles `ast::Type`
+/// Note: this resembles `ast::Type`. Arrays and objects are references, so cloning them is cheap
+/// and the clones point to the same array or object.
 #[derive(Clone, Debug)]
 pub enum Value {
     String(String),
-    Array(Vec<Value>),
+    Array(Rc<RefCell<Vec<Value>>>),
     Bool(bool),
     Int(i64),
-    Object(Object),
+    Object(Rc<Object>),
     Null
 }
 
+impl Value {
+    /// Compares two values like `==` does: arrays and objects are equal if they are the same
+    /// reference, the rest of values are compared by value
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::String(s1), Value::String(s2)) => s1 == s2,
+            (Value::Array(a1), Value::Array(a2)) => Rc::ptr_eq(a1, a2),
+            (Value::Bool(b1), Value::Bool(b2)) => b1 == b2,
+            (Value::Int(i1), Value::Int(i2)) => i1 == i2,
+            (Value::Object(o1), Value::Object(o2)) => Rc::ptr_eq(o1, o2),
+            (Value::Null, Value::Null) => true,
+            _ => false
+        }
+    }
+}
+
 /// Internal representation of an object
 #[derive(Clone, Debug)]
 pub struct Object {
     pub class: labels::ClassDecl,
-    pub fields: Vec<Value>,
+    /// Fields can be assigned through any of the references to the object
+    pub fields: RefCell<Vec<Value>>,
 }
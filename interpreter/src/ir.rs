@@ -34,29 +34,83 @@ pub enum Statement {
     Assign(Assign),
     Expression(Expression),
     Return(Option<Expression>),
-    VarDecl,
+    /// Allocates the stack slot of a variable, which must be the next free one
+    VarDecl(VarId),
+    /// Frees the stack slots of the given number of variables, at the end of the block that declared them
+    FreeVars(usize),
+    /// Jumps to the given address if the condition holds
     Branch(Expression, usize),
     Jump(usize),
-    Nop
+    /// Initializes the static fields of the class and runs its static constructor, unless that
+    /// has already started (static methods and constructors start with this)
+    InitClass(labels::ClassDecl)
 }
 
+impl Statement {
+    /// Stores the value in a variable
+    pub fn assign(var_id: VarId, value: Expression) -> Statement {
+        Statement::Assign(Assign { place: Place::Var(var_id), operator: None, value })
+    }
+}
+
+/// Stores the value in the place, after applying the operator of compound assignments (e.g.
+/// `x += 1` stores `x + 1`)
 #[derive(Clone, Debug)]
 pub struct Assign {
-    pub var_id: VarId,
+    pub place: Place,
+    pub operator: Option<BinaryOperator>,
     pub value: Expression
 }
 
+/// A location that can be read and written
+#[derive(Clone, Debug)]
+pub enum Place {
+    Var(VarId),
+    Field(Box<FieldAccess>),
+    Static(StaticField),
+    /// The element of an array at the given index
+    Element(Box<(Expression, Expression)>)
+}
+
 #[derive(Clone, Debug)]
 pub enum Expression {
     FieldAccess(Box<FieldAccess>),
+    /// Reading a static field initializes its class, if needed
+    StaticField(StaticField),
     Literal(Literal),
     /// BinaryOp desugars into intrinsic
     Intrinsic(Box<Intrinsic>),
     /// New desugars into new object + method call
     MethodCall(MethodCall),
+    /// Calls to virtual methods, dispatched on the class of the object at runtime
+    VirtualCall(VirtualCall),
+    /// Calls the implementation of an interface method, through the interface table of the class
+    /// of `this` (see `ClassInfo::interface_table`)
+    InterfaceCall(VirtualCall),
     /// Identifier desugars into a VarRead or MethodCall (for static methods)
     VarRead(VarId),
     NewObject(labels::ClassDecl),
+    /// Evaluates to `then` if the condition holds, and to `else_` otherwise, without evaluating
+    /// the other branch (`&&` and `||` desugar into this)
+    Conditional(Box<Conditional>),
+    /// Adds `delta` to an `int` place (`++` and `--` desugar into this)
+    Increment(Box<Increment>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Increment {
+    pub place: Place,
+    pub delta: i64,
+    /// Whether the expression evaluates to the old value of the place (`x++`) instead of the
+    /// new one (`++x`)
+    pub postfix: bool
+}
+
+#[derive(Clone, Debug)]
+pub struct Conditional {
+    pub condition: Expression,
+    pub then: Expression,
+    pub else_: Expression
 }
 
 #[derive(Clone, Debug)]
@@ -65,6 +119,13 @@ pub struct FieldAccess {
     pub field_id: FieldId
 }
 
+/// A static field, stored along with the other static fields of its class
+#[derive(Clone, Copy, Debug)]
+pub struct StaticField {
+    pub class: labels::ClassDecl,
+    pub field_id: FieldId
+}
+
 #[derive(Clone, Debug)]
 pub enum Literal {
     Bool(bool),
@@ -77,7 +138,16 @@ pub enum Literal {
 #[derive(Clone, Debug)]
 pub enum Intrinsic {
     IntOp(BinaryOperator, Expression, Expression),
+    /// `==` or `!=` on values of any type
+    Equality(BinaryOperator, Expression, Expression),
+    Not(Expression),
     PrintLine(Expression),
+    /// The number of elements of an array
+    ArrayLength(Expression),
+    /// The element of an array at the given index
+    ArrayElement(Expression, Expression),
+    /// An array of the given length, with all the elements set to the given value
+    NewArray(Expression, Expression),
 }
 
 #[derive(Clone, Debug)]
@@ -85,3 +155,11 @@ pub struct MethodCall {
     pub method_id: MethodId,
     pub arguments: Vec<Expression>
 }
+
+/// Calls the method in the given slot of the vtable of the class of `this`, which is the first
+/// argument (see `ClassInfo::vtable`)
+#[derive(Clone, Debug)]
+pub struct VirtualCall {
+    pub slot: usize,
+    pub arguments: Vec<Expression>
+}
@@ -1,25 +1,69 @@
 mod var_tracker;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use frontend::analysis::{self, QueryEngine};
+use frontend::analysis::{QueryEngine, TypeId};
 use frontend::analysis::labels;
 use frontend::ast;
 use ir::{self, FieldId, MethodId, VarId};
 use self::var_tracker::VarTracker;
 
+/// The target of jumps generated before their target is known (see `patch_jumps`)
+const UNKNOWN_ADDR: usize = usize::MAX;
+
+/// The vtable entry of abstract methods, which is never called, since the classes that do not
+/// override them cannot be instantiated
+const ABSTRACT_METHOD: MethodId = MethodId(usize::MAX);
+
 pub struct ClassInfo {
     pub name: String,
+    /// The names of the instance fields, starting with the ones inherited from the base class
     pub field_names: Vec<String>,
+    /// The value of each field of a new object, before running its constructor
+    pub field_defaults: Vec<ir::Literal>,
+    /// The value of each static field, before running the initializer of the class
+    pub static_field_defaults: Vec<ir::Literal>,
+    /// The method that runs the initializers of the static fields and the static constructor
+    pub initializer: MethodId,
+    /// The implementation of each virtual method for objects of the class, starting with the
+    /// slots inherited from the base class
+    pub vtable: Vec<MethodId>,
+    /// The implementation of each interface method for objects of the class, indexed by the slot
+    /// of the interface method
+    pub interface_table: HashMap<usize, MethodId>
 }
 
 pub struct LoweringContext<'engine, 'ast: 'engine> {
     pub ast: &'ast ast::Program,
     pub query_engine: &'engine mut QueryEngine<'ast>,
     methods: HashMap<labels::MethodDecl, MethodId>,
+    /// The vtable slot of each virtual method, shared by the methods that override it
+    method_slots: HashMap<labels::MethodDecl, usize>,
+    /// The slot of each interface method in the interface tables (see `ClassInfo::interface_table`)
+    interface_slots: HashMap<labels::MethodDecl, usize>,
+    /// The method implementing each interface slot, for each class
+    interface_impls: HashMap<labels::ClassDecl, HashMap<usize, labels::MethodDecl>>,
+    constructors: HashMap<labels::ConstructorDecl, MethodId>,
+    /// The implicit constructors of the classes that declare none
+    default_constructors: HashMap<labels::ClassDecl, MethodId>,
     fields: HashMap<labels::VarDecl, FieldId>,
+    static_fields: HashMap<labels::VarDecl, ir::StaticField>,
     classes: HashMap<labels::ClassDecl, ClassInfo>,
-    var_tracker: VarTracker
+    var_tracker: VarTracker,
+    /// Whether the method being lowered is a constructor, which returns `this`
+    in_constructor: bool,
+    /// The loops around the statement being lowered, the innermost one last
+    loops: Vec<LoopContext>
+}
+
+/// The jumps out of a loop, to be patched once the addresses of their targets are known
+struct LoopContext {
+    /// The number of variables in scope at the targets of `break` and `continue`
+    live_vars: usize,
+    /// The indices of the jumps to the end of the loop
+    breaks: Vec<usize>,
+    /// The indices of the jumps to the next iteration of the loop
+    continues: Vec<usize>
 }
 
 pub struct LoweringOutput {
@@ -33,12 +77,23 @@ impl<'engine, 'ast: 'engine> LoweringContext<'engine, 'ast> {
             ast,
             query_engine,
             methods: HashMap::new(),
+            method_slots: HashMap::new(),
+            interface_slots: HashMap::new(),
+            interface_impls: HashMap::new(),
+            constructors: HashMap::new(),
+            default_constructors: HashMap::new(),
             fields: HashMap::new(),
+            static_fields: HashMap::new(),
             classes: HashMap::new(),
-            var_tracker: VarTracker::default()
+            var_tracker: VarTracker::default(),
+            in_constructor: false,
+            loops: Vec::new()
         }
     }
 
+    /// Generates code for the program
+    ///
+    /// Note: the program must have been checked for errors beforehand (see `QueryEngine::check_program`)
     pub fn lower_program(mut self) -> LoweringOutput {
         let mut methods = Vec::new();
 
@@ -47,35 +102,146 @@ impl<'engine, 'ast: 'engine> LoweringContext<'engine, 'ast> {
         self.methods.insert(ast::fresh_label().assert_as_method_decl(), method_id);
         methods.push(self.lower_console_write_line());
 
-        // Assign an id to all methods and fields
-        for cd in self.ast.classes() {
-            let mut field_names = Vec::new();
+        // Assign a slot to all interface methods
+        for id in self.ast.interfaces() {
+            for md in &id.methods {
+                let slot = self.interface_slots.len();
+                self.interface_slots.insert(md.label.assert_as_method_decl(), slot);
+            }
+        }
+
+        // Assign an id to all methods, constructors and fields
+        // Note: the ids must follow the order in which code is generated below
+        let classes = self.classes_in_order();
+        let mut next_id = methods.len();
+        for &cd in &classes {
+            let class = cd.label.assert_as_class_decl();
+
+            // Inherited fields and vtable slots keep their ids, so the code of the base class can
+            // use them
+            let base = self.query_engine.query_base_class(class);
+            let (mut field_names, mut field_defaults, mut vtable) = match base {
+                Some(base) => {
+                    let info = &self.classes[&base];
+                    (info.field_names.clone(), info.field_defaults.clone(), info.vtable.clone())
+                }
+                None => (Vec::new(), Vec::new(), Vec::new())
+            };
+            let mut static_field_defaults = Vec::new();
             for ci in &cd.items {
                 match *ci {
                     ast::ClassItem::FieldDecl(ref fd) => {
-                        let field_id = field_names.len();
-                        field_names.push(fd.name.to_owned());
-                        self.fields.insert(fd.label.assert_as_var_decl(), FieldId(field_id));
+                        let ty = self.query_engine.query_field_type(fd.label.assert_as_var_decl());
+                        let default = self.default_value(ty);
+                        if fd.is_static {
+                            let field_id = FieldId(static_field_defaults.len());
+                            static_field_defaults.push(default);
+                            self.static_fields.insert(fd.label.assert_as_var_decl(), ir::StaticField { class, field_id });
+                        } else {
+                            let field_id = FieldId(field_names.len());
+                            field_names.push(fd.name.to_owned());
+                            field_defaults.push(default);
+                            self.fields.insert(fd.label.assert_as_var_decl(), field_id);
+                        }
                     }
                     ast::ClassItem::MethodDecl(ref md) => {
-                        let method_id = MethodId(self.methods.len());
-                        self.methods.insert(md.label.assert_as_method_decl(), method_id);
+                        // Abstract methods have no code, but they get a vtable slot like the
+                        // other virtual methods
+                        let method = md.label.assert_as_method_decl();
+                        let method_id = if md.is_abstract {
+                            ABSTRACT_METHOD
+                        } else {
+                            let method_id = MethodId(next_id);
+                            self.methods.insert(method, method_id);
+                            next_id += 1;
+                            method_id
+                        };
+
+                        // Virtual methods get a new slot, while overrides replace the
+                        // implementation in the slot of the overridden method
+                        let slot = match md.virtuality {
+                            ast::Virtuality::NonVirtual => continue,
+                            ast::Virtuality::Virtual => {
+                                vtable.push(method_id);
+                                vtable.len() - 1
+                            }
+                            ast::Virtuality::Override | ast::Virtuality::SealedOverride => {
+                                let overridden = self.query_engine.query_overridden_method(method).expect("Unknown overridden method");
+                                let slot = self.method_slots[&overridden];
+                                vtable[slot] = method_id;
+                                slot
+                            }
+                        };
+                        self.method_slots.insert(method, slot);
+                    }
+                    // The static constructor is part of the initializer of the class
+                    ast::ClassItem::ConstructorDecl(ref cd) if cd.is_static => {}
+                    ast::ClassItem::ConstructorDecl(ref cd) => {
+                        self.constructors.insert(cd.label.assert_as_constructor_decl(), MethodId(next_id));
+                        next_id += 1;
                     }
                 }
             }
 
-            self.classes.insert(cd.label.assert_as_class_decl(), ClassInfo {
+            if cd.constructors().next().is_none() {
+                self.default_constructors.insert(class, MethodId(next_id));
+                next_id += 1;
+            }
+
+            // Interface implementations are inherited from the base class, unless the class
+            // implements the interface again
+            let mut implementations = base.map_or_else(HashMap::new, |base| self.interface_impls[&base].clone());
+            for interface in self.query_engine.query_interfaces(class) {
+                let decl: &ast::InterfaceDecl = self.query_engine.nodes[&interface.as_label()].downcast();
+                for md in &decl.methods {
+                    let method = md.label.assert_as_method_decl();
+                    let implementation = self.query_engine.query_implementation(class, method).expect("Unimplemented interface method");
+                    implementations.insert(self.interface_slots[&method], implementation);
+                }
+            }
+
+            // Virtual implementations are dispatched through the vtable of the class
+            let interface_table = implementations.iter().map(|(&slot, implementation)| {
+                let method_id = match self.method_slots.get(implementation) {
+                    Some(&vtable_slot) => vtable[vtable_slot],
+                    None => self.methods[implementation]
+                };
+                (slot, method_id)
+            }).collect();
+            self.interface_impls.insert(class, implementations);
+
+            self.classes.insert(class, ClassInfo {
                 name: cd.name.to_owned(),
-                field_names
+                field_names,
+                field_defaults,
+                static_field_defaults,
+                initializer: MethodId(next_id),
+                vtable,
+                interface_table
             });
+            next_id += 1;
         }
 
         // Generate code
-        for md in self.ast.methods() {
-            methods.push(self.lower_method(md));
+        for &cd in &classes {
+            for ci in &cd.items {
+                match *ci {
+                    ast::ClassItem::FieldDecl(_) => {}
+                    ast::ClassItem::MethodDecl(ref md) if md.is_abstract => {}
+                    ast::ClassItem::MethodDecl(ref md) => methods.push(self.lower_method(cd, md)),
+                    ast::ClassItem::ConstructorDecl(ref ctor) if ctor.is_static => {}
+                    ast::ClassItem::ConstructorDecl(ref ctor) => methods.push(self.lower_constructor(cd, Some(ctor)))
+                }
+            }
+
+            if cd.constructors().next().is_none() {
+                methods.push(self.lower_constructor(cd, None));
+            }
+
+            methods.push(self.lower_class_initializer(cd));
         }
 
-        let ep = self.query_engine.entry_point().label.assert_as_method_decl();
+        let ep = self.query_engine.entry_point().expect("Program has no entry point").label.assert_as_method_decl();
         let program = ir::Program { methods, entry_point: self.methods[&ep] };
         LoweringOutput {
             program,
@@ -83,6 +249,32 @@ impl<'engine, 'ast: 'engine> LoweringContext<'engine, 'ast> {
         }
     }
 
+    /// Returns the classes of the program, with each class after its base class
+    fn classes_in_order(&mut self) -> Vec<&'ast ast::ClassDecl> {
+        let mut ordered = Vec::new();
+        let mut seen = HashSet::new();
+        for cd in self.ast.classes() {
+            // The base classes that have not been added yet, from the most derived one
+            let mut pending = Vec::new();
+            let mut current = Some(cd.label.assert_as_class_decl());
+            while let Some(class) = current {
+                if !seen.insert(class) {
+                    break;
+                }
+
+                pending.push(class);
+                current = self.query_engine.query_base_class(class);
+            }
+
+            ordered.extend(pending.into_iter().rev().map(|class| {
+                let decl: &ast::ClassDecl = self.query_engine.nodes[&class.as_label()].downcast();
+                decl
+            }));
+        }
+
+        ordered
+    }
+
     fn lower_console_write_line(&mut self) -> ir::Method {
         // We assume that the arguments are correctly passed. This is enforced by the type checker
         ir::Method {
@@ -102,167 +294,577 @@ impl<'engine, 'ast: 'engine> LoweringContext<'engine, 'ast> {
         }
     }
 
-    fn lower_method(&mut self, m: &ast::MethodDecl) -> ir::Method {
+    fn lower_method(&mut self, class: &ast::ClassDecl, m: &ast::MethodDecl) -> ir::Method {
         self.var_tracker.reset();
+        self.in_constructor = false;
+
+        // The first parameter of instance methods is `this` (see `VarId::this`)
+        if !m.is_static {
+            self.var_tracker.temp();
+        }
 
         // Track declared parameters
         for param in &m.params {
             self.var_tracker.var_decl(param.label.assert_as_var_decl());
         }
 
+        // Calling a static method initializes its class. Instance methods need an object, which
+        // has already done that
         let mut body = Vec::new();
-        self.lower_block(&m.body, &mut body, &m);
+        if m.is_static {
+            body.push(ir::Statement::InitClass(class.label.assert_as_class_decl()));
+        }
+
+        let statements = m.body.as_ref().expect("Class method without a body");
+        self.lower_block(statements, &mut body);
         ir::Method { body }
     }
 
-    fn lower_block(&mut self, block: &[ast::Statement], body: &mut Vec<ir::Statement>, parent_method: &ast::MethodDecl) {
+    /// Generates code for a constructor of the class, or for its implicit constructor if `None`
+    ///
+    /// Constructors take the new object as their first parameter and return it, so `new` desugars
+    /// into a call to the constructor with a `NewObject` as first argument. The class is
+    /// initialized first, if needed. Then, unless the constructor calls another one with
+    /// `this(...)`, the field initializers run in the order in which the fields are declared,
+    /// followed by the constructor of the base class (if any) and the body.
+    fn lower_constructor(&mut self, class: &ast::ClassDecl, constructor: Option<&ast::ConstructorDecl>) -> ir::Method {
+        self.var_tracker.reset();
+        self.in_constructor = true;
+        let this = self.var_tracker.temp();
+        let params = constructor.map_or(&[][..], |cd| &cd.params);
+        for param in params {
+            self.var_tracker.var_decl(param.label.assert_as_var_decl());
+        }
+
+        let class_label = class.label.assert_as_class_decl();
+        let mut body = vec![ir::Statement::InitClass(class_label)];
+        let initializer = constructor.and_then(|cd| cd.initializer.as_ref());
+        if initializer.is_none_or(|call| call.is_base) {
+            for fd in class.items.iter().filter_map(ast::ClassItem::field_decl).filter(|fd| !fd.is_static) {
+                if let Some(ref expr) = fd.assignment {
+                    let field_id = self.fields[&fd.label.assert_as_var_decl()];
+                    let place = ir::Place::Field(Box::new(ir::FieldAccess { target: ir::Expression::VarRead(this), field_id }));
+                    let value = self.lower_expression(expr);
+                    body.push(ir::Statement::Assign(ir::Assign { place, operator: None, value }));
+                }
+            }
+        }
+
+        // The called constructor is either the one in the initializer or the implicit call to the
+        // parameterless constructor of the base class
+        let (call_label, args) = match initializer {
+            Some(call) => (call.label, &call.args[..]),
+            None => (constructor.map_or(class.label, |cd| cd.label), &[][..])
+        };
+        let called_class = match initializer {
+            Some(call) if !call.is_base => Some(class_label),
+            _ => self.query_engine.query_base_class(class_label)
+        };
+        if let Some(called_class) = called_class {
+            let method_id = match self.query_engine.query_constructor(call_label) {
+                Some(label) => self.constructors[&label],
+                None => self.default_constructors[&called_class]
+            };
+            let mut arguments = vec![ir::Expression::VarRead(this)];
+            arguments.extend(args.iter().map(|arg| self.lower_expression(arg)));
+            let call = ir::MethodCall { method_id, arguments };
+            body.push(ir::Statement::Expression(ir::Expression::MethodCall(call)));
+        }
+
+        if let Some(cd) = constructor {
+            self.lower_block(&cd.body, &mut body);
+        }
+        body.push(ir::Statement::Return(Some(ir::Expression::VarRead(this))));
+        ir::Method { body }
+    }
+
+    /// Generates code for the initializer of the class, which runs the initializers of the static
+    /// fields in the order in which they are declared, followed by the static constructor
+    ///
+    /// Note: the interpreter runs it right before the first use of the class (see `ir::Statement::InitClass`)
+    fn lower_class_initializer(&mut self, class: &ast::ClassDecl) -> ir::Method {
+        self.var_tracker.reset();
+        self.in_constructor = false;
+
+        let mut body = Vec::new();
+        for fd in class.items.iter().filter_map(ast::ClassItem::field_decl).filter(|fd| fd.is_static) {
+            if let Some(ref expr) = fd.assignment {
+                let place = ir::Place::Static(self.static_fields[&fd.label.assert_as_var_decl()]);
+                let value = self.lower_expression(expr);
+                body.push(ir::Statement::Assign(ir::Assign { place, operator: None, value }));
+            }
+        }
+
+        if let Some(cd) = class.static_constructor() {
+            self.lower_block(&cd.body, &mut body);
+        }
+        ir::Method { body }
+    }
+
+    /// Generates code for a block, freeing the variables declared in it at its end
+    fn lower_block(&mut self, block: &[ast::Statement], body: &mut Vec<ir::Statement>) {
+        let live_vars = self.var_tracker.enter_block();
         for stmt in block {
-            self.lower_statement(stmt, body, parent_method);
+            self.lower_statement(stmt, body);
+        }
+
+        let declared = self.var_tracker.exit_block(live_vars);
+        if declared > 0 {
+            body.push(ir::Statement::FreeVars(declared));
+        }
+    }
+
+    /// Starts tracking the jumps out of a loop, which must be called when the variables in scope
+    /// are the same as at the targets of those jumps
+    fn enter_loop(&mut self) {
+        self.loops.push(LoopContext { live_vars: self.var_tracker.live(), breaks: Vec::new(), continues: Vec::new() });
+    }
+
+    /// Points the jumps out of the innermost loop to their targets
+    fn exit_loop(&mut self, body: &mut [ir::Statement], continue_addr: usize, end_addr: usize) {
+        let context = self.loops.pop().expect("Not inside of a loop");
+        patch_jumps(body, &context.breaks, end_addr);
+        patch_jumps(body, &context.continues, continue_addr);
+    }
+
+    /// Generates code for a `break` or a `continue`, returning the index of the jump to be patched
+    ///
+    /// Note: the variables declared inside of the loop are freed before jumping out of their blocks
+    fn lower_loop_jump(&mut self, body: &mut Vec<ir::Statement>) -> usize {
+        let live_vars = self.loops.last().expect("Jumps outside of loops are rejected by the checker").live_vars;
+        let skipped = self.var_tracker.live() - live_vars;
+        if skipped > 0 {
+            body.push(ir::Statement::FreeVars(skipped));
+        }
+
+        body.push(ir::Statement::Jump(UNKNOWN_ADDR));
+        body.len() - 1
+    }
+
+    /// Generates code that jumps if the condition evaluates to `jump_if`, and falls through otherwise
+    ///
+    /// `&&`, `||` and `!` become chains of branches, so the right operand of `&&` and `||` is only
+    /// evaluated if needed. The generated jumps are added to `jumps`, to be patched once their
+    /// target is known.
+    fn lower_branch(&mut self, condition: &ast::Expression, jump_if: bool, body: &mut Vec<ir::Statement>, jumps: &mut Vec<usize>) {
+        match *condition {
+            ast::Expression::UnaryOp(ref op) if matches!(op.operator, ast::UnaryOperator::Not) => {
+                self.lower_branch(&op.operand, !jump_if, body, jumps)
+            }
+            ast::Expression::BinaryOp(ref op) if op.operator.is_logical() => {
+                let is_and = matches!(op.operator, ast::BinaryOperator::And);
+                if is_and == jump_if {
+                    // Both operands must agree, so the right one is skipped if the left one does not
+                    let mut skip = Vec::new();
+                    self.lower_branch(&op.left, !jump_if, body, &mut skip);
+                    self.lower_branch(&op.right, jump_if, body, jumps);
+                    let end_addr = body.len();
+                    patch_jumps(body, &skip, end_addr);
+                } else {
+                    // Either operand is enough to jump
+                    self.lower_branch(&op.left, jump_if, body, jumps);
+                    self.lower_branch(&op.right, jump_if, body, jumps);
+                }
+            }
+            _ => {
+                let mut cond = self.lower_expression(condition);
+                if !jump_if {
+                    cond = ir::Expression::Intrinsic(Box::new(ir::Intrinsic::Not(cond)));
+                }
+                jumps.push(body.len());
+                body.push(ir::Statement::Branch(cond, UNKNOWN_ADDR));
+            }
         }
     }
 
-    fn lower_statement(&mut self, s: &ast::Statement, body: &mut Vec<ir::Statement>, parent_method: &ast::MethodDecl) {
+    fn lower_statement(&mut self, s: &ast::Statement, body: &mut Vec<ir::Statement>) {
         match *s {
             ast::Statement::Assign(ref assign) => {
-                body.push(self.lower_assignment(assign.label, &assign.expr, parent_method));
+                let place = self.lower_place(&assign.target);
+                let value = self.lower_expression(&assign.expr);
+                body.push(ir::Statement::Assign(ir::Assign { place, operator: assign.operator, value }));
             }
             ast::Statement::Expression(ref expr) => {
-                let expr = self.lower_expression(expr, parent_method);
+                let expr = self.lower_expression(expr);
                 body.push(ir::Statement::Expression(expr));
             }
             ast::Statement::Return(ref ret) => {
-                let ret_ty = self.query_engine.query_return_type(parent_method.label.assert_as_method_decl());
-
-                let void_id = self.query_engine.types_mut().get_id(analysis::Type::Void);
-                let expr_ty = ret.expr.as_ref().map(|e| self.query_engine.query_expr_type(e.label()).unwrap())
-                                               .unwrap_or(void_id);
-
-                if !self.query_engine.types().unify(ret_ty, expr_ty) {
-                    panic!("Type mismatch in return statement: {:?} and {:?}", ret_ty, expr_ty);
-                }
-
-                let expr = ret.expr.as_ref().map(|r| self.lower_expression(r, parent_method));
+                let expr = if self.in_constructor {
+                    Some(ir::Expression::VarRead(VarId::this()))
+                } else {
+                    ret.expr.as_ref().map(|r| self.lower_expression(r))
+                };
                 body.push(ir::Statement::Return(expr));
             }
             ast::Statement::VarDecl(ref var_decl) => {
                 // Track declared variables
-                self.var_tracker.var_decl(var_decl.label.assert_as_var_decl());
+                let var_id = self.var_tracker.var_decl(var_decl.label.assert_as_var_decl());
 
                 // Generate code
-                body.push(ir::Statement::VarDecl);
+                body.push(ir::Statement::VarDecl(var_id));
                 if let Some(ref expr) = var_decl.expr {
-                    body.push(self.lower_assignment(var_decl.label, expr, parent_method));
+                    let value = self.lower_expression(expr);
+                    body.push(ir::Statement::assign(var_id, value));
                 }
             }
             ast::Statement::IfThenElse(ref ite) => {
-                // Ensure the condition is well types
-                let ty = self.query_engine.query_expr_type(ite.condition.label()).expect("If condition has no type!");
-                let bool_ty = self.query_engine.types().bool_ty();
-                self.query_engine.types_mut().unify(ty, bool_ty);
+                // Skip `then` if the condition does not hold
+                let mut else_jumps = Vec::new();
+                self.lower_branch(&ite.condition, false, body, &mut else_jumps);
+                self.lower_block(&ite.then, body);
+
+                if ite.else_.is_empty() {
+                    let end_addr = body.len();
+                    patch_jumps(body, &else_jumps, end_addr);
+                } else {
+                    // Skip `else` after running `then`
+                    let end_jump = body.len();
+                    body.push(ir::Statement::Jump(UNKNOWN_ADDR));
+
+                    let else_addr = body.len();
+                    patch_jumps(body, &else_jumps, else_addr);
+                    self.lower_block(&ite.else_, body);
+                    let end_addr = body.len();
+                    patch_jumps(body, &[end_jump], end_addr);
+                }
+            }
+            ast::Statement::While(ref while_) => {
+                // Jump past the body if the condition does not hold
+                let start_addr = body.len();
+                let mut exit_jumps = Vec::new();
+                self.lower_branch(&while_.condition, false, body, &mut exit_jumps);
+
+                // Generate code for the body, followed by a jump back to the condition
+                self.enter_loop();
+                self.lower_block(&while_.body, body);
+                body.push(ir::Statement::Jump(start_addr));
+                let end_addr = body.len();
 
-                // Add a nop to be replaced later
-                let branch_i = body.len();
-                body.push(ir::Statement::Nop);
+                patch_jumps(body, &exit_jumps, end_addr);
+                self.exit_loop(body, start_addr, end_addr);
+            }
+            ast::Statement::DoWhile(ref do_while) => {
+                // Run the body, and go back to its start while the condition holds
+                let start_addr = body.len();
+                self.enter_loop();
+                self.lower_block(&do_while.body, body);
+                let condition_addr = body.len();
+                let mut repeat_jumps = Vec::new();
+                self.lower_branch(&do_while.condition, true, body, &mut repeat_jumps);
+                patch_jumps(body, &repeat_jumps, start_addr);
+                let end_addr = body.len();
+                self.exit_loop(body, condition_addr, end_addr);
+            }
+            ast::Statement::For(ref for_) => {
+                // Variables declared by the initializer live until the end of the loop
+                let live_vars = self.var_tracker.enter_block();
+                if let Some(ref init) = for_.init {
+                    self.lower_statement(init, body);
+                }
 
-                // Generate code for `else`
-                self.lower_block(&ite.else_, body, parent_method);
-                let then_addr = body.len() + 1; // We need to skip the nop below
+                // Jump past the body if the condition does not hold
+                let start_addr = body.len();
+                let mut exit_jumps = Vec::new();
+                if let Some(ref condition) = for_.condition {
+                    self.lower_branch(condition, false, body, &mut exit_jumps);
+                }
 
-                // Add a second nop to be replaced later
-                let jump_i = body.len();
-                body.push(ir::Statement::Nop);
+                // Generate code for the body and the step, followed by a jump back to the condition
+                self.enter_loop();
+                self.lower_block(&for_.body, body);
+                let step_addr = body.len();
+                if let Some(ref step) = for_.step {
+                    self.lower_statement(step, body);
+                }
+                body.push(ir::Statement::Jump(start_addr));
+                let end_addr = body.len();
 
-                // Generate code for `then`
-                self.lower_block(&ite.then, body, parent_method);
+                patch_jumps(body, &exit_jumps, end_addr);
+                self.exit_loop(body, step_addr, end_addr);
+
+                let declared = self.var_tracker.exit_block(live_vars);
+                if declared > 0 {
+                    body.push(ir::Statement::FreeVars(declared));
+                }
+            }
+            ast::Statement::Foreach(ref foreach) => {
+                // The collection and the index of the current element are kept in hidden variables
+                let live_vars = self.var_tracker.enter_block();
+                let array = self.var_tracker.temp();
+                let collection = self.lower_expression(&foreach.collection);
+                body.push(ir::Statement::VarDecl(array));
+                body.push(ir::Statement::assign(array, collection));
+                let index = self.var_tracker.temp();
+                body.push(ir::Statement::VarDecl(index));
+                body.push(ir::Statement::assign(index, ir::Expression::Literal(ir::Literal::Int(0))));
+
+                // Leave the loop once the index is past the last element
+                let start_addr = body.len();
+                let length = ir::Intrinsic::ArrayLength(ir::Expression::VarRead(array));
+                let done = ir::Intrinsic::IntOp(ast::BinaryOperator::Ge, ir::Expression::VarRead(index), ir::Expression::Intrinsic(Box::new(length)));
+                body.push(ir::Statement::Branch(ir::Expression::Intrinsic(Box::new(done)), UNKNOWN_ADDR));
+
+                // The loop variable is declared for each element, and only lives in the body
+                self.enter_loop();
+                let body_vars = self.var_tracker.enter_block();
+                let var = self.var_tracker.var_decl(foreach.var.label.assert_as_var_decl());
+                let element = ir::Intrinsic::ArrayElement(ir::Expression::VarRead(array), ir::Expression::VarRead(index));
+                body.push(ir::Statement::VarDecl(var));
+                body.push(ir::Statement::assign(var, ir::Expression::Intrinsic(Box::new(element))));
+                self.lower_block(&foreach.body, body);
+                let declared = self.var_tracker.exit_block(body_vars);
+                body.push(ir::Statement::FreeVars(declared));
+
+                // Move to the next element and go back to the start
+                let next_addr = body.len();
+                let next = ir::Intrinsic::IntOp(ast::BinaryOperator::Add, ir::Expression::VarRead(index), ir::Expression::Literal(ir::Literal::Int(1)));
+                body.push(ir::Statement::assign(index, ir::Expression::Intrinsic(Box::new(next))));
+                body.push(ir::Statement::Jump(start_addr));
                 let end_addr = body.len();
 
-                // Put a branch before the `else` block
-                let cond = self.lower_expression(&ite.condition, parent_method);
-                body[branch_i] = ir::Statement::Branch(cond, then_addr);
+                patch_jumps(body, &[start_addr], end_addr);
+                self.exit_loop(body, next_addr, end_addr);
 
-                // Put a jump after the `then` block
-                body[jump_i] = ir::Statement::Jump(end_addr);
+                let declared = self.var_tracker.exit_block(live_vars);
+                body.push(ir::Statement::FreeVars(declared));
+            }
+            ast::Statement::Break(_) => {
+                let jump_i = self.lower_loop_jump(body);
+                self.loops.last_mut().unwrap().breaks.push(jump_i);
+            }
+            ast::Statement::Continue(_) => {
+                let jump_i = self.lower_loop_jump(body);
+                self.loops.last_mut().unwrap().continues.push(jump_i);
+            }
+            ast::Statement::Error(_) => {
+                unreachable!("Programs with syntax errors cannot be lowered")
             }
         }
     }
 
-    fn lower_expression(&mut self, e: &ast::Expression, parent_method: &ast::MethodDecl) -> ir::Expression {
-        // Ensure everything is well typed
-        self.query_engine.query_expr_type(e.label());
+    fn lower_expression(&mut self, e: &ast::Expression) -> ir::Expression {
         // Generate code
         match *e {
             ast::Expression::BinaryOp(ref bin_op) => {
                 // Generate code
-                let left = self.lower_expression(&bin_op.left, parent_method);
-                let right = self.lower_expression(&bin_op.right, parent_method);
-                ir::Expression::Intrinsic(Box::new(
-                    ir::Intrinsic::IntOp(bin_op.operator, left, right)))
+                let left = self.lower_expression(&bin_op.left);
+                let right = self.lower_expression(&bin_op.right);
+                let intrinsic = match bin_op.operator {
+                    // Only one of the branches of a conditional is evaluated
+                    ast::BinaryOperator::And => {
+                        let else_ = ir::Expression::Literal(ir::Literal::Bool(false));
+                        return ir::Expression::Conditional(Box::new(ir::Conditional { condition: left, then: right, else_ }));
+                    }
+                    ast::BinaryOperator::Or => {
+                        let then = ir::Expression::Literal(ir::Literal::Bool(true));
+                        return ir::Expression::Conditional(Box::new(ir::Conditional { condition: left, then, else_: right }));
+                    }
+                    op if op.is_equality() => ir::Intrinsic::Equality(op, left, right),
+                    op => ir::Intrinsic::IntOp(op, left, right)
+                };
+                ir::Expression::Intrinsic(Box::new(intrinsic))
+            }
+            ast::Expression::UnaryOp(ref op) if op.operator.is_increment() => {
+                let place = self.lower_place(&op.operand);
+                let delta = match op.operator {
+                    ast::UnaryOperator::PreIncrement | ast::UnaryOperator::PostIncrement => 1,
+                    _ => -1
+                };
+                ir::Expression::Increment(Box::new(ir::Increment { place, delta, postfix: op.operator.is_postfix() }))
+            }
+            ast::Expression::UnaryOp(ref op) => {
+                let operand = self.lower_expression(&op.operand);
+                let intrinsic = match op.operator {
+                    ast::UnaryOperator::Not => ir::Intrinsic::Not(operand),
+                    // `-x` desugars into `0 - x`
+                    _ => ir::Intrinsic::IntOp(ast::BinaryOperator::Sub, ir::Expression::Literal(ir::Literal::Int(0)), operand)
+                };
+                ir::Expression::Intrinsic(Box::new(intrinsic))
             }
             ast::Expression::FieldAccess(ref fa) => {
-                let target = self.lower_expression(&fa.target, parent_method);
-                let field_label = self.query_engine.query_field(fa.label.assert_as_var_use());
-                let field_id = self.fields[&field_label];
-                ir::Expression::FieldAccess(Box::new(ir::FieldAccess { target, field_id }))
+                // Arrays have a `Length` property instead of fields
+                let target_ty = self.query_engine.query_expr_type(fa.target.label());
+                if target_ty.and_then(|ty| self.query_engine.types().property_ty(ty, &fa.field_name)).is_some() {
+                    let array = self.lower_expression(&fa.target);
+                    return ir::Expression::Intrinsic(Box::new(ir::Intrinsic::ArrayLength(array)));
+                }
+
+                field_value(self.lower_field(fa))
             }
             ast::Expression::Literal(ref l) => {
-                ir::Expression::Literal(match &l.kind {
-                    &ast::LiteralKind::Bool(b) => ir::Literal::Bool(b),
-                    &ast::LiteralKind::Int(i) => ir::Literal::Int(i),
-                    &ast::LiteralKind::String(ref s) => ir::Literal::String(s.clone()),
-                    &ast::LiteralKind::Array(_, ref exprs) => {
-                        let exprs = exprs.iter().map(|e| self.lower_expression(e, parent_method)).collect();
+                ir::Expression::Literal(match l.kind {
+                    ast::LiteralKind::Bool(b) => ir::Literal::Bool(b),
+                    ast::LiteralKind::Int(i) => ir::Literal::Int(i),
+                    ast::LiteralKind::String(ref s) => ir::Literal::String(s.clone()),
+                    ast::LiteralKind::Array(_, ref exprs) => {
+                        let exprs = exprs.iter().map(|e| self.lower_expression(e)).collect();
                         ir::Literal::Array(exprs)
                     },
-                    &ast::LiteralKind::Null => ir::Literal::Null
+                    ast::LiteralKind::Null => ir::Literal::Null
                 })
             }
             ast::Expression::MethodCall(ref mc) => {
-                let (method_id, is_static) = if mc.is_console_write_line() {
+                let (method_id, is_static, slot) = if mc.is_console_write_line() {
                     // Handle Console.WriteLine
-                    (MethodId(0), true)
+                    (MethodId(0), true, None)
                 } else {
-                    let label = self.query_engine.query_method_decl(mc.label.assert_as_method_use());
+                    let label = self.query_engine.query_method_decl(mc.label.assert_as_method_use()).expect("Unknown method");
+                    if let Some(&slot) = self.interface_slots.get(&label) {
+                        let mut arguments = vec![self.lower_call_target(mc)];
+                        arguments.extend(mc.args.iter().map(|arg| self.lower_expression(arg)));
+                        return ir::Expression::InterfaceCall(ir::VirtualCall { slot, arguments });
+                    }
+
                     let is_static = self.query_engine.query_is_static(label);
-                    let method_id = self.methods[&label];
-                    (method_id, is_static)
+                    // Abstract methods are always called through the vtable (calls through `base`
+                    // are rejected by the type checker)
+                    let method_id = self.methods.get(&label).cloned().unwrap_or(ABSTRACT_METHOD);
+                    (method_id, is_static, self.method_slots.get(&label).cloned())
                 };
 
                 let mut arguments = Vec::new();
                 if !is_static {
                     // Push this (method is not static)
-                    arguments.push(self.lower_expression(&mc.target, parent_method));
+                    arguments.push(self.lower_call_target(mc));
                 }
 
                 for arg in &mc.args {
-                    arguments.push(self.lower_expression(arg, parent_method));
+                    arguments.push(self.lower_expression(arg));
                 }
 
-                ir::Expression::MethodCall(ir::MethodCall { method_id, arguments })
+                // Calls through `base` are bound to the implementation of the base class
+                match slot {
+                    Some(slot) if !matches!(mc.target.as_deref(), Some(&ast::Expression::Base(_))) => {
+                        ir::Expression::VirtualCall(ir::VirtualCall { slot, arguments })
+                    }
+                    _ => ir::Expression::MethodCall(ir::MethodCall { method_id, arguments })
+                }
             }
             ast::Expression::New(ref n) => {
-                let class_label = self.query_engine.query_class_decl(&n.class_name);
-                ir::Expression::NewObject(class_label)
+                let class_label = self.query_engine.query_class_decl_at(&n.class_name, n.label).expect("Unknown class");
+                let method_id = match self.query_engine.query_constructor(n.label) {
+                    Some(label) => self.constructors[&label],
+                    None => self.default_constructors[&class_label]
+                };
+
+                let mut arguments = vec![ir::Expression::NewObject(class_label)];
+                arguments.extend(n.args.iter().map(|arg| self.lower_expression(arg)));
+                ir::Expression::MethodCall(ir::MethodCall { method_id, arguments })
+            }
+            ast::Expression::NewArray(ref new_array) => {
+                let length = self.lower_expression(&new_array.length);
+                let array_ty = self.query_engine.query_expr_type(e.label()).expect("Array creation without a type");
+                let elem_ty = self.query_engine.types().element_ty(array_ty).expect("Array creation without an array type");
+                let value = ir::Expression::Literal(self.default_value(elem_ty));
+                ir::Expression::Intrinsic(Box::new(ir::Intrinsic::NewArray(length, value)))
+            }
+            ast::Expression::Index(ref index) => {
+                let array = self.lower_expression(&index.target);
+                let index = self.lower_expression(&index.index);
+                ir::Expression::Intrinsic(Box::new(ir::Intrinsic::ArrayElement(array, index)))
             }
             ast::Expression::Identifier(ref i) => {
+                // The identifier may name a field of the current class
+                if let Some(field) = self.query_engine.query_field(i.label.assert_as_var_use()) {
+                    return field_value(self.lower_unqualified_field(field));
+                }
+
                 let var_label = self.query_engine.query_var_decl(i.label);
                 ir::Expression::VarRead(self.var_tracker.get_var_id(var_label))
             }
-            ast::Expression::This(_) => {
-                if parent_method.is_static {
-                    panic!("`this` keyword used inside static method");
-                }
-
+            ast::Expression::This(_) | ast::Expression::Base(_) => {
                 // When used from a method, the first parameter will always be this
                 ir::Expression::VarRead(VarId::this())
             }
+            ast::Expression::Error(_) => {
+                unreachable!("Programs with syntax errors cannot be lowered")
+            }
+        }
+    }
+
+    /// Lowers the object on which an instance method is called, which is `this` for calls without
+    /// a target
+    fn lower_call_target(&mut self, mc: &ast::MethodCall) -> ir::Expression {
+        match mc.target {
+            Some(ref target) => self.lower_expression(target),
+            None => ir::Expression::VarRead(VarId::this())
+        }
+    }
+
+    /// Lowers a field access to the place of the field
+    ///
+    /// Note: the target of static fields is the name of their class, so it is not evaluated
+    fn lower_field(&mut self, fa: &ast::FieldAccess) -> ir::Place {
+        let field_label = self.query_engine.query_field(fa.label.assert_as_var_use()).expect("Unknown field");
+        if let Some(&field) = self.static_fields.get(&field_label) {
+            return ir::Place::Static(field);
+        }
+
+        let target = self.lower_expression(&fa.target);
+        let field_id = self.fields[&field_label];
+        ir::Place::Field(Box::new(ir::FieldAccess { target, field_id }))
+    }
+
+    /// Lowers a field named without a target to its place, which is on `this` for instance fields
+    fn lower_unqualified_field(&mut self, field_label: labels::VarDecl) -> ir::Place {
+        if let Some(&field) = self.static_fields.get(&field_label) {
+            return ir::Place::Static(field);
+        }
+
+        let target = ir::Expression::VarRead(VarId::this());
+        let field_id = self.fields[&field_label];
+        ir::Place::Field(Box::new(ir::FieldAccess { target, field_id }))
+    }
+
+    /// Lowers the target of an assignment or an increment
+    fn lower_place(&mut self, target: &ast::Expression) -> ir::Place {
+        match *target {
+            ast::Expression::Identifier(ref i) => {
+                if let Some(field) = self.query_engine.query_field(i.label.assert_as_var_use()) {
+                    return self.lower_unqualified_field(field);
+                }
+
+                let decl_label = self.query_engine.query_var_decl(i.label);
+                ir::Place::Var(self.var_tracker.get_var_id(decl_label))
+            }
+            ast::Expression::FieldAccess(ref fa) => self.lower_field(fa),
+            ast::Expression::Index(ref index) => {
+                let array = self.lower_expression(&index.target);
+                let index = self.lower_expression(&index.index);
+                ir::Place::Element(Box::new((array, index)))
+            }
+            _ => unreachable!("Only variables, fields and array elements can be assigned to")
         }
     }
 
-    fn lower_assignment(&mut self, target: ast::Label, expr: &ast::Expression, parent_method: &ast::MethodDecl) -> ir::Statement {
-        // Note: right now, the only lvalues are variables. No array indexing.
-        let decl_label = self.query_engine.query_var_decl(target);
-        let value = self.lower_expression(expr, parent_method);
-        let var_id = self.var_tracker.get_var_id(decl_label);
-        ir::Statement::Assign(ir::Assign { var_id, value })
+    /// Returns the value of fields and array elements of the given type before they are assigned
+    fn default_value(&self, ty: TypeId) -> ir::Literal {
+        let types = self.query_engine.types();
+        if ty == types.int_ty() {
+            ir::Literal::Int(0)
+        } else if ty == types.bool_ty() {
+            ir::Literal::Bool(false)
+        } else {
+            ir::Literal::Null
+        }
+    }
+}
+
+/// Returns the value stored in the place of a field
+fn field_value(place: ir::Place) -> ir::Expression {
+    match place {
+        ir::Place::Field(field) => ir::Expression::FieldAccess(field),
+        ir::Place::Static(field) => ir::Expression::StaticField(field),
+        _ => unreachable!("Fields are lowered to field places")
+    }
+}
+
+/// Points the given jumps, which were generated before their target was known, to `addr`
+fn patch_jumps(body: &mut [ir::Statement], jumps: &[usize], addr: usize) {
+    for &i in jumps {
+        match body[i] {
+            ir::Statement::Branch(_, ref mut target) | ir::Statement::Jump(ref mut target) => *target = addr,
+            _ => unreachable!("Only jumps can be patched")
+        }
     }
 }
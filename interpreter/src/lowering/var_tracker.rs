@@ -5,17 +5,48 @@ use super::VarId;
 
 #[derive(Default)]
 pub struct VarTracker {
-    vars: HashMap<labels::VarDecl, VarId>
+    vars: HashMap<labels::VarDecl, VarId>,
+    /// The number of variables in scope, which is also the id of the next declared variable
+    live: usize
 }
 
 impl VarTracker {
     pub fn reset(&mut self) {
-        self.vars.clear()
+        self.vars.clear();
+        self.live = 0;
     }
 
-    pub fn var_decl(&mut self, var_decl: labels::VarDecl) {
-        let var_id = VarId(self.vars.len());
+    pub fn var_decl(&mut self, var_decl: labels::VarDecl) -> VarId {
+        let var_id = VarId(self.live);
         self.vars.insert(var_decl, var_id);
+        self.live += 1;
+        var_id
+    }
+
+    /// Returns the number of variables in scope
+    pub fn live(&self) -> usize {
+        self.live
+    }
+
+    /// Declares a variable that does not appear in the source code (e.g. the index of a `foreach` loop)
+    pub fn temp(&mut self) -> VarId {
+        let var_id = VarId(self.live);
+        self.live += 1;
+        var_id
+    }
+
+    /// Returns the state to be restored by `exit_block` at the end of the block
+    pub fn enter_block(&self) -> usize {
+        self.live
+    }
+
+    /// Takes the variables declared in the block out of scope, returning how many there were
+    ///
+    /// Note: the ids of those variables are reused by the variables declared afterwards
+    pub fn exit_block(&mut self, live_before: usize) -> usize {
+        let declared = self.live - live_before;
+        self.live = live_before;
+        declared
     }
 
     pub fn get_var_id(&mut self, var_decl: labels::VarDecl) -> VarId {
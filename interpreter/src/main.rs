@@ -4,17 +4,40 @@ mod interpreter;
 mod ir;
 mod lowering;
 
+use std::env;
+use std::fs;
+use std::process;
+
 use frontend::analysis::QueryEngine;
-use frontend::sample_programs;
+use frontend::format::{self, BraceStyle, Indent, Style};
+use frontend::{ast, sample_programs, syntax};
 use lowering::LoweringContext;
 
 fn main() {
-    let hw = sample_programs::variables();
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "fmt") {
+        return fmt(&args[1..]);
+    }
+
+    // Run the given C# files, or one of the sample programs if none was given
+    let paths = args;
+    let hw = if paths.is_empty() {
+        sample_programs::variables()
+    } else {
+        parse_files(&paths)
+    };
     println!("=== Program:");
     println!("{}", hw);
 
-    // Compile
+    // Check
     let mut query_engine = QueryEngine::new(&hw);
+    query_engine.check_program();
+    if query_engine.diagnostics().has_errors() {
+        eprint!("{}", query_engine.diagnostics().render(&hw.source_map));
+        process::exit(1);
+    }
+
+    // Compile
     let output = LoweringContext::new(&hw, &mut query_engine).lower_program();
 
     // Run
@@ -22,16 +45,645 @@ fn main() {
     interpreter::run(&output.program, output.classes);
 }
 
+/// Formats the given C# files in place or, with `--check`, reports the files that are not formatted
+///
+/// Usage: `fmt [--check] [--indent=<n>|tabs] [--brace-style=same-line|next-line] [--max-width=<n>] <files>`
+fn fmt(args: &[String]) {
+    let mut check = false;
+    let mut style = Style::default();
+    let mut paths = Vec::new();
+    for arg in args {
+        let (flag, value) = match arg.find('=') {
+            Some(i) => (&arg[..i], &arg[i + 1..]),
+            None => (&arg[..], "")
+        };
+
+        match (flag, value) {
+            ("--check", "") => check = true,
+            ("--indent", "tabs") => style.indent = Indent::Tabs,
+            ("--indent", n) if n.parse::<usize>().is_ok() => style.indent = Indent::Spaces(n.parse().unwrap()),
+            ("--brace-style", "same-line") => style.brace_style = BraceStyle::SameLine,
+            ("--brace-style", "next-line") => style.brace_style = BraceStyle::NextLine,
+            ("--max-width", n) if n.parse::<usize>().is_ok() => style.max_width = n.parse().unwrap(),
+            _ if arg.starts_with("--") => {
+                eprintln!("error: invalid option `{}`", arg);
+                process::exit(1);
+            }
+            _ => paths.push(arg.clone())
+        }
+    }
+
+    let program = parse_files(&paths);
+    let mut unformatted = false;
+    for file in program.source_map.files() {
+        let formatted = format::format_file(&program, file.id, style);
+        if formatted == file.src {
+            continue;
+        }
+
+        if check {
+            println!("`{}` is not formatted", file.name);
+            unformatted = true;
+        } else if let Err(e) = fs::write(&file.name, formatted) {
+            eprintln!("error: unable to write `{}`: {}", file.name, e);
+            process::exit(1);
+        }
+    }
+
+    if unformatted {
+        process::exit(1);
+    }
+}
+
+fn parse_files(paths: &[String]) -> ast::Program {
+    let files = paths.iter().map(|path| {
+        let src = fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("error: unable to read `{}`: {}", path, e);
+            process::exit(1)
+        });
+
+        (path.clone(), src)
+    }).collect();
+
+    let (program, diagnostics) = syntax::parse_files(files);
+    if diagnostics.has_errors() {
+        eprint!("{}", diagnostics.render(&program.source_map));
+        process::exit(1);
+    }
+
+    program
+}
+
 #[cfg(test)]
 mod test {
+    use frontend::analysis::QueryEngine;
+    use frontend::syntax;
     use interpreter;
     use ir::*;
+    use lowering::LoweringContext;
+
+    /// Runs the given C# program, returning what it printed
+    fn run_source(src: &str) -> String {
+        let (program, diagnostics) = syntax::parse("test.cs", src);
+        assert!(diagnostics.is_empty(), "{}", diagnostics.render(&program.source_map));
+        let mut query_engine = QueryEngine::new(&program);
+        query_engine.check_program();
+        assert!(!query_engine.diagnostics().has_errors(), "{}", query_engine.diagnostics().render(&program.source_map));
+
+        let output = LoweringContext::new(&program, &mut query_engine).lower_program();
+        let mut printed = Vec::new();
+        interpreter::run_with_output(&output.program, output.classes, &mut printed);
+        String::from_utf8(printed).unwrap()
+    }
 
     #[test]
     fn does_not_crash() {
         interpreter::run(&hello_world(), Default::default());
     }
 
+    #[test]
+    fn loops() {
+        let src = "\
+class Program {
+    static void Main() {
+        int i = 3;
+        bool more = true;
+        while (more) {
+            int twice = i * 2;
+            Console.WriteLine(twice);
+            i = i - 1;
+            more = Program.Positive(i);
+        }
+        do {
+            string s = \"once\";
+            Console.WriteLine(s);
+        } while (false);
+        while (false) Console.WriteLine(\"never\");
+        Console.WriteLine(i);
+    }
+    static bool Positive(int x) {
+        if (0 == x) { return false; }
+        return true;
+    }
+}";
+        assert_eq!(run_source(src), "6\n4\n2\nonce\n0\n");
+    }
+
+    #[test]
+    fn for_loops() {
+        let src = "\
+class Program {
+    static void Main() {
+        int[] values = new int[] { 1, 2, 3 };
+        int sum = 0;
+        foreach (int x in values) {
+            int square = x * x;
+            sum = sum + square;
+        }
+        Console.WriteLine(sum);
+        foreach (string s in new string[] { }) Console.WriteLine(s);
+        for (int i = 0; Program.Differ(i, 3); i = i + 1) {
+            int[] row = new int[] { i, i };
+            foreach (int x in row) Console.WriteLine(x);
+        }
+        int n = 2;
+        for (; Program.Differ(n, 0); ) n = n - 1;
+        Console.WriteLine(n);
+    }
+    static bool Differ(int x, int y) {
+        if (x == y) { return false; }
+        return true;
+    }
+}";
+        assert_eq!(run_source(src), "14\n0\n0\n1\n1\n2\n2\n0\n");
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let src = "\
+class Program {
+    static void Main() {
+        int total = 0;
+        for (int i = 0; true; i = i + 1) {
+            int a = i * 10;
+            if (i == 1) { int b = 0; continue; }
+            if (i == 3) { int c = 0; break; }
+            total = total + a;
+        }
+        Console.WriteLine(total);
+        foreach (int x in new int[] { 1, 2, 3, 4 }) {
+            if (x == 2) continue;
+            int y = x;
+            while (true) {
+                if (y == x) { string s = \"inner\"; break; }
+            }
+            if (x == 3) break;
+            Console.WriteLine(y);
+        }
+        int n = 0;
+        do {
+            n = n + 1;
+            if (n == 2) continue;
+            Console.WriteLine(n);
+        } while (Program.Differ(n, 3));
+        Console.WriteLine(total);
+    }
+    static bool Differ(int x, int y) {
+        if (x == y) { return false; }
+        return true;
+    }
+}";
+        assert_eq!(run_source(src), "20\n1\n1\n3\n20\n");
+    }
+
+    #[test]
+    fn comparisons() {
+        let src = "\
+class Program {
+    static void Main() {
+        int total = 0;
+        for (int i = 0; i < 10; i = i + 1) {
+            if (i >= 8) break;
+            if (i != 3) total = total + i;
+        }
+        Console.WriteLine(total);
+        Console.WriteLine(1 + 2 < 2 * 2 == true);
+        Console.WriteLine(3 <= 2);
+        Console.WriteLine(3 > 2);
+        Console.WriteLine(\"a\" == \"a\");
+        Console.WriteLine(false != true);
+        Program p = new Program();
+        Program q = p;
+        Console.WriteLine(p == q);
+        Console.WriteLine(p == new Program());
+        Console.WriteLine(p != null);
+        Console.WriteLine(null == null);
+        int[] a = new int[] { 1 };
+        Console.WriteLine(a == new int[] { 1 });
+    }
+}";
+        assert_eq!(run_source(src), "25\ntrue\nfalse\ntrue\ntrue\ntrue\ntrue\nfalse\ntrue\ntrue\nfalse\n");
+    }
+
+    #[test]
+    fn short_circuit() {
+        let src = "\
+class Program {
+    static void Main() {
+        if (Program.Loud(false) && Program.Loud(true)) Console.WriteLine(\"and\");
+        if (!(Program.Loud(true) || Program.Loud(true))) { } else { Console.WriteLine(\"or\"); }
+        int i = 0;
+        while (i < 5 && !(i == 2)) i = i + 1;
+        Console.WriteLine(i);
+        bool b = Program.Loud(false) || Program.Loud(true) && !Program.Loud(false);
+        Console.WriteLine(b);
+        Console.WriteLine(true && Program.Loud(false) || !true);
+    }
+    static bool Loud(bool b) {
+        Console.WriteLine(b);
+        return b;
+    }
+}";
+        assert_eq!(run_source(src), "false\ntrue\nor\n2\nfalse\ntrue\nfalse\ntrue\nfalse\nfalse\n");
+    }
+
+    #[test]
+    fn increments_and_compound_assignment() {
+        let src = "\
+class Program {
+    static void Main() {
+        int x = 5;
+        Console.WriteLine(x++);
+        Console.WriteLine(++x);
+        Console.WriteLine(x-- - --x);
+        Console.WriteLine(-x);
+        Console.WriteLine(- -x);
+        x += 10;
+        x -= 1;
+        x *= 3;
+        x /= 2;
+        Console.WriteLine(x);
+        int sum = 0;
+        for (int i = 0; i < 4; i++) sum += Program.Twice(i);
+        Console.WriteLine(sum);
+    }
+    static int Twice(int n) {
+        n *= 2;
+        return n;
+    }
+}";
+        assert_eq!(run_source(src), "5\n7\n2\n-5\n5\n21\n12\n");
+    }
+
+    #[test]
+    fn field_assignment() {
+        let src = "\
+class Counter {
+    int count;
+    Counter next;
+    void Reset() { this.count = 0; }
+    int Tick() {
+        this.count += 2;
+        return this.count++;
+    }
+}
+class Program {
+    static void Main() {
+        Counter c = new Counter();
+        c.Reset();
+        Counter alias = c;
+        alias.next = new Counter();
+        c.next.count = 40;
+        Console.WriteLine(c.Tick());
+        Console.WriteLine(alias.count);
+        Console.WriteLine(++c.next.count + c.next.count--);
+        Console.WriteLine(alias.next.count);
+    }
+}";
+        assert_eq!(run_source(src), "2\n3\n82\n40\n");
+    }
+
+    #[test]
+    fn arrays() {
+        let src = "\
+class Program {
+    static void Main() {
+        int[] squares = new int[4];
+        for (int i = 0; i < squares.Length; i++) squares[i] = i * i;
+        Console.WriteLine(squares);
+        squares[1] += 10;
+        squares[2]++;
+        Console.WriteLine(squares[1] + squares[2]);
+
+        bool[][] grid = new bool[2][];
+        grid[1] = new bool[3];
+        grid[1][2] = !grid[1][0];
+        Console.WriteLine(grid);
+        Console.WriteLine(new string[2]);
+        Console.WriteLine(new int[] { 7, 8, 9 }[2]);
+    }
+}";
+        assert_eq!(run_source(src), "[0, 1, 4, 9]\n16\n[null, [false, false, true]]\n[null, null]\n9\n");
+    }
+
+    #[test]
+    fn constructors() {
+        let src = "\
+class Point {
+    int x = Point.Log(\"x\", 1);
+    int y;
+    bool moved;
+    string name;
+    Point() : this(7) { }
+    Point(int x) : this(x, x * 2) { Console.WriteLine(\"Point(int)\"); }
+    Point(int x, int y) {
+        Console.WriteLine(this.x);
+        this.x = x;
+        this.y = y;
+        if (x > 100) {
+            return;
+        }
+        this.moved = true;
+    }
+    static int Log(string what, int value) {
+        Console.WriteLine(what);
+        return value;
+    }
+    int Add(int dx) { return this.x + dx; }
+}
+class Program {
+    static void Main() {
+        Point p = new Point();
+        Console.WriteLine(p);
+        Console.WriteLine(new Point(200, 1).moved);
+        Console.WriteLine(p.Add(35));
+        Console.WriteLine(new Program());
+    }
+}";
+        let expected = "x\n1\nPoint(int)\nPoint {\n    x: 7,\n    y: 14,\n    moved: true,\n    name: null,\n}\nx\n1\nfalse\n42\nProgram {\n}\n";
+        assert_eq!(run_source(src), expected);
+    }
+
+    #[test]
+    fn static_fields() {
+        let src = "\
+class Counter {
+    static int created;
+    static int step = Counter.Log(\"step\", 2);
+    int id = next++;
+    static int next = 1;
+    static Counter() {
+        Console.WriteLine(\"static Counter\");
+        created += 100;
+    }
+    Counter() { created += step; }
+    static int Log(string what, int value) {
+        Console.WriteLine(what);
+        return value;
+    }
+}
+class Unused {
+    static Unused() { Console.WriteLine(\"never\"); }
+}
+class Program {
+    static string greeting = \"static Program\";
+    static Program() { Console.WriteLine(greeting); }
+    static void Main() {
+        Console.WriteLine(\"Main\");
+        Console.WriteLine(Counter.created);
+        Counter a = new Counter();
+        Console.WriteLine(new Counter().id);
+        Counter.created++;
+        Console.WriteLine(Counter.created);
+    }
+}";
+        let expected = "static Program\nMain\nstep\nstatic Counter\n100\n2\n105\n";
+        assert_eq!(run_source(src), expected);
+    }
+
+    #[test]
+    fn instance_fields() {
+        let src = "\
+class Rect {
+    int w;
+    int h = 1;
+    static int count;
+    Rect(int width, int h) {
+        w = width;
+        this.h = h;
+        count++;
+    }
+    int Area() { return w * h; }
+    void Grow() {
+        w++;
+        h += 1;
+    }
+}
+class Square : Rect {
+    Square(int side) : base(side, side) { }
+    int Side() { return w; }
+}
+class Program {
+    static void Main() {
+        Rect r = new Rect(2, 3);
+        Console.WriteLine(r.Area());
+        r.Grow();
+        Console.WriteLine(r.Area());
+        Square s = new Square(4);
+        Console.WriteLine(s.Side());
+        Console.WriteLine(Rect.count);
+    }
+}";
+        assert_eq!(run_source(src), "6\n12\n4\n2\n");
+    }
+
+    #[test]
+    fn inheritance() {
+        let src = "\
+class Animal {
+    int legs = 4;
+    string name;
+    Animal(string name) {
+        Console.WriteLine(name);
+        this.name = name;
+    }
+    int Legs() { return this.legs; }
+}
+class Bird : Animal {
+    bool flies = true;
+    Bird(string name) : base(name) { this.legs = 2; }
+    Bird() : this(\"anon\") { }
+    int Legs() { return base.Legs() * 10; }
+}
+class Base {
+    static Base() { Console.WriteLine(\"static Base\"); }
+    Base() { Console.WriteLine(\"Base\"); }
+}
+class Derived : Base {
+    static Derived() { Console.WriteLine(\"static Derived\"); }
+    int x = Derived.Log(1);
+    static int Log(int x) { Console.WriteLine(\"initializer\"); return x; }
+}
+class Program {
+    static int Legs(Animal a) { return a.Legs(); }
+    static void Main() {
+        Bird b = new Bird();
+        Console.WriteLine(Program.Legs(b));
+        Console.WriteLine(b.Legs());
+        Console.WriteLine(b.flies);
+        Console.WriteLine(new Derived().x);
+    }
+}";
+        let expected = "anon\n2\n20\ntrue\nstatic Derived\ninitializer\nstatic Base\nBase\n1\n";
+        assert_eq!(run_source(src), expected);
+    }
+
+    #[test]
+    fn calls_without_target() {
+        let src = "\
+class A {
+    static int Zero() { return 10; }
+    virtual int One() { return 1; }
+    int Sum() { return One() + Zero(); }
+}
+class B : A {
+    override int One() { return 2; }
+    static int Two() { return Zero() + 2; }
+}
+class Program {
+    static int Seven() { return 7; }
+    static void Main() {
+        A a = new B();
+        Console.WriteLine(a.Sum());
+        Console.WriteLine(B.Two());
+        Console.WriteLine(Seven());
+    }
+}";
+        assert_eq!(run_source(src), "12\n12\n7\n");
+    }
+
+    #[test]
+    fn virtual_dispatch() {
+        let src = "\
+class Shape {
+    virtual string Name() { return \"shape\"; }
+    virtual int Sides() { return 0; }
+    string Describe() { return this.Name(); }
+    void Hello() { Console.WriteLine(\"Shape.Hello\"); }
+}
+class Square : Shape {
+    override string Name() { return \"square\"; }
+    sealed override int Sides() { return base.Sides() + 4; }
+    new void Hello() { Console.WriteLine(\"Square.Hello\"); }
+}
+class Hider : Square {
+    new virtual string Name() { return \"hider\"; }
+}
+class Program {
+    static void Main() {
+        Shape[] shapes = new Shape[] { new Shape(), new Square(), new Hider() };
+        foreach (Shape s in shapes) {
+            Console.WriteLine(s.Describe());
+            Console.WriteLine(s.Sides());
+            s.Hello();
+        }
+        Square square = new Square();
+        square.Hello();
+        Hider hider = new Hider();
+        Console.WriteLine(hider.Name());
+    }
+}";
+        let expected = "shape\n0\nShape.Hello\nsquare\n4\nShape.Hello\nsquare\n4\nShape.Hello\nSquare.Hello\nhider\n";
+        assert_eq!(run_source(src), expected);
+    }
+
+    #[test]
+    fn interface_dispatch() {
+        let src = "\
+interface INamed {
+    string Name();
+}
+interface IShape : INamed {
+    int Sides();
+}
+class Shape : IShape {
+    public virtual string Name() { return \"shape\"; }
+    public int Sides() { return 0; }
+}
+class Square : Shape {
+    public override string Name() { return \"square\"; }
+    public new int Sides() { return 4; }
+}
+class Triangle : Shape, IShape {
+    public new int Sides() { return 3; }
+}
+class Program {
+    static void Main() {
+        IShape[] shapes = new IShape[] { new Shape(), new Square(), new Triangle() };
+        foreach (IShape s in shapes) {
+            INamed named = s;
+            Console.WriteLine(named.Name());
+            Console.WriteLine(s.Sides());
+        }
+    }
+}";
+        let expected = "shape\n0\nsquare\n0\nshape\n3\n";
+        assert_eq!(run_source(src), expected);
+    }
+
+    #[test]
+    fn abstract_methods() {
+        let src = "\
+interface ISized {
+    int Size();
+}
+abstract class Shape : ISized {
+    public abstract string Name();
+    public abstract int Size();
+    public void Describe() { Console.WriteLine(this.Name()); }
+}
+abstract class Polygon : Shape {
+    public override string Name() { return \"polygon\"; }
+}
+class Square : Polygon {
+    public override int Size() { return 4; }
+}
+class Circle : Shape {
+    public override string Name() { return \"circle\"; }
+    public override int Size() { return 1; }
+}
+class Program {
+    static void Main() {
+        ISized[] shapes = new ISized[] { new Square(), new Circle() };
+        foreach (ISized s in shapes) {
+            Console.WriteLine(s.Size());
+        }
+        Shape shape = new Square();
+        shape.Describe();
+    }
+}";
+        let expected = "4\n1\npolygon\n";
+        assert_eq!(run_source(src), expected);
+    }
+
+    #[test]
+    fn overloads() {
+        let src = "\
+class Animal { }
+class Dog : Animal { }
+class Printer {
+    virtual void Print(Animal a) { Console.WriteLine(\"Animal\"); }
+    void Print(Dog d) { Console.WriteLine(\"Dog\"); }
+    void Print(int x) { Console.WriteLine(\"int\"); }
+    void Print(int x, int y) { Console.WriteLine(\"int, int\"); }
+    void Print(string s) { Console.WriteLine(\"string\"); }
+}
+class Fancy : Printer {
+    override void Print(Animal a) { Console.WriteLine(\"Fancy\"); base.Print(a); }
+}
+class Program {
+    static void Main() {
+        Printer p = new Fancy();
+        p.Print(1);
+        p.Print(1, 2);
+        p.Print(\"s\");
+        p.Print(new Dog());
+        Animal a = new Dog();
+        p.Print(a);
+        Fancy f = new Fancy();
+        f.Print(new Dog());
+    }
+}";
+        let expected = "int\nint, int\nstring\nDog\nFancy\nAnimal\nDog\n";
+        assert_eq!(run_source(src), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 3 is out of range for an array of length 3")]
+    fn array_bounds() {
+        run_source("class Program { static void Main() { int[] a = new int[3]; a[a.Length] = 1; } }");
+    }
+
     pub fn hello_world() -> Program {
         let methods = vec![
             Method {
@@ -1,158 +1,734 @@
 extern crate frontend;
 
-use std::collections::HashMap;
-use frontend::analysis::{self, AstPreprocessor, Type, TypeId, TypeMap};
+use std::collections::{HashMap, HashSet};
+use frontend::analysis::{self, errors, AstPreprocessor, ClassTable, Resolver, ScopeId, TypeId, TypeMap};
 use frontend::ast::*;
 use frontend::ast::visitor::Visitor;
+use frontend::diagnostics::{Diagnostic, Diagnostics};
+use frontend::syntax::Span;
 
-pub fn check(program: &Program) -> (HashMap<Label, TypeId>, TypeMap) {
+/// Type checks the whole program, returning the type of each expression and the problems found
+///
+/// Note: identifiers referring to a class have no type
+pub fn check(program: &Program) -> (HashMap<Label, TypeId>, TypeMap, Diagnostics) {
     let results = AstPreprocessor::preprocess(program);
     let mut visitor = TypeckVisitor {
-        class_map: results.classes_by_name,
+        classes: results.classes,
         node_map: results.nodes,
         var_map: results.var_map,
         this_map: results.this_map,
+        field_map: results.field_map,
+        static_calls: results.static_calls,
+        foreach_vars: results.foreach_vars,
         output: HashMap::new(),
-        types: TypeMap::default()
+        types: TypeMap::default(),
+        decl_types: HashMap::new(),
+        return_ty: None,
+        constructor_calls: HashMap::new(),
+        overrides: HashMap::new(),
+        diagnostics: results.diagnostics
     };
 
     visitor.visit_ast(&program.items);
-    (visitor.output, visitor.types)
+    (visitor.output, visitor.types, visitor.diagnostics)
 }
 
 struct TypeckVisitor<'a> {
-    pub class_map: HashMap<&'a str, &'a ClassDecl>,
+    pub classes: ClassTable<'a>,
     pub node_map: HashMap<Label, Node<'a>>,
     pub var_map: HashMap<Label, &'a VarDecl>,
     pub this_map: HashMap<Label, &'a ClassDecl>,
+    pub field_map: HashMap<Label, &'a FieldDecl>,
+    pub static_calls: HashSet<Label>,
+    pub foreach_vars: HashSet<Label>,
     pub output: HashMap<Label, TypeId>,
-    pub types: TypeMap
+    pub types: TypeMap,
+    pub decl_types: HashMap<Label, TypeId>,
+    pub return_ty: Option<TypeId>,
+    /// The constructor called by each `this(...)`, used to find constructors that call themselves
+    pub constructor_calls: HashMap<Label, &'a ConstructorDecl>,
+    /// The method overridden by each `override` method, or `None` if it is invalid (see
+    /// `resolve_override`)
+    pub overrides: HashMap<Label, Option<&'a MethodDecl>>,
+    pub diagnostics: Diagnostics
+}
+
+impl<'a> TypeckVisitor<'a> {
+    /// Resolves a type written in the given scope, reporting an error if it is unknown
+    fn resolve_ast_ty(&mut self, ty: &Type, scope: ScopeId, span: Span) -> TypeId {
+        match self.types.get_from_ast_ty(ty, &self.classes, scope) {
+            Ok(ty) => ty,
+            Err(e) => {
+                let diagnostic = self.classes.ambiguity_error(span, &e.name, scope).unwrap_or_else(|| errors::unknown_type(span, &e.name));
+                self.diagnostics.push(diagnostic);
+                self.types.error_ty()
+            }
+        }
+    }
+
+    /// Resolves the declared type of a variable, field or method
+    ///
+    /// Note: the result is cached, so unknown types are reported only once
+    fn decl_ty(&mut self, decl: Label, ty: &Type, span: Span) -> TypeId {
+        if let Some(&ty) = self.decl_types.get(&decl) {
+            return ty;
+        }
+
+        let ty = self.resolve_ast_ty(ty, self.classes.scope_of(decl), span);
+        self.decl_types.insert(decl, ty);
+        ty
+    }
+
+    fn check_unify(&mut self, expected: TypeId, found: TypeId, span: Span) {
+        if !self.types.unify(expected, found) {
+            let diagnostic = errors::mismatched_types(span, &self.types.name(expected), &self.types.name(found));
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// Checks that an index or an array length is an `int`
+    fn check_index(&mut self, index: &'a Expression) {
+        let int_ty = self.types.int_ty();
+        let ty = self.value_ty(index);
+        self.check_unify(int_ty, ty, index.span());
+    }
+
+    /// Reports assignments and increments of values that cannot be modified
+    fn check_assignable(&mut self, target: &Expression) {
+        match *target {
+            Expression::Identifier(ref i) if self.var_map.get(&i.label).is_some_and(|vd| self.foreach_vars.contains(&vd.label)) => {
+                self.diagnostics.push(errors::foreach_variable_assignment(i.span, &i.name));
+            }
+            Expression::FieldAccess(ref fa) => {
+                if let Some(&target_ty) = self.output.get(&fa.target.label().as_label()) {
+                    if self.types.property_ty(target_ty, &fa.field_name).is_some() {
+                        self.diagnostics.push(errors::read_only(fa.span, &fa.field_name));
+                    }
+                }
+            }
+            _ => ()
+        }
+    }
+
+    /// Returns the declaration of the interface of the given type, if it is an interface
+    fn interface_of_type(&self, ty: TypeId) -> Option<&'a InterfaceDecl> {
+        if ty == self.types.any_ty() {
+            return None;
+        }
+
+        self.types.get(ty).as_interface_decl().map(|id| self.node_map[&id.as_label()].downcast())
+    }
+
+    /// Returns the declaration of the class of the given type, reporting an error if the type is not a class
+    fn class_of_type(&mut self, ty: TypeId, span: Span) -> Option<&'a ClassDecl> {
+        if ty == self.types.error_ty() {
+            return None;
+        }
+
+        if ty != self.types.any_ty() {
+            if let analysis::Type::Class(cd) = self.types.get(ty) {
+                return Some(self.node_map[&cd.as_label()].downcast());
+            }
+        }
+
+        let diagnostic = errors::not_an_object(span, &self.types.name(ty));
+        self.diagnostics.push(diagnostic);
+        None
+    }
+
+    fn method_call_ty(&mut self, mc: &'a MethodCall) -> TypeId {
+        let arg_tys: Vec<TypeId> = mc.args.iter().map(|arg| self.value_ty(arg)).collect();
+
+        // Built in Console.WriteLine
+        if mc.is_console_write_line() {
+            if arg_tys.len() != 1 {
+                self.diagnostics.push(errors::wrong_argument_count(mc.span, "Console.WriteLine", 1, arg_tys.len()));
+            }
+            for (arg, &arg_ty) in mc.args.iter().zip(&arg_tys) {
+                if arg_ty == self.types.void_ty() {
+                    self.diagnostics.push(errors::mismatched_types(arg.span(), "a value", "void"));
+                }
+            }
+
+            return self.types.void_ty();
+        }
+
+        let target = match mc.target {
+            Some(ref target) => target,
+            // A call without target calls a method of the enclosing class (or of one of its base
+            // classes), which must be static if there is no `this`
+            None => {
+                let is_static = if self.static_calls.contains(&mc.label) { Some(true) } else { None };
+                return self.class_method_call_ty(mc, self.this_map[&mc.label], is_static, &arg_tys);
+            }
+        };
+
+        // Method of an interface, or of one of the interfaces it extends
+        let target_ty = self.output.get(&target.label().as_label()).cloned();
+        if let Some(interface) = target_ty.and_then(|ty| self.interface_of_type(ty)) {
+            let overloads = self.classes.find_interface_overloads(interface, &mc.method_name);
+            if overloads.iter().all(Vec::is_empty) {
+                let diagnostic = errors::unknown_method(mc.span, "interface", self.classes.interface_full_name(interface), &mc.method_name);
+                self.diagnostics.push(diagnostic);
+                return self.types.error_ty();
+            }
+
+            return match self.resolve_overload(mc, overloads) {
+                Some(method_decl) => self.check_call(mc, method_decl, &arg_tys),
+                None => self.types.error_ty()
+            };
+        }
+
+        // Get class decl of target
+        let (class_decl, is_static) = match target_ty {
+            // Non-static method
+            Some(ty) => match self.class_of_type(ty, target.span()) {
+                Some(class_decl) => (class_decl, false),
+                None => return self.types.error_ty()
+            },
+            // Static method. The target names a class (or a namespace, which is an error)
+            None => {
+                let path = target.path().expect("Only names of classes and namespaces have no type");
+                match self.classes.resolve(&path, self.classes.scope_of(mc.label)) {
+                    Some(class_decl) => (class_decl, true),
+                    None => {
+                        self.diagnostics.push(errors::expected_class(target.span(), &path));
+                        return self.types.error_ty();
+                    }
+                }
+            }
+        };
+
+        self.class_method_call_ty(mc, class_decl, Some(is_static), &arg_tys)
+    }
+
+    /// Checks a call to a method of the given class (or of one of its base classes), reporting an
+    /// error if the method is not static when `is_static` is `Some(true)`, or vice versa
+    fn class_method_call_ty(&mut self, mc: &'a MethodCall, class_decl: &'a ClassDecl, is_static: Option<bool>, arg_tys: &[TypeId]) -> TypeId {
+        match self.resolve_class_method(mc, class_decl, is_static) {
+            Some(method_decl) => self.check_call(mc, method_decl, arg_tys),
+            None => self.types.error_ty()
+        }
+    }
+
+    /// Checks the arguments of a call to the given method, returning the type of the call
+    fn check_call(&mut self, mc: &MethodCall, method_decl: &'a MethodDecl, arg_tys: &[TypeId]) -> TypeId {
+        // Collect parameter types
+        let param_tys = self.param_tys(method_decl);
+
+        // Check length and unification of types
+        if param_tys.len() != arg_tys.len() {
+            let diagnostic = errors::wrong_argument_count(mc.span, &method_decl.name, param_tys.len(), arg_tys.len())
+                .with_secondary(method_decl.span, "method defined here".to_string());
+            self.diagnostics.push(diagnostic);
+        }
+        for ((arg, param), (&arg_ty, &param_ty)) in mc.args.iter().zip(&method_decl.params).zip(arg_tys.iter().zip(&param_tys)) {
+            if !self.types.unify(param_ty, arg_ty) {
+                let diagnostic = errors::mismatched_types(arg.span(), &self.types.name(param_ty), &self.types.name(arg_ty))
+                    .with_secondary(param.span, "parameter declared here".to_string());
+                self.diagnostics.push(diagnostic);
+            }
+        }
+
+        // The type of the method call is the return type of the method decl
+        self.decl_ty(method_decl.label, &method_decl.return_ty, method_decl.span)
+    }
+}
+
+impl<'a> Resolver<'a> for TypeckVisitor<'a> {
+    fn classes(&self) -> &ClassTable<'a> {
+        &self.classes
+    }
+
+    fn types(&self) -> &TypeMap {
+        &self.types
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Note: the expression must have been visited already
+    fn value_ty(&mut self, expr: &'a Expression) -> TypeId {
+        match self.output.get(&expr.label().as_label()) {
+            Some(&ty) => ty,
+            None => {
+                // Only names of classes and namespaces have no type
+                let name = expr.path().expect("Only names of classes and namespaces have no type");
+                let scope = self.classes.scope_of(expr.label().as_label());
+                let kind = if self.classes.is_class(&name, scope) { "class" } else { "namespace" };
+                self.diagnostics.push(errors::expected_value(expr.span(), kind, &name));
+                let error_ty = self.types.error_ty();
+                self.output.insert(expr.label().as_label(), error_ty);
+                error_ty
+            }
+        }
+    }
+
+    fn param_ty(&mut self, param: &'a VarDecl) -> TypeId {
+        self.decl_ty(param.label, &param.ty, param.span)
+    }
+
+    fn return_ty(&mut self, md: &'a MethodDecl) -> TypeId {
+        self.decl_ty(md.label, &md.return_ty, md.span)
+    }
+
+    fn class_of(&self, member: Label) -> &'a ClassDecl {
+        self.this_map[&member]
+    }
+
+    fn resolve_override(&mut self, md: &'a MethodDecl) -> Option<&'a MethodDecl> {
+        if let Some(&overridden) = self.overrides.get(&md.label) {
+            return overridden;
+        }
+
+        let overridden = self.find_overridden(md);
+        self.overrides.insert(md.label, overridden);
+        overridden
+    }
+
+    /// Note: the constructors of the class must have been visited already
+    fn called_constructor(&mut self, call: &'a ConstructorCall) -> Option<&'a ConstructorDecl> {
+        self.constructor_calls.get(&call.label).cloned()
+    }
 }
 
 impl<'a> Visitor<'a> for TypeckVisitor<'a> {
+    fn visit_class_decl(&mut self, decl: &'a ClassDecl) {
+        visitor::walk_class_decl(self, decl);
+
+        // The implicit constructor calls the parameterless constructor of the base class
+        if decl.constructors().next().is_none() {
+            if let Some(base) = self.classes.base_of(decl) {
+                self.find_constructor(base, &[], decl.span);
+            }
+        }
+
+        self.check_implementations(decl);
+        self.check_abstract_members(decl);
+
+        // Calls between constructors can only be followed once all of them have been resolved
+        for cd in decl.constructors() {
+            if let Some(ref call) = cd.initializer {
+                if self.calls_itself(cd) {
+                    self.diagnostics.push(errors::recursive_constructor(call.span, &cd.name));
+                }
+            }
+        }
+    }
+
+    fn visit_field_decl(&mut self, decl: &'a FieldDecl) {
+        visitor::walk_field_decl(self, decl);
+
+        let ty = self.decl_ty(decl.label, &decl.ty, decl.span);
+        if let Some(ref expr) = decl.assignment {
+            let found = self.value_ty(expr);
+            self.check_unify(ty, found, expr.span());
+        }
+    }
+
+    fn visit_method_decl(&mut self, decl: &'a MethodDecl) {
+        self.return_ty = Some(self.decl_ty(decl.label, &decl.return_ty, decl.span));
+        visitor::walk_method_decl(self, decl);
+        self.return_ty = None;
+
+        self.resolve_override(decl);
+    }
+
+    fn visit_constructor_decl(&mut self, decl: &'a ConstructorDecl) {
+        self.return_ty = Some(self.types.void_ty());
+        visitor::walk_constructor_decl(self, decl);
+        self.return_ty = None;
+
+        // Implicit call to the parameterless constructor of the base class
+        if decl.initializer.is_none() && !decl.is_static {
+            if let Some(base) = self.classes.base_of(self.this_map[&decl.label]) {
+                self.find_constructor(base, &[], decl.span);
+            }
+        }
+    }
+
+    fn visit_constructor_call(&mut self, call: &'a ConstructorCall) {
+        visitor::walk_constructor_call(self, call);
+
+        let mut class_decl = self.this_map[&call.label];
+        if call.is_base {
+            class_decl = match self.classes.base_of(class_decl) {
+                Some(base) => base,
+                None => {
+                    self.diagnostics.push(errors::no_base_class(call.span, self.classes.full_name(class_decl)));
+                    return;
+                }
+            };
+        }
+
+        if let Some(cd) = self.find_constructor(class_decl, &call.args, call.span) {
+            self.constructor_calls.insert(call.label, cd);
+        }
+    }
+
+    fn visit_assign(&mut self, assign: &'a Assign) {
+        visitor::walk_assign(self, assign);
+
+        let expected = self.value_ty(&assign.target);
+        self.check_assignable(&assign.target);
+        let found = self.value_ty(&assign.expr);
+        match assign.operator {
+            None => self.check_unify(expected, found, assign.expr.span()),
+            // Compound assignments store the result of the operation in the target
+            Some(operator) => match self.types.binary_op_ty(operator, expected, found) {
+                Some(ty) => self.check_unify(expected, ty, assign.span),
+                None => {
+                    let diagnostic = errors::invalid_operands(assign.span, operator, &self.types.name(expected), &self.types.name(found));
+                    self.diagnostics.push(diagnostic);
+                }
+            }
+        }
+    }
+
+    fn visit_return(&mut self, ret: &'a Return) {
+        visitor::walk_return(self, ret);
+
+        let expected = self.return_ty.expect("Return statement outside of a method");
+        match ret.expr {
+            Some(ref expr) => {
+                let found = self.value_ty(expr);
+                self.check_unify(expected, found, expr.span());
+            }
+            None => {
+                let void_ty = self.types.void_ty();
+                self.check_unify(expected, void_ty, ret.span);
+            }
+        }
+    }
+
+    fn visit_var_decl(&mut self, var_decl: &'a VarDecl) {
+        visitor::walk_var_decl(self, var_decl);
+
+        let ty = self.decl_ty(var_decl.label, &var_decl.ty, var_decl.span);
+        if let Some(ref expr) = var_decl.expr {
+            let found = self.value_ty(expr);
+            self.check_unify(ty, found, expr.span());
+        }
+    }
+
+    fn visit_if_then_else(&mut self, ite: &'a IfThenElse) {
+        visitor::walk_if_then_else(self, ite);
+
+        let found = self.value_ty(&ite.condition);
+        let bool_ty = self.types.bool_ty();
+        self.check_unify(bool_ty, found, ite.condition.span());
+    }
+
+    fn visit_while(&mut self, while_: &'a While) {
+        visitor::walk_while(self, while_);
+
+        let found = self.value_ty(&while_.condition);
+        let bool_ty = self.types.bool_ty();
+        self.check_unify(bool_ty, found, while_.condition.span());
+    }
+
+    fn visit_do_while(&mut self, do_while: &'a DoWhile) {
+        visitor::walk_do_while(self, do_while);
+
+        let found = self.value_ty(&do_while.condition);
+        let bool_ty = self.types.bool_ty();
+        self.check_unify(bool_ty, found, do_while.condition.span());
+    }
+
+    fn visit_for(&mut self, for_: &'a For) {
+        visitor::walk_for(self, for_);
+
+        if let Some(ref condition) = for_.condition {
+            let found = self.value_ty(condition);
+            let bool_ty = self.types.bool_ty();
+            self.check_unify(bool_ty, found, condition.span());
+        }
+    }
+
+    fn visit_foreach(&mut self, foreach: &'a Foreach) {
+        visitor::walk_foreach(self, foreach);
+
+        let var_ty = self.decl_ty(foreach.var.label, &foreach.var.ty, foreach.var.span);
+        let collection_ty = self.value_ty(&foreach.collection);
+        match self.types.element_ty(collection_ty) {
+            Some(element_ty) => self.check_unify(var_ty, element_ty, foreach.var.span),
+            None => {
+                let diagnostic = errors::not_iterable(foreach.collection.span(), &self.types.name(collection_ty));
+                self.diagnostics.push(diagnostic);
+            }
+        }
+    }
+
+    fn visit_method_call(&mut self, mc: &'a MethodCall) {
+        // The target of Console.WriteLine is built in, so only the arguments need to be checked
+        if mc.is_console_write_line() {
+            for arg in &mc.args {
+                self.visit_expression(arg);
+            }
+        } else {
+            visitor::walk_method_call(self, mc);
+        }
+    }
+
     fn visit_expression(&mut self, expr: &'a Expression) {
         visitor::walk_expression(self, expr);
 
         match *expr {
             Expression::FieldAccess(ref fa) => {
-                // Get the type of the target (we assume the type is already known)
-                let target_ty = *self.output.get(&fa.target.label().as_label()).expect("Target of field access has no type");
+                // The target may be a namespace, in which case this is the name of a class or a nested namespace
+                if let Some(path) = fa.target.path() {
+                    let scope = self.classes.scope_of(fa.label);
+                    if !self.output.contains_key(&fa.target.label().as_label()) && !self.classes.is_class(&path, scope) {
+                        let path = format!("{}.{}", path, fa.field_name);
+                        if !self.classes.is_class(&path, scope) && !self.classes.is_namespace(&path, scope) {
+                            self.diagnostics.push(errors::unknown_type(fa.span, &path));
+                            let error_ty = self.types.error_ty();
+                            self.output.insert(fa.label, error_ty);
+                        }
 
-                // Go to the class, find the field declaration and return its type
-                match self.types.get(target_ty) {
-                    Type::Class(cd) => {
-                        let class_decl: &ClassDecl = self.node_map[&cd.as_label()].downcast();
-                        let field = class_decl.find_field(&fa.field_name);
-                        let field_decl: &FieldDecl = self.node_map[&field].downcast();
+                        return;
+                    }
+                }
+
+                // Get the class of the target (we assume the type of the target is already known)
+                let class_decl = match self.output.get(&fa.target.label().as_label()) {
+                    // Instance field
+                    Some(&target_ty) => {
+                        // Arrays have properties instead of fields
+                        if let Some(ty) = self.types.property_ty(target_ty, &fa.field_name) {
+                            self.output.insert(fa.label, ty);
+                            return;
+                        }
 
-                        // Save the type to the table
-                        self.output.insert(fa.label, self.types.get_from_ast_ty(&field_decl.ty, &self.class_map));
+                        // Interfaces have no fields
+                        if let Some(interface) = self.interface_of_type(target_ty) {
+                            let diagnostic = errors::unknown_field(fa.span, "interface", self.classes.interface_full_name(interface), &fa.field_name);
+                            self.diagnostics.push(diagnostic);
+                            let error_ty = self.types.error_ty();
+                            self.output.insert(fa.label, error_ty);
+                            return;
+                        }
+
+                        self.class_of_type(target_ty, fa.target.span()).map(|cd| (cd, false))
                     }
-                    x => {
-                        panic!("Attempted to access a field of something that is not a class: {:?}", x)
+                    // Static field. The target names a class, since namespaces are handled above
+                    None => {
+                        let path = fa.target.path().expect("Only names of classes and namespaces have no type");
+                        let class_decl = self.classes.resolve(&path, self.classes.scope_of(fa.label));
+                        Some((class_decl.expect("The target of a field access without a type is a class"), true))
                     }
-                }
+                };
+
+                // Find the field declaration and return its type
+                let ty = match class_decl {
+                    Some((class_decl, is_static)) => match self.classes.find_field(class_decl, &fa.field_name) {
+                        Some(field_decl) => {
+                            if field_decl.is_static != is_static {
+                                let diagnostic = errors::static_field_mismatch(fa.span, &field_decl.name, field_decl.is_static)
+                                    .with_secondary(field_decl.span, "field defined here".to_string());
+                                self.diagnostics.push(diagnostic);
+                            }
+
+                            self.decl_ty(field_decl.label, &field_decl.ty, field_decl.span)
+                        }
+                        None => {
+                            let diagnostic = errors::unknown_field(fa.span, "class", self.classes.full_name(class_decl), &fa.field_name);
+                            self.diagnostics.push(diagnostic);
+                            self.types.error_ty()
+                        }
+                    },
+                    None => self.types.error_ty()
+                };
+
+                // Save the type to the table
+                self.output.insert(fa.label, ty);
             }
             Expression::Literal(ref l) => {
-                let ty = match &l.kind {
-                    &LiteralKind::Bool(_) => {
+                let ty = match l.kind {
+                    LiteralKind::Bool(_) => {
                         self.types.bool_ty()
                     }
-                    &LiteralKind::Int(_) => {
+                    LiteralKind::Int(_) => {
                         self.types.int_ty()
                     }
-                    &LiteralKind::Null => {
+                    LiteralKind::Null => {
                         self.types.any_ty()
                     }
-                    &LiteralKind::String(_) => {
+                    LiteralKind::String(_) => {
                         self.types.string_ty()
                     }
-                    &LiteralKind::Array(ref ast_ty, _) => {
-                        let inner_ty = self.types.get_from_ast_ty(ast_ty, &self.class_map);
-                        self.types.get_id(analysis::Type::Array(inner_ty))
+                    LiteralKind::Array(ref ast_ty, ref items) => {
+                        let inner_ty = self.resolve_ast_ty(ast_ty, self.classes.scope_of(l.label), l.span);
+                        for item in items {
+                            let item_ty = self.value_ty(item);
+                            self.check_unify(inner_ty, item_ty, item.span());
+                        }
+
+                        if inner_ty == self.types.error_ty() {
+                            inner_ty
+                        } else {
+                            self.types.get_id(analysis::Type::Array(inner_ty))
+                        }
                     }
                 };
 
                 self.output.insert(l.label, ty);
             }
             Expression::MethodCall(ref mc) => {
-                // Built in Console.WriteLine
-                if mc.is_console_write_line() {
-                    self.output.insert(mc.label, self.types.void_ty());
-                    return;
+                let ty = self.method_call_ty(mc);
+                self.output.insert(mc.label, ty);
+            }
+            Expression::Identifier(ref i) => {
+                // Get the var decl associated to this identifier and return its type
+                // Note: it is possible that the identifier refers to a class or a namespace. In that case it gets no type.
+                let scope = self.classes.scope_of(i.label);
+                if let Some(field_decl) = self.field_map.get(&i.label) {
+                    let ty = self.decl_ty(field_decl.label, &field_decl.ty, field_decl.span);
+                    self.output.insert(i.label, ty);
+                } else if let Some(var_decl) = self.var_map.get(&i.label) {
+                    let ty = self.decl_ty(var_decl.label, &var_decl.ty, var_decl.span);
+                    self.output.insert(i.label, ty);
+                } else if !self.classes.is_class(&i.name, scope) && !self.classes.is_namespace(&i.name, scope) {
+                    let diagnostic = self.classes.ambiguity_error(i.span, &i.name, scope).unwrap_or_else(|| errors::unresolved_name(i.span, &i.name));
+                    self.diagnostics.push(diagnostic);
+                    let error_ty = self.types.error_ty();
+                    self.output.insert(i.label, error_ty);
                 }
+            }
+            Expression::BinaryOp(ref bo) => {
+                let left_ty = self.value_ty(&bo.left);
+                let right_ty = self.value_ty(&bo.right);
 
-                // Get class decl of target
-                let class_decl = match self.output.get(&mc.target.label().as_label()) {
-                    Some(ty) => {
-                        // Non-static method
-                        let decl_label = self.types.get(*ty).class_decl();
-
-                        // Stupid workaround the borrow checker
-                        let class_name: &str = &self.node_map[&decl_label.as_label()].downcast::<ClassDecl>().name;
-                        self.class_map[class_name]
-                    }
+                let ty = match self.types.binary_op_ty(bo.operator, left_ty, right_ty) {
+                    Some(ty) => ty,
                     None => {
-                        // Static method
-                        let class: &str = &mc.target.identifier().name;
-                        self.class_map[class]
+                        let diagnostic = errors::invalid_operands(bo.span, bo.operator, &self.types.name(left_ty), &self.types.name(right_ty));
+                        self.diagnostics.push(diagnostic);
+                        self.types.error_ty()
                     }
                 };
-
-                // Find the method
-                let method_decl = class_decl.find_method_any(&mc.method_name);
-
-                // Collect parameter types
-                let mut param_tys = Vec::new();
-                for param in &method_decl.params {
-                    let ty = self.types.get_from_ast_ty(&param.ty, &self.class_map);
-                    param_tys.push(ty);
-                }
-
-                // Collect arg types
-                let mut arg_tys: Vec<TypeId> = Vec::new();
-                for arg in &mc.args {
-                    let ty = *self.output.get(&arg.label().as_label()).expect("Unable to get type of method argument");
-                    arg_tys.push(ty);
+                self.output.insert(bo.label, ty);
+            }
+            Expression::UnaryOp(ref uo) => {
+                let operand_ty = self.value_ty(&uo.operand);
+                if uo.operator.is_increment() {
+                    self.check_assignable(&uo.operand);
                 }
 
-                // Check length and unification of types
-                if param_tys.len() != arg_tys.len() {
-                    panic!("Mismatched param and arg length in method call");
-                }
-                for (&param_ty, &arg_ty) in param_tys.iter().zip(arg_tys.iter()) {
-                    if !self.types.unify(param_ty, arg_ty) {
-                        panic!("Mismatched types in method call arguments");
+                let ty = match self.types.unary_op_ty(uo.operator, operand_ty) {
+                    Some(ty) => ty,
+                    None => {
+                        let diagnostic = errors::invalid_operand(uo.span, uo.operator, &self.types.name(operand_ty));
+                        self.diagnostics.push(diagnostic);
+                        self.types.error_ty()
                     }
-                }
-
-                // The type of the method call is the return type of the method decl
-                self.output.insert(mc.label, self.types.get_from_ast_ty(&method_decl.return_ty, &self.class_map));
+                };
+                self.output.insert(uo.label, ty);
             }
-            Expression::Identifier(ref i) => {
-                // Get the var decl associated to this identifier and return its type
-                // Note: it is possible that the identifier refers to a class name. In that case we return None.
-                if let Some(var_decl) = self.var_map.get(&i.label) {
-                    self.output.insert(i.label, self.types.get_from_ast_ty(&var_decl.ty, &self.class_map));
+            Expression::New(ref n) => {
+                let scope = self.classes.scope_of(n.label);
+                if let Some(interface) = self.classes.resolve_interface(&n.class_name, scope) {
+                    let diagnostic = errors::abstract_instantiation(n.span, "interface", self.classes.interface_full_name(interface));
+                    self.diagnostics.push(diagnostic);
+                    let ty = self.types.get_from_interface(interface, &self.classes);
+                    self.output.insert(n.label, ty);
+                    return;
                 }
+
+                let ty = match self.classes.resolve(&n.class_name, scope) {
+                    Some(class_decl) if class_decl.is_abstract => {
+                        let diagnostic = errors::abstract_instantiation(n.span, "abstract class", self.classes.full_name(class_decl));
+                        self.diagnostics.push(diagnostic);
+                        self.types.get_from_class(class_decl, &self.classes)
+                    }
+                    Some(class_decl) => {
+                        self.find_constructor(class_decl, &n.args, n.span);
+                        self.types.get_from_class(class_decl, &self.classes)
+                    }
+                    None => {
+                        let diagnostic = self.classes.ambiguity_error(n.span, &n.class_name, scope)
+                                             .unwrap_or_else(|| errors::unknown_type(n.span, &n.class_name));
+                        self.diagnostics.push(diagnostic);
+                        self.types.error_ty()
+                    }
+                };
+                self.output.insert(n.label, ty);
             }
-            Expression::BinaryOp(ref bo) => {
-                let left_ty = *self.output.get(&bo.left.label().as_label()).expect("No type found for lhs of binary op");
-                let right_ty = *self.output.get(&bo.right.label().as_label()).expect("No type found for rhs of binary op");
-                let well_typed = left_ty == right_ty && left_ty == self.types.int_ty();
-                if !well_typed {
-                    panic!("Mismatched types in binary operation");
-                }
-                // We only support int operations
-                self.output.insert(bo.label, self.types.int_ty());
+            Expression::NewArray(ref n) => {
+                self.check_index(&n.length);
+                let elem_ty = self.resolve_ast_ty(&n.elem_ty, self.classes.scope_of(n.label), n.span);
+                let ty = if elem_ty == self.types.error_ty() {
+                    elem_ty
+                } else {
+                    self.types.get_id(analysis::Type::Array(elem_ty))
+                };
+                self.output.insert(n.label, ty);
             }
-            Expression::New(ref n) => {
-                self.output.insert(n.label, self.types.get_from_class_name(&n.class_name, &self.class_map));
+            Expression::Index(ref i) => {
+                let target_ty = self.value_ty(&i.target);
+                self.check_index(&i.index);
+                let ty = match self.types.element_ty(target_ty) {
+                    Some(ty) => ty,
+                    None => {
+                        self.diagnostics.push(errors::not_indexable(i.target.span(), &self.types.name(target_ty)));
+                        self.types.error_ty()
+                    }
+                };
+                self.output.insert(i.label, ty);
             }
             Expression::This(ref t) => {
                 let class_decl = self.this_map[&t.label];
-                self.output.insert(t.label, self.types.get_from_class_name(&class_decl.name, &self.class_map));
+                let ty = self.types.get_from_class(class_decl, &self.classes);
+                self.output.insert(t.label, ty);
             }
+            Expression::Base(ref b) => {
+                // `base` is the current object, seen as an instance of the base class
+                let class_decl = self.this_map[&b.label];
+                let ty = match self.classes.base_of(class_decl) {
+                    Some(base) => self.types.get_from_class(base, &self.classes),
+                    None => {
+                        self.diagnostics.push(errors::no_base_class(b.span, self.classes.full_name(class_decl)));
+                        self.types.error_ty()
+                    }
+                };
+                self.output.insert(b.label, ty);
+            }
+            Expression::Error(ref e) => {
+                // The syntax error has already been reported
+                let error_ty = self.types.error_ty();
+                self.output.insert(e.label, error_ty);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use frontend::analysis::QueryEngine;
+    use frontend::diagnostics::{Code, Diagnostics};
+    use frontend::sample_programs::problems;
+    use frontend::syntax::{self, Location, SourceMap};
+
+    /// Returns the codes and locations of the diagnostics, ignoring the order in which they were
+    /// reported
+    fn sorted(diagnostics: &Diagnostics, source_map: &SourceMap) -> Vec<(Code, Location)> {
+        let mut diagnostics: Vec<_> = diagnostics.iter()
+                                                 .map(|d| (d.code, source_map.location(d.primary_span().unwrap()).unwrap()))
+                                                 .collect();
+        diagnostics.sort_by_key(|&(code, location)| (location.line, location.column, code.as_str()));
+        diagnostics
+    }
+
+    #[test]
+    fn agrees_with_query_engine() {
+        for &(name, src) in problems::ALL {
+            let (program, diagnostics) = syntax::parse("test.cs", src);
+            assert!(diagnostics.is_empty(), "{} has syntax errors", name);
+            let mut engine = QueryEngine::new(&program);
+            engine.check_program();
+            let (_, _, diagnostics) = super::check(&program);
+            assert_eq!(sorted(&diagnostics, &program.source_map), sorted(engine.diagnostics(), &program.source_map), "in {}", name);
         }
     }
 }